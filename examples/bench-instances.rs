@@ -0,0 +1,41 @@
+//! Measures how long it takes to cull and pack 50k rotated quads, which is the CPU-side work of
+//! a busy frame. No window is needed: the GL calls go to a `RecordingBackend`.
+//!
+//! Compare the default cgmath implementation with glam:
+//!
+//! ```sh
+//! cargo run --release --example bench-instances
+//! cargo run --release --example bench-instances --features glam
+//! ```
+
+use sprowl::backend::{self, RecordingBackend};
+use sprowl::renderer::RendererBuilder;
+use sprowl::sprites::{QuadInstance, standard_quad_shader};
+use std::rc::Rc;
+use std::time::Instant;
+
+const INSTANCES: usize = 50_000;
+const FRAMES: u32 = 200;
+
+fn main() {
+    let recorder = Rc::new(RecordingBackend::new());
+    backend::set_backend(recorder.clone());
+    let mut renderer = RendererBuilder::new(INSTANCES).standard_quad_layout().build_with(standard_quad_shader().unwrap());
+    // about a quarter of the quads are outside
+    renderer.set_cull_bounds(Some((0.0, 0.0, 1024.0, 600.0)));
+    let mut total_us = 0u128;
+    for frame in 0..FRAMES {
+        recorder.clear();
+        let t0 = Instant::now();
+        for i in 0..INSTANCES {
+            let quad = QuadInstance::new([(i % 256) as f32 * 4.0, (i / 256) as f32 * 4.0], [32.0, 32.0])
+                .with_rotation((frame as f32 + i as f32) * 0.1);
+            renderer.add_elem_with_bounds(&quad, quad.bounds());
+        }
+        renderer.draw();
+        total_us += t0.elapsed().as_micros();
+    }
+    let stats = renderer.frame_stats();
+    let backend = if cfg!(feature = "glam") { "glam" } else { "cgmath" };
+    println!("{}: {} instances, {}us per frame on average ({} culled in total)", backend, INSTANCES, total_us / FRAMES as u128, stats.culled);
+}
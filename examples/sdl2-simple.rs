@@ -1,11 +1,13 @@
 use sdl2::keyboard::Keycode;
-use sdl2::event::{Event, WindowEvent};
+use sdl2::event::Event;
 use sprowl::{
-    cgmath::{Matrix4, Vector2, Vector3, Vector4},
+    cgmath::{Vector2, Vector3, Vector4},
+    camera::Camera2D,
+    sdl::{SdlContext, WindowSettings},
     Color,
     shader::{Shader, Uniform},
-    renderer::{Renderer, RendererBuilder, AsVertexData},
-    render_storage::{RenderStorage, texture::TextureArrayLayer, font::{AdvancedLayout, WordPos, FontStemDrawCall}, TextureKind, FontId},
+    renderer::{Renderer, RendererBuilder, AsVertexData, VertexLayout},
+    render_storage::{RenderStorage, atlas::TextureHandle, font::{AdvancedLayout, InlineImage, WordPos, FontStemDrawCall}, TextureKind, FontId},
 };
 use std::mem::transmute;
 use std::cmp::min;
@@ -46,26 +48,20 @@ impl GraphicElement {
                 let (scale_x, scale_y) = t.scale.unwrap_or((1.0, 1.0));
                 let (width, height) = match t.crop {
                     Some((_, _, w, h,)) => (w, h),
-                    None => (stats.width, stats.height),
+                    None => stats.size(),
                 };
                 let (max_w, max_h) = render_storage.get_max_dims(TextureKind::RGBA);
                 let crop = match t.crop {
                     Some((x, y, w, h)) => {
+                        // the crop is relative to the texture, which may be packed with others
                         (
-                            x as f32 / max_w as f32,
-                            y as f32 / max_h as f32,
+                            (stats.x as i32 + x) as f32 / max_w as f32,
+                            (stats.y as i32 + y) as f32 / max_h as f32,
                             w as f32 / max_w as f32,
                             h as f32 / max_h as f32,
                         )
                     },
-                    None => {
-                        (
-                            0.0,
-                            0.0,
-                            width as f32 / max_w as f32,
-                            height as f32 / max_h as f32,
-                        )
-                    }
+                    None => t.texture.uv,
                 };
                 renderer.add_elem(&VertexData {
                     position: Vector2::new(t.x as f32, t.y as f32),
@@ -75,13 +71,14 @@ impl GraphicElement {
                     crop: Some(crop),
                     kind: 0,
                     effect: 0,
-                    layer: t.texture,
+                    layer: t.texture.layer,
                     secondary_texture_layer: 0,
                     effect_color: Color::<f32>::black().to_vec3(),
                 })
             },
             GraphicElement::Text(t) => {
                 let (max_w, max_h) = render_storage.get_max_dims(TextureKind::Grayscale);
+                let rgba_max_dims = render_storage.get_max_dims(TextureKind::RGBA);
                 let (font, mut texture) = render_storage.get_font_with_texture(t.font).unwrap();
                 match t.width {
                     Some(max_width) => {
@@ -93,18 +90,24 @@ impl GraphicElement {
                             t.center,
                             max_width
                         ).iter().cloned().collect::<Vec<WordPos<'_>>>();
-                        for WordPos { word, origin, .. } in font_layout {
-                            let word_layout = font.word_to_draw_call(
-                                &mut texture, word, t.font_size
-                            );
-                            render_word(renderer, &word_layout, origin, (max_w, max_h));
+                        for word_pos in font_layout {
+                            let WordPos { origin, size, inline_image, .. } = word_pos;
+                            if let Some(image) = inline_image {
+                                render_inline_image(renderer, &image, origin, size, rgba_max_dims);
+                                continue;
+                            }
+                            let word = word_pos.visual_word();
+                            match font.word_to_draw_call(&mut texture, &word, t.font_size) {
+                                Ok(word_layout) => render_word(renderer, &word_layout, origin, (max_w, max_h)),
+                                Err(e) => log::warn!("could not draw word {:?}: {}", word, e),
+                            };
                         };
                     },
                     None => {
-                        let word_layout = font.word_to_draw_call(
-                            &mut texture, &t.text, t.font_size
-                        );
-                        render_word(renderer, &word_layout, Vector2::new(t.x, t.y), (max_w, max_h));
+                        match font.word_to_draw_call(&mut texture, &t.text, t.font_size) {
+                            Ok(word_layout) => render_word(renderer, &word_layout, Vector2::new(t.x, t.y), (max_w, max_h)),
+                            Err(e) => log::warn!("could not draw text {:?}: {}", t.text, e),
+                        };
                     }
                 };
             },
@@ -112,22 +115,40 @@ impl GraphicElement {
     }
 }
 
+pub fn render_inline_image(renderer: &mut Renderer<ExampleUniform>, image: &InlineImage, origin: Vector2<f32>, size: Vector2<f32>, texture_layer_dims: (u32, u32)) {
+    let (max_w, max_h) = texture_layer_dims;
+    let (x, y, w, h) = image.crop;
+    let crop = Some((
+        x as f32 / max_w as f32,
+        y as f32 / max_h as f32,
+        w as f32 / max_w as f32,
+        h as f32 / max_h as f32,
+    ));
+    renderer.add_elem(&VertexData {
+        position: origin,
+        size,
+        rot_pivot: size / 2.0,
+        rot: 0.0,
+        crop,
+        kind: 0,
+        effect: 0,
+        layer: image.texture,
+        secondary_texture_layer: 0,
+        effect_color: Color::<f32>::black().to_vec3(),
+    });
+}
+
 pub fn render_word(renderer: &mut Renderer<ExampleUniform>, word_layout: &[FontStemDrawCall], origin: Vector2<f32>, texture_layer_dims: (u32, u32)) {
     let (max_w, max_h) = texture_layer_dims;
     for character in word_layout {
-        let (w, h) = (character.source_crop.2, character.source_crop.3);
-        let crop = Some((
-            // 1 represents the padding for borders: we need the characters to be 1 pixel wider
-            // to be able to show an outline.
-            (character.source_crop.0 - 1f32) as f32 / max_w as f32,
-            (character.source_crop.1 - 1f32) as f32 / max_h as f32,
-            (character.source_crop.2 + 2f32) as f32 / max_w as f32,
-            (character.source_crop.3 + 2f32) as f32 / max_h as f32,
-        ));
+        // the glyphs are padded in the atlas: draw the padding too, to have room for an outline.
+        let (x, y, w, h) = character.padded_crop();
+        let crop = Some((x / max_w as f32, y / max_h as f32, w / max_w as f32, h / max_h as f32));
+        let (dest_origin, size) = character.padded_dest();
         renderer.add_elem(&VertexData {
-            position: Vector2::new((origin.x + character.dest_origin.x - 1f32) as f32, (origin.y + character.dest_origin.y - 1f32) as f32),
-            size: Vector2::new((w + 2f32) as f32, (h + 2f32) as f32),
-            rot_pivot: Vector2::new((w + 2f32) as f32 / 2.0, (h + 2f32) as f32 / 2.0),
+            position: origin + dest_origin,
+            size,
+            rot_pivot: size / 2.0,
             rot: 0.0,
             crop,
             kind: 1,
@@ -155,7 +176,7 @@ pub struct GraphicTexture {
     pub x: i32,
     pub y: i32,
     pub rot: f32,
-    pub texture: TextureArrayLayer,
+    pub texture: TextureHandle,
     pub scale: Option<(f32, f32)>,
 }
 
@@ -243,12 +264,12 @@ impl Uniform for ExampleUniform {
     }
 }
 
-fn run(sdl_context: &sdl2::Sdl, window: &sdl2::video::Window) {
+fn run(sdl: &SdlContext) {
     let mut frames = 0u32;
     let mut compute_us = 0;
     let mut draw_us = 0;
     let mut swap_us = 0;
-    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut event_pump = sdl.event_pump().unwrap();
 
     if let Some(x) = sprowl::gl_utils::gl_get_error() {
         panic!("gl error code after initializing: {}", x);
@@ -262,21 +283,20 @@ fn run(sdl_context: &sdl2::Sdl, window: &sdl2::video::Window) {
         &["texture_rgba", "texture_gray"]
     ).expect("error when creating shader");
     let mut renderer = RendererBuilder::new(16384)
-        // layout = 1 -> vec4 crop 
-        .with_instanced_vertex_attrib(4, gl::FLOAT)
-        // vec2 translation
-        .with_instanced_vertex_attrib(2, gl::FLOAT)
-        // vec2 scale
-        .with_instanced_vertex_attrib(2, gl::FLOAT)
-        // vec2 rot_pivot
-        .with_instanced_vertex_attrib(2, gl::FLOAT)
-        // rot
-        .with_instanced_vertex_attrib(1, gl::FLOAT)
-        .with_instanced_vertex_attrib(1, gl::UNSIGNED_INT)
-        .with_instanced_vertex_attrib(1, gl::UNSIGNED_INT)
-        .with_instanced_vertex_attrib(1, gl::UNSIGNED_INT)
-        .with_instanced_vertex_attrib(1, gl::UNSIGNED_INT)
-        .with_instanced_vertex_attrib(3, gl::FLOAT)
+        .with_index_buffer(true)
+        // must match the order of `VertexData::add_vertex_data`
+        .with_vertex_layout(VertexLayout::new()
+            .with_field("crop", 4, gl::FLOAT)
+            .with_field("translation", 2, gl::FLOAT)
+            .with_field("scale", 2, gl::FLOAT)
+            .with_field("rot_pivot", 2, gl::FLOAT)
+            .with_field("rotation", 1, gl::FLOAT)
+            .with_field("_kind", 1, gl::UNSIGNED_INT)
+            .with_field("_layer", 1, gl::UNSIGNED_INT)
+            .with_field("_secondary_layer", 1, gl::UNSIGNED_INT)
+            .with_field("_effect", 1, gl::UNSIGNED_INT)
+            .with_field("_effect_color", 3, gl::FLOAT)
+        )
         .build_with(shader);
 
     let mut render_storage = RenderStorage::new();
@@ -287,10 +307,11 @@ fn run(sdl_context: &sdl2::Sdl, window: &sdl2::video::Window) {
     let shapes_id = render_storage.add_texture_from_image_bytes(include_bytes!("../res/shapes.png"), None).unwrap();
     let _noise_id = render_storage.add_texture_from_image_bytes(include_bytes!("../res/noise.png"), None).unwrap();
 
-    // font must always be from static resources, so use the include_bytes! macro.
-    let font_id = render_storage.add_font_from_bytes(include_bytes!("../res/DejaVuSerif.ttf"));
+    // fonts can also be loaded at runtime with add_font_from_path or add_font_from_vec.
+    let font_id = render_storage.add_font_from_bytes_unchecked(include_bytes!("../res/DejaVuSerif.ttf"));
 
-    let mut current_size = window.drawable_size();
+    let mut current_size = sdl.drawable_size();
+    let mut camera = Camera2D::new(current_size.0, current_size.1);
 
     log::info!("Running main loop...");
     let mut last_time = std::time::Instant::now();
@@ -305,26 +326,16 @@ fn run(sdl_context: &sdl2::Sdl, window: &sdl2::video::Window) {
                 Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running
                 },
-                Event::Window { win_event: WindowEvent::SizeChanged(w, h), ..} => {
-                    debug_assert!(w >= 0);
-                    debug_assert!(h >= 0);
-                    renderer.set_viewport(w as u32, h as u32);
-                    current_size = (w as u32, h as u32);
+                event => if let Some(size) = sdl.handle_event(&event, &mut renderer) {
+                    current_size = size;
+                    // keep (0, 0) at the top left corner of the window
+                    camera = Camera2D::new(current_size.0, current_size.1);
                 },
-                _ => {}
             }
         }
 
         renderer.clear(Some(Color::from_rgb(192u8, 192, 192)));
-        let view_matrix = Matrix4::<f32>::from(cgmath::Ortho {
-            left: 0.0,
-            right: (current_size.0 as f32),
-            bottom: current_size.1 as f32,
-            top: 0.0,
-            near: -1.0,
-            far: 1.0
-        });
-        renderer.shader.set_matrix4(ExampleUniform::View, &view_matrix);
+        renderer.shader.set_matrix4(ExampleUniform::View, &camera.view_matrix());
         renderer.shader.set_float(ExampleUniform::T, t as f32);
 
         for x in 0..64i32 {
@@ -371,7 +382,7 @@ fn run(sdl_context: &sdl2::Sdl, window: &sdl2::video::Window) {
         renderer.draw();
         let t2 = std::time::Instant::now();
 
-        window.gl_swap_window();
+        sdl.swap();
         let t3 = std::time::Instant::now();
         compute_us += (t1 - t0).as_micros();
         draw_us += (t2 - t1).as_micros();
@@ -393,69 +404,12 @@ fn run(sdl_context: &sdl2::Sdl, window: &sdl2::video::Window) {
 fn main() {
     env_logger::init();
     log::info!("Starting program");
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    
-    let gl_attr = video_subsystem.gl_attr();
-    gl_attr.set_context_profile(::sdl2::video::GLProfile::Core);
-    gl_attr.set_context_version(3, 3);
-    
-    let window = video_subsystem.window("Window", 1280, 720)
-        .resizable()
-        .opengl()
-        .build()
-        .unwrap();
-
-
-    let _ctx = window.gl_create_context().unwrap();
-    gl::load_with(|name| video_subsystem.gl_get_proc_address(name) as *const _);
-    println!("MS {} {}", gl_attr.multisample_buffers(), gl_attr.multisample_samples());
-    // Enable anti-aliasing
-    gl_attr.set_multisample_buffers(0);
-    gl_attr.set_multisample_samples(0);
-
-    // unsafe { gl::Disable(gl::MULTISAMPLE); };
-
-    println!("MS {} {}", gl_attr.multisample_buffers(), gl_attr.multisample_samples());
-    
-    // Yes, we're still using the Core profile
-    debug_assert_eq!(gl_attr.context_profile(), sdl2::video::GLProfile::Core);
-    // ... and we're still using OpenGL 3.3
-    debug_assert_eq!(gl_attr.context_version(), (3, 3));
-
+    let settings = WindowSettings::new("Window", 1280, 720).with_vsync(false);
+    let sdl = sprowl::sdl2_init(&settings).expect("failed to create the window");
     if let Some(e) = sprowl::gl_utils::gl_get_error() {
         panic!("opengl fatal error {:x} while initializing", e);
     }
 
-
-    video_subsystem.gl_set_swap_interval(sdl2::video::SwapInterval::Immediate).expect("failed to disable vsync");
-    // now that we are initialized, run the actual program
-
-    log::info!(
-        "OpenGL Vendor: {}",
-        sprowl::gl_utils::gl_get_string(gl::VENDOR).to_string_lossy(),
-    );
-    log::info!(
-        "OpenGL Renderer: {}",
-        sprowl::gl_utils::gl_get_string(gl::RENDERER).to_string_lossy(),
-    );
-    log::info!(
-        "OpenGL Version: {}, GLSL Version: {}",
-        sprowl::gl_utils::gl_get_string(gl::VERSION).to_string_lossy(),
-        sprowl::gl_utils::gl_get_string(gl::SHADING_LANGUAGE_VERSION).to_string_lossy(),
-    );
-    log::info!("OpenGL MAX_TEXTURE_SIZE:             {}", sprowl::gl_utils::gl_get_int(gl::MAX_TEXTURE_SIZE));
-    log::info!("OpenGL MAX_3D_TEXTURE_SIZE:          {}", sprowl::gl_utils::gl_get_int(gl::MAX_3D_TEXTURE_SIZE));
-    log::info!("OpenGL MAX_ARRAY_TEXTURE_LAYERS:     {}", sprowl::gl_utils::gl_get_int(gl::MAX_ARRAY_TEXTURE_LAYERS));
-    log::info!("OpenGL MAX_ELEMENTS_VERTICES:        {}", sprowl::gl_utils::gl_get_int(gl::MAX_ELEMENTS_VERTICES));
-    log::info!("OpenGL MAX_ELEMENTS_INDICES:         {}", sprowl::gl_utils::gl_get_int(gl::MAX_ELEMENTS_INDICES));
-    log::info!("OpenGL MAX_VERTEX_ATTRIBS:           {}", sprowl::gl_utils::gl_get_int(gl::MAX_VERTEX_ATTRIBS));
-    log::info!("OpenGL MAX_UNIFORM_COMPONENTS:       {}", sprowl::gl_utils::gl_get_int(gl::MAX_VERTEX_UNIFORM_COMPONENTS));
-    log::info!("OpenGL MAX_VERTEX_OUTPUT_COMPONENTS: {}", sprowl::gl_utils::gl_get_int(gl::MAX_VERTEX_OUTPUT_COMPONENTS));
-    log::info!("OpenGL Multisampling:                {}", unsafe { gl::IsEnabled(gl::MULTISAMPLE) });
-    // log::info!("OpenGL Multisampling ARB:                {}", unsafe { gl::IsEnabled(gl::MULTISAMPLE_ARB) });
-    println!("MS {} {}", gl_attr.multisample_buffers(), gl_attr.multisample_samples());
-
     log::info!("Initialized OpenGL, running...");
-    run(&sdl_context, &window);
-}
\ No newline at end of file
+    run(&sdl);
+}
@@ -0,0 +1,139 @@
+use super::{Animation, AnimationFrame};
+use crate::render_storage::texture::TextureArrayLayer;
+use crate::sprites::Flip;
+
+/// What to draw for the current frame of an `Animator`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimatorFrame {
+    pub texture_layer: TextureArrayLayer,
+    /// in pixels: (x, y, w, h)
+    pub crop: (u32, u32, u32, u32),
+    pub flip: Flip,
+}
+
+/// Plays an `Animation`: keeps track of the time, and gives the frame to show.
+///
+/// ```ignore
+/// let mut animator = Animator::new(walk_animation);
+/// // every frame
+/// animator.update(dt);
+/// let frame = animator.current();
+/// // use frame.texture_layer, frame.crop and frame.flip in your instance data
+/// ```
+#[derive(Debug, Clone)]
+pub struct Animator {
+    animation: Animation,
+    time: f32,
+    speed: f32,
+    playing: bool,
+    flip: Flip,
+}
+
+impl Animator {
+    pub fn new(animation: Animation) -> Animator {
+        Animator {
+            animation,
+            time: 0.0,
+            speed: 1.0,
+            playing: true,
+            flip: Flip::None,
+        }
+    }
+
+    /// Switch to another animation, from its start. Speed and flip are kept.
+    pub fn play(&mut self, animation: Animation) {
+        self.animation = animation;
+        self.restart();
+    }
+
+    #[inline]
+    pub fn animation(&self) -> &Animation {
+        &self.animation
+    }
+
+    /// Advance by `dt` seconds, multiplied by the speed. Does nothing while paused.
+    pub fn update(&mut self, dt: f32) {
+        if self.playing {
+            self.time = (self.time + dt * self.speed).max(0.0);
+        }
+    }
+
+    /// 1.0 is the normal speed, 2.0 twice as fast.
+    #[inline]
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    #[inline]
+    pub fn resume(&mut self) {
+        self.playing = true;
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Go back to the first frame, and resume.
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    /// Time since the start of the animation, in seconds.
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[inline]
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.max(0.0);
+    }
+
+    /// Flip applied to every frame, for instance to make a character face left.
+    #[inline]
+    pub fn set_flip(&mut self, flip: Flip) {
+        self.flip = flip;
+    }
+
+    #[inline]
+    pub fn flip(&self) -> Flip {
+        self.flip
+    }
+
+    /// Returns true if the animation doesn't loop and its last frame has been shown long enough.
+    pub fn is_finished(&self) -> bool {
+        self.animation.is_finished(self.time)
+    }
+
+    #[inline]
+    pub fn frame_index(&self) -> usize {
+        self.animation.frame_index_at(self.time)
+    }
+
+    /// The current frame, with its full duration.
+    pub fn current_frame(&self) -> &AnimationFrame {
+        self.animation.frame_at(self.time)
+    }
+
+    /// What to draw now.
+    pub fn current(&self) -> AnimatorFrame {
+        let frame = self.current_frame();
+        AnimatorFrame {
+            texture_layer: frame.texture_layer,
+            crop: frame.crop,
+            flip: self.flip,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::render_storage::texture::TextureArrayLayer;
+
+/// A single frame of an `Animation`: a part of a layer, shown for `duration` seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationFrame {
+    pub texture_layer: TextureArrayLayer,
+    /// in pixels: (x, y, w, h)
+    pub crop: (u32, u32, u32, u32),
+    /// in seconds
+    pub duration: f32,
+}
+
+/// What an `Animation` does once its last frame is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    /// Stay on the last frame.
+    Once,
+    /// Start again from the first frame.
+    #[default]
+    Loop,
+    /// Play backwards down to the first frame, then forwards again, and so on. The first and last
+    /// frames are not repeated.
+    PingPong,
+}
+
+/// A list of frames played one after the other.
+///
+/// An animation holds no state by itself: you ask which frame to show at a given time, or let an
+/// `Animator` keep track of it.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    frames: Vec<AnimationFrame>,
+    loop_mode: LoopMode,
+    total_duration: f32,
+}
+
+/// Another name for `Animation`: a clip of a spritesheet.
+pub type AnimationClip = Animation;
+
+impl Animation {
+    /// # Panics
+    ///
+    /// Panics if `frames` is empty
+    pub fn new(frames: Vec<AnimationFrame>, looping: bool) -> Animation {
+        Animation::with_loop_mode(frames, if looping { LoopMode::Loop } else { LoopMode::Once })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `frames` is empty
+    pub fn with_loop_mode(frames: Vec<AnimationFrame>, loop_mode: LoopMode) -> Animation {
+        assert!(!frames.is_empty(), "an animation must have at least one frame");
+        let total_duration = frames.iter().map(|f| f.duration).sum();
+        Animation {
+            frames,
+            loop_mode,
+            total_duration,
+        }
+    }
+
+    /// Create an animation from a horizontal strip of `count` frames of the same size in a
+    /// spritesheet, starting at `origin` (in pixels).
+    ///
+    /// If you used the atlas, `origin` is relative to the layer, see `TextureHandle::crop_in_layer`.
+    pub fn from_strip(texture_layer: TextureArrayLayer, origin: (u32, u32), frame_size: (u32, u32), count: u32, frame_duration: f32, looping: bool) -> Animation {
+        let frames = (0..count).map(|i| {
+            AnimationFrame {
+                texture_layer,
+                crop: (origin.0 + i * frame_size.0, origin.1, frame_size.0, frame_size.1),
+                duration: frame_duration,
+            }
+        }).collect();
+        Animation::new(frames, looping)
+    }
+
+    #[inline]
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    #[inline]
+    pub fn loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
+
+    /// Returns true for `LoopMode::Loop` and `LoopMode::PingPong`.
+    #[inline]
+    pub fn is_looping(&self) -> bool {
+        self.loop_mode != LoopMode::Once
+    }
+
+    /// Duration of the frames, in seconds. A loop of a ping-pong animation lasts longer, see
+    /// `cycle_duration`.
+    #[inline]
+    pub fn total_duration(&self) -> f32 {
+        self.total_duration
+    }
+
+    /// Duration of a single loop of the animation, in seconds: there and back for ping-pong
+    /// animations.
+    pub fn cycle_duration(&self) -> f32 {
+        match self.loop_mode {
+            LoopMode::PingPong if self.frames.len() > 2 => {
+                let inner_frames = &self.frames[1..self.frames.len() - 1];
+                self.total_duration + inner_frames.iter().map(|f| f.duration).sum::<f32>()
+            },
+            _ => self.total_duration,
+        }
+    }
+
+    /// Returns true if the animation is not looping, and `time` is past its end.
+    pub fn is_finished(&self, time: f32) -> bool {
+        !self.is_looping() && time >= self.total_duration
+    }
+
+    /// Returns the number of full loops done, and the index of the frame to show at `time`.
+    ///
+    /// Non-looping animations stay on their last frame once finished.
+    pub fn frame_position_at(&self, time: f32) -> (u32, usize) {
+        if self.total_duration <= 0.0 {
+            return (0, self.frames.len() - 1);
+        }
+        let cycle_duration = self.cycle_duration();
+        let (loops, mut time) = if self.is_looping() {
+            ((time / cycle_duration).floor() as u32, time % cycle_duration)
+        } else if time >= self.total_duration {
+            return (0, self.frames.len() - 1);
+        } else {
+            (0, time)
+        };
+        if time >= self.total_duration {
+            // on the way back of a ping-pong animation, from the frame before the last one.
+            time -= self.total_duration;
+            for i in (1..self.frames.len() - 1).rev() {
+                if time < self.frames[i].duration {
+                    return (loops, i);
+                }
+                time -= self.frames[i].duration;
+            }
+            return (loops, 0);
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            if time < frame.duration {
+                return (loops, i);
+            }
+            time -= frame.duration;
+        }
+        // rounding errors may bring us here
+        (loops, self.frames.len() - 1)
+    }
+
+    /// Returns the index of the frame to show at `time`.
+    #[inline]
+    pub fn frame_index_at(&self, time: f32) -> usize {
+        self.frame_position_at(time).1
+    }
+
+    /// Returns the frame to show at `time`.
+    #[inline]
+    pub fn frame_at(&self, time: f32) -> &AnimationFrame {
+        &self.frames[self.frame_index_at(time)]
+    }
+}
@@ -0,0 +1,7 @@
+mod animator;
+mod frames;
+mod state_machine;
+
+pub use animator::*;
+pub use frames::*;
+pub use state_machine::*;
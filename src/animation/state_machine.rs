@@ -0,0 +1,209 @@
+use smallvec::SmallVec;
+
+use super::{Animation, AnimationFrame};
+
+pub type AnimationStateId = usize;
+
+/// When a transition from a state to another should happen.
+///
+/// `P` is your own type of parameters given to `AnimationStateMachine::update`, for instance a
+/// struct with the speed of your character and whether it's on the ground.
+pub enum TransitionCondition<P> {
+    /// Once the animation of the current state is done. Never happens for looping animations.
+    Finished,
+    /// Once the current state has been played for this amount of seconds.
+    After(f32),
+    /// As soon as the predicate returns true.
+    When(Box<dyn Fn(&P) -> bool>),
+}
+
+impl<P> std::fmt::Debug for TransitionCondition<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TransitionCondition::Finished => write!(f, "Finished"),
+            TransitionCondition::After(t) => write!(f, "After({})", t),
+            TransitionCondition::When(_) => write!(f, "When(..)"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Transition<P> {
+    to: AnimationStateId,
+    condition: TransitionCondition<P>,
+}
+
+#[derive(Debug)]
+struct AnimationState<P> {
+    name: &'static str,
+    animation: Animation,
+    transitions: Vec<Transition<P>>,
+    /// (frame index, event name)
+    events: Vec<(usize, &'static str)>,
+}
+
+/// Sent by `AnimationStateMachine::update` when a frame with an event is reached,
+/// e.g. "footstep" on the 3rd frame of a walking animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationEvent {
+    pub state: AnimationStateId,
+    pub frame_index: usize,
+    pub name: &'static str,
+}
+
+/// A small state machine to choose which animation to play, e.g. "idle", "walk", "jump".
+///
+/// Transitions are checked in the order they were added, and the first one to match is taken.
+/// At most one transition happens per `update`.
+#[derive(Debug)]
+pub struct AnimationStateMachine<P> {
+    states: Vec<AnimationState<P>>,
+    current: AnimationStateId,
+    /// time spent in the current state, in seconds
+    elapsed: f32,
+    /// (loops, frame index) reached during the last update, None if the state just started.
+    frame_position: Option<(u32, usize)>,
+}
+
+impl<P> AnimationStateMachine<P> {
+    /// Create a state machine, with its initial state.
+    pub fn new(initial_name: &'static str, initial_animation: Animation) -> AnimationStateMachine<P> {
+        AnimationStateMachine {
+            states: vec![AnimationState {
+                name: initial_name,
+                animation: initial_animation,
+                transitions: Vec::new(),
+                events: Vec::new(),
+            }],
+            current: 0,
+            elapsed: 0.0,
+            frame_position: None,
+        }
+    }
+
+    /// Add a new state, and returns its id to add transitions and events.
+    pub fn add_state(&mut self, name: &'static str, animation: Animation) -> AnimationStateId {
+        self.states.push(AnimationState {
+            name,
+            animation,
+            transitions: Vec::new(),
+            events: Vec::new(),
+        });
+        self.states.len() - 1
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `from` or `to` are not valid states
+    pub fn add_transition(&mut self, from: AnimationStateId, to: AnimationStateId, condition: TransitionCondition<P>) {
+        assert!(to < self.states.len(), "state {} does not exist", to);
+        self.states[from].transitions.push(Transition { to, condition });
+    }
+
+    /// Send an event named `name` every time the frame `frame_index` of the state is reached.
+    /// With `LoopMode::PingPong`, events are only sent while the animation plays forward.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` is not a valid state
+    pub fn add_event(&mut self, state: AnimationStateId, frame_index: usize, name: &'static str) {
+        debug_assert!(frame_index < self.states[state].animation.frames().len());
+        self.states[state].events.push((frame_index, name));
+    }
+
+    /// Returns the id of the state with the given name, if any.
+    pub fn state_id(&self, name: &str) -> Option<AnimationStateId> {
+        self.states.iter().position(|s| s.name == name)
+    }
+
+    #[inline]
+    pub fn current_state(&self) -> AnimationStateId {
+        self.current
+    }
+
+    #[inline]
+    pub fn current_state_name(&self) -> &'static str {
+        self.states[self.current].name
+    }
+
+    /// Time spent in the current state, in seconds.
+    #[inline]
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Returns the frame to draw right now.
+    pub fn current_frame(&self) -> &AnimationFrame {
+        self.states[self.current].animation.frame_at(self.elapsed)
+    }
+
+    /// Switch to a state right away, ignoring transitions. Its animation restarts from the beginning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` is not a valid state
+    pub fn set_state(&mut self, state: AnimationStateId) {
+        assert!(state < self.states.len(), "state {} does not exist", state);
+        self.current = state;
+        self.elapsed = 0.0;
+        self.frame_position = None;
+    }
+
+    fn push_events_between(&self, from: Option<(u32, usize)>, to: (u32, usize), events: &mut SmallVec<[AnimationEvent; 2]>) {
+        let state = &self.states[self.current];
+        if state.events.is_empty() {
+            return;
+        }
+        let frame_count = state.animation.frames().len();
+        let mut push_frame_events = |frame_index: usize| {
+            for &(_, name) in state.events.iter().filter(|(i, _)| *i == frame_index) {
+                events.push(AnimationEvent { state: self.current, frame_index, name });
+            }
+        };
+        let (mut loops, mut index) = match from {
+            Some(from) => from,
+            None => {
+                push_frame_events(0);
+                (0, 0)
+            }
+        };
+        // if we skipped several loops at once (huge dt), only send the events of one loop.
+        if to.0 > loops + 1 {
+            loops = to.0 - 1;
+        }
+        while (loops, index) < to {
+            index += 1;
+            if index >= frame_count {
+                index = 0;
+                loops += 1;
+            }
+            push_frame_events(index);
+        }
+    }
+
+    /// Advance time by `dt` seconds, follow the transitions which apply, and return the events
+    /// of all the frames reached in the meantime.
+    pub fn update(&mut self, dt: f32, params: &P) -> SmallVec<[AnimationEvent; 2]> {
+        let mut events = SmallVec::new();
+        self.elapsed += dt;
+        let new_position = self.states[self.current].animation.frame_position_at(self.elapsed);
+        self.push_events_between(self.frame_position, new_position, &mut events);
+        self.frame_position = Some(new_position);
+
+        let state = &self.states[self.current];
+        let next_state = state.transitions.iter().find(|transition| {
+            match &transition.condition {
+                TransitionCondition::Finished => state.animation.is_finished(self.elapsed),
+                TransitionCondition::After(t) => self.elapsed >= *t,
+                TransitionCondition::When(predicate) => predicate(params),
+            }
+        }).map(|transition| transition.to);
+
+        if let Some(next_state) = next_state {
+            self.set_state(next_state);
+            self.push_events_between(None, (0, 0), &mut events);
+            self.frame_position = Some((0, 0));
+        }
+        events
+    }
+}
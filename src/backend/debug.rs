@@ -0,0 +1,90 @@
+//! Error checking of every GL call, with the `gl-debug` feature.
+//!
+//! By default, `glGetError` is called after every call, and errors are logged along with the name
+//! and the arguments of the function. If the context supports `KHR_debug` (GL 4.3), call
+//! `install_debug_callback` once it is current: the messages of the driver are logged instead,
+//! with the name of the function being called, and `glGetError` isn't called anymore.
+//!
+//! Since checking clears the error flags of GL, the last error is kept for `gl_get_error`.
+
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::fmt::Debug;
+use std::os::raw::c_void;
+
+use super::gl::{self, debug_args, types::*};
+use super::with_backend;
+
+thread_local! {
+    /// the function being called, for the debug callback
+    static CURRENT_CALL: Cell<&'static str> = const { Cell::new("") };
+    static CALLBACK_INSTALLED: Cell<bool> = const { Cell::new(false) };
+    /// the last error read by `check_error`, not yet returned by `gl_get_error`
+    static LAST_ERROR: Cell<GLenum> = const { Cell::new(gl::NO_ERROR) };
+}
+
+pub (crate) fn enter(function: &'static str) {
+    CURRENT_CALL.with(|current| current.set(function));
+}
+
+fn error_name(error: GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        _ => "unknown error",
+    }
+}
+
+/// Log every error raised by the last call, which was `function(args)`.
+pub (crate) fn check_error(function: &'static str, args: &[&dyn Debug]) {
+    if function == "GetError" || CALLBACK_INSTALLED.with(Cell::get) {
+        return;
+    }
+    loop {
+        let error = with_backend(|backend| unsafe { backend.GetError() });
+        if error == gl::NO_ERROR {
+            break;
+        }
+        log::error!("gl{}({}) failed: {} (0x{:X})", function, debug_args(args), error_name(error), error);
+        LAST_ERROR.with(|last_error| last_error.set(error));
+    }
+}
+
+/// The last error read by `check_error` since the previous call, if any.
+pub (crate) fn take_error() -> Option<GLenum> {
+    match LAST_ERROR.with(|last_error| last_error.replace(gl::NO_ERROR)) {
+        gl::NO_ERROR => None,
+        error => Some(error),
+    }
+}
+
+extern "system" fn debug_callback(_source: GLenum, _gltype: GLenum, id: GLuint, severity: GLenum, _length: GLsizei, message: *const GLchar, _user_param: *mut c_void) {
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    let function = CURRENT_CALL.with(Cell::get);
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::error!("gl{}: {} (id={})", function, message, id),
+        gl::DEBUG_SEVERITY_MEDIUM | gl::DEBUG_SEVERITY_LOW => log::warn!("gl{}: {} (id={})", function, message, id),
+        _ => log::debug!("gl{}: {} (id={})", function, message, id),
+    }
+}
+
+/// Log the messages of the driver with `glDebugMessageCallback`, if the context supports it.
+///
+/// Messages are synchronous, so that they can be attributed to the function being called.
+/// Returns false if `KHR_debug` isn't supported, in which case `glGetError` keeps being called
+/// after every call.
+pub fn install_debug_callback() -> bool {
+    if !gl::DebugMessageCallback::is_loaded() {
+        return false;
+    }
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_callback), std::ptr::null());
+    }
+    CALLBACK_INSTALLED.with(|installed| installed.set(true));
+    true
+}
@@ -0,0 +1,212 @@
+//! Mirrors the `gl` crate: types and constants are re-exported as they are, and the functions
+//! used by this crate call the `GlBackend` of the current thread.
+//!
+//! Functions used by the crate must be added to the list at the end of this file.
+#![allow(non_snake_case, clippy::too_many_arguments, clippy::missing_safety_doc)]
+
+pub use ::gl::*;
+use ::gl::types::*;
+use std::fmt::Debug;
+use std::os::raw::c_void;
+
+use super::{NativeBackend, RecordingBackend, with_backend};
+
+unsafe fn empty_info_log(buf_size: GLsizei, length: *mut GLsizei, info_log: *mut GLchar) {
+    if !length.is_null() {
+        *length = 0;
+    }
+    if buf_size > 0 {
+        *info_log = 0;
+    }
+}
+
+/// Arguments formatted with `Debug`, separated by commas.
+pub (crate) fn debug_args(args: &[&dyn Debug]) -> String {
+    args.iter().map(|arg| format!("{:?}", arg)).collect::<Vec<_>>().join(", ")
+}
+
+/// For every function, generates a method of `GlBackend`, its implementation for both backends,
+/// and a function with the same name as the one of the `gl` crate, along with its `is_loaded`.
+///
+/// The block after every function is what the `RecordingBackend` (`$rec`) returns.
+macro_rules! gl_functions {
+    ($rec:ident; $($name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)? $record:block)*) => {
+        /// The OpenGL functions used by this crate, see the `backend` module.
+        ///
+        /// Methods have the same names and arguments as the functions of the `gl` crate.
+        pub trait GlBackend {
+            /// Whether `function` can be called, like `gl::Function::is_loaded`.
+            fn is_loaded(&self, function: &'static str) -> bool;
+
+            $(unsafe fn $name(&self, $($arg: $ty),*) $(-> $ret)?;)*
+        }
+
+        impl GlBackend for NativeBackend {
+            fn is_loaded(&self, function: &'static str) -> bool {
+                match function {
+                    $(stringify!($name) => ::gl::$name::is_loaded(),)*
+                    _ => false,
+                }
+            }
+
+            $(#[inline]
+            unsafe fn $name(&self, $($arg: $ty),*) $(-> $ret)? {
+                ::gl::$name($($arg),*)
+            })*
+        }
+
+        impl GlBackend for RecordingBackend {
+            fn is_loaded(&self, _function: &'static str) -> bool {
+                true
+            }
+
+            $(#[allow(unused_variables)]
+            unsafe fn $name(&self, $($arg: $ty),*) $(-> $ret)? {
+                let $rec = self;
+                $rec.record(stringify!($name), debug_args(&[$(&$arg),*]));
+                $record
+            })*
+        }
+
+        $(#[inline]
+        #[allow(clippy::let_unit_value)]
+        pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+            #[cfg(feature = "gl-debug")]
+            super::debug::enter(stringify!($name));
+            let result = with_backend(|backend| backend.$name($($arg),*));
+            #[cfg(feature = "gl-debug")]
+            super::debug::check_error(stringify!($name), &[$(&$arg),*]);
+            result
+        }
+
+        pub mod $name {
+            pub fn is_loaded() -> bool {
+                crate::backend::with_backend(|backend| backend.is_loaded(stringify!($name)))
+            }
+        })*
+    };
+}
+
+gl_functions! { rec;
+    ActiveTexture(texture: GLenum) {}
+    AttachShader(program: GLuint, shader: GLuint) {}
+    BeginQuery(target: GLenum, id: GLuint) {}
+    BindBuffer(target: GLenum, buffer: GLuint) {}
+    BindFramebuffer(target: GLenum, framebuffer: GLuint) {}
+    BindRenderbuffer(target: GLenum, renderbuffer: GLuint) {}
+    BindSampler(unit: GLuint, sampler: GLuint) {}
+    BindTexture(target: GLenum, texture: GLuint) {}
+    BindVertexArray(array: GLuint) {}
+    BlendEquation(mode: GLenum) {}
+    BlendEquationSeparate(modeRGB: GLenum, modeAlpha: GLenum) {}
+    BlendFunc(sfactor: GLenum, dfactor: GLenum) {}
+    BlendFuncSeparate(sfactorRGB: GLenum, dfactorRGB: GLenum, sfactorAlpha: GLenum, dfactorAlpha: GLenum) {}
+    BlitFramebuffer(srcX0: GLint, srcY0: GLint, srcX1: GLint, srcY1: GLint, dstX0: GLint, dstY0: GLint, dstX1: GLint, dstY1: GLint, mask: GLbitfield, filter: GLenum) {}
+    BufferData(target: GLenum, size: GLsizeiptr, data: *const c_void, usage: GLenum) {}
+    BufferStorage(target: GLenum, size: GLsizeiptr, data: *const c_void, flags: GLbitfield) {}
+    BufferSubData(target: GLenum, offset: GLintptr, size: GLsizeiptr, data: *const c_void) {}
+    CheckFramebufferStatus(target: GLenum) -> GLenum { FRAMEBUFFER_COMPLETE }
+    Clear(mask: GLbitfield) {}
+    ClearColor(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {}
+    ClearDepth(depth: GLdouble) {}
+    ClearDepthf(d: GLfloat) {}
+    ClearStencil(s: GLint) {}
+    ClientWaitSync(sync: GLsync, flags: GLbitfield, timeout: GLuint64) -> GLenum { ALREADY_SIGNALED }
+    ColorMask(red: GLboolean, green: GLboolean, blue: GLboolean, alpha: GLboolean) {}
+    CompileShader(shader: GLuint) {}
+    CompressedTexImage3D(target: GLenum, level: GLint, internalformat: GLenum, width: GLsizei, height: GLsizei, depth: GLsizei, border: GLint, imageSize: GLsizei, data: *const c_void) {}
+    CompressedTexSubImage3D(target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, zoffset: GLint, width: GLsizei, height: GLsizei, depth: GLsizei, format: GLenum, imageSize: GLsizei, data: *const c_void) {}
+    CopyImageSubData(srcName: GLuint, srcTarget: GLenum, srcLevel: GLint, srcX: GLint, srcY: GLint, srcZ: GLint, dstName: GLuint, dstTarget: GLenum, dstLevel: GLint, dstX: GLint, dstY: GLint, dstZ: GLint, srcWidth: GLsizei, srcHeight: GLsizei, srcDepth: GLsizei) {}
+    CopyTexSubImage3D(target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, zoffset: GLint, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {}
+    CreateProgram() -> GLuint { rec.new_name() }
+    CreateShader(type_: GLenum) -> GLuint { rec.new_name() }
+    DebugMessageCallback(callback: GLDEBUGPROC, userParam: *const c_void) {}
+    DeleteBuffers(n: GLsizei, buffers: *const GLuint) {}
+    DeleteFramebuffers(n: GLsizei, framebuffers: *const GLuint) {}
+    DeleteProgram(program: GLuint) {}
+    DeleteQueries(n: GLsizei, ids: *const GLuint) {}
+    DeleteRenderbuffers(n: GLsizei, renderbuffers: *const GLuint) {}
+    DeleteSamplers(count: GLsizei, samplers: *const GLuint) {}
+    DeleteShader(shader: GLuint) {}
+    DeleteSync(sync: GLsync) {}
+    DeleteTextures(n: GLsizei, textures: *const GLuint) {}
+    DeleteVertexArrays(n: GLsizei, arrays: *const GLuint) {}
+    DepthFunc(func: GLenum) {}
+    DepthMask(flag: GLboolean) {}
+    DetachShader(program: GLuint, shader: GLuint) {}
+    Disable(cap: GLenum) {}
+    DrawArrays(mode: GLenum, first: GLint, count: GLsizei) {}
+    DrawArraysInstanced(mode: GLenum, first: GLint, count: GLsizei, instancecount: GLsizei) {}
+    DrawArraysInstancedBaseInstance(mode: GLenum, first: GLint, count: GLsizei, instancecount: GLsizei, baseinstance: GLuint) {}
+    DrawElementsInstanced(mode: GLenum, count: GLsizei, type_: GLenum, indices: *const c_void, instancecount: GLsizei) {}
+    DrawElementsInstancedBaseInstance(mode: GLenum, count: GLsizei, type_: GLenum, indices: *const c_void, instancecount: GLsizei, baseinstance: GLuint) {}
+    Enable(cap: GLenum) {}
+    EnableVertexAttribArray(index: GLuint) {}
+    EndQuery(target: GLenum) {}
+    FenceSync(condition: GLenum, flags: GLbitfield) -> GLsync { rec.new_name() as usize as GLsync }
+    FramebufferRenderbuffer(target: GLenum, attachment: GLenum, renderbuffertarget: GLenum, renderbuffer: GLuint) {}
+    FramebufferTexture2D(target: GLenum, attachment: GLenum, textarget: GLenum, texture: GLuint, level: GLint) {}
+    FramebufferTextureLayer(target: GLenum, attachment: GLenum, texture: GLuint, level: GLint, layer: GLint) {}
+    GenBuffers(n: GLsizei, buffers: *mut GLuint) { rec.gen_names(n, buffers) }
+    GenFramebuffers(n: GLsizei, framebuffers: *mut GLuint) { rec.gen_names(n, framebuffers) }
+    GenQueries(n: GLsizei, ids: *mut GLuint) { rec.gen_names(n, ids) }
+    GenRenderbuffers(n: GLsizei, renderbuffers: *mut GLuint) { rec.gen_names(n, renderbuffers) }
+    GenSamplers(count: GLsizei, samplers: *mut GLuint) { rec.gen_names(count, samplers) }
+    GenTextures(n: GLsizei, textures: *mut GLuint) { rec.gen_names(n, textures) }
+    GenVertexArrays(n: GLsizei, arrays: *mut GLuint) { rec.gen_names(n, arrays) }
+    GenerateMipmap(target: GLenum) {}
+    GetActiveAttrib(program: GLuint, index: GLuint, bufSize: GLsizei, length: *mut GLsizei, size: *mut GLint, type_: *mut GLenum, name: *mut GLchar) { empty_info_log(bufSize, length, name) }
+    GetAttribLocation(program: GLuint, name: *const GLchar) -> GLint { -1 }
+    GetError() -> GLenum { NO_ERROR }
+    GetFloatv(pname: GLenum, data: *mut GLfloat) { *data = 0.0 }
+    GetIntegerv(pname: GLenum, data: *mut GLint) { rec.get_integerv(pname, data) }
+    GetProgramInfoLog(program: GLuint, bufSize: GLsizei, length: *mut GLsizei, infoLog: *mut GLchar) { empty_info_log(bufSize, length, infoLog) }
+    GetProgramiv(program: GLuint, pname: GLenum, params: *mut GLint) { *params = if pname == LINK_STATUS { TRUE as GLint } else { 0 } }
+    GetQueryObjectiv(id: GLuint, pname: GLenum, params: *mut GLint) { *params = TRUE as GLint }
+    GetQueryObjectui64v(id: GLuint, pname: GLenum, params: *mut GLuint64) { *params = 0 }
+    GetShaderInfoLog(shader: GLuint, bufSize: GLsizei, length: *mut GLsizei, infoLog: *mut GLchar) { empty_info_log(bufSize, length, infoLog) }
+    GetShaderiv(shader: GLuint, pname: GLenum, params: *mut GLint) { *params = if pname == COMPILE_STATUS { TRUE as GLint } else { 0 } }
+    GetString(name: GLenum) -> *const GLubyte { b"sprowl RecordingBackend\0".as_ptr() }
+    GetUniformLocation(program: GLuint, name: *const GLchar) -> GLint { rec.new_name() as GLint }
+    IsEnabled(cap: GLenum) -> GLboolean { FALSE }
+    LinkProgram(program: GLuint) {}
+    MapBufferRange(target: GLenum, offset: GLintptr, length: GLsizeiptr, access: GLbitfield) -> *mut c_void { std::ptr::null_mut() }
+    PixelStorei(pname: GLenum, param: GLint) {}
+    ReadPixels(x: GLint, y: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *mut c_void) {}
+    RenderbufferStorage(target: GLenum, internalformat: GLenum, width: GLsizei, height: GLsizei) {}
+    SamplerParameterf(sampler: GLuint, pname: GLenum, param: GLfloat) {}
+    SamplerParameteri(sampler: GLuint, pname: GLenum, param: GLint) {}
+    Scissor(x: GLint, y: GLint, width: GLsizei, height: GLsizei) {}
+    ShaderSource(shader: GLuint, count: GLsizei, string: *const *const GLchar, length: *const GLint) {}
+    StencilFunc(func: GLenum, ref_: GLint, mask: GLuint) {}
+    StencilMask(mask: GLuint) {}
+    StencilOp(fail: GLenum, zfail: GLenum, zpass: GLenum) {}
+    TexImage2D(target: GLenum, level: GLint, internalformat: GLint, width: GLsizei, height: GLsizei, border: GLint, format: GLenum, type_: GLenum, pixels: *const c_void) {}
+    TexImage3D(target: GLenum, level: GLint, internalformat: GLint, width: GLsizei, height: GLsizei, depth: GLsizei, border: GLint, format: GLenum, type_: GLenum, pixels: *const c_void) {}
+    TexParameterf(target: GLenum, pname: GLenum, param: GLfloat) {}
+    TexParameteri(target: GLenum, pname: GLenum, param: GLint) {}
+    TexSubImage2D(target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *const c_void) {}
+    TexSubImage3D(target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, zoffset: GLint, width: GLsizei, height: GLsizei, depth: GLsizei, format: GLenum, type_: GLenum, pixels: *const c_void) {}
+    Uniform1f(location: GLint, v0: GLfloat) {}
+    Uniform1fv(location: GLint, count: GLsizei, value: *const GLfloat) {}
+    Uniform1i(location: GLint, v0: GLint) {}
+    Uniform1iv(location: GLint, count: GLsizei, value: *const GLint) {}
+    Uniform1ui(location: GLint, v0: GLuint) {}
+    Uniform1uiv(location: GLint, count: GLsizei, value: *const GLuint) {}
+    Uniform2f(location: GLint, v0: GLfloat, v1: GLfloat) {}
+    Uniform2fv(location: GLint, count: GLsizei, value: *const GLfloat) {}
+    Uniform2i(location: GLint, v0: GLint, v1: GLint) {}
+    Uniform3f(location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {}
+    Uniform3fv(location: GLint, count: GLsizei, value: *const GLfloat) {}
+    Uniform3i(location: GLint, v0: GLint, v1: GLint, v2: GLint) {}
+    Uniform4f(location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat, v3: GLfloat) {}
+    Uniform4fv(location: GLint, count: GLsizei, value: *const GLfloat) {}
+    Uniform4i(location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {}
+    UniformMatrix4fv(location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat) {}
+    UnmapBuffer(target: GLenum) -> GLboolean { TRUE }
+    UseProgram(program: GLuint) {}
+    VertexAttribDivisor(index: GLuint, divisor: GLuint) {}
+    VertexAttribIPointer(index: GLuint, size: GLint, type_: GLenum, stride: GLsizei, pointer: *const c_void) {}
+    VertexAttribPointer(index: GLuint, size: GLint, type_: GLenum, normalized: GLboolean, stride: GLsizei, pointer: *const c_void) {}
+    Viewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei) {}
+}
@@ -0,0 +1,866 @@
+//! A `GlBackend` calling a `glow::Context`, for windowing stacks which don't expose the function
+//! pointers the `gl` crate needs, and for WebGL2.
+//!
+//! glow works with typed objects instead of names, and with slices instead of pointers: the
+//! backend hands out its own names for the objects it creates, and sizes the pointers from the
+//! other arguments, like the driver would. Functions glow (or WebGL2) doesn't have are reported
+//! as not loaded, and the crate does without them.
+
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_void;
+
+use ::glow::HasContext;
+use hashbrown::HashMap;
+
+use super::gl::{self, types::*, GlBackend};
+
+/// Functions which are never called on glow: what needs them checks `is_loaded` first.
+const UNSUPPORTED: &[&str] = &[
+    "BufferStorage",
+    "CopyImageSubData",
+    "DebugMessageCallback",
+    "DrawArraysInstancedBaseInstance",
+    "DrawElementsInstancedBaseInstance",
+    "GetQueryObjectui64v",
+    "MapBufferRange",
+    "UnmapBuffer",
+];
+
+/// Objects of one kind, by the name handed out for them. Names start at 1, 0 being "none".
+#[derive(Debug)]
+struct Names<T> {
+    objects: Vec<Option<T>>,
+    free: Vec<GLuint>,
+}
+
+impl<T> Default for Names<T> {
+    fn default() -> Names<T> {
+        Names { objects: Vec::new(), free: Vec::new() }
+    }
+}
+
+impl<T> Names<T> {
+    fn insert(&mut self, object: T) -> GLuint {
+        match self.free.pop() {
+            Some(name) => {
+                self.objects[name as usize - 1] = Some(object);
+                name
+            },
+            None => {
+                self.objects.push(Some(object));
+                self.objects.len() as GLuint
+            },
+        }
+    }
+
+    fn get(&self, name: GLuint) -> Option<&T> {
+        name.checked_sub(1).and_then(|i| self.objects.get(i as usize)).and_then(Option::as_ref)
+    }
+
+    fn remove(&mut self, name: GLuint) -> Option<T> {
+        let object = name.checked_sub(1).and_then(|i| self.objects.get_mut(i as usize)).and_then(Option::take);
+        if object.is_some() {
+            self.free.push(name);
+        }
+        object
+    }
+}
+
+impl<T: Copy> Names<T> {
+    fn object(&self, name: GLuint) -> Option<T> {
+        self.get(name).copied()
+    }
+}
+
+/// Makes every GL call of the crate on a `glow::Context`.
+///
+/// ```ignore
+/// let context = unsafe { glow::Context::from_loader_function(|s| window.get_proc_address(s)) };
+/// backend::set_backend(Rc::new(GlowBackend::new(context)));
+/// ```
+pub struct GlowBackend<C: HasContext> {
+    context: C,
+    shaders: RefCell<Names<C::Shader>>,
+    programs: RefCell<Names<C::Program>>,
+    buffers: RefCell<Names<C::Buffer>>,
+    vertex_arrays: RefCell<Names<C::VertexArray>>,
+    textures: RefCell<Names<C::Texture>>,
+    samplers: RefCell<Names<C::Sampler>>,
+    framebuffers: RefCell<Names<C::Framebuffer>>,
+    renderbuffers: RefCell<Names<C::Renderbuffer>>,
+    queries: RefCell<Names<C::Query>>,
+    fences: RefCell<Names<C::Fence>>,
+    uniform_locations: RefCell<Names<C::UniformLocation>>,
+    /// uniform locations handed out for every program, freed along with it
+    program_uniforms: RefCell<HashMap<GLuint, Vec<GLuint>>>,
+    /// glow returns `String`s, `glGetString` returns pointers which must stay valid
+    strings: RefCell<HashMap<GLenum, CString>>,
+    // glow can't return the names it doesn't know about, so the bindings which are read back
+    // are tracked here.
+    draw_framebuffer: Cell<GLuint>,
+    read_framebuffer: Cell<GLuint>,
+    pixel_unpack_buffer: Cell<GLuint>,
+    pack_alignment: Cell<GLint>,
+    unpack_alignment: Cell<GLint>,
+}
+
+impl<C: HasContext> std::fmt::Debug for GlowBackend<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("GlowBackend")
+            .field("textures", &self.textures.borrow().objects.len())
+            .field("buffers", &self.buffers.borrow().objects.len())
+            .field("programs", &self.programs.borrow().objects.len())
+            .finish()
+    }
+}
+
+impl<C: HasContext> GlowBackend<C> {
+    /// The context must be current on the thread the backend is set for.
+    pub fn new(context: C) -> GlowBackend<C> {
+        GlowBackend {
+            context,
+            shaders: RefCell::default(),
+            programs: RefCell::default(),
+            buffers: RefCell::default(),
+            vertex_arrays: RefCell::default(),
+            textures: RefCell::default(),
+            samplers: RefCell::default(),
+            framebuffers: RefCell::default(),
+            renderbuffers: RefCell::default(),
+            queries: RefCell::default(),
+            fences: RefCell::default(),
+            uniform_locations: RefCell::default(),
+            program_uniforms: RefCell::default(),
+            strings: RefCell::default(),
+            draw_framebuffer: Cell::new(0),
+            read_framebuffer: Cell::new(0),
+            pixel_unpack_buffer: Cell::new(0),
+            pack_alignment: Cell::new(4),
+            unpack_alignment: Cell::new(4),
+        }
+    }
+
+    #[inline]
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    /// Give the context back, along with the objects created through the backend.
+    pub fn into_context(self) -> C {
+        self.context
+    }
+
+    /// Create `n` objects with `create`, and write their names to `names`.
+    unsafe fn gen<T>(&self, table: &RefCell<Names<T>>, n: GLsizei, names: *mut GLuint, create: impl Fn(&C) -> Result<T, String>) {
+        for i in 0..n.max(0) as usize {
+            *names.add(i) = match create(&self.context) {
+                Ok(object) => table.borrow_mut().insert(object),
+                Err(e) => {
+                    log::error!("glow failed to create an object: {}", e);
+                    0
+                },
+            };
+        }
+    }
+
+    /// Delete the objects named by the `n` first `names`.
+    unsafe fn delete<T>(&self, table: &RefCell<Names<T>>, n: GLsizei, names: *const GLuint, delete: impl Fn(&C, T)) {
+        for i in 0..n.max(0) as usize {
+            if let Some(object) = table.borrow_mut().remove(*names.add(i)) {
+                delete(&self.context, object);
+            }
+        }
+    }
+
+    fn with_uniform<F: FnOnce(&C, Option<&C::UniformLocation>)>(&self, location: GLint, f: F) {
+        let uniform_locations = self.uniform_locations.borrow();
+        f(&self.context, if location < 0 { None } else { uniform_locations.get(location as GLuint) })
+    }
+
+    /// The pixels of a texture upload: an offset in the bound `GL_PIXEL_UNPACK_BUFFER` if any,
+    /// a slice of `size` bytes otherwise.
+    unsafe fn unpack_data<'a>(&self, pixels: *const c_void, size: usize) -> ::glow::PixelUnpackData<'a> {
+        if self.pixel_unpack_buffer.get() != 0 {
+            ::glow::PixelUnpackData::BufferOffset(pixels as usize as u32)
+        } else {
+            ::glow::PixelUnpackData::Slice(std::slice::from_raw_parts(pixels as *const u8, size))
+        }
+    }
+}
+
+/// Size in bytes of `width` x `height` x `depth` pixels, rows being aligned to `alignment`.
+fn image_size(width: GLsizei, height: GLsizei, depth: GLsizei, format: GLenum, type_: GLenum, alignment: GLint) -> usize {
+    let components = match format {
+        // packed depth and stencil (`UNSIGNED_INT_24_8`) is a single 4 byte component
+        gl::RED | gl::RED_INTEGER | gl::DEPTH_COMPONENT | gl::STENCIL_INDEX | gl::DEPTH_STENCIL => 1,
+        gl::RG | gl::RG_INTEGER => 2,
+        gl::RGB | gl::BGR | gl::RGB_INTEGER => 3,
+        _ => 4,
+    };
+    let component_size = match type_ {
+        gl::UNSIGNED_BYTE | gl::BYTE => 1,
+        gl::UNSIGNED_SHORT | gl::SHORT | gl::HALF_FLOAT => 2,
+        _ => 4,
+    };
+    let alignment = alignment.max(1) as usize;
+    let row = width.max(0) as usize * components * component_size;
+    let aligned_row = row.div_ceil(alignment) * alignment;
+    aligned_row * height.max(0) as usize * depth.max(0) as usize
+}
+
+/// Copy `s` to a buffer of `buf_size` bytes, NUL included, like `glGetShaderInfoLog`.
+unsafe fn write_c_string(s: &str, buf_size: GLsizei, length: *mut GLsizei, out: *mut GLchar) {
+    if buf_size <= 0 {
+        return;
+    }
+    let len = s.len().min(buf_size as usize - 1);
+    std::ptr::copy_nonoverlapping(s.as_ptr() as *const GLchar, out, len);
+    *out.add(len) = 0;
+    if !length.is_null() {
+        *length = len as GLsizei;
+    }
+}
+
+unsafe fn c_str<'a>(s: *const GLchar) -> std::borrow::Cow<'a, str> {
+    CStr::from_ptr(s).to_string_lossy()
+}
+
+#[allow(non_snake_case)]
+impl<C: HasContext> GlBackend for GlowBackend<C> {
+    fn is_loaded(&self, function: &'static str) -> bool {
+        !UNSUPPORTED.contains(&function)
+    }
+
+    unsafe fn ActiveTexture(&self, texture: GLenum) {
+        self.context.active_texture(texture)
+    }
+
+    unsafe fn AttachShader(&self, program: GLuint, shader: GLuint) {
+        if let (Some(program), Some(shader)) = (self.programs.borrow().object(program), self.shaders.borrow().object(shader)) {
+            self.context.attach_shader(program, shader);
+        }
+    }
+
+    unsafe fn BeginQuery(&self, target: GLenum, id: GLuint) {
+        if let Some(query) = self.queries.borrow().object(id) {
+            self.context.begin_query(target, query);
+        }
+    }
+
+    unsafe fn BindBuffer(&self, target: GLenum, buffer: GLuint) {
+        if target == gl::PIXEL_UNPACK_BUFFER {
+            self.pixel_unpack_buffer.set(buffer);
+        }
+        self.context.bind_buffer(target, self.buffers.borrow().object(buffer))
+    }
+
+    unsafe fn BindFramebuffer(&self, target: GLenum, framebuffer: GLuint) {
+        if target == gl::FRAMEBUFFER || target == gl::DRAW_FRAMEBUFFER {
+            self.draw_framebuffer.set(framebuffer);
+        }
+        if target == gl::FRAMEBUFFER || target == gl::READ_FRAMEBUFFER {
+            self.read_framebuffer.set(framebuffer);
+        }
+        self.context.bind_framebuffer(target, self.framebuffers.borrow().object(framebuffer))
+    }
+
+    unsafe fn BindRenderbuffer(&self, target: GLenum, renderbuffer: GLuint) {
+        self.context.bind_renderbuffer(target, self.renderbuffers.borrow().object(renderbuffer))
+    }
+
+    unsafe fn BindSampler(&self, unit: GLuint, sampler: GLuint) {
+        self.context.bind_sampler(unit, self.samplers.borrow().object(sampler))
+    }
+
+    unsafe fn BindTexture(&self, target: GLenum, texture: GLuint) {
+        self.context.bind_texture(target, self.textures.borrow().object(texture))
+    }
+
+    unsafe fn BindVertexArray(&self, array: GLuint) {
+        self.context.bind_vertex_array(self.vertex_arrays.borrow().object(array))
+    }
+
+    unsafe fn BlendEquation(&self, mode: GLenum) {
+        self.context.blend_equation(mode)
+    }
+
+    unsafe fn BlendEquationSeparate(&self, mode_rgb: GLenum, mode_alpha: GLenum) {
+        self.context.blend_equation_separate(mode_rgb, mode_alpha)
+    }
+
+    unsafe fn BlendFunc(&self, sfactor: GLenum, dfactor: GLenum) {
+        self.context.blend_func(sfactor, dfactor)
+    }
+
+    unsafe fn BlendFuncSeparate(&self, sfactor_rgb: GLenum, dfactor_rgb: GLenum, sfactor_alpha: GLenum, dfactor_alpha: GLenum) {
+        self.context.blend_func_separate(sfactor_rgb, dfactor_rgb, sfactor_alpha, dfactor_alpha)
+    }
+
+    unsafe fn BlitFramebuffer(&self, src_x0: GLint, src_y0: GLint, src_x1: GLint, src_y1: GLint, dst_x0: GLint, dst_y0: GLint, dst_x1: GLint, dst_y1: GLint, mask: GLbitfield, filter: GLenum) {
+        self.context.blit_framebuffer(src_x0, src_y0, src_x1, src_y1, dst_x0, dst_y0, dst_x1, dst_y1, mask, filter)
+    }
+
+    unsafe fn BufferData(&self, target: GLenum, size: GLsizeiptr, data: *const c_void, usage: GLenum) {
+        if data.is_null() {
+            self.context.buffer_data_size(target, size as i32, usage)
+        } else {
+            self.context.buffer_data_u8_slice(target, std::slice::from_raw_parts(data as *const u8, size as usize), usage)
+        }
+    }
+
+    unsafe fn BufferStorage(&self, _target: GLenum, _size: GLsizeiptr, _data: *const c_void, _flags: GLbitfield) {}
+
+    unsafe fn BufferSubData(&self, target: GLenum, offset: GLintptr, size: GLsizeiptr, data: *const c_void) {
+        self.context.buffer_sub_data_u8_slice(target, offset as i32, std::slice::from_raw_parts(data as *const u8, size as usize))
+    }
+
+    unsafe fn CheckFramebufferStatus(&self, target: GLenum) -> GLenum {
+        self.context.check_framebuffer_status(target)
+    }
+
+    unsafe fn Clear(&self, mask: GLbitfield) {
+        self.context.clear(mask)
+    }
+
+    unsafe fn ClearColor(&self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+        self.context.clear_color(red, green, blue, alpha)
+    }
+
+    unsafe fn ClearDepth(&self, depth: GLdouble) {
+        self.context.clear_depth_f32(depth as f32)
+    }
+
+    unsafe fn ClearDepthf(&self, d: GLfloat) {
+        self.context.clear_depth_f32(d)
+    }
+
+    unsafe fn ClearStencil(&self, s: GLint) {
+        self.context.clear_stencil(s)
+    }
+
+    unsafe fn ClientWaitSync(&self, sync: GLsync, flags: GLbitfield, timeout: GLuint64) -> GLenum {
+        match self.fences.borrow().object(sync as usize as GLuint) {
+            Some(fence) => self.context.client_wait_sync(fence, flags, timeout.min(i32::MAX as u64) as i32),
+            None => gl::WAIT_FAILED,
+        }
+    }
+
+    unsafe fn ColorMask(&self, red: GLboolean, green: GLboolean, blue: GLboolean, alpha: GLboolean) {
+        self.context.color_mask(red != gl::FALSE, green != gl::FALSE, blue != gl::FALSE, alpha != gl::FALSE)
+    }
+
+    unsafe fn CompileShader(&self, shader: GLuint) {
+        if let Some(shader) = self.shaders.borrow().object(shader) {
+            self.context.compile_shader(shader);
+        }
+    }
+
+    unsafe fn CompressedTexImage3D(&self, target: GLenum, level: GLint, internalformat: GLenum, width: GLsizei, height: GLsizei, depth: GLsizei, border: GLint, image_size: GLsizei, data: *const c_void) {
+        let data = if data.is_null() { &[][..] } else { std::slice::from_raw_parts(data as *const u8, image_size as usize) };
+        self.context.compressed_tex_image_3d(target, level, internalformat as i32, width, height, depth, border, image_size, data)
+    }
+
+    unsafe fn CompressedTexSubImage3D(&self, target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, zoffset: GLint, width: GLsizei, height: GLsizei, depth: GLsizei, format: GLenum, image_size: GLsizei, data: *const c_void) {
+        let data = ::glow::CompressedPixelUnpackData::Slice(std::slice::from_raw_parts(data as *const u8, image_size as usize));
+        self.context.compressed_tex_sub_image_3d(target, level, xoffset, yoffset, zoffset, width, height, depth, format, data)
+    }
+
+    unsafe fn CopyImageSubData(&self, _src_name: GLuint, _src_target: GLenum, _src_level: GLint, _src_x: GLint, _src_y: GLint, _src_z: GLint, _dst_name: GLuint, _dst_target: GLenum, _dst_level: GLint, _dst_x: GLint, _dst_y: GLint, _dst_z: GLint, _src_width: GLsizei, _src_height: GLsizei, _src_depth: GLsizei) {}
+
+    unsafe fn CopyTexSubImage3D(&self, target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, zoffset: GLint, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.context.copy_tex_sub_image_3d(target, level, xoffset, yoffset, zoffset, x, y, width, height)
+    }
+
+    unsafe fn CreateProgram(&self) -> GLuint {
+        match self.context.create_program() {
+            Ok(program) => self.programs.borrow_mut().insert(program),
+            Err(e) => {
+                log::error!("glow failed to create a program: {}", e);
+                0
+            },
+        }
+    }
+
+    unsafe fn CreateShader(&self, type_: GLenum) -> GLuint {
+        match self.context.create_shader(type_) {
+            Ok(shader) => self.shaders.borrow_mut().insert(shader),
+            Err(e) => {
+                log::error!("glow failed to create a shader: {}", e);
+                0
+            },
+        }
+    }
+
+    unsafe fn DebugMessageCallback(&self, _callback: GLDEBUGPROC, _user_param: *const c_void) {}
+
+    unsafe fn DeleteBuffers(&self, n: GLsizei, buffers: *const GLuint) {
+        self.delete(&self.buffers, n, buffers, |context, buffer| context.delete_buffer(buffer))
+    }
+
+    unsafe fn DeleteFramebuffers(&self, n: GLsizei, framebuffers: *const GLuint) {
+        self.delete(&self.framebuffers, n, framebuffers, |context, framebuffer| context.delete_framebuffer(framebuffer))
+    }
+
+    unsafe fn DeleteProgram(&self, program: GLuint) {
+        if let Some(locations) = self.program_uniforms.borrow_mut().remove(&program) {
+            let mut uniform_locations = self.uniform_locations.borrow_mut();
+            for location in locations {
+                uniform_locations.remove(location);
+            }
+        }
+        if let Some(program) = self.programs.borrow_mut().remove(program) {
+            self.context.delete_program(program);
+        }
+    }
+
+    unsafe fn DeleteQueries(&self, n: GLsizei, ids: *const GLuint) {
+        self.delete(&self.queries, n, ids, |context, query| context.delete_query(query))
+    }
+
+    unsafe fn DeleteRenderbuffers(&self, n: GLsizei, renderbuffers: *const GLuint) {
+        self.delete(&self.renderbuffers, n, renderbuffers, |context, renderbuffer| context.delete_renderbuffer(renderbuffer))
+    }
+
+    unsafe fn DeleteSamplers(&self, count: GLsizei, samplers: *const GLuint) {
+        self.delete(&self.samplers, count, samplers, |context, sampler| context.delete_sampler(sampler))
+    }
+
+    unsafe fn DeleteShader(&self, shader: GLuint) {
+        if let Some(shader) = self.shaders.borrow_mut().remove(shader) {
+            self.context.delete_shader(shader);
+        }
+    }
+
+    unsafe fn DeleteSync(&self, sync: GLsync) {
+        if let Some(fence) = self.fences.borrow_mut().remove(sync as usize as GLuint) {
+            self.context.delete_sync(fence);
+        }
+    }
+
+    unsafe fn DeleteTextures(&self, n: GLsizei, textures: *const GLuint) {
+        self.delete(&self.textures, n, textures, |context, texture| context.delete_texture(texture))
+    }
+
+    unsafe fn DeleteVertexArrays(&self, n: GLsizei, arrays: *const GLuint) {
+        self.delete(&self.vertex_arrays, n, arrays, |context, array| context.delete_vertex_array(array))
+    }
+
+    unsafe fn DepthFunc(&self, func: GLenum) {
+        self.context.depth_func(func)
+    }
+
+    unsafe fn DepthMask(&self, flag: GLboolean) {
+        self.context.depth_mask(flag != gl::FALSE)
+    }
+
+    unsafe fn DetachShader(&self, program: GLuint, shader: GLuint) {
+        if let (Some(program), Some(shader)) = (self.programs.borrow().object(program), self.shaders.borrow().object(shader)) {
+            self.context.detach_shader(program, shader);
+        }
+    }
+
+    unsafe fn Disable(&self, cap: GLenum) {
+        self.context.disable(cap)
+    }
+
+    unsafe fn DrawArrays(&self, mode: GLenum, first: GLint, count: GLsizei) {
+        self.context.draw_arrays(mode, first, count)
+    }
+
+    unsafe fn DrawArraysInstanced(&self, mode: GLenum, first: GLint, count: GLsizei, instancecount: GLsizei) {
+        self.context.draw_arrays_instanced(mode, first, count, instancecount)
+    }
+
+    unsafe fn DrawArraysInstancedBaseInstance(&self, _mode: GLenum, _first: GLint, _count: GLsizei, _instancecount: GLsizei, _baseinstance: GLuint) {}
+
+    unsafe fn DrawElementsInstanced(&self, mode: GLenum, count: GLsizei, type_: GLenum, indices: *const c_void, instancecount: GLsizei) {
+        self.context.draw_elements_instanced(mode, count, type_, indices as usize as i32, instancecount)
+    }
+
+    unsafe fn DrawElementsInstancedBaseInstance(&self, _mode: GLenum, _count: GLsizei, _type_: GLenum, _indices: *const c_void, _instancecount: GLsizei, _baseinstance: GLuint) {}
+
+    unsafe fn Enable(&self, cap: GLenum) {
+        self.context.enable(cap)
+    }
+
+    unsafe fn EnableVertexAttribArray(&self, index: GLuint) {
+        self.context.enable_vertex_attrib_array(index)
+    }
+
+    unsafe fn EndQuery(&self, target: GLenum) {
+        self.context.end_query(target)
+    }
+
+    unsafe fn FenceSync(&self, condition: GLenum, flags: GLbitfield) -> GLsync {
+        match self.context.fence_sync(condition, flags) {
+            Ok(fence) => self.fences.borrow_mut().insert(fence) as usize as GLsync,
+            Err(e) => {
+                log::error!("glow failed to create a fence: {}", e);
+                std::ptr::null()
+            },
+        }
+    }
+
+    unsafe fn FramebufferRenderbuffer(&self, target: GLenum, attachment: GLenum, renderbuffertarget: GLenum, renderbuffer: GLuint) {
+        self.context.framebuffer_renderbuffer(target, attachment, renderbuffertarget, self.renderbuffers.borrow().object(renderbuffer))
+    }
+
+    unsafe fn FramebufferTexture2D(&self, target: GLenum, attachment: GLenum, textarget: GLenum, texture: GLuint, level: GLint) {
+        self.context.framebuffer_texture_2d(target, attachment, textarget, self.textures.borrow().object(texture), level)
+    }
+
+    unsafe fn FramebufferTextureLayer(&self, target: GLenum, attachment: GLenum, texture: GLuint, level: GLint, layer: GLint) {
+        self.context.framebuffer_texture_layer(target, attachment, self.textures.borrow().object(texture), level, layer)
+    }
+
+    unsafe fn GenBuffers(&self, n: GLsizei, buffers: *mut GLuint) {
+        self.gen(&self.buffers, n, buffers, |context| context.create_buffer())
+    }
+
+    unsafe fn GenFramebuffers(&self, n: GLsizei, framebuffers: *mut GLuint) {
+        self.gen(&self.framebuffers, n, framebuffers, |context| context.create_framebuffer())
+    }
+
+    unsafe fn GenQueries(&self, n: GLsizei, ids: *mut GLuint) {
+        self.gen(&self.queries, n, ids, |context| context.create_query())
+    }
+
+    unsafe fn GenRenderbuffers(&self, n: GLsizei, renderbuffers: *mut GLuint) {
+        self.gen(&self.renderbuffers, n, renderbuffers, |context| context.create_renderbuffer())
+    }
+
+    unsafe fn GenSamplers(&self, count: GLsizei, samplers: *mut GLuint) {
+        self.gen(&self.samplers, count, samplers, |context| context.create_sampler())
+    }
+
+    unsafe fn GenTextures(&self, n: GLsizei, textures: *mut GLuint) {
+        self.gen(&self.textures, n, textures, |context| context.create_texture())
+    }
+
+    unsafe fn GenVertexArrays(&self, n: GLsizei, arrays: *mut GLuint) {
+        self.gen(&self.vertex_arrays, n, arrays, |context| context.create_vertex_array())
+    }
+
+    unsafe fn GenerateMipmap(&self, target: GLenum) {
+        self.context.generate_mipmap(target)
+    }
+
+    unsafe fn GetActiveAttrib(&self, program: GLuint, index: GLuint, buf_size: GLsizei, length: *mut GLsizei, size: *mut GLint, type_: *mut GLenum, name: *mut GLchar) {
+        let attribute = self.programs.borrow().object(program).and_then(|program| self.context.get_active_attribute(program, index));
+        match attribute {
+            Some(attribute) => {
+                *size = attribute.size;
+                *type_ = attribute.atype;
+                write_c_string(&attribute.name, buf_size, length, name);
+            },
+            None => write_c_string("", buf_size, length, name),
+        }
+    }
+
+    unsafe fn GetAttribLocation(&self, program: GLuint, name: *const GLchar) -> GLint {
+        self.programs.borrow().object(program)
+            .and_then(|program| self.context.get_attrib_location(program, &c_str(name)))
+            .map_or(-1, |location| location as GLint)
+    }
+
+    unsafe fn GetError(&self) -> GLenum {
+        self.context.get_error()
+    }
+
+    unsafe fn GetFloatv(&self, pname: GLenum, data: *mut GLfloat) {
+        *data = self.context.get_parameter_f32(pname)
+    }
+
+    unsafe fn GetIntegerv(&self, pname: GLenum, data: *mut GLint) {
+        match pname {
+            gl::DRAW_FRAMEBUFFER_BINDING => *data = self.draw_framebuffer.get() as GLint,
+            gl::READ_FRAMEBUFFER_BINDING => *data = self.read_framebuffer.get() as GLint,
+            gl::PACK_ALIGNMENT => *data = self.pack_alignment.get(),
+            gl::UNPACK_ALIGNMENT => *data = self.unpack_alignment.get(),
+            gl::VIEWPORT | gl::SCISSOR_BOX => self.context.get_parameter_i32_slice(pname, std::slice::from_raw_parts_mut(data, 4)),
+            _ => *data = self.context.get_parameter_i32(pname),
+        }
+    }
+
+    unsafe fn GetProgramInfoLog(&self, program: GLuint, buf_size: GLsizei, length: *mut GLsizei, info_log: *mut GLchar) {
+        let log = self.programs.borrow().object(program).map(|program| self.context.get_program_info_log(program)).unwrap_or_default();
+        write_c_string(&log, buf_size, length, info_log)
+    }
+
+    unsafe fn GetProgramiv(&self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        let program = match self.programs.borrow().object(program) {
+            Some(program) => program,
+            None => return,
+        };
+        *params = match pname {
+            gl::LINK_STATUS => self.context.get_program_link_status(program) as GLint,
+            gl::INFO_LOG_LENGTH => self.context.get_program_info_log(program).len() as GLint + 1,
+            gl::ACTIVE_ATTRIBUTES => self.context.get_active_attributes(program) as GLint,
+            gl::ACTIVE_ATTRIBUTE_MAX_LENGTH => (0..self.context.get_active_attributes(program))
+                .filter_map(|i| self.context.get_active_attribute(program, i))
+                .map(|attribute| attribute.name.len() as GLint + 1)
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        };
+    }
+
+    unsafe fn GetQueryObjectiv(&self, id: GLuint, pname: GLenum, params: *mut GLint) {
+        if let Some(query) = self.queries.borrow().object(id) {
+            *params = self.context.get_query_parameter_u32(query, pname) as GLint;
+        }
+    }
+
+    unsafe fn GetQueryObjectui64v(&self, _id: GLuint, _pname: GLenum, params: *mut GLuint64) {
+        *params = 0
+    }
+
+    unsafe fn GetShaderInfoLog(&self, shader: GLuint, buf_size: GLsizei, length: *mut GLsizei, info_log: *mut GLchar) {
+        let log = self.shaders.borrow().object(shader).map(|shader| self.context.get_shader_info_log(shader)).unwrap_or_default();
+        write_c_string(&log, buf_size, length, info_log)
+    }
+
+    unsafe fn GetShaderiv(&self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        let shader = match self.shaders.borrow().object(shader) {
+            Some(shader) => shader,
+            None => return,
+        };
+        *params = match pname {
+            gl::COMPILE_STATUS => self.context.get_shader_compile_status(shader) as GLint,
+            gl::INFO_LOG_LENGTH => self.context.get_shader_info_log(shader).len() as GLint + 1,
+            _ => 0,
+        };
+    }
+
+    unsafe fn GetString(&self, name: GLenum) -> *const GLubyte {
+        let mut strings = self.strings.borrow_mut();
+        let string = strings.entry(name).or_insert_with(|| {
+            let string = self.context.get_parameter_string(name);
+            CString::new(string.replace('\0', "")).unwrap_or_default()
+        });
+        // the CString is never replaced nor dropped before the backend, so its buffer stays put.
+        string.as_ptr() as *const GLubyte
+    }
+
+    unsafe fn GetUniformLocation(&self, program: GLuint, name: *const GLchar) -> GLint {
+        let location = self.programs.borrow().object(program)
+            .and_then(|program_object| self.context.get_uniform_location(program_object, &c_str(name)));
+        match location {
+            Some(location) => {
+                let name = self.uniform_locations.borrow_mut().insert(location);
+                self.program_uniforms.borrow_mut().entry(program).or_default().push(name);
+                name as GLint
+            },
+            None => -1,
+        }
+    }
+
+    unsafe fn IsEnabled(&self, cap: GLenum) -> GLboolean {
+        if self.context.is_enabled(cap) { gl::TRUE } else { gl::FALSE }
+    }
+
+    unsafe fn LinkProgram(&self, program: GLuint) {
+        if let Some(program) = self.programs.borrow().object(program) {
+            self.context.link_program(program);
+        }
+    }
+
+    unsafe fn MapBufferRange(&self, _target: GLenum, _offset: GLintptr, _length: GLsizeiptr, _access: GLbitfield) -> *mut c_void {
+        std::ptr::null_mut()
+    }
+
+    unsafe fn PixelStorei(&self, pname: GLenum, param: GLint) {
+        match pname {
+            gl::PACK_ALIGNMENT => self.pack_alignment.set(param),
+            gl::UNPACK_ALIGNMENT => self.unpack_alignment.set(param),
+            _ => {},
+        }
+        self.context.pixel_store_i32(pname, param)
+    }
+
+    unsafe fn ReadPixels(&self, x: GLint, y: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *mut c_void) {
+        let size = image_size(width, height, 1, format, type_, self.pack_alignment.get());
+        let pixels = ::glow::PixelPackData::Slice(std::slice::from_raw_parts_mut(pixels as *mut u8, size));
+        self.context.read_pixels(x, y, width, height, format, type_, pixels)
+    }
+
+    unsafe fn RenderbufferStorage(&self, target: GLenum, internalformat: GLenum, width: GLsizei, height: GLsizei) {
+        self.context.renderbuffer_storage(target, internalformat, width, height)
+    }
+
+    unsafe fn SamplerParameterf(&self, sampler: GLuint, pname: GLenum, param: GLfloat) {
+        if let Some(sampler) = self.samplers.borrow().object(sampler) {
+            self.context.sampler_parameter_f32(sampler, pname, param);
+        }
+    }
+
+    unsafe fn SamplerParameteri(&self, sampler: GLuint, pname: GLenum, param: GLint) {
+        if let Some(sampler) = self.samplers.borrow().object(sampler) {
+            self.context.sampler_parameter_i32(sampler, pname, param);
+        }
+    }
+
+    unsafe fn Scissor(&self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.context.scissor(x, y, width, height)
+    }
+
+    unsafe fn ShaderSource(&self, shader: GLuint, count: GLsizei, string: *const *const GLchar, length: *const GLint) {
+        let mut source = String::new();
+        for i in 0..count.max(0) as usize {
+            let part = *string.add(i);
+            if length.is_null() || *length.add(i) < 0 {
+                source.push_str(&c_str(part));
+            } else {
+                let bytes = std::slice::from_raw_parts(part as *const u8, *length.add(i) as usize);
+                source.push_str(&String::from_utf8_lossy(bytes));
+            }
+        }
+        if let Some(shader) = self.shaders.borrow().object(shader) {
+            self.context.shader_source(shader, &source);
+        }
+    }
+
+    unsafe fn StencilFunc(&self, func: GLenum, ref_: GLint, mask: GLuint) {
+        self.context.stencil_func(func, ref_, mask)
+    }
+
+    unsafe fn StencilMask(&self, mask: GLuint) {
+        self.context.stencil_mask(mask)
+    }
+
+    unsafe fn StencilOp(&self, fail: GLenum, zfail: GLenum, zpass: GLenum) {
+        self.context.stencil_op(fail, zfail, zpass)
+    }
+
+    unsafe fn TexImage2D(&self, target: GLenum, level: GLint, internalformat: GLint, width: GLsizei, height: GLsizei, border: GLint, format: GLenum, type_: GLenum, pixels: *const c_void) {
+        let pixels = if pixels.is_null() {
+            None
+        } else {
+            Some(std::slice::from_raw_parts(pixels as *const u8, image_size(width, height, 1, format, type_, self.unpack_alignment.get())))
+        };
+        self.context.tex_image_2d(target, level, internalformat, width, height, border, format, type_, pixels)
+    }
+
+    unsafe fn TexImage3D(&self, target: GLenum, level: GLint, internalformat: GLint, width: GLsizei, height: GLsizei, depth: GLsizei, border: GLint, format: GLenum, type_: GLenum, pixels: *const c_void) {
+        let pixels = if pixels.is_null() {
+            None
+        } else {
+            Some(std::slice::from_raw_parts(pixels as *const u8, image_size(width, height, depth, format, type_, self.unpack_alignment.get())))
+        };
+        self.context.tex_image_3d(target, level, internalformat, width, height, depth, border, format, type_, pixels)
+    }
+
+    unsafe fn TexParameterf(&self, target: GLenum, pname: GLenum, param: GLfloat) {
+        self.context.tex_parameter_f32(target, pname, param)
+    }
+
+    unsafe fn TexParameteri(&self, target: GLenum, pname: GLenum, param: GLint) {
+        self.context.tex_parameter_i32(target, pname, param)
+    }
+
+    unsafe fn TexSubImage2D(&self, target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *const c_void) {
+        let pixels = self.unpack_data(pixels, image_size(width, height, 1, format, type_, self.unpack_alignment.get()));
+        self.context.tex_sub_image_2d(target, level, xoffset, yoffset, width, height, format, type_, pixels)
+    }
+
+    unsafe fn TexSubImage3D(&self, target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, zoffset: GLint, width: GLsizei, height: GLsizei, depth: GLsizei, format: GLenum, type_: GLenum, pixels: *const c_void) {
+        let pixels = self.unpack_data(pixels, image_size(width, height, depth, format, type_, self.unpack_alignment.get()));
+        self.context.tex_sub_image_3d(target, level, xoffset, yoffset, zoffset, width, height, depth, format, type_, pixels)
+    }
+
+    unsafe fn Uniform1f(&self, location: GLint, v0: GLfloat) {
+        self.with_uniform(location, |context, location| context.uniform_1_f32(location, v0))
+    }
+
+    unsafe fn Uniform1fv(&self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        let values = std::slice::from_raw_parts(value, count.max(0) as usize);
+        self.with_uniform(location, |context, location| context.uniform_1_f32_slice(location, values))
+    }
+
+    unsafe fn Uniform1i(&self, location: GLint, v0: GLint) {
+        self.with_uniform(location, |context, location| context.uniform_1_i32(location, v0))
+    }
+
+    unsafe fn Uniform1iv(&self, location: GLint, count: GLsizei, value: *const GLint) {
+        let values = std::slice::from_raw_parts(value, count.max(0) as usize);
+        self.with_uniform(location, |context, location| context.uniform_1_i32_slice(location, values))
+    }
+
+    unsafe fn Uniform1ui(&self, location: GLint, v0: GLuint) {
+        self.with_uniform(location, |context, location| context.uniform_1_u32(location, v0))
+    }
+
+    unsafe fn Uniform1uiv(&self, location: GLint, count: GLsizei, value: *const GLuint) {
+        let values = std::slice::from_raw_parts(value, count.max(0) as usize);
+        self.with_uniform(location, |context, location| context.uniform_1_u32_slice(location, values))
+    }
+
+    unsafe fn Uniform2f(&self, location: GLint, v0: GLfloat, v1: GLfloat) {
+        self.with_uniform(location, |context, location| context.uniform_2_f32(location, v0, v1))
+    }
+
+    unsafe fn Uniform2fv(&self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        let values = std::slice::from_raw_parts(value, count.max(0) as usize * 2);
+        self.with_uniform(location, |context, location| context.uniform_2_f32_slice(location, values))
+    }
+
+    unsafe fn Uniform2i(&self, location: GLint, v0: GLint, v1: GLint) {
+        self.with_uniform(location, |context, location| context.uniform_2_i32(location, v0, v1))
+    }
+
+    unsafe fn Uniform3f(&self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+        self.with_uniform(location, |context, location| context.uniform_3_f32(location, v0, v1, v2))
+    }
+
+    unsafe fn Uniform3fv(&self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        let values = std::slice::from_raw_parts(value, count.max(0) as usize * 3);
+        self.with_uniform(location, |context, location| context.uniform_3_f32_slice(location, values))
+    }
+
+    unsafe fn Uniform3i(&self, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+        self.with_uniform(location, |context, location| context.uniform_3_i32(location, v0, v1, v2))
+    }
+
+    unsafe fn Uniform4f(&self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat, v3: GLfloat) {
+        self.with_uniform(location, |context, location| context.uniform_4_f32(location, v0, v1, v2, v3))
+    }
+
+    unsafe fn Uniform4fv(&self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        let values = std::slice::from_raw_parts(value, count.max(0) as usize * 4);
+        self.with_uniform(location, |context, location| context.uniform_4_f32_slice(location, values))
+    }
+
+    unsafe fn Uniform4i(&self, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+        self.with_uniform(location, |context, location| context.uniform_4_i32(location, v0, v1, v2, v3))
+    }
+
+    unsafe fn UniformMatrix4fv(&self, location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat) {
+        let values = std::slice::from_raw_parts(value, count.max(0) as usize * 16);
+        self.with_uniform(location, |context, location| context.uniform_matrix_4_f32_slice(location, transpose != gl::FALSE, values))
+    }
+
+    unsafe fn UnmapBuffer(&self, _target: GLenum) -> GLboolean {
+        gl::FALSE
+    }
+
+    unsafe fn UseProgram(&self, program: GLuint) {
+        self.context.use_program(self.programs.borrow().object(program))
+    }
+
+    unsafe fn VertexAttribDivisor(&self, index: GLuint, divisor: GLuint) {
+        self.context.vertex_attrib_divisor(index, divisor)
+    }
+
+    unsafe fn VertexAttribIPointer(&self, index: GLuint, size: GLint, type_: GLenum, stride: GLsizei, pointer: *const c_void) {
+        self.context.vertex_attrib_pointer_i32(index, size, type_, stride, pointer as usize as i32)
+    }
+
+    unsafe fn VertexAttribPointer(&self, index: GLuint, size: GLint, type_: GLenum, normalized: GLboolean, stride: GLsizei, pointer: *const c_void) {
+        self.context.vertex_attrib_pointer_f32(index, size, type_, normalized != gl::FALSE, stride, pointer as usize as i32)
+    }
+
+    unsafe fn Viewport(&self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.context.viewport(x, y, width, height)
+    }
+}
@@ -0,0 +1,64 @@
+//! Every OpenGL call of the crate goes through `backend::gl`, which mirrors the `gl` crate, but
+//! calls the `GlBackend` of the current thread instead.
+//!
+//! By default that's the `gl` crate itself. Set a `RecordingBackend` with `set_backend` to run
+//! the crate without a GL context: in unit tests on a CI machine, or to dry-run your draw
+//! submission and look at the calls it would make.
+//!
+//! With the `glow` feature, a `GlowBackend` makes the calls on a `glow::Context` instead, for
+//! windowing stacks which only give you one, and for WebGL2.
+//!
+//! With the `gl-debug` feature, every call is checked for errors, which are logged with the
+//! name and the arguments of the failing function, see `install_debug_callback`.
+//!
+//! ```ignore
+//! let recorder = Rc::new(RecordingBackend::new());
+//! backend::set_backend(recorder.clone());
+//! let mut renderer = RendererBuilder::new(1024).with_instanced_vertex_attrib(4, gl::FLOAT, 1).build_with(shader);
+//! renderer.add_elem(&my_sprite);
+//! renderer.draw();
+//! assert_eq!(recorder.count("DrawArraysInstanced"), 1);
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub mod gl;
+mod recording;
+#[cfg(feature = "gl-debug")]
+pub (crate) mod debug;
+#[cfg(feature = "glow")]
+mod glow_backend;
+
+pub use self::gl::GlBackend;
+pub use recording::*;
+#[cfg(feature = "gl-debug")]
+pub use debug::install_debug_callback;
+#[cfg(feature = "glow")]
+pub use glow_backend::GlowBackend;
+
+/// Calls the `gl` crate, whose functions must have been loaded with `gl::load_with`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeBackend;
+
+thread_local! {
+    // None is the native backend, to avoid going through a `Rc` in the common case.
+    static BACKEND: RefCell<Option<Rc<dyn GlBackend>>> = RefCell::new(None);
+}
+
+/// Make every GL call of the crate on this thread go through `backend`.
+pub fn set_backend(backend: Rc<dyn GlBackend>) {
+    BACKEND.with(|current| *current.borrow_mut() = Some(backend));
+}
+
+/// Go back to calling the `gl` crate on this thread.
+pub fn reset_backend() {
+    BACKEND.with(|current| *current.borrow_mut() = None);
+}
+
+pub (crate) fn with_backend<R, F: FnOnce(&dyn GlBackend) -> R>(f: F) -> R {
+    BACKEND.with(|current| match &*current.borrow() {
+        Some(backend) => f(&**backend),
+        None => f(&NativeBackend),
+    })
+}
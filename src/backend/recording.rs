@@ -0,0 +1,95 @@
+use std::cell::{Cell, RefCell};
+
+use super::gl::{self, types::*};
+
+/// A call recorded by a `RecordingBackend`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlCall {
+    /// Name of the function, without the `gl` prefix: "DrawArraysInstanced".
+    pub function: &'static str,
+    /// Arguments formatted with `Debug`, separated by commas.
+    pub args: String,
+}
+
+/// A backend which records every call instead of making it, so that the crate can run without
+/// a GL context.
+///
+/// Calls that return something return a plausible value: new names for `GenTextures` or
+/// `CreateProgram`, successful compilations, no errors, and so on. Mapping a buffer fails, so
+/// the renderer never uses persistent mapping.
+#[derive(Debug)]
+pub struct RecordingBackend {
+    calls: RefCell<Vec<GlCall>>,
+    next_name: Cell<GLuint>,
+    /// (width, height) returned for `GL_VIEWPORT`
+    viewport: (GLint, GLint),
+}
+
+impl Default for RecordingBackend {
+    fn default() -> RecordingBackend {
+        RecordingBackend::with_viewport(800, 600)
+    }
+}
+
+impl RecordingBackend {
+    /// A recorder with a viewport of 800x600.
+    pub fn new() -> RecordingBackend {
+        RecordingBackend::default()
+    }
+
+    pub fn with_viewport(width: i32, height: i32) -> RecordingBackend {
+        RecordingBackend {
+            calls: RefCell::new(Vec::new()),
+            next_name: Cell::new(1),
+            viewport: (width, height),
+        }
+    }
+
+    /// Every call recorded so far, in order.
+    pub fn calls(&self) -> Vec<GlCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// Returns the calls recorded so far, and forgets them.
+    pub fn take_calls(&self) -> Vec<GlCall> {
+        std::mem::take(&mut *self.calls.borrow_mut())
+    }
+
+    /// Number of calls to `function` recorded so far.
+    pub fn count(&self, function: &str) -> usize {
+        self.calls.borrow().iter().filter(|call| call.function == function).count()
+    }
+
+    pub fn clear(&self) {
+        self.calls.borrow_mut().clear();
+    }
+
+    pub (crate) fn record(&self, function: &'static str, args: String) {
+        self.calls.borrow_mut().push(GlCall { function, args });
+    }
+
+    pub (crate) fn new_name(&self) -> GLuint {
+        let name = self.next_name.get();
+        self.next_name.set(name + 1);
+        name
+    }
+
+    /// Fill `names` with `n` new names, like `glGenTextures` and the like.
+    pub (crate) unsafe fn gen_names(&self, n: GLsizei, names: *mut GLuint) {
+        for i in 0..n.max(0) as usize {
+            *names.add(i) = self.new_name();
+        }
+    }
+
+    pub (crate) unsafe fn get_integerv(&self, pname: GLenum, data: *mut GLint) {
+        match pname {
+            gl::VIEWPORT => {
+                let viewport = [0, 0, self.viewport.0, self.viewport.1];
+                std::ptr::copy_nonoverlapping(viewport.as_ptr(), data, 4);
+            },
+            gl::MAX_ARRAY_TEXTURE_LAYERS => *data = 2048,
+            gl::MAX_TEXTURE_SIZE => *data = 16384,
+            _ => *data = 0,
+        }
+    }
+}
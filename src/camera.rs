@@ -0,0 +1,130 @@
+//! A 2D camera, to avoid re-implementing the view matrix, zoom and letterboxing in every game.
+
+use cgmath::{Deg, Matrix4, Vector2, Vector3};
+
+/// An orthographic camera looking at `position`, with the y axis pointing down.
+///
+/// Give `view_matrix()` to your shader (typically with `Shader::set_matrix4`), and use
+/// `screen_to_world` to know what is under the mouse.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera2D {
+    /// The point of the world at the center of the screen.
+    pub position: Vector2<f32>,
+    /// 2.0 makes everything twice as big.
+    pub zoom: f32,
+    /// Rotation of the camera, in degrees.
+    pub rotation: f32,
+    viewport_size: Vector2<f32>,
+    virtual_size: Option<Vector2<f32>>,
+}
+
+impl Camera2D {
+    /// Create a camera for a viewport of the given size, in pixels.
+    ///
+    /// The camera is placed so that the world matches the screen: (0, 0) is the top left corner.
+    pub fn new(viewport_width: u32, viewport_height: u32) -> Camera2D {
+        let viewport_size = Vector2::new(viewport_width as f32, viewport_height as f32);
+        Camera2D {
+            position: viewport_size / 2.0,
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport_size,
+            virtual_size: None,
+        }
+    }
+
+    /// Always show (at least) an area of this size, whatever the size of the viewport, and keep
+    /// its aspect ratio. Use `letterbox` to know which part of the screen it takes.
+    pub fn with_virtual_size(mut self, width: f32, height: f32) -> Camera2D {
+        self.virtual_size = Some(Vector2::new(width, height));
+        self
+    }
+
+    /// Call this when the window is resized, along with `Renderer::set_viewport`.
+    pub fn set_viewport_size(&mut self, width: u32, height: u32) {
+        self.viewport_size = Vector2::new(width as f32, height as f32);
+    }
+
+    #[inline]
+    pub fn viewport_size(&self) -> Vector2<f32> {
+        self.viewport_size
+    }
+
+    /// The zoom actually used, which takes the virtual size into account.
+    pub fn effective_zoom(&self) -> f32 {
+        match self.virtual_size {
+            Some(virtual_size) => self.zoom * self.virtual_scale(virtual_size),
+            None => self.zoom,
+        }
+    }
+
+    fn virtual_scale(&self, virtual_size: Vector2<f32>) -> f32 {
+        (self.viewport_size.x / virtual_size.x).min(self.viewport_size.y / virtual_size.y)
+    }
+
+    /// The part of the screen used by the virtual area, in pixels (x, y, w, h). Without a virtual
+    /// size, this is the whole viewport.
+    ///
+    /// You can give it to `Renderer::push_clip_rect` to draw black bars around the game.
+    pub fn letterbox(&self) -> (i32, i32, u32, u32) {
+        match self.virtual_size {
+            Some(virtual_size) => {
+                let size = virtual_size * self.virtual_scale(virtual_size);
+                let origin = (self.viewport_size - size) / 2.0;
+                (origin.x.round() as i32, origin.y.round() as i32, size.x.round() as u32, size.y.round() as u32)
+            },
+            None => (0, 0, self.viewport_size.x as u32, self.viewport_size.y as u32),
+        }
+    }
+
+    /// Returns the matrix converting world coordinates to OpenGL's clip space.
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let projection = Matrix4::<f32>::from(cgmath::Ortho {
+            left: 0.0,
+            right: self.viewport_size.x,
+            bottom: self.viewport_size.y,
+            top: 0.0,
+            near: -1.0,
+            far: 1.0,
+        });
+        let zoom = self.effective_zoom();
+        projection
+            * Matrix4::from_translation((self.viewport_size / 2.0).extend(0.0))
+            * Matrix4::from_angle_z(Deg(-self.rotation))
+            * Matrix4::from_nonuniform_scale(zoom, zoom, 1.0)
+            * Matrix4::from_translation(Vector3::new(-self.position.x, -self.position.y, 0.0))
+    }
+
+    fn rotate(v: Vector2<f32>, degrees: f32) -> Vector2<f32> {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+    }
+
+    /// Converts a position in the world to a position on the screen, in pixels.
+    pub fn world_to_screen(&self, world: Vector2<f32>) -> Vector2<f32> {
+        Self::rotate(world - self.position, -self.rotation) * self.effective_zoom() + self.viewport_size / 2.0
+    }
+
+    /// Converts a position on the screen (e.g. the mouse) in pixels, to a position in the world.
+    pub fn screen_to_world(&self, screen: Vector2<f32>) -> Vector2<f32> {
+        Self::rotate((screen - self.viewport_size / 2.0) / self.effective_zoom(), self.rotation) + self.position
+    }
+
+    /// The part of the world visible on the screen: (x, y, w, h), enlarged to contain the whole
+    /// screen when the camera is rotated.
+    pub fn visible_bounds(&self) -> (f32, f32, f32, f32) {
+        let corners = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(self.viewport_size.x, 0.0),
+            Vector2::new(0.0, self.viewport_size.y),
+            self.viewport_size,
+        ];
+        let (mut min, mut max) = (Vector2::new(f32::MAX, f32::MAX), Vector2::new(f32::MIN, f32::MIN));
+        for corner in &corners {
+            let world = self.screen_to_world(*corner);
+            min = Vector2::new(min.x.min(world.x), min.y.min(world.y));
+            max = Vector2::new(max.x.max(world.x), max.y.max(world.y));
+        }
+        (min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+}
@@ -104,4 +104,132 @@ impl Color<u8> {
             a: f32::from(self.a) / 255.0,
         }
     }
-}
\ No newline at end of file
+}
+
+/// HSL and HSV helpers. Hues are in degrees, saturation, lightness and value go from 0 to 1.
+///
+/// For `Color<u8>`, go through `to_color_f32` and `to_color_u8`.
+impl Color<f32> {
+    pub fn to_color_u8(self) -> Color<u8> {
+        let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Color {
+            r: to_u8(self.r),
+            g: to_u8(self.g),
+            b: to_u8(self.b),
+            a: to_u8(self.a),
+        }
+    }
+
+    /// Opaque color from hue, saturation and lightness.
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Color<f32> {
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Self::from_hue_chroma(h, chroma, l - chroma / 2.0)
+    }
+
+    /// Opaque color from hue, saturation and value.
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Color<f32> {
+        let chroma = v * s;
+        Self::from_hue_chroma(h, chroma, v - chroma)
+    }
+
+    // `m` is what is added to every component to match the lightness/value.
+    fn from_hue_chroma(h: f32, chroma: f32, m: f32) -> Color<f32> {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Color::from_rgb(r + m, g + m, b + m)
+    }
+
+    /// Returns the hue, and the min and max of the components.
+    fn hue_min_max(self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let delta = max - min;
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / delta).rem_euclid(6.0)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / delta + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / delta + 4.0)
+        };
+        (hue, min, max)
+    }
+
+    /// Returns (hue, saturation, lightness). Alpha is ignored.
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (h, min, max) = self.hue_min_max();
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        (h, s, l)
+    }
+
+    /// Returns (hue, saturation, value). Alpha is ignored.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (h, min, max) = self.hue_min_max();
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+        (h, s, max)
+    }
+
+    /// Increase the lightness by `amount` (from 0 to 1), keeping the alpha.
+    pub fn lighten(self, amount: f32) -> Color<f32> {
+        let (h, s, l) = self.to_hsl();
+        Color::from_hsl(h, s, (l + amount).clamp(0.0, 1.0)).with_alpha(self.a)
+    }
+
+    /// Decrease the lightness by `amount` (from 0 to 1), keeping the alpha.
+    pub fn darken(self, amount: f32) -> Color<f32> {
+        self.lighten(-amount)
+    }
+
+    /// Rotate the hue by `degrees`, keeping the alpha. 180 gives the complementary color.
+    pub fn rotate_hue(self, degrees: f32) -> Color<f32> {
+        let (h, s, l) = self.to_hsl();
+        Color::from_hsl(h + degrees, s, l).with_alpha(self.a)
+    }
+
+    /// Linear interpolation in RGBA: `t` = 0 returns `self`, `t` = 1 returns `other`.
+    pub fn lerp(self, other: Color<f32>, t: f32) -> Color<f32> {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Color {
+            r: lerp(self.r, other.r),
+            g: lerp(self.g, other.g),
+            b: lerp(self.b, other.b),
+            a: lerp(self.a, other.a),
+        }
+    }
+}
+
+/// Whether the RGB of the textures is multiplied by their alpha, see
+/// `RenderStorageConfig::with_alpha_mode` and `RendererBuilder::with_alpha_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AlphaMode {
+    /// Textures are stored as they are loaded.
+    #[default]
+    Straight,
+    /// Textures are premultiplied when uploaded, and drawn with `(ONE, ONE_MINUS_SRC_ALPHA)`:
+    /// filtered edges blend with their transparent neighbors without dark halos.
+    Premultiplied,
+}
+
+/// Multiply the RGB of RGBA pixels by their alpha, in place.
+pub fn premultiply_alpha(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let a = u16::from(pixel[3]);
+        for c in &mut pixel[..3] {
+            *c = ((u16::from(*c) * a + 127) / 255) as u8;
+        }
+    }
+}
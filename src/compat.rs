@@ -0,0 +1,140 @@
+//! Stable names for the types of our dependencies which appear in the public API.
+//!
+//! `cgmath`, `rusttype` and `image` are still re-exported at the root of the crate, but if you only
+//! use the names and functions defined here, upgrading them (or switching to other crates) in
+//! sprowl should not break your code.
+
+/// A 2D vector of `f32`.
+pub type Vec2 = cgmath::Vector2<f32>;
+/// A 3D vector of `f32`.
+pub type Vec3 = cgmath::Vector3<f32>;
+/// A 4D vector of `f32`.
+pub type Vec4 = cgmath::Vector4<f32>;
+/// A 4x4 matrix of `f32`, column major.
+pub type Mat4 = cgmath::Matrix4<f32>;
+
+/// A parsed font, as stored by `RenderStorage`.
+pub type FontData = rusttype::Font<'static>;
+/// An image with 8-bit RGBA pixels, row major.
+pub type ImageData = image::RgbaImage;
+/// The format of an image, given to `RenderStorage::add_texture_from_image_bytes`.
+pub type ImageFormat = image::ImageFormat;
+
+#[inline]
+pub fn vec2(x: f32, y: f32) -> Vec2 {
+    Vec2::new(x, y)
+}
+
+#[inline]
+pub fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+#[inline]
+pub fn vec4(x: f32, y: f32, z: f32, w: f32) -> Vec4 {
+    Vec4::new(x, y, z, w)
+}
+
+/// Build a matrix from its columns.
+pub fn mat4_from_cols_array(cols: &[[f32; 4]; 4]) -> Mat4 {
+    Mat4::from(*cols)
+}
+
+/// Returns the columns of a matrix, which every math library knows how to read.
+pub fn mat4_to_cols_array(mat: &Mat4) -> [[f32; 4]; 4] {
+    (*mat).into()
+}
+
+/// An orthographic projection, with (left, top) being the top left corner of the screen.
+pub fn ortho(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+    Mat4::from(cgmath::Ortho { left, right, bottom, top, near, far })
+}
+
+/// Read a font from owned bytes. Returns `None` if the bytes are not a valid font.
+pub fn font_from_vec(bytes: Vec<u8>) -> Option<FontData> {
+    FontData::try_from_vec(bytes)
+}
+
+/// Model matrix of a quad: scaled to `size`, rotated by `rotation` degrees around `rot_pivot`,
+/// then moved to `translation`.
+///
+/// This is the same transformation as the example vertex shader, if you'd rather compute it on the
+/// CPU and send a `mat4` per instance. With the `glam` feature, glam is used to compute it, which
+/// is quite a bit faster when building tens of thousands of instances per frame: see
+/// `QuadInstance::bounds` and the `bench-instances` example.
+pub fn model_matrix(translation: Vec2, size: Vec2, rot_pivot: Vec2, rotation: f32) -> Mat4 {
+    mat4_from_cols_array(&model_matrix_cols(translation, size, rot_pivot, rotation))
+}
+
+/// Same as `model_matrix`, but returns the columns directly, ready to be written to your
+/// instanced vertex buffer.
+#[cfg(not(feature = "glam"))]
+pub fn model_matrix_cols(translation: Vec2, size: Vec2, rot_pivot: Vec2, rotation: f32) -> [[f32; 4]; 4] {
+    let m = Mat4::from_translation(translation.extend(0.0))
+        * Mat4::from_translation(rot_pivot.extend(0.0))
+        * Mat4::from_angle_z(cgmath::Deg(-rotation))
+        * Mat4::from_translation(-rot_pivot.extend(0.0))
+        * Mat4::from_nonuniform_scale(size.x, size.y, 1.0);
+    m.into()
+}
+
+/// Same as `model_matrix`, but returns the columns directly, ready to be written to your
+/// instanced vertex buffer.
+#[cfg(feature = "glam")]
+pub fn model_matrix_cols(translation: Vec2, size: Vec2, rot_pivot: Vec2, rotation: f32) -> [[f32; 4]; 4] {
+    let pivot = glam::Vec3::new(rot_pivot.x, rot_pivot.y, 0.0);
+    let m = glam::Mat4::from_translation(glam::Vec3::new(translation.x, translation.y, 0.0))
+        * glam::Mat4::from_translation(pivot)
+        * glam::Mat4::from_rotation_z(-rotation.to_radians())
+        * glam::Mat4::from_translation(-pivot)
+        * glam::Mat4::from_scale(glam::Vec3::new(size.x, size.y, 1.0));
+    m.to_cols_array_2d()
+}
+
+/// Conversions between our math types and glam's.
+///
+/// These are functions and not `From` impls, because neither side is defined in this crate.
+#[cfg(feature = "glam")]
+pub mod glam_conv {
+    use super::{Vec2, Vec3, Vec4, Mat4};
+
+    #[inline]
+    pub fn vec2_to_glam(v: Vec2) -> glam::Vec2 {
+        glam::Vec2::new(v.x, v.y)
+    }
+
+    #[inline]
+    pub fn vec2_from_glam(v: glam::Vec2) -> Vec2 {
+        Vec2::new(v.x, v.y)
+    }
+
+    #[inline]
+    pub fn vec3_to_glam(v: Vec3) -> glam::Vec3 {
+        glam::Vec3::new(v.x, v.y, v.z)
+    }
+
+    #[inline]
+    pub fn vec3_from_glam(v: glam::Vec3) -> Vec3 {
+        Vec3::new(v.x, v.y, v.z)
+    }
+
+    #[inline]
+    pub fn vec4_to_glam(v: Vec4) -> glam::Vec4 {
+        glam::Vec4::new(v.x, v.y, v.z, v.w)
+    }
+
+    #[inline]
+    pub fn vec4_from_glam(v: glam::Vec4) -> Vec4 {
+        Vec4::new(v.x, v.y, v.z, v.w)
+    }
+
+    #[inline]
+    pub fn mat4_to_glam(m: &Mat4) -> glam::Mat4 {
+        glam::Mat4::from_cols_array_2d(&super::mat4_to_cols_array(m))
+    }
+
+    #[inline]
+    pub fn mat4_from_glam(m: &glam::Mat4) -> Mat4 {
+        super::mat4_from_cols_array(&m.to_cols_array_2d())
+    }
+}
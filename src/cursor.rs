@@ -0,0 +1,140 @@
+//! Helpers to draw a custom mouse cursor, and an optional trail following it.
+//!
+//! Like the text helpers, this module does not draw anything by itself: it gives you a list of
+//! `CursorDrawCall`, which you can then convert to your own vertex data.
+//!
+//! Don't forget to hide the OS cursor, otherwise both will be shown: with
+//! `SdlContext::set_os_cursor_visible(false)` or `GlutinContext::set_os_cursor_visible(false)`,
+//! or with your windowing library (for instance `sdl2::mouse::MouseUtil::show_cursor(false)`).
+
+use cgmath::Vector2;
+use std::collections::VecDeque;
+
+use crate::render_storage::texture::TextureArrayLayer;
+
+/// Represents something to draw for the cursor: either the cursor itself, or a part of its trail.
+#[derive(Debug, Clone, Copy)]
+pub struct CursorDrawCall {
+    /// Top-left corner of where the cursor should be drawn.
+    pub dest_origin: Vector2<f32>,
+    pub dest_size: Vector2<f32>,
+    // in pixels
+    pub source_crop: (f32, f32, f32, f32),
+    pub texture_layer: TextureArrayLayer,
+    /// 1.0 for the cursor itself, and lower for the trail, the older the point is.
+    pub alpha: f32,
+}
+
+#[derive(Debug, Clone)]
+struct CursorTrail {
+    /// (position, age in seconds), the most recent position is at the front.
+    points: VecDeque<(Vector2<f32>, f32)>,
+    max_points: usize,
+    lifetime: f32,
+}
+
+/// A custom cursor, drawn from a part of a texture.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    texture_layer: TextureArrayLayer,
+    crop: (u32, u32, u32, u32),
+    hotspot: Vector2<f32>,
+    scale: f32,
+    position: Vector2<f32>,
+    trail: Option<CursorTrail>,
+}
+
+impl Cursor {
+    /// Create a new cursor.
+    ///
+    /// `crop` is the part of the layer to draw in pixels (x, y, w, h), and `hotspot` is the
+    /// position of the "click point" within that crop, in pixels as well. For a classic arrow,
+    /// the hotspot is (0, 0), while for a crosshair it is the center of the image.
+    pub fn new(texture_layer: TextureArrayLayer, crop: (u32, u32, u32, u32), hotspot: Vector2<f32>) -> Cursor {
+        Cursor {
+            texture_layer,
+            crop,
+            hotspot,
+            scale: 1.0,
+            position: Vector2::new(0.0, 0.0),
+            trail: None,
+        }
+    }
+
+    /// Draw the cursor bigger or smaller than its texture. The hotspot is scaled as well.
+    pub fn with_scale(mut self, scale: f32) -> Cursor {
+        self.scale = scale;
+        self
+    }
+
+    /// Leave a trail of at most `max_points` fading copies of the cursor behind it. Every
+    /// copy disappears after `lifetime` seconds.
+    pub fn with_trail(mut self, max_points: usize, lifetime: f32) -> Cursor {
+        self.trail = Some(CursorTrail {
+            points: VecDeque::with_capacity(max_points),
+            max_points,
+            lifetime,
+        });
+        self
+    }
+
+    #[inline]
+    pub fn position(&self) -> Vector2<f32> {
+        self.position
+    }
+
+    /// Update the position of the cursor, and age its trail by `dt` seconds.
+    ///
+    /// You should call this once per frame, even if the mouse didn't move, so that the trail fades.
+    pub fn update(&mut self, position: Vector2<f32>, dt: f32) {
+        let previous_position = self.position;
+        self.position = position;
+        if let Some(trail) = &mut self.trail {
+            for (_, age) in &mut trail.points {
+                *age += dt;
+            }
+            let lifetime = trail.lifetime;
+            while trail.points.back().map(|(_, age)| *age >= lifetime).unwrap_or(false) {
+                trail.points.pop_back();
+            }
+            if previous_position != position && trail.max_points > 0 {
+                if trail.points.len() >= trail.max_points {
+                    trail.points.pop_back();
+                }
+                trail.points.push_front((previous_position, 0.0));
+            }
+        }
+    }
+
+    /// Remove every point of the trail, for instance when the cursor is teleported.
+    pub fn clear_trail(&mut self) {
+        if let Some(trail) = &mut self.trail {
+            trail.points.clear();
+        }
+    }
+
+    fn draw_call_at(&self, position: Vector2<f32>, alpha: f32) -> CursorDrawCall {
+        let (x, y, w, h) = self.crop;
+        CursorDrawCall {
+            dest_origin: position - self.hotspot * self.scale,
+            dest_size: Vector2::new(w as f32, h as f32) * self.scale,
+            source_crop: (x as f32, y as f32, w as f32, h as f32),
+            texture_layer: self.texture_layer,
+            alpha,
+        }
+    }
+
+    /// Returns what to draw, in order: the oldest points of the trail first, and the cursor last,
+    /// so that it is drawn on top of everything else.
+    pub fn draw_calls(&self) -> Vec<CursorDrawCall> {
+        let mut results = Vec::with_capacity(1 + self.trail.as_ref().map(|t| t.points.len()).unwrap_or(0));
+        if let Some(trail) = &self.trail {
+            for (position, age) in trail.points.iter().rev() {
+                let alpha = 1.0 - (age / trail.lifetime).min(1.0);
+                results.push(self.draw_call_at(*position, alpha));
+            }
+        }
+        results.push(self.draw_call_at(self.position, 1.0));
+        results
+    }
+}
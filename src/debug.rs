@@ -0,0 +1,207 @@
+//! Statistics about batching and caches, and an overlay to show them in game.
+//!
+//! `Renderer::frame_stats` counts instances, draw calls and uploaded bytes, along with the GPU
+//! time of `draw` when `Renderer::set_gpu_timing` is enabled, and `RenderStorage::storage_stats`
+//! gives the occupancy of the texture arrays and of the glyph atlas. `DebugOverlay` draws both
+//! as text and bars, converted to your own vertex data like the other helpers.
+//!
+//! ```ignore
+//! let overlay = DebugOverlay::new(font_id);
+//! // at the end of the frame
+//! overlay.add_to_renderer(&mut renderer, &mut render_storage, |draw_call| to_vertex_data(draw_call))?;
+//! renderer.draw();
+//! renderer.reset_frame_stats();
+//! ```
+
+use cgmath::Vector2;
+use std::time::Duration;
+
+use crate::color::Color;
+use crate::error::SprowlError;
+use crate::render_storage::{FontId, RenderStorage, font::FontStemDrawCall};
+use crate::renderer::{AsVertexData, Renderer};
+use crate::shader::Uniform;
+
+/// What a `Renderer` submitted since the last `Renderer::reset_frame_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameStats {
+    /// Instances drawn.
+    pub instances: usize,
+    /// Calls to `glDraw*`: one per `draw`, plus one for every `max_instances` instances.
+    pub draw_calls: usize,
+    /// Instance data sent to the GPU, in bytes.
+    pub bytes_uploaded: usize,
+    /// Elements skipped by `Renderer::add_elem_with_bounds`, being outside of the cull bounds.
+    pub culled: usize,
+    /// GPU time of all the `draw`s of a frame, with `Renderer::set_gpu_timing`. This is a
+    /// previous frame: the results of timer queries arrive a few frames late.
+    pub gpu_time: Option<Duration>,
+}
+
+/// Occupancy of a `Texture2DArray`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextureArrayStats {
+    /// Layers holding a texture.
+    pub used_layers: usize,
+    /// Layers allocated on the GPU, used or not.
+    pub allocated_layers: usize,
+    pub layer_size: (u32, u32),
+}
+
+impl TextureArrayStats {
+    /// Fraction of the allocated layers which are used, from 0.0 to 1.0.
+    pub fn occupancy(&self) -> f32 {
+        self.used_layers as f32 / self.allocated_layers.max(1) as f32
+    }
+}
+
+/// Occupancy of the `GlyphAtlas`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GlyphCacheStats {
+    pub glyphs: usize,
+    /// Fraction of the layer used by glyphs, from 0.0 to 1.0.
+    pub occupancy: f32,
+    /// Size of the atlas in pixels, see `GlyphAtlas::set_max_size`.
+    pub size: (u32, u32),
+}
+
+/// Everything stored by a `RenderStorage`, see `RenderStorage::storage_stats`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StorageStats {
+    /// One per RGBA tier, the default one first, see `RenderStorage::add_rgba_tier`.
+    pub rgba: Vec<TextureArrayStats>,
+    pub grayscale: TextureArrayStats,
+    /// One per format and size of compressed textures.
+    pub compressed: Vec<TextureArrayStats>,
+    pub glyph_cache: GlyphCacheStats,
+    /// Glyphs converted to MSDF, stored in the RGBA array.
+    pub msdf_glyphs: usize,
+}
+
+/// Something to draw for the overlay, to convert to your own vertex data.
+pub enum DebugDrawCall {
+    /// A plain rectangle: the background, or a bar.
+    Rect {
+        /// Top-left corner of the rectangle.
+        dest_origin: Vector2<f32>,
+        dest_size: Vector2<f32>,
+        color: Color<u8>,
+    },
+    /// A glyph of the text, from the grayscale array.
+    Glyph {
+        glyph: FontStemDrawCall,
+        color: Color<u8>,
+    },
+}
+
+/// Draws `FrameStats` and `StorageStats` in a corner of the screen: one line of text per stat, with
+/// a bar for the ones which can fill up.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugOverlay {
+    pub font_id: FontId,
+    pub font_size: f32,
+    /// Top-left corner of the overlay, in pixels.
+    pub origin: Vector2<f32>,
+    /// Width of the bars, in pixels.
+    pub bar_width: f32,
+    pub text_color: Color<u8>,
+    pub background_color: Color<u8>,
+    pub bar_color: Color<u8>,
+}
+
+impl DebugOverlay {
+    /// White text on a translucent black background, in the top-left corner.
+    pub fn new(font_id: FontId) -> DebugOverlay {
+        DebugOverlay {
+            font_id,
+            font_size: 14.0,
+            origin: Vector2::new(4.0, 4.0),
+            bar_width: 80.0,
+            text_color: Color::white(),
+            background_color: Color::from_rgba(0, 0, 0, 180),
+            bar_color: Color::from_rgb(80, 200, 120),
+        }
+    }
+
+    /// The text of every line, along with the value of its bar, if any.
+    pub fn lines(frame_stats: &FrameStats, storage_stats: &StorageStats) -> Vec<(String, Option<f32>)> {
+        fn array_line(name: &str, stats: &TextureArrayStats) -> (String, Option<f32>) {
+            (format!("{}: {}/{} layers of {}x{}", name, stats.used_layers, stats.allocated_layers, stats.layer_size.0, stats.layer_size.1), Some(stats.occupancy()))
+        }
+        let mut lines = vec![
+            (format!("instances: {}", frame_stats.instances), None),
+            (format!("draw calls: {}", frame_stats.draw_calls), None),
+            (format!("uploaded: {} KB", frame_stats.bytes_uploaded / 1024), None),
+        ];
+        if let Some(gpu_time) = frame_stats.gpu_time {
+            lines.push((format!("gpu: {:.2} ms", gpu_time.as_secs_f64() * 1000.0), None));
+        }
+        if frame_stats.culled > 0 {
+            lines.push((format!("culled: {}", frame_stats.culled), None));
+        }
+        for (i, stats) in storage_stats.rgba.iter().enumerate() {
+            lines.push(array_line(&format!("rgba #{}", i), stats));
+        }
+        lines.push(array_line("grayscale", &storage_stats.grayscale));
+        for (i, stats) in storage_stats.compressed.iter().enumerate() {
+            lines.push(array_line(&format!("compressed #{}", i), stats));
+        }
+        let glyph_cache = &storage_stats.glyph_cache;
+        lines.push((format!("glyph cache: {} glyphs, {:.0}% of {}x{}", glyph_cache.glyphs, glyph_cache.occupancy * 100.0, glyph_cache.size.0, glyph_cache.size.1), Some(glyph_cache.occupancy)));
+        lines.push((format!("msdf glyphs: {}", storage_stats.msdf_glyphs), None));
+        lines
+    }
+
+    /// Everything to draw, back to front: the background, then the text and the bars.
+    ///
+    /// Returns an error if the font doesn't exist, or if the glyphs don't fit in the glyph atlas.
+    pub fn draw_calls(&self, frame_stats: &FrameStats, render_storage: &mut RenderStorage) -> Result<Vec<DebugDrawCall>, SprowlError> {
+        let lines = Self::lines(frame_stats, &render_storage.storage_stats());
+        let no_font_error = render_storage.no_font_error(self.font_id);
+        let (font_renderer, mut atlas_ref) = render_storage.get_font_with_texture(self.font_id)
+            .ok_or(no_font_error)?;
+        let line_height = font_renderer.y_length(self.font_size).ceil();
+        let padding = (self.font_size / 4.0).ceil();
+
+        let mut glyphs: Vec<DebugDrawCall> = Vec::new();
+        let mut text_width: f32 = 0.0;
+        for (i, (text, _)) in lines.iter().enumerate() {
+            let line_origin = self.origin + Vector2::new(padding, padding + i as f32 * line_height);
+            for mut glyph in font_renderer.word_to_draw_call(&mut atlas_ref, text, self.font_size)? {
+                text_width = text_width.max(glyph.dest_origin.x + glyph.dest_size.x);
+                glyph.dest_origin += line_origin;
+                glyphs.push(DebugDrawCall::Glyph { glyph, color: self.text_color });
+            }
+        }
+
+        let bar_x = self.origin.x + padding * 2.0 + text_width;
+        let mut results = vec![DebugDrawCall::Rect {
+            dest_origin: self.origin,
+            dest_size: Vector2::new(bar_x + self.bar_width + padding - self.origin.x, padding * 2.0 + lines.len() as f32 * line_height),
+            color: self.background_color,
+        }];
+        results.append(&mut glyphs);
+        for (i, (_, value)) in lines.iter().enumerate() {
+            if let Some(value) = value {
+                let bar_height = (line_height / 2.0).ceil();
+                let bar_y = self.origin.y + padding + i as f32 * line_height + (line_height - bar_height) / 2.0;
+                results.push(DebugDrawCall::Rect {
+                    dest_origin: Vector2::new(bar_x, bar_y),
+                    dest_size: Vector2::new(self.bar_width * value.clamp(0.0, 1.0), bar_height),
+                    color: self.bar_color,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Add the overlay to the renderer, `to_vertex_data` converting every part to your own vertex
+    /// data. The stats shown are the ones of `renderer` so far, without the overlay itself.
+    pub fn add_to_renderer<U: Uniform, E: AsVertexData, F: FnMut(&DebugDrawCall) -> E>(&self, renderer: &mut Renderer<U>, render_storage: &mut RenderStorage, mut to_vertex_data: F) -> Result<(), SprowlError> {
+        let frame_stats = renderer.frame_stats();
+        for draw_call in &self.draw_calls(&frame_stats, render_storage)? {
+            renderer.add_elem(&to_vertex_data(draw_call));
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,52 @@
 
-/// Describes an error that might happen when drawing something.
+/// Every error of the crate, also available as `sprowl::Error`.
+///
+/// Functions which panic instead of returning it are suffixed with `_unchecked`.
 #[derive(Debug)]
 pub enum SprowlError {
     MissingTextureId(u32),
-    MissingFontId(u32)
+    MissingFontId(u32),
+    /// The `StaticBatch` was removed from its renderer, see `Renderer::remove_static_batch`.
+    MissingBatchId(u32),
+    /// The bytes given could not be parsed as a font.
+    InvalidFont,
+    /// Glyphs could not be written to the glyph atlas, even at a smaller size.
+    GlyphCache(crate::render_storage::font::CacheWriteErr),
+    Io(std::io::Error),
+    /// The image could not be decoded.
+    Image(image::ImageError),
+    /// The texture array has no layer left, and could not be grown.
+    TextureArrayFull { max_layers: u32 },
+    /// The texture is larger than the layers of the texture array it was added to.
+    TextureTooLarge { width: u32, height: u32, max: (u32, u32) },
+    /// The bytes could not be parsed as a DDS or KTX2 texture, or its format is not supported.
+    InvalidCompressedTexture(&'static str),
+    /// A compressed texture array can't be reallocated without `glCopyImageSubData` (GL 4.3):
+    /// its textures would be lost.
+    CompressedCopyUnsupported,
+    /// A framebuffer could not be created, with the status returned by `glCheckFramebufferStatus`.
+    IncompleteFramebuffer(u32),
+    /// A shader failed to compile or link, or lacks one of its uniforms.
+    Shader(crate::shader::ShaderError),
+    /// An OpenGL call raised this error, see `gl_utils::gl_check_error`.
+    Gl(u32),
+    /// Another asset is already registered under this key.
+    DuplicateAssetKey(String),
+    /// The crop of a region goes past its texture, see `RenderStorage::define_region`.
+    RegionOutOfBounds(String),
+    /// The metadata of a spritesheet could not be read, see `render_storage::sheet`.
+    InvalidSpriteSheet(String),
+    /// A .fnt file could not be read, or its pages don't match it, see `BitmapFont`.
+    InvalidBitmapFont(String),
+    /// The font is a `BitmapFont`, which has no outlines to rasterize.
+    UnsupportedForBitmapFont(u32),
+    /// A texture can only be reloaded in place by an image of the same size.
+    TextureSizeMismatch { expected: (u32, u32), found: (u32, u32) },
+    /// A `RenderStorageConfig` asks for texture arrays the GL context can't allocate.
+    InvalidStorageConfig(String),
+    /// The window or its GL context could not be created, or a frame could not be presented,
+    /// see `sdl2_init` and `glutin_init`.
+    Window(String),
 }
 
 impl std::fmt::Display for SprowlError {
@@ -11,8 +54,55 @@ impl std::fmt::Display for SprowlError {
         match self {
             SprowlError::MissingTextureId(id) => write!(f, "texture with id {} was not found", id),
             SprowlError::MissingFontId(id) => write!(f, "font with id {} was not found", id),
+            SprowlError::MissingBatchId(id) => write!(f, "static batch with id {} was not found", id),
+            SprowlError::InvalidFont => write!(f, "invalid font data"),
+            SprowlError::GlyphCache(e) => write!(f, "failed to write to the glyph atlas: {}", e),
+            SprowlError::Io(e) => write!(f, "io error: {}", e),
+            SprowlError::Image(e) => write!(f, "image error: {}", e),
+            SprowlError::TextureArrayFull { max_layers } => write!(f, "texture array is full ({} layers)", max_layers),
+            SprowlError::TextureTooLarge { width, height, max } => write!(f, "texture is {}x{}, larger than the {}x{} layers of its texture array", width, height, max.0, max.1),
+            SprowlError::InvalidCompressedTexture(reason) => write!(f, "invalid compressed texture: {}", reason),
+            SprowlError::CompressedCopyUnsupported => write!(f, "compressed textures can't be copied without glCopyImageSubData"),
+            SprowlError::IncompleteFramebuffer(status) => write!(f, "incomplete framebuffer (status 0x{:X})", status),
+            SprowlError::Shader(e) => write!(f, "{}", e),
+            SprowlError::Gl(error) => write!(f, "OpenGL error 0x{:X}", error),
+            SprowlError::TextureSizeMismatch { expected, found } => write!(f, "image is {}x{}, expected {}x{}", found.0, found.1, expected.0, expected.1),
+            SprowlError::DuplicateAssetKey(key) => write!(f, "an asset is already registered as {:?}", key),
+            SprowlError::RegionOutOfBounds(key) => write!(f, "region {:?} is not within its texture", key),
+            SprowlError::InvalidSpriteSheet(reason) => write!(f, "invalid spritesheet metadata: {}", reason),
+            SprowlError::InvalidBitmapFont(reason) => write!(f, "invalid bitmap font: {}", reason),
+            SprowlError::UnsupportedForBitmapFont(id) => write!(f, "font with id {} is a bitmap font, which has no outlines", id),
+            SprowlError::InvalidStorageConfig(reason) => write!(f, "invalid render storage config: {}", reason),
+            SprowlError::Window(reason) => write!(f, "window error: {}", reason),
         }
     }
 }
 
-impl std::error::Error for SprowlError {}
\ No newline at end of file
+impl std::error::Error for SprowlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SprowlError::Io(e) => Some(e),
+            SprowlError::Image(e) => Some(e),
+            SprowlError::Shader(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SprowlError {
+    fn from(e: std::io::Error) -> SprowlError {
+        SprowlError::Io(e)
+    }
+}
+
+impl From<crate::shader::ShaderError> for SprowlError {
+    fn from(e: crate::shader::ShaderError) -> SprowlError {
+        SprowlError::Shader(e)
+    }
+}
+
+impl From<image::ImageError> for SprowlError {
+    fn from(e: image::ImageError) -> SprowlError {
+        SprowlError::Image(e)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,198 @@
+//! Save and restore the GL state sprowl changes, to draw within a larger GL application (an
+//! immediate mode UI, another engine) without trampling its own rendering.
+//!
+//! ```ignore
+//! // your own rendering ...
+//! {
+//!     let _bound = storage.set_active_scoped();
+//!     renderer.draw();
+//! } // the textures, program, blend mode and so on of your application are back
+//! // ... more of your own rendering
+//! ```
+//!
+//! With `Renderer::set_preserve_gl_state`, every `draw` saves and restores the state as well, and
+//! sets its own (program, blend and depth modes, viewport, clip rect) again beforehand, in case
+//! the application changed it since.
+//!
+//! Objects are restored by name: this only works with the native backend, whose names are the
+//! ones of the driver.
+
+use crate::backend::gl::{self, types::*};
+use crate::gl_utils::gl_get_int;
+
+/// Texture units whose bindings are saved, at most: sprowl doesn't use more than that.
+pub const SAVED_TEXTURE_UNITS: u32 = 16;
+
+/// The bindings of a texture unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TextureUnitState {
+    texture_2d: GLuint,
+    texture_2d_array: GLuint,
+    sampler: GLuint,
+}
+
+/// A snapshot of the GL state sprowl changes, taken by `capture` and put back by `restore`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlState {
+    program: GLuint,
+    vertex_array: GLuint,
+    array_buffer: GLuint,
+    draw_framebuffer: GLuint,
+    read_framebuffer: GLuint,
+    active_texture: GLenum,
+    texture_units: Vec<TextureUnitState>,
+    viewport: [GLint; 4],
+    scissor_box: [GLint; 4],
+    scissor_test: bool,
+    blend: bool,
+    /// rgb, alpha
+    blend_equation: (GLenum, GLenum),
+    /// src rgb, dst rgb, src alpha, dst alpha
+    blend_func: (GLenum, GLenum, GLenum, GLenum),
+    depth_test: bool,
+    depth_func: GLenum,
+    depth_mask: bool,
+    stencil_test: bool,
+    cull_face: bool,
+    color_mask: [bool; 4],
+    pack_alignment: GLint,
+    unpack_alignment: GLint,
+}
+
+fn get_ints<const N: usize>(name: GLenum) -> [GLint; N] {
+    let mut values = [0; N];
+    unsafe { gl::GetIntegerv(name, values.as_mut_ptr()); }
+    values
+}
+
+fn is_enabled(cap: GLenum) -> bool {
+    unsafe { gl::IsEnabled(cap) == gl::TRUE }
+}
+
+fn set_enabled(cap: GLenum, enabled: bool) {
+    unsafe {
+        if enabled {
+            gl::Enable(cap);
+        } else {
+            gl::Disable(cap);
+        }
+    }
+}
+
+fn gl_bool(value: bool) -> GLboolean {
+    if value { gl::TRUE } else { gl::FALSE }
+}
+
+impl GlState {
+    /// Read the current state, with a glGet for every part of it: not something to do many
+    /// times per frame.
+    pub fn capture() -> GlState {
+        let active_texture = gl_get_int(gl::ACTIVE_TEXTURE) as GLenum;
+        let unit_count = (gl_get_int(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS).max(0) as u32).min(SAVED_TEXTURE_UNITS);
+        let texture_units = (0..unit_count).map(|unit| {
+            unsafe { gl::ActiveTexture(gl::TEXTURE0 + unit); }
+            TextureUnitState {
+                texture_2d: gl_get_int(gl::TEXTURE_BINDING_2D) as GLuint,
+                texture_2d_array: gl_get_int(gl::TEXTURE_BINDING_2D_ARRAY) as GLuint,
+                sampler: gl_get_int(gl::SAMPLER_BINDING) as GLuint,
+            }
+        }).collect();
+        unsafe { gl::ActiveTexture(active_texture); }
+        let color_mask = get_ints::<4>(gl::COLOR_WRITEMASK);
+        GlState {
+            program: gl_get_int(gl::CURRENT_PROGRAM) as GLuint,
+            vertex_array: gl_get_int(gl::VERTEX_ARRAY_BINDING) as GLuint,
+            array_buffer: gl_get_int(gl::ARRAY_BUFFER_BINDING) as GLuint,
+            draw_framebuffer: gl_get_int(gl::DRAW_FRAMEBUFFER_BINDING) as GLuint,
+            read_framebuffer: gl_get_int(gl::READ_FRAMEBUFFER_BINDING) as GLuint,
+            active_texture,
+            texture_units,
+            viewport: get_ints(gl::VIEWPORT),
+            scissor_box: get_ints(gl::SCISSOR_BOX),
+            scissor_test: is_enabled(gl::SCISSOR_TEST),
+            blend: is_enabled(gl::BLEND),
+            blend_equation: (gl_get_int(gl::BLEND_EQUATION_RGB) as GLenum, gl_get_int(gl::BLEND_EQUATION_ALPHA) as GLenum),
+            blend_func: (
+                gl_get_int(gl::BLEND_SRC_RGB) as GLenum,
+                gl_get_int(gl::BLEND_DST_RGB) as GLenum,
+                gl_get_int(gl::BLEND_SRC_ALPHA) as GLenum,
+                gl_get_int(gl::BLEND_DST_ALPHA) as GLenum,
+            ),
+            depth_test: is_enabled(gl::DEPTH_TEST),
+            depth_func: gl_get_int(gl::DEPTH_FUNC) as GLenum,
+            depth_mask: gl_get_int(gl::DEPTH_WRITEMASK) != 0,
+            stencil_test: is_enabled(gl::STENCIL_TEST),
+            cull_face: is_enabled(gl::CULL_FACE),
+            color_mask: [color_mask[0] != 0, color_mask[1] != 0, color_mask[2] != 0, color_mask[3] != 0],
+            pack_alignment: gl_get_int(gl::PACK_ALIGNMENT),
+            unpack_alignment: gl_get_int(gl::UNPACK_ALIGNMENT),
+        }
+    }
+
+    /// Set the state back to what it was when captured.
+    pub fn restore(&self) {
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::BindVertexArray(self.vertex_array);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.array_buffer);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.draw_framebuffer);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.read_framebuffer);
+            for (unit, state) in self.texture_units.iter().enumerate() {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as GLuint);
+                gl::BindTexture(gl::TEXTURE_2D, state.texture_2d);
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, state.texture_2d_array);
+                gl::BindSampler(unit as GLuint, state.sampler);
+            }
+            gl::ActiveTexture(self.active_texture);
+            let [x, y, w, h] = self.viewport;
+            gl::Viewport(x, y, w, h);
+            let [x, y, w, h] = self.scissor_box;
+            gl::Scissor(x, y, w, h);
+            gl::BlendEquationSeparate(self.blend_equation.0, self.blend_equation.1);
+            let (src_rgb, dst_rgb, src_alpha, dst_alpha) = self.blend_func;
+            gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            gl::DepthFunc(self.depth_func);
+            gl::DepthMask(gl_bool(self.depth_mask));
+            let [r, g, b, a] = self.color_mask;
+            gl::ColorMask(gl_bool(r), gl_bool(g), gl_bool(b), gl_bool(a));
+            gl::PixelStorei(gl::PACK_ALIGNMENT, self.pack_alignment);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, self.unpack_alignment);
+        }
+        set_enabled(gl::SCISSOR_TEST, self.scissor_test);
+        set_enabled(gl::BLEND, self.blend);
+        set_enabled(gl::DEPTH_TEST, self.depth_test);
+        set_enabled(gl::STENCIL_TEST, self.stencil_test);
+        set_enabled(gl::CULL_FACE, self.cull_face);
+    }
+}
+
+/// Captures the GL state when created, and restores it when dropped.
+#[derive(Debug)]
+pub struct GlStateGuard {
+    state: GlState,
+}
+
+impl GlStateGuard {
+    pub fn new() -> GlStateGuard {
+        GlStateGuard {
+            state: GlState::capture(),
+        }
+    }
+
+    /// The state which will be restored.
+    pub fn state(&self) -> &GlState {
+        &self.state
+    }
+}
+
+impl Default for GlStateGuard {
+    fn default() -> GlStateGuard {
+        GlStateGuard::new()
+    }
+}
+
+impl Drop for GlStateGuard {
+    fn drop(&mut self) {
+        self.state.restore();
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! Those functions are mostly used internally, but are still publicly available for convenience. 
 
-use gl::types::{GLint, GLenum, GLfloat};
+use crate::backend::gl::{self, types::{GLint, GLenum, GLfloat}};
+use crate::error::SprowlError;
 use std::{
     mem::MaybeUninit,
     ffi::CStr
@@ -30,11 +31,42 @@ pub fn gl_get_string(name: GLenum) -> &'static CStr {
     }
 }
 
+/// Returns the last error raised by any call. Enable the `gl-debug` feature to know which call
+/// raised it.
 pub fn gl_get_error() -> Option<GLenum> {
+    // the errors were read after the call which raised them
+    #[cfg(feature = "gl-debug")]
+    {
+        if let Some(error) = crate::backend::debug::take_error() {
+            return Some(error);
+        }
+    }
     let r = unsafe { gl::GetError() };
     if r == 0 {
         None
     } else {
         Some(r)
     }
-}
\ No newline at end of file
+}
+
+/// Same as `gl_get_error`, as a `Result` to use with `?`.
+pub fn gl_check_error() -> Result<(), SprowlError> {
+    match gl_get_error() {
+        Some(error) => Err(SprowlError::Gl(error)),
+        None => Ok(()),
+    }
+}
+
+/// Log the vendor, the version and the limits of the current context.
+pub fn log_gl_info() {
+    log::info!("OpenGL vendor: {}", gl_get_string(gl::VENDOR).to_string_lossy());
+    log::info!("OpenGL renderer: {}", gl_get_string(gl::RENDERER).to_string_lossy());
+    log::info!(
+        "OpenGL version: {}, GLSL version: {}",
+        gl_get_string(gl::VERSION).to_string_lossy(),
+        gl_get_string(gl::SHADING_LANGUAGE_VERSION).to_string_lossy(),
+    );
+    log::info!("GL_MAX_TEXTURE_SIZE: {}", gl_get_int(gl::MAX_TEXTURE_SIZE));
+    log::info!("GL_MAX_ARRAY_TEXTURE_LAYERS: {}", gl_get_int(gl::MAX_ARRAY_TEXTURE_LAYERS));
+    log::info!("GL_MAX_VERTEX_ATTRIBS: {}", gl_get_int(gl::MAX_VERTEX_ATTRIBS));
+}
@@ -0,0 +1,137 @@
+//! Window and GL context setup with glutin (and winit), with the `glutin` feature.
+//!
+//! `glutin_init` creates the window and its context with the attributes sprowl needs (3.3 core
+//! or ES 3.0, a depth and stencil buffer for `DepthMode` and the shadows of `lighting`), loads
+//! the functions of the `gl` crate and logs what the driver reports. See `sdl` for the same with
+//! SDL2.
+//!
+//! ```ignore
+//! let event_loop = EventLoop::new();
+//! let context = sprowl::glutin_init(&WindowSettings::new("game", 1280, 720), &event_loop)?;
+//! let mut renderer = RendererBuilder::new(16384).standard_quad_layout().build_with(standard_quad_shader()?);
+//! context.fit_viewport(&mut renderer);
+//! event_loop.run(move |event, _, control_flow| {
+//!     context.handle_event(&event, &mut renderer);
+//!     match event {
+//!         Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => *control_flow = ControlFlow::Exit,
+//!         Event::MainEventsCleared => {
+//!             renderer.clear(None);
+//!             // ...
+//!             renderer.draw();
+//!             context.swap().unwrap();
+//!         },
+//!         _ => {},
+//!     }
+//! });
+//! ```
+
+use glutin::dpi::LogicalSize;
+use glutin::event::{Event, WindowEvent};
+use glutin::event_loop::EventLoopWindowTarget;
+use glutin::window::{Window, WindowBuilder};
+use glutin::{Api, ContextBuilder, GlRequest, PossiblyCurrent, WindowedContext};
+
+use crate::error::SprowlError;
+use crate::renderer::Renderer;
+use crate::shader::{GlProfile, Uniform};
+use crate::window::{after_context_creation, window_error};
+
+pub use crate::window::WindowSettings;
+
+/// A window whose GL context is current, created by `glutin_init`.
+///
+/// The context lives as long as this struct: drop your renderers and your `RenderStorage`
+/// first.
+pub struct GlutinContext {
+    context: WindowedContext<PossiblyCurrent>,
+    gl_profile: GlProfile,
+}
+
+/// Create a window on `event_loop` and make its GL context current, as described by
+/// `settings`, then load the functions of the `gl` crate.
+pub fn glutin_init<T>(settings: &WindowSettings, event_loop: &EventLoopWindowTarget<T>) -> Result<GlutinContext, SprowlError> {
+    let window_builder = WindowBuilder::new()
+        .with_title(settings.title.as_str())
+        .with_inner_size(LogicalSize::new(settings.width, settings.height))
+        .with_resizable(settings.resizable);
+    let context_builder = match settings.gl_profile {
+        GlProfile::Core => ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+            .with_gl_profile(glutin::GlProfile::Core),
+        GlProfile::Es => ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGlEs, (3, 0))),
+    };
+    let context = context_builder
+        .with_gl_debug_flag(settings.debug_context)
+        .with_vsync(settings.vsync)
+        .with_double_buffer(Some(true))
+        .with_depth_buffer(24)
+        .with_stencil_buffer(8)
+        .with_multisampling(settings.multisample_samples as u16)
+        .build_windowed(window_builder, event_loop)
+        .map_err(window_error)?;
+    let context = unsafe { context.make_current() }.map_err(|(_, e)| window_error(e))?;
+    ::gl::load_with(|name| context.get_proc_address(name) as *const _);
+
+    after_context_creation(settings);
+
+    Ok(GlutinContext {
+        context,
+        gl_profile: GlProfile::detect(),
+    })
+}
+
+impl GlutinContext {
+    /// The profile of the context which was created, which may not be the one asked for.
+    pub fn gl_profile(&self) -> GlProfile {
+        self.gl_profile
+    }
+
+    pub fn window(&self) -> &Window {
+        self.context.window()
+    }
+
+    pub fn context(&self) -> &WindowedContext<PossiblyCurrent> {
+        &self.context
+    }
+
+    /// The size of the window in pixels, which is bigger than its size on high-DPI displays.
+    pub fn drawable_size(&self) -> (u32, u32) {
+        let size = self.window().inner_size();
+        (size.width, size.height)
+    }
+
+    /// Set the viewport of `renderer` to the whole window.
+    pub fn fit_viewport<U: Uniform>(&self, renderer: &mut Renderer<U>) {
+        let (width, height) = self.drawable_size();
+        renderer.set_viewport(width, height);
+    }
+
+    /// Follow the resizes of the window, and the changes of its scale factor: returns its new
+    /// drawable size if `event` resized it, after resizing the surface of the context (needed on
+    /// Wayland) and updating the viewport of `renderer`.
+    pub fn handle_event<T, U: Uniform>(&self, event: &Event<T>, renderer: &mut Renderer<U>) -> Option<(u32, u32)> {
+        let size = match event {
+            Event::WindowEvent { window_id, event } if *window_id == self.window().id() => match event {
+                WindowEvent::Resized(size) => *size,
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => **new_inner_size,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.context.resize(size);
+        renderer.set_viewport(size.width, size.height);
+        Some((size.width, size.height))
+    }
+
+    /// Show or hide the cursor of the OS over the window, typically hidden while drawing a
+    /// `cursor::Cursor`.
+    pub fn set_os_cursor_visible(&self, visible: bool) {
+        self.window().set_cursor_visible(visible);
+    }
+
+    /// Present the frame.
+    pub fn swap(&self) -> Result<(), SprowlError> {
+        self.context.swap_buffers().map_err(window_error)
+    }
+}
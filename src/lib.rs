@@ -17,13 +17,51 @@
 //! experience. As such, we use instanced rendering to only call glDrawArrays once, with
 //! only two textures bound: one RGBA, for the usual textures, and one grayscale, for the text.
 //!
-//! Checkout sdl2-simple example for a basic example.
+//! Checkout sdl2-simple example for a basic example. With the `sdl2` or the `glutin` feature,
+//! `sdl2_init` and `glutin_init` create a window and a GL context ready for sprowl.
+//!
+//! If you want to be shielded from version bumps of `cgmath`, `rusttype` and `image`, prefer the
+//! types of the `compat` module (also in `prelude`) over the re-exported crates.
 
 pub mod renderer;
 pub mod render_storage;
 
 pub mod gl_utils;
 
+pub mod gl_state;
+
+pub mod backend;
+
+/// Helpers to draw a custom mouse cursor.
+pub mod cursor;
+
+/// A 2D camera with pan, zoom and rotation.
+pub mod camera;
+
+/// Spritesheet animations, and a state machine to switch between them.
+pub mod animation;
+
+/// Helpers to draw sprites made of several parts, like nine-patches.
+pub mod sprites;
+
+/// Big tile maps, drawn by chunks kept on the GPU.
+pub mod tilemap;
+
+/// Particle emitters, simulated on the CPU and drawn as instances.
+pub mod particles;
+
+/// Lines, polylines and polygons, drawn as instanced quads.
+pub mod primitives;
+
+/// Fullscreen passes applied to the whole frame, like bloom or a vignette.
+pub mod postprocess;
+
+/// Dynamic point and cone lights, with optional normal maps.
+pub mod lighting;
+
+/// Batching and cache statistics, and an overlay to show them.
+pub mod debug;
+
 mod error;
 pub use self::error::{SprowlError as Error};
 
@@ -33,6 +71,24 @@ pub use self::color::*;
 /// Everything to use shaders and build your own.
 pub mod shader;
 
+#[cfg(any(feature = "sdl2", feature = "glutin"))]
+mod window;
+
+/// Window and GL context setup with SDL2.
+#[cfg(feature = "sdl2")]
+pub mod sdl;
+#[cfg(feature = "sdl2")]
+pub use self::sdl::sdl2_init;
+
+/// Window and GL context setup with glutin.
+#[cfg(feature = "glutin")]
+pub mod glutin_window;
+#[cfg(feature = "glutin")]
+pub use self::glutin_window::glutin_init;
+
+pub mod compat;
+pub mod prelude;
+
 pub use rusttype;
 pub use smallvec;
 pub use cgmath;
@@ -0,0 +1,15 @@
+#version 330 core
+
+// Multiplies the scene by the ambient light and the light map.
+in vec2 uv;
+out vec4 color;
+
+uniform sampler2D scene_texture;
+uniform sampler2D light_map;
+uniform vec3 ambient;
+
+void main() {
+    vec4 scene = texture(scene_texture, uv);
+    vec3 light = ambient + texture(light_map, uv).rgb;
+    color = vec4(scene.rgb * light, scene.a);
+}
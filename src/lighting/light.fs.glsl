@@ -0,0 +1,43 @@
+#version 330 core
+
+// The fragment shader of `Light`, added to the light map.
+in vec2 local;
+flat in vec4 color;
+flat in float radius;
+flat in float height;
+flat in vec2 cone;
+
+out vec4 frag_color;
+
+// the normals of the scene, flat (0.5, 0.5, 1.0) where nothing was drawn
+uniform sampler2D normal_map;
+// size of the light map, in pixels
+uniform vec2 target_size;
+// 0 if no normal map was drawn this frame
+uniform int use_normals;
+
+void main()
+{
+    float distance = length(local);
+    if (distance >= 1.0) {
+        discard;
+    }
+    float attenuation = (1.0 - distance) * (1.0 - distance);
+
+    if (cone.y > -1.0) {
+        vec2 direction = vec2(cos(cone.x), sin(cone.x));
+        float angle = dot(local / max(distance, 0.0001), direction);
+        // a small falloff instead of a hard edge
+        attenuation *= smoothstep(cone.y, mix(cone.y, 1.0, 0.1), angle);
+    }
+
+    if (use_normals != 0) {
+        vec3 normal = texture(normal_map, gl_FragCoord.xy / target_size).xyz * 2.0 - 1.0;
+        // normal maps point their green up, while the y of the world goes down
+        normal.y = -normal.y;
+        vec3 to_light = normalize(vec3(-local * radius, height));
+        attenuation *= max(dot(normalize(normal), to_light), 0.0);
+    }
+
+    frag_color = vec4(color.rgb * attenuation, 1.0);
+}
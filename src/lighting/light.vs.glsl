@@ -0,0 +1,31 @@
+#version 330 core
+
+// The vertex shader of `Light`: a quad of 2 * radius around the light.
+layout (location = 0) in vec2 pos;
+layout (location = 1) in vec2 light_position;
+layout (location = 2) in float light_radius;
+layout (location = 3) in vec4 light_color;
+layout (location = 4) in float light_intensity;
+layout (location = 5) in float light_height;
+// (direction in radians, cosine of the half angle), the cosine being -1.0 for point lights
+layout (location = 6) in vec2 light_cone;
+
+// from (-1, -1) to (1, 1), relative to the light
+out vec2 local;
+flat out vec4 color;
+flat out float radius;
+flat out float height;
+flat out vec2 cone;
+
+uniform mat4 view;
+
+void main()
+{
+    local = pos * 2.0 - 1.0;
+    gl_Position = view * vec4(light_position + local * light_radius, 0.0, 1.0);
+
+    color = vec4(light_color.rgb * light_color.a * light_intensity, 1.0);
+    radius = light_radius;
+    height = light_height;
+    cone = light_cone;
+}
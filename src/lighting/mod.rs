@@ -0,0 +1,440 @@
+//! Dynamic 2D lights: point and cone lights are added to a light map, which is then multiplied
+//! with the scene.
+//!
+//! Between `Lighting::begin` and `Lighting::end`, everything is drawn to an offscreen scene
+//! texture, like with a `PostProcessChain`. Sprites with a normal map can also draw it between
+//! `begin_normals` and `end`, with the same camera: lights then shade them by the angle of their
+//! surface. `end` adds the lights to the light map, and draws the lit scene to the framebuffer
+//! bound before `begin`.
+//!
+//! ```ignore
+//! let mut lighting = Lighting::new(width, height)?;
+//! lighting.ambient = Color::<u8>::from_rgb(40, 40, 60);
+//! // every frame
+//! lighting.begin(&mut renderer);
+//! renderer.clear(None);
+//! renderer.add_elem(&QuadInstance::new(position, size).with_texture(&wall));
+//! lighting.begin_normals(&mut renderer);
+//! renderer.add_elem(&QuadInstance::new(position, size).with_texture(&wall_normals));
+//! lighting.end(&mut renderer, &[Light::point(torch, 200.0, Color::<u8>::from_rgb(255, 180, 100))], &camera.view_matrix());
+//! ```
+//!
+//! Normal maps are regular RGBA textures, so they are packed and stored like any other texture.
+//! They are drawn as they are: a rotated or flipped sprite doesn't rotate its normals.
+//!
+//! Walls and other obstacles can be registered as `Occluder` segments, which cast hard shadows.
+//! Occluders stay registered from one frame to the next, until `clear_occluders`:
+//!
+//! ```ignore
+//! lighting.add_occluders(&Occluder::rect(wall_position, wall_size));
+//! ```
+//!
+//! Every light reaching an occluder is drawn on its own: its shadows are drawn to the stencil
+//! buffer of the light map first, then the light where there is no shadow. Lights without any
+//! occluder in their radius, or which don't `casts_shadows`, are still drawn in one call.
+
+mod shadow;
+
+pub use shadow::*;
+
+use cgmath::{Matrix4, Vector2, Vector3};
+use std::mem::MaybeUninit;
+
+use crate::backend::gl::{self, types::*};
+use crate::color::Color;
+use crate::error::SprowlError;
+use crate::gl_state::GlStateGuard;
+use crate::postprocess::{FULLSCREEN_VERTEX_GLSL, RenderTarget};
+use crate::renderer::{Renderer, RendererBuilder, VertexLayout, instance_data};
+use crate::shader::{Shader, Uniform};
+
+/// The vertex shader of `Light`, with a `mat4 view` uniform.
+pub const LIGHT_VERTEX_GLSL: &str = include_str!("light.vs.glsl");
+/// The fragment shader of `Light`, with the texture unit "normal_map".
+pub const LIGHT_FRAGMENT_GLSL: &str = include_str!("light.fs.glsl");
+
+/// Lights drawn in a single call, more are drawn in several calls.
+const MAX_LIGHTS_PER_DRAW: usize = 1024;
+/// Occluders drawn in a single call, same as above.
+const MAX_OCCLUDERS_PER_DRAW: usize = 4096;
+/// Shadows are extruded this many times the radius of their light, to go past the light even
+/// at the corners of its quad.
+const SHADOW_EXTRUSION: f32 = 64.0;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum LightUniform {
+    View,
+    TargetSize,
+    UseNormals,
+}
+
+impl Uniform for LightUniform {
+    fn name(&self) -> &str {
+        match self {
+            LightUniform::View => "view",
+            LightUniform::TargetSize => "target_size",
+            LightUniform::UseNormals => "use_normals",
+        }
+    }
+
+    fn for_each<F: FnMut(Self)>(mut f: F) {
+        f(LightUniform::View);
+        f(LightUniform::TargetSize);
+        f(LightUniform::UseNormals);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum CompositeUniform {
+    Ambient,
+}
+
+impl Uniform for CompositeUniform {
+    fn name(&self) -> &str {
+        match self {
+            CompositeUniform::Ambient => "ambient",
+        }
+    }
+
+    fn for_each<F: FnMut(Self)>(mut f: F) {
+        f(CompositeUniform::Ambient);
+    }
+}
+
+/// A light, drawn as instance data by `LIGHT_VERTEX_GLSL` and `LIGHT_FRAGMENT_GLSL`:
+///
+/// * "light_position" (`vec2`)
+/// * "light_radius" (`float`)
+/// * "light_color" (`vec4`, packed as 4 normalized bytes)
+/// * "light_intensity" (`float`)
+/// * "light_height" (`float`)
+/// * "light_cone" (`vec2`), its direction in radians and the cosine of its half angle
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    /// In world coordinates, like the sprites.
+    pub position: Vector2<f32>,
+    /// Distance at which the light fades out completely.
+    pub radius: f32,
+    /// The alpha of the color multiplies its intensity.
+    pub color: Color<u8>,
+    /// 1.0 by default. Above 1.0, lit sprites get brighter than their texture.
+    pub intensity: f32,
+    /// Distance between the light and the plane of the sprites, for normal maps: low lights
+    /// light the sides of bumps, high lights light everything evenly.
+    pub height: f32,
+    /// (direction, half angle) in degrees for a cone light, clockwise on screen from the x axis.
+    pub cone: Option<(f32, f32)>,
+    /// Whether occluders block this light. True by default.
+    pub casts_shadows: bool,
+}
+
+impl Light {
+    /// A light shining in every direction.
+    pub fn point(position: Vector2<f32>, radius: f32, color: Color<u8>) -> Light {
+        Light {
+            position,
+            radius,
+            color,
+            intensity: 1.0,
+            height: radius / 4.0,
+            cone: None,
+            casts_shadows: true,
+        }
+    }
+
+    /// A light shining towards `direction`, up to `half_angle` on each side, in degrees.
+    pub fn cone(position: Vector2<f32>, radius: f32, color: Color<u8>, direction: f32, half_angle: f32) -> Light {
+        Light {
+            cone: Some((direction, half_angle)),
+            ..Light::point(position, radius, color)
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Light {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_height(mut self, height: f32) -> Light {
+        self.height = height;
+        self
+    }
+
+    /// A light going through occluders, cheaper to draw.
+    pub fn without_shadows(mut self) -> Light {
+        self.casts_shadows = false;
+        self
+    }
+
+    /// The cone as written in "light_cone".
+    fn cone_data(&self) -> [f32; 2] {
+        match self.cone {
+            Some((direction, half_angle)) => [direction.to_radians(), half_angle.to_radians().cos()],
+            None => [0.0, -1.0],
+        }
+    }
+}
+
+instance_data!(impl AsVertexData for Light |light| {
+    "light_position": [f32; 2] = [light.position.x, light.position.y],
+    "light_radius": f32 = light.radius,
+    "light_color": Color<u8> = light.color,
+    "light_intensity": f32 = light.intensity,
+    "light_height": f32 = light.height,
+    "light_cone": [f32; 2] = light.cone_data(),
+});
+
+/// The scene, normal and light map textures, and the shaders lighting the scene, see the module
+/// documentation.
+pub struct Lighting {
+    scene: RenderTarget,
+    normals: RenderTarget,
+    light_map: RenderTarget,
+    lights: Renderer<LightUniform>,
+    shadows: Renderer<ShadowUniform>,
+    occluders: Vec<Occluder>,
+    composite: Shader<CompositeUniform>,
+    /// empty, the fullscreen triangle doesn't need any attrib
+    vao: GLuint,
+    /// bound before `begin`, `end` draws to it
+    output_framebuffer: GLuint,
+    /// whether `begin_normals` was called since `begin`
+    has_normals: bool,
+    /// Light everything gets without any `Light`. Black by default.
+    pub ambient: Color<u8>,
+}
+
+impl std::fmt::Debug for Lighting {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Lighting")
+            .field("scene", &self.scene)
+            .field("normals", &self.normals)
+            .field("light_map", &self.light_map)
+            .field("occluders", &self.occluders.len())
+            .field("ambient", &self.ambient)
+            .finish()
+    }
+}
+
+impl Lighting {
+    /// Lighting for `width` x `height` frames.
+    ///
+    /// Returns an error if the shaders can't be built, or if the textures can't be allocated.
+    ///
+    /// The GL state (blend and depth modes, bindings) is left as it was: building the renderers
+    /// of the lights and shadows would otherwise set theirs.
+    pub fn new(width: u32, height: u32) -> Result<Lighting, SprowlError> {
+        let _state = GlStateGuard::new();
+        let light_shader = Shader::new(LIGHT_FRAGMENT_GLSL, LIGHT_VERTEX_GLSL, &["normal_map"])?;
+        let lights = RendererBuilder::new(MAX_LIGHTS_PER_DRAW)
+            .with_vertex_layout(Light::with_fields(VertexLayout::new()))
+            .build_with(light_shader);
+        let shadow_shader = Shader::new(SHADOW_FRAGMENT_GLSL, SHADOW_VERTEX_GLSL, &[])?;
+        let shadows = RendererBuilder::new(MAX_OCCLUDERS_PER_DRAW)
+            .with_vertex_layout(Occluder::with_fields(VertexLayout::new()))
+            .build_with(shadow_shader);
+        let composite = Shader::new(include_str!("composite.fs.glsl"), FULLSCREEN_VERTEX_GLSL, &["scene_texture", "light_map"])?;
+        let mut vao: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let vao = unsafe {
+            gl::GenVertexArrays(1, vao.as_mut_ptr());
+            vao.assume_init()
+        };
+        Ok(Lighting {
+            scene: RenderTarget::new(width, height)?,
+            normals: RenderTarget::new(width, height)?,
+            light_map: RenderTarget::with_stencil(width, height)?,
+            lights,
+            shadows,
+            occluders: Vec::new(),
+            composite,
+            vao,
+            output_framebuffer: 0,
+            has_normals: false,
+            ambient: Color::<u8>::from_rgb(0, 0, 0),
+        })
+    }
+
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        self.scene.size()
+    }
+
+    /// Call this when the window is resized, along with `Renderer::set_viewport`.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), SprowlError> {
+        self.scene.resize(width, height)?;
+        self.normals.resize(width, height)?;
+        self.light_map.resize(width, height)
+    }
+
+    /// Cast shadows from `occluders` as well, until `clear_occluders`.
+    pub fn add_occluders(&mut self, occluders: &[Occluder]) {
+        self.occluders.extend_from_slice(occluders);
+    }
+
+    pub fn add_occluder(&mut self, occluder: Occluder) {
+        self.occluders.push(occluder);
+    }
+
+    pub fn clear_occluders(&mut self) {
+        self.occluders.clear();
+    }
+
+    #[inline]
+    pub fn occluders(&self) -> &[Occluder] {
+        &self.occluders
+    }
+
+    /// The texture the scene is drawn to, until the next `begin`.
+    #[inline]
+    pub fn scene(&self) -> &RenderTarget {
+        &self.scene
+    }
+
+    /// The light added by the lights of the last `end`, without the ambient light.
+    #[inline]
+    pub fn light_map(&self) -> &RenderTarget {
+        &self.light_map
+    }
+
+    /// Draw everything to the scene texture from now on. Elements added before this call are
+    /// drawn right away, to the current framebuffer.
+    pub fn begin<U: Uniform>(&mut self, renderer: &mut Renderer<U>) {
+        renderer.flush();
+        self.has_normals = false;
+        let (width, height) = self.scene.size();
+        unsafe {
+            let mut output_framebuffer: GLint = 0;
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut output_framebuffer);
+            self.output_framebuffer = output_framebuffer as GLuint;
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.scene.framebuffer_id());
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+    }
+
+    /// Draw the normal maps of the sprites from now on, until `end`. Elements added before this
+    /// call are drawn right away, to the scene.
+    ///
+    /// The normals are cleared to a flat surface first: sprites without a normal map don't need
+    /// to draw anything.
+    pub fn begin_normals<U: Uniform>(&mut self, renderer: &mut Renderer<U>) {
+        renderer.flush();
+        self.has_normals = true;
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.normals.framebuffer_id());
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::ClearColor(0.5, 0.5, 1.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        renderer.apply_clip_rect();
+    }
+
+    /// Add `lights` to the light map, and draw the scene multiplied by the light to the
+    /// framebuffer bound before `begin`. `view` must be the view matrix used to draw the scene.
+    ///
+    /// Elements added before this call are drawn first. Afterwards, the program of the renderer
+    /// is in use again, and its viewport, clip rect, blend and depth modes are restored.
+    pub fn end<U: Uniform>(&mut self, renderer: &mut Renderer<U>, lights: &[Light], view: &Matrix4<f32>) {
+        renderer.flush();
+        let (width, height) = self.scene.size();
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.light_map.framebuffer_id());
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            // lights add up
+            gl::Enable(gl::BLEND);
+            gl::BlendEquation(gl::FUNC_ADD);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.normals.texture_id());
+        }
+        self.lights.shader.use_program();
+        self.lights.shader.set_matrix4(LightUniform::View, view);
+        self.lights.shader.set_vector2(LightUniform::TargetSize, &Vector2::new(width as f32, height as f32));
+        self.lights.shader.set_int(LightUniform::UseNormals, self.has_normals as GLint);
+        let mut shadowed_lights = Vec::new();
+        for light in lights {
+            if light.casts_shadows && self.occluders.iter().any(|o| o.is_within(light.position, light.radius)) {
+                shadowed_lights.push(light);
+            } else {
+                self.lights.add_elem(light);
+            }
+        }
+        self.lights.flush();
+        if !shadowed_lights.is_empty() {
+            self.draw_shadowed_lights(&shadowed_lights, view);
+        }
+
+        let ambient = self.ambient.to_color_f32();
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.output_framebuffer);
+            gl::Disable(gl::BLEND);
+            gl::BindTexture(gl::TEXTURE_2D, self.scene.texture_id());
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, self.light_map.texture_id());
+        }
+        self.composite.use_program();
+        self.composite.set_vector3(CompositeUniform::Ambient, &Vector3::new(ambient.r, ambient.g, ambient.b));
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.output_framebuffer);
+        }
+        renderer.apply_blend_mode();
+        renderer.apply_depth_mode();
+        renderer.shader.use_program();
+        let (viewport_width, viewport_height) = renderer.viewport;
+        renderer.set_viewport(viewport_width, viewport_height);
+    }
+
+    /// Draw every light after its shadows, with the light map bound and the light shader set up.
+    fn draw_shadowed_lights(&mut self, lights: &[&Light], view: &Matrix4<f32>) {
+        self.shadows.shader.use_program();
+        self.shadows.shader.set_matrix4(ShadowUniform::View, view);
+        unsafe {
+            gl::Enable(gl::STENCIL_TEST);
+            gl::StencilMask(0xFF);
+            gl::ClearStencil(0);
+        }
+        for light in lights {
+            unsafe {
+                gl::Clear(gl::STENCIL_BUFFER_BIT);
+                gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                gl::StencilFunc(gl::ALWAYS, 1, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+            }
+            self.shadows.shader.use_program();
+            self.shadows.shader.set_vector2(ShadowUniform::LightPosition, &light.position);
+            self.shadows.shader.set_float(ShadowUniform::Extrusion, light.radius * SHADOW_EXTRUSION);
+            for occluder in self.occluders.iter().filter(|o| o.is_within(light.position, light.radius)) {
+                self.shadows.add_elem(occluder);
+            }
+            self.shadows.flush();
+
+            unsafe {
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                gl::StencilFunc(gl::EQUAL, 0, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            }
+            self.lights.shader.use_program();
+            self.lights.add_elem(*light);
+            self.lights.flush();
+        }
+        unsafe {
+            gl::Disable(gl::STENCIL_TEST);
+        }
+    }
+}
+
+impl Drop for Lighting {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
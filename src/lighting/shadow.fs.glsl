@@ -0,0 +1,9 @@
+#version 330 core
+
+// The fragment shader of `Occluder`: shadows only write to the stencil buffer.
+out vec4 frag_color;
+
+void main()
+{
+    frag_color = vec4(0.0);
+}
@@ -0,0 +1,79 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::renderer::instance_data;
+use crate::shader::Uniform;
+
+/// The vertex shader of `Occluder`, with the uniforms "view", "light_position" and "extrusion".
+pub const SHADOW_VERTEX_GLSL: &str = include_str!("shadow.vs.glsl");
+/// The fragment shader of `Occluder`. It doesn't write any color, only the stencil matters.
+pub const SHADOW_FRAGMENT_GLSL: &str = include_str!("shadow.fs.glsl");
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub (crate) enum ShadowUniform {
+    View,
+    LightPosition,
+    Extrusion,
+}
+
+impl Uniform for ShadowUniform {
+    fn name(&self) -> &str {
+        match self {
+            ShadowUniform::View => "view",
+            ShadowUniform::LightPosition => "light_position",
+            ShadowUniform::Extrusion => "extrusion",
+        }
+    }
+
+    fn for_each<F: FnMut(Self)>(mut f: F) {
+        f(ShadowUniform::View);
+        f(ShadowUniform::LightPosition);
+        f(ShadowUniform::Extrusion);
+    }
+}
+
+/// A segment blocking the light, casting a hard shadow away from every `Light` which
+/// `casts_shadows`.
+///
+/// Drawn as instance data by `SHADOW_VERTEX_GLSL`: "occluder_segment" (`vec4`), both ends of the
+/// segment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Occluder {
+    /// In world coordinates, like the sprites.
+    pub a: Vector2<f32>,
+    pub b: Vector2<f32>,
+}
+
+impl Occluder {
+    pub fn segment(a: Vector2<f32>, b: Vector2<f32>) -> Occluder {
+        Occluder { a, b }
+    }
+
+    /// The 4 sides of a rectangle, `position` being its top-left corner.
+    pub fn rect(position: Vector2<f32>, size: Vector2<f32>) -> [Occluder; 4] {
+        let top_right = position + Vector2::new(size.x, 0.0);
+        let bottom_right = position + size;
+        let bottom_left = position + Vector2::new(0.0, size.y);
+        [
+            Occluder::segment(position, top_right),
+            Occluder::segment(top_right, bottom_right),
+            Occluder::segment(bottom_right, bottom_left),
+            Occluder::segment(bottom_left, position),
+        ]
+    }
+
+    /// Whether any point of the segment is within `radius` of `point`.
+    pub fn is_within(&self, point: Vector2<f32>, radius: f32) -> bool {
+        let ab = self.b - self.a;
+        let length2 = ab.magnitude2();
+        let t = if length2 > 0.0 {
+            ((point - self.a).dot(ab) / length2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (self.a + ab * t - point).magnitude2() <= radius * radius
+    }
+}
+
+instance_data!(impl AsVertexData for Occluder |occluder| {
+    "occluder_segment": [f32; 4] = [occluder.a.x, occluder.a.y, occluder.b.x, occluder.b.y],
+});
@@ -0,0 +1,20 @@
+#version 330 core
+
+// The vertex shader of `Occluder`: the segment, extruded away from the light.
+layout (location = 0) in vec2 pos;
+// (ax, ay, bx, by)
+layout (location = 1) in vec4 occluder_segment;
+
+uniform mat4 view;
+uniform vec2 light_position;
+// how far the shadow goes, far enough to leave the radius of the light
+uniform float extrusion;
+
+void main()
+{
+    // pos.x chooses the end of the segment, pos.y the near or the far side of the shadow
+    vec2 point = mix(occluder_segment.xy, occluder_segment.zw, pos.x);
+    vec2 away = point - light_position;
+    away = away / max(length(away), 0.0001);
+    gl_Position = view * vec4(point + away * extrusion * pos.y, 0.0, 1.0);
+}
@@ -0,0 +1,369 @@
+//! Particle emitters, simulated on the CPU and drawn as instances.
+//!
+//! An emitter spawns particles according to its `EmitterConfig`, moves them, and makes them
+//! change size and color over their life with `Curve`s. Particles live in a pool allocated once,
+//! and are written to the instanced buffer of the `Renderer` all at once, so thousands of them are
+//! drawn with a single call as long as `max_instances` allows it.
+//!
+//! ```ignore
+//! let mut emitter = ParticleEmitter::new(EmitterConfig::default(), vec2(400.0, 300.0));
+//! // every frame
+//! emitter.update(dt);
+//! emitter.add_to_renderer(&mut renderer, |particle| to_vertex_data(particle));
+//! renderer.draw();
+//! ```
+
+use cgmath::Vector2;
+
+use crate::color::Color;
+use crate::renderer::{AsVertexData, Renderer, instance_data};
+use crate::shader::Uniform;
+
+/// Values which can be interpolated by a `Curve`.
+pub trait Interpolate: Copy {
+    fn interpolate(self, other: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vector2<f32> {
+    fn interpolate(self, other: Vector2<f32>, t: f32) -> Vector2<f32> {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Color<f32> {
+    fn interpolate(self, other: Color<f32>, t: f32) -> Color<f32> {
+        self.lerp(other, t)
+    }
+}
+
+/// A value changing over the life of a particle, from 0.0 (birth) to 1.0 (death), linearly
+/// interpolated between keys.
+#[derive(Debug, Clone)]
+pub struct Curve<T: Interpolate> {
+    /// (time, value), sorted by time
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Interpolate> Curve<T> {
+    /// The same value during the whole life.
+    pub fn constant(value: T) -> Curve<T> {
+        Curve { keys: vec![(0.0, value)] }
+    }
+
+    /// From `start` at birth to `end` at death.
+    pub fn linear(start: T, end: T) -> Curve<T> {
+        Curve { keys: vec![(0.0, start), (1.0, end)] }
+    }
+
+    /// Add a key at `time`, between 0.0 and 1.0.
+    pub fn with_key(mut self, time: f32, value: T) -> Curve<T> {
+        let index = self.keys.partition_point(|(key_time, _)| *key_time <= time);
+        self.keys.insert(index, (time, value));
+        self
+    }
+
+    /// The value at `time`, between 0.0 and 1.0. Before the first key and after the last one,
+    /// the value of the closest key is used.
+    pub fn sample(&self, time: f32) -> T {
+        let index = self.keys.partition_point(|(key_time, _)| *key_time <= time);
+        if index == 0 {
+            return self.keys[0].1;
+        }
+        if index == self.keys.len() {
+            return self.keys[index - 1].1;
+        }
+        let (t0, v0) = self.keys[index - 1];
+        let (t1, v1) = self.keys[index];
+        v0.interpolate(v1, (time - t0) / (t1 - t0))
+    }
+}
+
+/// How an emitter spawns its particles, and how they evolve.
+///
+/// Ranges are (min, max), and every particle picks a random value in them.
+#[derive(Debug, Clone)]
+pub struct EmitterConfig {
+    /// Particles spawned per second while emitting.
+    pub spawn_rate: f32,
+    /// No more particles are spawned while this many are alive.
+    pub max_particles: usize,
+    /// in seconds
+    pub lifetime: (f32, f32),
+    /// Direction of the particles when spawned, in degrees: 0 is right, 90 is down.
+    pub direction: f32,
+    /// The direction of every particle is randomly picked up to `spread / 2` degrees away from
+    /// `direction`. 360 spawns particles in every direction.
+    pub spread: f32,
+    /// in units per second
+    pub speed: (f32, f32),
+    /// Multiplies the speed over the life of the particle.
+    pub speed_curve: Curve<f32>,
+    /// Added to the velocity every second, for gravity or wind.
+    pub acceleration: Vector2<f32>,
+    /// Particles spawn at a random position in a rect of this size, centered on the emitter.
+    pub spawn_area: Vector2<f32>,
+    /// Size of the particles over their life, in units.
+    pub size: Curve<f32>,
+    pub color: Curve<Color<f32>>,
+    /// Rotation speed of the particles, in degrees per second.
+    pub angular_speed: (f32, f32),
+}
+
+impl Default for EmitterConfig {
+    /// White particles spawned in every direction, fading out in one second.
+    fn default() -> EmitterConfig {
+        EmitterConfig {
+            spawn_rate: 100.0,
+            max_particles: 1000,
+            lifetime: (0.8, 1.2),
+            direction: 0.0,
+            spread: 360.0,
+            speed: (50.0, 100.0),
+            speed_curve: Curve::constant(1.0),
+            acceleration: Vector2::new(0.0, 0.0),
+            spawn_area: Vector2::new(0.0, 0.0),
+            size: Curve::constant(4.0),
+            color: Curve::linear(Color::white(), Color::white().with_alpha(0.0)),
+            angular_speed: (0.0, 0.0),
+        }
+    }
+}
+
+/// A living particle.
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    /// Center of the particle.
+    pub position: Vector2<f32>,
+    /// Velocity before `EmitterConfig::speed_curve` is applied.
+    pub velocity: Vector2<f32>,
+    /// Seconds since the particle was spawned.
+    pub age: f32,
+    pub lifetime: f32,
+    /// in degrees
+    pub rotation: f32,
+    pub angular_speed: f32,
+    /// Sampled from `EmitterConfig::size` on every update.
+    pub size: f32,
+    /// Sampled from `EmitterConfig::color` on every update.
+    pub color: Color<f32>,
+}
+
+impl Particle {
+    /// From 0.0 when spawned to 1.0 when it dies.
+    #[inline]
+    pub fn life(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+
+    /// The particle as `ParticleInstanceData`.
+    pub fn instance_data(&self) -> ParticleInstanceData {
+        ParticleInstanceData {
+            center: [self.position.x, self.position.y],
+            size: self.size,
+            rotation: self.rotation,
+            color: [self.color.r, self.color.g, self.color.b, self.color.a],
+        }
+    }
+}
+
+/// A `Particle` as instance data:
+///
+/// * "particle_center" (`vec2`)
+/// * "particle_size" (`float`)
+/// * "particle_rotation" (`float`), in degrees
+/// * "particle_color" (`vec4`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleInstanceData {
+    pub center: [f32; 2],
+    pub size: f32,
+    pub rotation: f32,
+    pub color: [f32; 4],
+}
+
+instance_data!(impl AsVertexData for ParticleInstanceData |particle| {
+    "particle_center": [f32; 2] = particle.center,
+    "particle_size": f32 = particle.size,
+    "particle_rotation": f32 = particle.rotation,
+    "particle_color": [f32; 4] = particle.color,
+});
+
+/// A xorshift generator: particles don't need good randomness, but they need a lot of it.
+#[derive(Debug, Clone)]
+struct Rng(u32);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+/// Spawns and simulates particles, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct ParticleEmitter {
+    config: EmitterConfig,
+    /// Where particles spawn.
+    pub position: Vector2<f32>,
+    emitting: bool,
+    particles: Vec<Particle>,
+    /// particles to spawn, carried over from one update to the next
+    spawn_accumulator: f32,
+    rng: Rng,
+    /// reused every frame, to avoid reallocating
+    instanced_vb: Vec<u8>,
+}
+
+impl ParticleEmitter {
+    pub fn new(config: EmitterConfig, position: Vector2<f32>) -> ParticleEmitter {
+        ParticleEmitter::with_seed(config, position, 0x9E37_79B9)
+    }
+
+    /// Same as `new`, with the seed of the random generator, to get the same particles every time.
+    pub fn with_seed(config: EmitterConfig, position: Vector2<f32>, seed: u32) -> ParticleEmitter {
+        ParticleEmitter {
+            particles: Vec::with_capacity(config.max_particles),
+            config,
+            position,
+            emitting: true,
+            spawn_accumulator: 0.0,
+            // xorshift gets stuck on 0.
+            rng: Rng(seed.max(1)),
+            instanced_vb: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &EmitterConfig {
+        &self.config
+    }
+
+    /// Changes are applied to the particles spawned from now on, but `size`, `color` and
+    /// `speed_curve` apply to every particle.
+    #[inline]
+    pub fn config_mut(&mut self) -> &mut EmitterConfig {
+        &mut self.config
+    }
+
+    /// Start or stop spawning particles. Living particles keep being updated.
+    #[inline]
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+    }
+
+    #[inline]
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    #[inline]
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Number of living particles.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Remove every particle.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_accumulator = 0.0;
+    }
+
+    /// Spawn `count` particles at once, within the limit of `max_particles`.
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.particles.len() >= self.config.max_particles {
+                break;
+            }
+            let particle = self.spawn();
+            self.particles.push(particle);
+        }
+    }
+
+    fn spawn(&mut self) -> Particle {
+        let config = &self.config;
+        let rng = &mut self.rng;
+        let direction = (config.direction + (rng.next_f32() - 0.5) * config.spread).to_radians();
+        let speed = rng.range(config.speed);
+        let offset = Vector2::new(
+            (rng.next_f32() - 0.5) * config.spawn_area.x,
+            (rng.next_f32() - 0.5) * config.spawn_area.y,
+        );
+        Particle {
+            position: self.position + offset,
+            velocity: Vector2::new(direction.cos(), direction.sin()) * speed,
+            age: 0.0,
+            lifetime: rng.range(config.lifetime).max(f32::EPSILON),
+            rotation: rng.next_f32() * 360.0,
+            angular_speed: rng.range(config.angular_speed),
+            size: config.size.sample(0.0),
+            color: config.color.sample(0.0),
+        }
+    }
+
+    /// Move every particle by `dt` seconds, remove the dead ones and spawn new ones.
+    pub fn update(&mut self, dt: f32) {
+        let config = &self.config;
+        let mut i = 0;
+        while i < self.particles.len() {
+            let particle = &mut self.particles[i];
+            particle.age += dt;
+            if particle.age >= particle.lifetime {
+                // the order of the particles doesn't matter.
+                self.particles.swap_remove(i);
+                continue;
+            }
+            let life = particle.life();
+            particle.velocity += config.acceleration * dt;
+            particle.position += particle.velocity * config.speed_curve.sample(life) * dt;
+            particle.rotation += particle.angular_speed * dt;
+            particle.size = config.size.sample(life);
+            particle.color = config.color.sample(life);
+            i += 1;
+        }
+
+        if self.emitting {
+            self.spawn_accumulator += self.config.spawn_rate * dt;
+            let count = self.spawn_accumulator.floor();
+            self.spawn_accumulator -= count;
+            self.burst(count as usize);
+        }
+    }
+
+    /// Add every particle to the renderer at once, `to_vertex_data` converting them to your own
+    /// vertex data.
+    pub fn add_to_renderer<U: Uniform, E: AsVertexData, F: FnMut(&Particle) -> E>(&mut self, renderer: &mut Renderer<U>, mut to_vertex_data: F) {
+        self.instanced_vb.clear();
+        let mut instance_count = 0;
+        for particle in &self.particles {
+            instance_count += to_vertex_data(particle).add_vertex_data(&mut self.instanced_vb) as usize;
+        }
+        renderer.add_raw_instances(&self.instanced_vb, instance_count);
+    }
+
+    /// Same as `add_to_renderer`, for a renderer whose layout is the one of
+    /// `ParticleInstanceData::with_fields`.
+    pub fn add_instances_to_renderer<U: Uniform>(&mut self, renderer: &mut Renderer<U>) {
+        self.add_to_renderer(renderer, |particle| particle.instance_data());
+    }
+}
@@ -0,0 +1,14 @@
+#version 330 core
+
+in vec2 uv;
+out vec4 color;
+
+// the blurred bright parts
+uniform sampler2D input_texture;
+uniform sampler2D scene_texture;
+uniform float intensity;
+
+void main() {
+    vec4 scene = texture(scene_texture, uv);
+    color = vec4(scene.rgb + texture(input_texture, uv).rgb * intensity, scene.a);
+}
@@ -0,0 +1,20 @@
+#version 330 core
+
+in vec2 uv;
+out vec4 color;
+
+uniform sampler2D input_texture;
+// (radius, 0) to blur horizontally, (0, radius) vertically, in texels
+uniform vec2 direction;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec2 texel = direction / vec2(textureSize(input_texture, 0));
+    vec4 sum = texture(input_texture, uv) * weights[0];
+    for (int i = 1; i < 5; i++) {
+        sum += texture(input_texture, uv + texel * float(i)) * weights[i];
+        sum += texture(input_texture, uv - texel * float(i)) * weights[i];
+    }
+    color = sum;
+}
@@ -0,0 +1,14 @@
+#version 330 core
+
+in vec2 uv;
+out vec4 color;
+
+uniform sampler2D input_texture;
+// luminance under which pixels don't glow
+uniform float threshold;
+
+void main() {
+    vec4 c = texture(input_texture, uv);
+    float luminance = dot(c.rgb, vec3(0.2126, 0.7152, 0.0722));
+    color = vec4(c.rgb * max(luminance - threshold, 0.0) / max(luminance, 0.0001), 1.0);
+}
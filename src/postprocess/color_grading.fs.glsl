@@ -0,0 +1,23 @@
+#version 330 core
+
+in vec2 uv;
+out vec4 color;
+
+uniform sampler2D input_texture;
+// added to every channel
+uniform float brightness;
+// 1.0 keeps the contrast
+uniform float contrast;
+// 0.0 is grayscale, 1.0 keeps the colors
+uniform float saturation;
+// multiplies every channel
+uniform vec3 tint;
+
+void main() {
+    vec4 c = texture(input_texture, uv);
+    vec3 rgb = c.rgb + brightness;
+    rgb = (rgb - 0.5) * contrast + 0.5;
+    float luminance = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+    rgb = mix(vec3(luminance), rgb, saturation) * tint;
+    color = vec4(clamp(rgb, 0.0, 1.0), c.a);
+}
@@ -0,0 +1,214 @@
+//! Fullscreen passes applied to the whole frame: bloom, vignette, color grading, or your own.
+//!
+//! Between `PostProcessChain::begin` and `PostProcessChain::end`, everything is drawn to an
+//! offscreen texture. `end` then runs every pass in order, each one reading the result of the
+//! previous one, and the last one draws to the framebuffer which was bound before `begin`.
+//!
+//! ```ignore
+//! let mut chain = PostProcessChain::new(width, height)?;
+//! chain.add_bloom(0.7, 1.0, 2.0)?;
+//! chain.add_pass(VignettePass::new()?);
+//! // every frame
+//! chain.begin(&mut renderer);
+//! renderer.clear(None);
+//! // add and draw elements as usual
+//! renderer.draw();
+//! chain.end(&mut renderer);
+//! ```
+//!
+//! Your own passes are `ShaderPass`es: a vertex shader `FULLSCREEN_VERTEX_GLSL`, and a fragment
+//! shader reading `in vec2 uv`. The input of the pass is bound to texture unit 0 and the scene,
+//! before any pass, to unit 1: name them with the `texture_units` of `Shader::new`.
+
+mod passes;
+mod target;
+
+pub use passes::*;
+pub use target::*;
+
+use crate::backend::gl::{self, types::*};
+use crate::error::SprowlError;
+use crate::renderer::Renderer;
+use crate::shader::{ShaderError, Uniform};
+use std::mem::MaybeUninit;
+
+/// A vertex shader drawing a triangle which covers the screen, giving `out vec2 uv` to the
+/// fragment shader. Draws without any vertex attrib.
+pub const FULLSCREEN_VERTEX_GLSL: &str = include_str!("fullscreen.vs.glsl");
+
+/// What a pass knows about the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassContext {
+    /// Size of the input and of the output of the pass, in pixels.
+    pub size: (u32, u32),
+    /// Position of the pass in the chain.
+    pub index: usize,
+}
+
+/// A fullscreen pass of a `PostProcessChain`.
+pub trait PostProcessPass {
+    /// Use the program of the pass and set its uniforms. The chain then binds the textures and
+    /// draws a fullscreen triangle.
+    fn prepare(&mut self, context: &PassContext);
+}
+
+/// Renders the scene to a texture, and applies passes to it, see the module documentation.
+pub struct PostProcessChain {
+    scene: RenderTarget,
+    /// the passes alternate between these two
+    ping_pong: [RenderTarget; 2],
+    passes: Vec<Box<dyn PostProcessPass>>,
+    /// empty, the fullscreen triangle doesn't need any attrib
+    vao: GLuint,
+    /// bound before `begin`, the last pass draws to it
+    output_framebuffer: GLuint,
+}
+
+impl std::fmt::Debug for PostProcessChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PostProcessChain")
+            .field("scene", &self.scene)
+            .field("ping_pong", &self.ping_pong)
+            .field("passes", &self.passes.len())
+            .finish()
+    }
+}
+
+impl PostProcessChain {
+    /// A chain without any pass, drawing `width` x `height` frames. The scene is copied as is
+    /// until passes are added.
+    pub fn new(width: u32, height: u32) -> Result<PostProcessChain, SprowlError> {
+        let mut vao: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let vao = unsafe {
+            gl::GenVertexArrays(1, vao.as_mut_ptr());
+            vao.assume_init()
+        };
+        Ok(PostProcessChain {
+            scene: RenderTarget::new(width, height)?,
+            ping_pong: [RenderTarget::new(width, height)?, RenderTarget::new(width, height)?],
+            passes: Vec::new(),
+            vao,
+            output_framebuffer: 0,
+        })
+    }
+
+    /// Add a pass at the end of the chain.
+    pub fn add_pass<P: PostProcessPass + 'static>(&mut self, pass: P) {
+        self.passes.push(Box::new(pass));
+    }
+
+    pub fn with_pass<P: PostProcessPass + 'static>(mut self, pass: P) -> Self {
+        self.add_pass(pass);
+        self
+    }
+
+    /// Add the passes of a bloom: the parts brighter than `threshold` are blurred by
+    /// `radius` and added to the scene, multiplied by `intensity`.
+    ///
+    /// Since the scene is added back, the bloom should be the first effect of the chain.
+    pub fn add_bloom(&mut self, threshold: f32, intensity: f32, radius: f32) -> Result<(), ShaderError> {
+        self.add_pass(BrightPass::new(threshold)?);
+        self.add_pass(BlurPass::new(radius, false)?);
+        self.add_pass(BlurPass::new(radius, true)?);
+        self.add_pass(BloomCombinePass::new(intensity)?);
+        Ok(())
+    }
+
+    /// Remove every pass.
+    pub fn clear_passes(&mut self) {
+        self.passes.clear();
+    }
+
+    #[inline]
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        self.scene.size()
+    }
+
+    /// Call this when the window is resized, along with `Renderer::set_viewport`.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), SprowlError> {
+        self.scene.resize(width, height)?;
+        for target in &mut self.ping_pong {
+            target.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    /// The texture the scene is drawn to, until the next `begin`.
+    #[inline]
+    pub fn scene(&self) -> &RenderTarget {
+        &self.scene
+    }
+
+    /// Draw everything to the scene texture from now on. Elements added before this call are
+    /// drawn right away, to the current framebuffer.
+    pub fn begin<U: Uniform>(&mut self, renderer: &mut Renderer<U>) {
+        renderer.flush();
+        let (width, height) = self.scene.size();
+        unsafe {
+            let mut output_framebuffer: GLint = 0;
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut output_framebuffer);
+            self.output_framebuffer = output_framebuffer as GLuint;
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.scene.framebuffer_id());
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+    }
+
+    /// Apply every pass to the scene, the last one drawing to the framebuffer bound before `begin`.
+    ///
+    /// Elements added before this call are drawn to the scene first. Afterwards, the program of
+    /// the renderer is in use again, and its viewport, clip rect and blend mode are restored.
+    pub fn end<U: Uniform>(&mut self, renderer: &mut Renderer<U>) {
+        renderer.flush();
+        let (width, height) = self.scene.size();
+        unsafe {
+            // the passes replace every pixel, whatever the clip rect.
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::Disable(gl::DEPTH_TEST);
+            if self.passes.is_empty() {
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.scene.framebuffer_id());
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.output_framebuffer);
+                gl::BlitFramebuffer(0, 0, width as GLint, height as GLint, 0, 0, width as GLint, height as GLint, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+            } else {
+                gl::ActiveTexture(gl::TEXTURE1);
+                gl::BindTexture(gl::TEXTURE_2D, self.scene.texture_id());
+                gl::BindVertexArray(self.vao);
+                let mut input = self.scene.texture_id();
+                let last = self.passes.len() - 1;
+                for (index, pass) in self.passes.iter_mut().enumerate() {
+                    let target = &self.ping_pong[index % 2];
+                    gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, if index == last { self.output_framebuffer } else { target.framebuffer_id() });
+                    gl::ActiveTexture(gl::TEXTURE0);
+                    gl::BindTexture(gl::TEXTURE_2D, input);
+                    pass.prepare(&PassContext { size: (width, height), index });
+                    gl::DrawArrays(gl::TRIANGLES, 0, 3);
+                    input = target.texture_id();
+                }
+                gl::BindVertexArray(0);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::ActiveTexture(gl::TEXTURE1);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::ActiveTexture(gl::TEXTURE0);
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.output_framebuffer);
+        }
+        renderer.apply_blend_mode();
+        renderer.apply_depth_mode();
+        renderer.shader.use_program();
+        let (viewport_width, viewport_height) = renderer.viewport;
+        renderer.set_viewport(viewport_width, viewport_height);
+    }
+}
+
+impl Drop for PostProcessChain {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use cgmath::{Vector2, Vector3, Vector4};
+
+use crate::color::Color;
+use crate::shader::{Shader, ShaderError, Uniform};
+use super::{FULLSCREEN_VERTEX_GLSL, PassContext, PostProcessPass};
+
+/// Declares the uniforms of a built-in pass.
+macro_rules! pass_uniforms {
+    ($name:ident { $($variant:ident => $glsl:expr),* $(,)? }) => {
+        #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+        enum $name {
+            $($variant),*
+        }
+
+        impl Uniform for $name {
+            fn name(&self) -> &str {
+                match self {
+                    $($name::$variant => $glsl),*
+                }
+            }
+
+            fn for_each<F: FnMut(Self)>(mut f: F) {
+                $(f($name::$variant);)*
+            }
+        }
+    };
+}
+
+pass_uniforms!(BlurUniform { Direction => "direction" });
+pass_uniforms!(BrightUniform { Threshold => "threshold" });
+pass_uniforms!(BloomCombineUniform { Intensity => "intensity" });
+pass_uniforms!(VignetteUniform { Radius => "radius", Softness => "softness", Color => "vignette_color" });
+pass_uniforms!(ColorGradingUniform { Brightness => "brightness", Contrast => "contrast", Saturation => "saturation", Tint => "tint" });
+
+/// Half of a gaussian blur: blurs along a single axis, so a blur is made of an horizontal and a
+/// vertical pass.
+#[derive(Debug)]
+pub struct BlurPass {
+    shader: Shader<BlurUniform>,
+    /// Distance between two samples, in texels. The blur spans 4 samples on each side.
+    pub radius: f32,
+    pub vertical: bool,
+}
+
+impl BlurPass {
+    pub fn new(radius: f32, vertical: bool) -> Result<BlurPass, ShaderError> {
+        let shader = Shader::new(include_str!("blur.fs.glsl"), FULLSCREEN_VERTEX_GLSL, &["input_texture"])?;
+        Ok(BlurPass { shader, radius, vertical })
+    }
+}
+
+impl PostProcessPass for BlurPass {
+    fn prepare(&mut self, _context: &PassContext) {
+        let direction = if self.vertical { Vector2::new(0.0, self.radius) } else { Vector2::new(self.radius, 0.0) };
+        self.shader.use_program();
+        self.shader.set_vector2(BlurUniform::Direction, &direction);
+    }
+}
+
+/// Keeps the parts brighter than `threshold`, the first step of a bloom.
+#[derive(Debug)]
+pub struct BrightPass {
+    shader: Shader<BrightUniform>,
+    /// Luminance, from 0.0 to 1.0, under which pixels are black.
+    pub threshold: f32,
+}
+
+impl BrightPass {
+    pub fn new(threshold: f32) -> Result<BrightPass, ShaderError> {
+        let shader = Shader::new(include_str!("bright.fs.glsl"), FULLSCREEN_VERTEX_GLSL, &["input_texture"])?;
+        Ok(BrightPass { shader, threshold })
+    }
+}
+
+impl PostProcessPass for BrightPass {
+    fn prepare(&mut self, _context: &PassContext) {
+        self.shader.use_program();
+        self.shader.set_float(BrightUniform::Threshold, self.threshold);
+    }
+}
+
+/// Adds its input to the scene, the last step of a bloom.
+#[derive(Debug)]
+pub struct BloomCombinePass {
+    shader: Shader<BloomCombineUniform>,
+    pub intensity: f32,
+}
+
+impl BloomCombinePass {
+    pub fn new(intensity: f32) -> Result<BloomCombinePass, ShaderError> {
+        let shader = Shader::new(include_str!("bloom_combine.fs.glsl"), FULLSCREEN_VERTEX_GLSL, &["input_texture", "scene_texture"])?;
+        Ok(BloomCombinePass { shader, intensity })
+    }
+}
+
+impl PostProcessPass for BloomCombinePass {
+    fn prepare(&mut self, _context: &PassContext) {
+        self.shader.use_program();
+        self.shader.set_float(BloomCombineUniform::Intensity, self.intensity);
+    }
+}
+
+/// Darkens (or tints) the edges of the screen.
+#[derive(Debug)]
+pub struct VignettePass {
+    shader: Shader<VignetteUniform>,
+    /// Distance from the center where the vignette starts, 1.0 being the corners.
+    pub radius: f32,
+    /// Distance over which the vignette goes from nothing to `color`.
+    pub softness: f32,
+    /// The alpha is how much of the color is reached.
+    pub color: Color<u8>,
+}
+
+impl VignettePass {
+    /// A black vignette starting at half of the way to the corners.
+    pub fn new() -> Result<VignettePass, ShaderError> {
+        let shader = Shader::new(include_str!("vignette.fs.glsl"), FULLSCREEN_VERTEX_GLSL, &["input_texture"])?;
+        Ok(VignettePass { shader, radius: 0.5, softness: 0.5, color: Color::from_rgba(0, 0, 0, 200) })
+    }
+}
+
+impl PostProcessPass for VignettePass {
+    fn prepare(&mut self, _context: &PassContext) {
+        let color = self.color.to_color_f32();
+        self.shader.use_program();
+        self.shader.set_float(VignetteUniform::Radius, self.radius);
+        self.shader.set_float(VignetteUniform::Softness, self.softness);
+        self.shader.set_vector4(VignetteUniform::Color, &Vector4::new(color.r, color.g, color.b, color.a));
+    }
+}
+
+/// Changes the brightness, contrast and saturation of the screen, and tints it.
+#[derive(Debug)]
+pub struct ColorGradingPass {
+    shader: Shader<ColorGradingUniform>,
+    /// Added to every channel, 0.0 by default.
+    pub brightness: f32,
+    /// 1.0 by default.
+    pub contrast: f32,
+    /// 0.0 is grayscale, 1.0 (the default) keeps the colors.
+    pub saturation: f32,
+    /// Multiplies every channel, white by default.
+    pub tint: Color<u8>,
+}
+
+impl ColorGradingPass {
+    /// A pass which doesn't change anything until its fields are changed.
+    pub fn new() -> Result<ColorGradingPass, ShaderError> {
+        let shader = Shader::new(include_str!("color_grading.fs.glsl"), FULLSCREEN_VERTEX_GLSL, &["input_texture"])?;
+        Ok(ColorGradingPass { shader, brightness: 0.0, contrast: 1.0, saturation: 1.0, tint: Color::white() })
+    }
+}
+
+impl PostProcessPass for ColorGradingPass {
+    fn prepare(&mut self, _context: &PassContext) {
+        let tint = self.tint.to_color_f32();
+        self.shader.use_program();
+        self.shader.set_float(ColorGradingUniform::Brightness, self.brightness);
+        self.shader.set_float(ColorGradingUniform::Contrast, self.contrast);
+        self.shader.set_float(ColorGradingUniform::Saturation, self.saturation);
+        self.shader.set_vector3(ColorGradingUniform::Tint, &Vector3::new(tint.r, tint.g, tint.b));
+    }
+}
+
+type SetUniforms<U> = Box<dyn FnMut(&mut Shader<U>, &PassContext)>;
+
+/// A pass with your own fragment shader, see the module documentation.
+pub struct ShaderPass<U: Uniform> {
+    pub shader: Shader<U>,
+    set_uniforms: SetUniforms<U>,
+}
+
+impl<U: Uniform> ShaderPass<U> {
+    /// `set_uniforms` is called before every draw, after the program is in use.
+    pub fn new<F: FnMut(&mut Shader<U>, &PassContext) + 'static>(shader: Shader<U>, set_uniforms: F) -> ShaderPass<U> {
+        ShaderPass { shader, set_uniforms: Box::new(set_uniforms) }
+    }
+}
+
+impl<U: Uniform> std::fmt::Debug for ShaderPass<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShaderPass").field("shader", &self.shader).finish()
+    }
+}
+
+impl<U: Uniform> PostProcessPass for ShaderPass<U> {
+    fn prepare(&mut self, context: &PassContext) {
+        self.shader.use_program();
+        (self.set_uniforms)(&mut self.shader, context);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::backend::gl::{self, types::*};
+use crate::error::SprowlError;
+use std::{
+    mem::MaybeUninit,
+    ptr,
+};
+
+/// An RGBA texture with its framebuffer, to draw into instead of the screen.
+#[derive(Debug)]
+pub struct RenderTarget {
+    fbo: GLuint,
+    texture: GLuint,
+    /// depth and stencil renderbuffer, see `with_stencil`
+    stencil: Option<GLuint>,
+    size: (u32, u32),
+}
+
+impl RenderTarget {
+    /// Returns an error if the framebuffer is not complete, typically because the size is 0 or
+    /// too big for the context.
+    pub fn new(width: u32, height: u32) -> Result<RenderTarget, SprowlError> {
+        let mut fbo: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let mut texture: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let (fbo, texture) = unsafe {
+            gl::GenFramebuffers(1, fbo.as_mut_ptr());
+            gl::GenTextures(1, texture.as_mut_ptr());
+            (fbo.assume_init(), texture.assume_init())
+        };
+        let mut target = RenderTarget { fbo, texture, stencil: None, size: (0, 0) };
+        target.resize(width, height)?;
+        Ok(target)
+    }
+
+    /// A target with a depth and stencil buffer as well, reallocated along with the texture.
+    ///
+    /// Returns an error like `new`.
+    pub fn with_stencil(width: u32, height: u32) -> Result<RenderTarget, SprowlError> {
+        let mut target = RenderTarget::new(width, height)?;
+        let mut renderbuffer: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        target.stencil = Some(unsafe {
+            gl::GenRenderbuffers(1, renderbuffer.as_mut_ptr());
+            renderbuffer.assume_init()
+        });
+        target.resize(width, height)?;
+        Ok(target)
+    }
+
+    /// Reallocate the texture, whose content is lost.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), SprowlError> {
+        self.size = (width, height);
+        let status = unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as GLint, width as GLsizei, height as GLsizei, 0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            let mut previous_framebuffer: GLint = 0;
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo);
+            gl::FramebufferTexture2D(gl::DRAW_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.texture, 0);
+            if let Some(renderbuffer) = self.stencil {
+                gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+                gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width as GLsizei, height as GLsizei);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+                gl::FramebufferRenderbuffer(gl::DRAW_FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, renderbuffer);
+            }
+            let status = gl::CheckFramebufferStatus(gl::DRAW_FRAMEBUFFER);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, previous_framebuffer as GLuint);
+            status
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(SprowlError::IncompleteFramebuffer(status));
+        }
+        Ok(())
+    }
+
+    /// Whether the target was created with `with_stencil`.
+    #[inline]
+    pub fn has_stencil(&self) -> bool {
+        self.stencil.is_some()
+    }
+
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// The name of the `GL_TEXTURE_2D`, to sample it yourself.
+    #[inline]
+    pub fn texture_id(&self) -> GLuint {
+        self.texture
+    }
+
+    #[inline]
+    pub fn framebuffer_id(&self) -> GLuint {
+        self.fbo
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+            if let Some(renderbuffer) = self.stencil {
+                gl::DeleteRenderbuffers(1, &renderbuffer);
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+#version 330 core
+
+in vec2 uv;
+out vec4 color;
+
+uniform sampler2D input_texture;
+// distance from the center where the vignette starts, 1.0 being the corners
+uniform float radius;
+// distance over which it goes from nothing to `vignette_color`
+uniform float softness;
+uniform vec4 vignette_color;
+
+void main() {
+    vec4 c = texture(input_texture, uv);
+    float distance_to_center = length(uv - 0.5) * 1.41421356;
+    float amount = smoothstep(radius, radius + softness, distance_to_center) * vignette_color.a;
+    color = vec4(mix(c.rgb, vignette_color.rgb, amount), c.a);
+}
@@ -0,0 +1,10 @@
+//! The most commonly used types, to import with `use sprowl::prelude::*;`.
+
+pub use crate::compat::{Vec2, Vec3, Vec4, Mat4, vec2, vec3, vec4};
+pub use crate::camera::Camera2D;
+pub use crate::color::{AlphaMode, Color};
+pub use crate::error::SprowlError;
+pub use crate::renderer::{AsVertexData, BatchId, BlendMode, DepthMode, DrawCommandBuffer, GrowthPolicy, InstanceWriter, LayerId, LayerSettings, Renderer, RendererBuilder, StaticBatch, VertexLayout, VirtualResolution, WorldWrap, WrappableVertexData};
+pub use crate::render_storage::{FontId, RenderStorage, TextureKind, TextureLoadOptions, atlas::{TextureHandle, TextureRegion}, repack::TextureRemap};
+pub use crate::shader::{Shader, ShaderError, StandardGlobals, Uniform};
+pub use crate::sprites::{Flip, MeshVertex, QuadInstance, QuadUniform, standard_mesh_shader, standard_quad_shader};
@@ -0,0 +1,31 @@
+// Evaluates a `Fill` packed by sprowl's `FillInstanceData`.
+//
+// Forward the 4 fill attribs from the vertex shader (`flat`), along with the position of the
+// fragment in the same coordinates as the bounds given to `Fill::instance_data`, and call
+// `fill_color` in the fragment shader.
+vec4 fill_unpack_color(uint color) {
+    return vec4(float((color >> 24) & 0xFFu), float((color >> 16) & 0xFFu), float((color >> 8) & 0xFFu), float(color & 0xFFu)) / 255.0;
+}
+
+vec4 fill_color(uint kind, vec4 params, vec4 offsets, uvec4 colors, vec2 position) {
+    if (kind == 0u) {
+        return fill_unpack_color(colors.x);
+    }
+    float t;
+    if (kind == 1u) {
+        // linear: from params.xy to params.zw
+        vec2 axis = params.zw - params.xy;
+        t = dot(position - params.xy, axis) / dot(axis, axis);
+    } else {
+        // radial: centered on params.xy, with the radii params.zw
+        t = length((position - params.xy) / params.zw);
+    }
+    t = clamp(t, 0.0, 1.0);
+    vec4 color = fill_unpack_color(colors.x);
+    for (int i = 1; i < 4; i++) {
+        float span = offsets[i] - offsets[i - 1];
+        float k = span > 0.0 ? clamp((t - offsets[i - 1]) / span, 0.0, 1.0) : step(offsets[i], t);
+        color = mix(color, fill_unpack_color(colors[i]), k);
+    }
+    return color;
+}
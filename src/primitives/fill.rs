@@ -0,0 +1,124 @@
+use cgmath::Vector2;
+
+use crate::color::Color;
+use crate::renderer::instance_data;
+
+/// A shader function to evaluate a `Fill`, see the comments in the source.
+pub const FILL_GLSL: &str = include_str!("fill.glsl");
+
+/// Gradients are packed with at most this many stops, the others are ignored.
+pub const MAX_GRADIENT_STOPS: usize = 4;
+
+/// A color of a gradient.
+#[derive(Debug, Clone, Copy)]
+pub struct GradientStop {
+    /// Where the color is reached, from 0.0 (the start of the gradient) to 1.0 (its end).
+    pub offset: f32,
+    pub color: Color<u8>,
+}
+
+impl GradientStop {
+    pub fn new(offset: f32, color: Color<u8>) -> GradientStop {
+        GradientStop { offset, color }
+    }
+}
+
+/// How to fill a rect or a shape. Stops must be sorted by offset.
+#[derive(Debug, Clone)]
+pub enum Fill {
+    Solid(Color<u8>),
+    /// A gradient across the bounds of the shape. `angle` is in degrees: 0 goes from left to right,
+    /// 90 from top to bottom.
+    LinearGradient { stops: Vec<GradientStop>, angle: f32 },
+    /// A gradient from the center of the bounds of the shape (offset 0.0) to its edges (offset 1.0).
+    RadialGradient { stops: Vec<GradientStop> },
+}
+
+impl From<Color<u8>> for Fill {
+    fn from(color: Color<u8>) -> Fill {
+        Fill::Solid(color)
+    }
+}
+
+fn pack_color(color: Color<u8>) -> u32 {
+    u32::from_be_bytes([color.r, color.g, color.b, color.a])
+}
+
+/// Pack the stops, padded by repeating the last one.
+fn pack_stops(stops: &[GradientStop]) -> ([f32; MAX_GRADIENT_STOPS], [u32; MAX_GRADIENT_STOPS]) {
+    debug_assert!(stops.windows(2).all(|w| w[0].offset <= w[1].offset), "gradient stops must be sorted by offset");
+    let stops = &stops[..stops.len().min(MAX_GRADIENT_STOPS)];
+    let last = stops.last().map_or(0, |stop| pack_color(stop.color));
+    let mut offsets = [1.0; MAX_GRADIENT_STOPS];
+    let mut colors = [last; MAX_GRADIENT_STOPS];
+    for (i, stop) in stops.iter().enumerate() {
+        offsets[i] = stop.offset;
+        colors[i] = pack_color(stop.color);
+    }
+    (offsets, colors)
+}
+
+impl Fill {
+    /// Pack the fill for a shape whose bounding box is `bounds` (x, y, w, h). Gradients are
+    /// evaluated in the coordinates of the bounds, so shapes sharing the same bounds share the
+    /// same gradient.
+    pub fn instance_data(&self, bounds: (f32, f32, f32, f32)) -> FillInstanceData {
+        let (x, y, w, h) = bounds;
+        let center = Vector2::new(x + w / 2.0, y + h / 2.0);
+        match self {
+            Fill::Solid(color) => FillInstanceData {
+                kind: 0,
+                params: [0.0; 4],
+                offsets: [0.0; MAX_GRADIENT_STOPS],
+                colors: [pack_color(*color); MAX_GRADIENT_STOPS],
+            },
+            Fill::LinearGradient { stops, angle } => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                // like CSS, the gradient spans the whole rect in its direction.
+                let half_length = (w * cos.abs() + h * sin.abs()) / 2.0;
+                let start = center - Vector2::new(cos, sin) * half_length;
+                let end = center + Vector2::new(cos, sin) * half_length;
+                let (offsets, colors) = pack_stops(stops);
+                FillInstanceData {
+                    kind: 1,
+                    params: [start.x, start.y, end.x, end.y],
+                    offsets,
+                    colors,
+                }
+            },
+            Fill::RadialGradient { stops } => {
+                let (offsets, colors) = pack_stops(stops);
+                FillInstanceData {
+                    kind: 2,
+                    params: [center.x, center.y, (w / 2.0).max(f32::EPSILON), (h / 2.0).max(f32::EPSILON)],
+                    offsets,
+                    colors,
+                }
+            },
+        }
+    }
+}
+
+/// A `Fill` as instance data, evaluated by `FILL_GLSL`:
+///
+/// * "fill_kind" (`uint`)
+/// * "fill_params" (`vec4`)
+/// * "fill_offsets" (`vec4`), the offsets of the stops
+/// * "fill_colors" (`uvec4`), the colors of the stops as `0xRRGGBBAA`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillInstanceData {
+    /// 0 for solid, 1 for linear, 2 for radial.
+    pub kind: u32,
+    /// linear: start (x, y) and end (x, y). radial: center (x, y) and radii (x, y).
+    pub params: [f32; 4],
+    pub offsets: [f32; MAX_GRADIENT_STOPS],
+    /// one color per stop, as `0xRRGGBBAA`
+    pub colors: [u32; MAX_GRADIENT_STOPS],
+}
+
+instance_data!(impl FillInstanceData |fill| {
+    "fill_kind": u32 = fill.kind,
+    "fill_params": [f32; 4] = fill.params,
+    "fill_offsets": [f32; 4] = fill.offsets,
+    "fill_colors": [u32; MAX_GRADIENT_STOPS] = fill.colors,
+});
@@ -0,0 +1,328 @@
+//! Lines, polylines and filled polygons, batched as instances of the usual quad.
+//!
+//! Every part of a primitive is a `PrimitiveDrawCall`: a quad whose 4 corners are given, which
+//! can be squashed into a triangle. Like the other helpers, nothing is drawn by this module: give
+//! the corners to your vertex shader, and move the vertices there with `PRIMITIVES_GLSL`.
+//!
+//! ```ignore
+//! let mut batch = PrimitiveBatch::new();
+//! batch.polyline(&points, false, &StrokeStyle::new(3.0, Color::white()).with_join(LineJoin::Round));
+//! batch.fill_polygon(&shape, Color::from_rgb(200, 40, 40));
+//! batch.fill_rect((10.0, 10.0, 200.0, 20.0), Fill::LinearGradient { stops, angle: 0.0 });
+//! batch.add_to_renderer(&mut renderer, |draw_call| to_vertex_data(draw_call));
+//! ```
+
+mod triangulate;
+mod fill;
+
+pub use triangulate::triangulate;
+pub use fill::*;
+
+use cgmath::{InnerSpace, Vector2};
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::renderer::{AsVertexData, Renderer};
+use crate::shader::Uniform;
+
+/// Shader functions to draw a `PrimitiveDrawCall`, see the comments in the source.
+pub const PRIMITIVES_GLSL: &str = include_str!("primitives.glsl");
+
+/// Width of the fringe added on both sides of anti-aliased lines, in pixels.
+const FRINGE_WIDTH: f32 = 1.0;
+/// Maximum distance between a round cap or join and its approximation, in pixels.
+const ARC_TOLERANCE: f32 = 0.25;
+
+/// A part of a primitive, drawn as a quad.
+#[derive(Debug, Clone, Copy)]
+pub struct PrimitiveDrawCall {
+    /// Where the corners (0, 0), (1, 0), (1, 1) and (0, 1) of the quad should be drawn. For a
+    /// triangle, the last one is the same as the first one.
+    pub corners: [Vector2<f32>; 4],
+    /// The color or the gradient, see `FILL_GLSL`.
+    pub fill: FillInstanceData,
+    /// Fraction of the quad along its y axis which fades out on both sides, for anti-aliased lines.
+    /// 0 for everything else.
+    pub feather: f32,
+}
+
+impl PrimitiveDrawCall {
+    /// The corners as (x, y) pairs, in order.
+    pub fn corners_f32(&self) -> [f32; 8] {
+        let c = &self.corners;
+        [c[0].x, c[0].y, c[1].x, c[1].y, c[2].x, c[2].y, c[3].x, c[3].y]
+    }
+}
+
+/// The shape of the ends of an open polyline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// The line stops at its end points.
+    #[default]
+    Butt,
+    /// The line goes on for half its width.
+    Square,
+    Round,
+}
+
+/// The shape of the corners of a polyline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// A sharp corner, replaced by `Bevel` past the miter limit.
+    #[default]
+    Miter,
+    Bevel,
+    Round,
+}
+
+/// How lines are drawn.
+#[derive(Debug, Clone, Copy)]
+pub struct StrokeStyle {
+    /// Width of the line, in pixels.
+    pub width: f32,
+    pub color: Color<u8>,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// Miter joins longer than `miter_limit * width / 2` are beveled instead.
+    pub miter_limit: f32,
+    /// Add a fringe fading out on both sides of the segments. Joins and caps are not anti-aliased.
+    pub anti_aliased: bool,
+}
+
+impl StrokeStyle {
+    /// Anti-aliased, with butt caps and miter joins.
+    pub fn new(width: f32, color: Color<u8>) -> StrokeStyle {
+        StrokeStyle {
+            width,
+            color,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+            miter_limit: 4.0,
+            anti_aliased: true,
+        }
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> StrokeStyle {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> StrokeStyle {
+        self.join = join;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> StrokeStyle {
+        self.miter_limit = miter_limit;
+        self
+    }
+
+    pub fn with_anti_aliasing(mut self, anti_aliased: bool) -> StrokeStyle {
+        self.anti_aliased = anti_aliased;
+        self
+    }
+
+    fn fill(&self) -> FillInstanceData {
+        Fill::Solid(self.color).instance_data((0.0, 0.0, 0.0, 0.0))
+    }
+}
+
+/// The vector rotated by 90 degrees.
+fn perp(v: Vector2<f32>) -> Vector2<f32> {
+    Vector2::new(-v.y, v.x)
+}
+
+/// Number of segments approximating an arc within `ARC_TOLERANCE`.
+fn arc_segments(radius: f32, sweep: f32) -> usize {
+    let step = 2.0 * (1.0 - ARC_TOLERANCE / radius.max(ARC_TOLERANCE)).acos();
+    ((sweep.abs() / step.max(0.01)).ceil() as usize).clamp(1, 64)
+}
+
+/// A list of primitives to draw, in order.
+#[derive(Debug, Clone, Default)]
+pub struct PrimitiveBatch {
+    draw_calls: Vec<PrimitiveDrawCall>,
+}
+
+impl PrimitiveBatch {
+    pub fn new() -> PrimitiveBatch {
+        PrimitiveBatch::default()
+    }
+
+    pub fn draw_calls(&self) -> &[PrimitiveDrawCall] {
+        &self.draw_calls
+    }
+
+    pub fn clear(&mut self) {
+        self.draw_calls.clear();
+    }
+
+    fn triangle(&mut self, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, fill: FillInstanceData) {
+        self.draw_calls.push(PrimitiveDrawCall { corners: [a, b, c, a], fill, feather: 0.0 });
+    }
+
+    /// A fan of triangles around `center`, from `start` (relative to the center) and turning by
+    /// `sweep` radians.
+    fn arc(&mut self, center: Vector2<f32>, start: Vector2<f32>, sweep: f32, fill: FillInstanceData) {
+        let radius = start.magnitude();
+        let start_angle = start.y.atan2(start.x);
+        let segments = arc_segments(radius, sweep);
+        let point = |i: usize| {
+            let angle = start_angle + sweep * i as f32 / segments as f32;
+            center + Vector2::new(angle.cos(), angle.sin()) * radius
+        };
+        for i in 0..segments {
+            self.triangle(center, point(i), point(i + 1), fill);
+        }
+    }
+
+    /// A single segment from `a` to `b`, without caps.
+    fn segment(&mut self, a: Vector2<f32>, b: Vector2<f32>, style: &StrokeStyle) {
+        let direction = (b - a).normalize();
+        let fringe = if style.anti_aliased { FRINGE_WIDTH } else { 0.0 };
+        let half_width = style.width / 2.0 + fringe;
+        let normal = perp(direction) * half_width;
+        self.draw_calls.push(PrimitiveDrawCall {
+            corners: [a - normal, b - normal, b + normal, a + normal],
+            fill: style.fill(),
+            feather: fringe / (half_width * 2.0),
+        });
+    }
+
+    fn cap(&mut self, point: Vector2<f32>, outwards: Vector2<f32>, style: &StrokeStyle) {
+        let half_width = style.width / 2.0;
+        match style.cap {
+            LineCap::Butt => {},
+            LineCap::Square => {
+                let normal = perp(outwards) * half_width;
+                let end = point + outwards * half_width;
+                self.draw_calls.push(PrimitiveDrawCall {
+                    corners: [point - normal, end - normal, end + normal, point + normal],
+                    fill: style.fill(),
+                    feather: 0.0,
+                });
+            },
+            LineCap::Round => self.arc(point, perp(outwards) * half_width, -PI, style.fill()),
+        }
+    }
+
+    /// Fill the gap at `point` between a segment going towards `incoming`, and the next one
+    /// going towards `outgoing`.
+    fn join(&mut self, point: Vector2<f32>, incoming: Vector2<f32>, outgoing: Vector2<f32>, style: &StrokeStyle) {
+        let turn = incoming.x * outgoing.y - incoming.y * outgoing.x;
+        if turn.abs() < 1e-6 && incoming.dot(outgoing) > 0.0 {
+            return;
+        }
+        // the gap is on the outer side of the turn.
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let half_width = style.width / 2.0;
+        let outer_in = perp(incoming) * (half_width * side);
+        let outer_out = perp(outgoing) * (half_width * side);
+        match style.join {
+            LineJoin::Round => {
+                let mut sweep = outer_out.y.atan2(outer_out.x) - outer_in.y.atan2(outer_in.x);
+                if sweep > PI {
+                    sweep -= 2.0 * PI;
+                } else if sweep < -PI {
+                    sweep += 2.0 * PI;
+                }
+                self.arc(point, outer_in, sweep, style.fill());
+            },
+            LineJoin::Miter => {
+                let bisector = outer_in + outer_out;
+                // 1 / cos(half the angle between the segments)
+                let ratio = if bisector.magnitude2() > 1e-12 {
+                    1.0 / bisector.normalize().dot(outer_in.normalize()).max(1e-6)
+                } else {
+                    f32::INFINITY
+                };
+                if ratio <= style.miter_limit {
+                    let tip = point + bisector.normalize() * (half_width * ratio);
+                    self.draw_calls.push(PrimitiveDrawCall {
+                        corners: [point, point + outer_in, tip, point + outer_out],
+                        fill: style.fill(),
+                        feather: 0.0,
+                    });
+                } else {
+                    self.triangle(point, point + outer_in, point + outer_out, style.fill());
+                }
+            },
+            LineJoin::Bevel => self.triangle(point, point + outer_in, point + outer_out, style.fill()),
+        }
+    }
+
+    /// A line from `a` to `b`.
+    pub fn line(&mut self, a: Vector2<f32>, b: Vector2<f32>, style: &StrokeStyle) {
+        self.polyline(&[a, b], false, style);
+    }
+
+    /// Lines going through every point, in order. If `closed`, the last point is joined to the
+    /// first one, and there are no caps.
+    ///
+    /// Consecutive points at the same position are ignored.
+    pub fn polyline(&mut self, points: &[Vector2<f32>], closed: bool, style: &StrokeStyle) {
+        let mut points: Vec<Vector2<f32>> = points.to_vec();
+        points.dedup_by(|a, b| (*a - *b).magnitude2() < 1e-12);
+        if closed && points.len() > 1 && (points[0] - points[points.len() - 1]).magnitude2() < 1e-12 {
+            points.pop();
+        }
+        if points.len() < 2 {
+            return;
+        }
+        let segment_count = if closed { points.len() } else { points.len() - 1 };
+        let segment = |i: usize| (points[i], points[(i + 1) % points.len()]);
+        let direction = |i: usize| {
+            let (a, b) = segment(i);
+            (b - a).normalize()
+        };
+
+        for i in 0..segment_count {
+            let (a, b) = segment(i);
+            self.segment(a, b, style);
+        }
+        for (i, &point) in points.iter().enumerate().take(segment_count).skip(1) {
+            self.join(point, direction(i - 1), direction(i), style);
+        }
+        if closed {
+            self.join(points[0], direction(segment_count - 1), direction(0), style);
+        } else {
+            self.cap(points[0], -direction(0), style);
+            self.cap(points[points.len() - 1], direction(segment_count - 1), style);
+        }
+    }
+
+    /// Fill a polygon, convex or not, see `triangulate`. Gradients span the bounding box of the
+    /// polygon.
+    ///
+    /// Edges are not anti-aliased: draw the outline with a thin `polyline` on top for smooth edges.
+    pub fn fill_polygon<F: Into<Fill>>(&mut self, points: &[Vector2<f32>], fill: F) {
+        if points.is_empty() {
+            return;
+        }
+        let (min, max) = points.iter().fold((points[0], points[0]), |(min, max), p| {
+            (Vector2::new(min.x.min(p.x), min.y.min(p.y)), Vector2::new(max.x.max(p.x), max.y.max(p.y)))
+        });
+        let fill = fill.into().instance_data((min.x, min.y, max.x - min.x, max.y - min.y));
+        for [a, b, c] in triangulate(points) {
+            self.triangle(points[a], points[b], points[c], fill);
+        }
+    }
+
+    /// Fill a rect (x, y, w, h), for panels or health bars.
+    pub fn fill_rect<F: Into<Fill>>(&mut self, rect: (f32, f32, f32, f32), fill: F) {
+        let (x, y, w, h) = rect;
+        self.draw_calls.push(PrimitiveDrawCall {
+            corners: [Vector2::new(x, y), Vector2::new(x + w, y), Vector2::new(x + w, y + h), Vector2::new(x, y + h)],
+            fill: fill.into().instance_data(rect),
+            feather: 0.0,
+        });
+    }
+
+    /// Add every primitive to the renderer, `to_vertex_data` converting every part to your own
+    /// vertex data.
+    pub fn add_to_renderer<U: Uniform, E: AsVertexData, F: FnMut(&PrimitiveDrawCall) -> E>(&self, renderer: &mut Renderer<U>, mut to_vertex_data: F) {
+        for draw_call in &self.draw_calls {
+            renderer.add_elem(&to_vertex_data(draw_call));
+        }
+    }
+}
@@ -0,0 +1,20 @@
+// Reads a `PrimitiveDrawCall` drawn with sprowl's instanced quads.
+//
+// In the vertex shader, `primitive_position` moves the vertex `pos` of the unit quad (attrib 0)
+// to its corner: `corners01` holds the corners at (0, 0) and (1, 0), `corners23` the ones at
+// (1, 1) and (0, 1). Pass `pos.y` and the feather of the draw call to the fragment shader.
+//
+// In the fragment shader, multiply the alpha of the color by `primitive_coverage(pos_y, feather)`
+// for smooth edges.
+vec2 primitive_position(vec2 pos, vec4 corners01, vec4 corners23) {
+    vec2 bottom = mix(corners01.xy, corners01.zw, pos.x);
+    vec2 top = mix(corners23.zw, corners23.xy, pos.x);
+    return mix(bottom, top, pos.y);
+}
+
+float primitive_coverage(float pos_y, float feather) {
+    if (feather <= 0.0) {
+        return 1.0;
+    }
+    return clamp(min(pos_y, 1.0 - pos_y) / feather, 0.0, 1.0);
+}
@@ -0,0 +1,53 @@
+use cgmath::Vector2;
+
+fn cross(o: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+/// Twice the signed area of the polygon, positive if its points are counter-clockwise (with the
+/// y axis pointing up).
+fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    (0..points.len()).map(|i| {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        a.x * b.y - b.x * a.y
+    }).sum()
+}
+
+fn in_triangle(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> bool {
+    // the triangle is counter-clockwise, so p is inside if it's on the left of every edge.
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+/// Split a simple polygon, convex or not, into triangles by ear clipping. Returns the indices of
+/// the points of every triangle.
+///
+/// Points can be in any winding order. Self-intersecting polygons give triangles anyway, but they
+/// may overlap or leave holes.
+pub fn triangulate(points: &[Vector2<f32>]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0.0 {
+        remaining.reverse();
+    }
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let is_ear = |i: usize| {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            cross(pa, pb, pc) > 0.0 && !remaining.iter().any(|&other| {
+                let p = points[other];
+                other != a && other != b && other != c && p != pa && p != pb && p != pc && in_triangle(p, pa, pb, pc)
+            })
+        };
+        // without any ear, the polygon is degenerate or self-intersecting: clip anything to
+        // make progress.
+        let ear = (0..n).find(|&i| is_ear(i)).unwrap_or(0);
+        triangles.push([remaining[(ear + n - 1) % n], remaining[ear], remaining[(ear + 1) % n]]);
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
@@ -0,0 +1,345 @@
+//! Packing of small textures into shared layers of a `Texture2DArray`.
+//!
+//! Every layer of the RGBA texture array is 1024x1024 by default, so giving a whole layer to a 32x32 tile
+//! wastes a lot of VRAM. Instead, small textures are packed into "atlas layers" with a simple
+//! shelf packer: textures are put left to right on shelves, and a new shelf is opened below
+//! the last one when no existing shelf has room.
+
+use super::texture::{Texture2DArray, TextureArrayLayer};
+use crate::error::SprowlError;
+
+/// Textures with a width or a height bigger than this are given a whole layer instead.
+pub const ATLAS_MAX_SPRITE_SIZE: u32 = 256;
+
+/// Empty pixels left between two packed textures, so that sampling at the edge of a texture
+/// does not bleed on its neighbour.
+const PADDING: u32 = 1;
+
+/// A rectangle within a layer, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextureRect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> TextureRect {
+        TextureRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+/// Returned when adding a texture: tells you where the texture has been stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureHandle {
+    /// The RGBA tier this texture is in, see `tiers`. 0 unless other tiers were added.
+    pub array: u32,
+    /// The layer of the texture array this texture is in.
+    pub layer: TextureArrayLayer,
+    /// The part of the layer used by the texture, in pixels.
+    pub rect: TextureRect,
+    /// The same as `rect`, but normalized by the dimensions of the layer: (x, y, w, h).
+    ///
+    /// You can use this directly as the texture coordinates of your vertex data.
+    pub uv: (f32, f32, f32, f32),
+}
+
+impl TextureHandle {
+    pub (crate) fn new(layer: TextureArrayLayer, rect: TextureRect, layer_dims: (u32, u32)) -> TextureHandle {
+        let (max_w, max_h) = (layer_dims.0 as f32, layer_dims.1 as f32);
+        TextureHandle {
+            array: 0,
+            layer,
+            rect,
+            uv: (
+                rect.x as f32 / max_w,
+                rect.y as f32 / max_h,
+                rect.width as f32 / max_w,
+                rect.height as f32 / max_h,
+            ),
+        }
+    }
+
+    /// The texture unit the array of this texture is bound to: give it to the sampler of your shader.
+    pub fn texture_unit(&self) -> u32 {
+        super::tiers::rgba_tier_texture_unit(self.array)
+    }
+
+    /// Converts a crop relative to this texture (x, y, w, h) into a crop relative to its layer,
+    /// which is what `InlineImage` or `Cursor` expect for instance.
+    pub fn crop_in_layer(&self, crop: (u32, u32, u32, u32)) -> (u32, u32, u32, u32) {
+        (self.rect.x + crop.0, self.rect.y + crop.1, crop.2, crop.3)
+    }
+
+    /// A part of this texture, in pixels relative to the texture: (x, y, w, h). The crop is not
+    /// checked, see `RenderStorage::define_region` for that.
+    pub fn region(&self, crop: (u32, u32, u32, u32)) -> TextureRegion {
+        let (scale_x, scale_y) = (self.uv.2 / self.rect.width.max(1) as f32, self.uv.3 / self.rect.height.max(1) as f32);
+        let (x, y, width, height) = self.crop_in_layer(crop);
+        TextureRegion {
+            array: self.array,
+            layer: self.layer,
+            rect: TextureRect::new(x, y, width, height),
+            uv: (
+                self.uv.0 + crop.0 as f32 * scale_x,
+                self.uv.1 + crop.1 as f32 * scale_y,
+                crop.2 as f32 * scale_x,
+                crop.3 as f32 * scale_y,
+            ),
+        }
+    }
+
+    /// Whether `crop` (x, y, w, h), relative to this texture, is within it.
+    pub fn contains_crop(&self, crop: (u32, u32, u32, u32)) -> bool {
+        crop.0.checked_add(crop.2).is_some_and(|right| right <= self.rect.width)
+            && crop.1.checked_add(crop.3).is_some_and(|bottom| bottom <= self.rect.height)
+    }
+}
+
+/// A part of a texture, typically a sprite of a spritesheet, defined once by its crop and drawn
+/// without knowing it: see `RenderStorage::define_region` and `QuadInstance::with_region`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureRegion {
+    /// The RGBA tier of its texture.
+    pub array: u32,
+    pub layer: TextureArrayLayer,
+    /// The part of the layer, in pixels.
+    pub rect: TextureRect,
+    /// The same as `rect`, normalized by the dimensions of the layer: (x, y, w, h).
+    pub uv: (f32, f32, f32, f32),
+}
+
+impl TextureRegion {
+    pub fn size(&self) -> (u32, u32) {
+        self.rect.size()
+    }
+
+    /// Whether this region is a part of `texture`.
+    pub fn is_in(&self, texture: &TextureHandle) -> bool {
+        let (rect, outer) = (self.rect, texture.rect);
+        (self.array, self.layer) == (texture.array, texture.layer)
+            && rect.x >= outer.x && rect.y >= outer.y
+            && rect.x + rect.width <= outer.x + outer.width && rect.y + rect.height <= outer.y + outer.height
+    }
+}
+
+impl From<TextureHandle> for TextureRegion {
+    /// The whole texture.
+    fn from(texture: TextureHandle) -> TextureRegion {
+        TextureRegion {
+            array: texture.array,
+            layer: texture.layer,
+            rect: texture.rect,
+            uv: texture.uv,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    // x where the next texture of this shelf will be placed.
+    next_x: u32,
+}
+
+/// A layer shared by several small textures.
+#[derive(Debug, Clone)]
+pub (crate) struct AtlasLayer {
+    pub (crate) layer: TextureArrayLayer,
+    width: u32,
+    height: u32,
+    shelves: Vec<Shelf>,
+    // number of textures still alive in this layer.
+    live_textures: usize,
+}
+
+impl AtlasLayer {
+    pub (crate) fn new(layer: TextureArrayLayer, width: u32, height: u32) -> AtlasLayer {
+        AtlasLayer {
+            layer,
+            width,
+            height,
+            shelves: Vec::new(),
+            live_textures: 0,
+        }
+    }
+
+    /// Tries to find some room for a texture of the given size.
+    pub (crate) fn allocate(&mut self, width: u32, height: u32) -> Option<TextureRect> {
+        let padded_w = width + PADDING;
+        let padded_h = height + PADDING;
+        let layer_width = self.width;
+        // pick the shelf which wastes the least height
+        let best_shelf = self.shelves.iter_mut()
+            .filter(|shelf| shelf.height >= padded_h && shelf.next_x + padded_w <= layer_width)
+            .min_by_key(|shelf| shelf.height - padded_h);
+        let rect = match best_shelf {
+            Some(shelf) => {
+                let rect = TextureRect::new(shelf.next_x, shelf.y, width, height);
+                shelf.next_x += padded_w;
+                rect
+            },
+            None => {
+                let y = self.shelves.last().map(|shelf| shelf.y + shelf.height).unwrap_or(0);
+                if y + padded_h > self.height || padded_w > self.width {
+                    return None;
+                }
+                self.shelves.push(Shelf { y, height: padded_h, next_x: padded_w });
+                TextureRect::new(0, y, width, height)
+            }
+        };
+        self.live_textures += 1;
+        Some(rect)
+    }
+
+    /// Number of textures still alive in this layer.
+    pub (crate) fn live_textures(&self) -> usize {
+        self.live_textures
+    }
+
+    /// Marks a texture of this layer as removed. Returns true if the layer is now empty.
+    ///
+    /// Shelf packing can't re-use a hole in the middle of a shelf, so the space is only
+    /// reclaimed once every texture of the layer has been removed.
+    pub (crate) fn free(&mut self) -> bool {
+        debug_assert!(self.live_textures > 0);
+        self.live_textures -= 1;
+        if self.live_textures == 0 {
+            self.shelves.clear();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Reserve room for a texture of the given size in `texture_array`, packed in one of
+/// `atlas_layers` if it's small enough. Nothing is uploaded.
+///
+/// Returns an error if a new layer was needed, but the texture array is full.
+pub (crate) fn allocate_texture(texture_array: &mut Texture2DArray, atlas_layers: &mut Vec<AtlasLayer>, size: (u32, u32)) -> Result<TextureHandle, SprowlError> {
+    let (width, height) = size;
+    let layer_dims = (texture_array.max_width, texture_array.max_height);
+    // layers of small tiers may be too small to pack even an ATLAS_MAX_SPRITE_SIZE texture.
+    let too_big = width > ATLAS_MAX_SPRITE_SIZE || height > ATLAS_MAX_SPRITE_SIZE
+        || width + PADDING > layer_dims.0 || height + PADDING > layer_dims.1;
+    if too_big {
+        let layer = texture_array.add_empty_texture(width, height)?;
+        let rect = TextureRect::new(0, 0, width, height);
+        clear_padding(texture_array, layer, rect);
+        return Ok(TextureHandle::new(layer, rect, layer_dims));
+    }
+
+    let allocated = atlas_layers.iter_mut().find_map(|atlas_layer| {
+        atlas_layer.allocate(width, height).map(|rect| (atlas_layer.layer, rect))
+    });
+    let (layer, rect) = match allocated {
+        Some(allocated) => allocated,
+        None => {
+            let layer = texture_array.add_empty_texture(layer_dims.0, layer_dims.1)?;
+            let mut atlas_layer = AtlasLayer::new(layer, layer_dims.0, layer_dims.1);
+            let rect = atlas_layer.allocate(width, height).expect("sprite too big for an empty atlas layer");
+            atlas_layers.push(atlas_layer);
+            (layer, rect)
+        }
+    };
+    clear_padding(texture_array, layer, rect);
+    Ok(TextureHandle::new(layer, rect, layer_dims))
+}
+
+/// Clear the `PADDING` pixels right of and below `rect`, within the layer. Layers are recycled
+/// once their textures are removed, so the padding may still hold the pixels of an old texture,
+/// which the edges of the new one would bleed with.
+fn clear_padding(texture_array: &mut Texture2DArray, layer: TextureArrayLayer, rect: TextureRect) {
+    if texture_array.format.is_compressed() {
+        return;
+    }
+    let right = rect.x + rect.width;
+    let bottom = rect.y + rect.height;
+    let padding_w = PADDING.min(texture_array.max_width.saturating_sub(right));
+    let padding_h = PADDING.min(texture_array.max_height.saturating_sub(bottom));
+    // 4 bytes per pixel whatever the format: the rows of a thin strip of a grayscale texture are
+    // still aligned to 4 bytes by `GL_UNPACK_ALIGNMENT`.
+    let zeros = vec![0u8; (rect.width.max(rect.height) + PADDING) as usize * PADDING as usize * 4];
+    if padding_w > 0 {
+        texture_array.update_texture(layer, &zeros, right as i32, rect.y as i32, padding_w, rect.height + padding_h);
+    }
+    if padding_h > 0 {
+        texture_array.update_texture(layer, &zeros, rect.x as i32, bottom as i32, rect.width, padding_h);
+    }
+}
+
+/// Upload the content of a texture previously allocated with `allocate_texture`.
+pub (crate) fn upload_texture(texture_array: &mut Texture2DArray, handle: &TextureHandle, bytes: &[u8]) {
+    let rect = handle.rect;
+    texture_array.update_texture(handle.layer, bytes, rect.x as i32, rect.y as i32, rect.width, rect.height);
+}
+
+/// Upload a texture to `texture_array`, packed in one of `atlas_layers` if it's small enough.
+pub (crate) fn pack_texture(texture_array: &mut Texture2DArray, atlas_layers: &mut Vec<AtlasLayer>, bytes: &[u8], size: (u32, u32)) -> Result<TextureHandle, SprowlError> {
+    let handle = allocate_texture(texture_array, atlas_layers, size)?;
+    upload_texture(texture_array, &handle, bytes);
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shelves_are_filled_left_to_right_then_top_to_bottom() {
+        let mut atlas_layer = AtlasLayer::new(0, 128, 128);
+        assert_eq!(atlas_layer.allocate(32, 32), Some(TextureRect::new(0, 0, 32, 32)));
+        assert_eq!(atlas_layer.allocate(32, 32), Some(TextureRect::new(33, 0, 32, 32)));
+        // too tall for the first shelf: a new one is opened below it
+        assert_eq!(atlas_layer.allocate(48, 48), Some(TextureRect::new(0, 33, 48, 48)));
+        // both shelves have room, the one wasting the least height is used
+        assert_eq!(atlas_layer.allocate(16, 16), Some(TextureRect::new(66, 0, 16, 16)));
+        assert_eq!(atlas_layer.allocate(40, 40), Some(TextureRect::new(49, 33, 40, 40)));
+        assert_eq!(atlas_layer.live_textures(), 5);
+    }
+
+    #[test]
+    fn full_layers_refuse_textures() {
+        let mut atlas_layer = AtlasLayer::new(0, 64, 64);
+        assert!(atlas_layer.allocate(64, 10).is_none(), "the padding doesn't fit");
+        assert!(atlas_layer.allocate(40, 40).is_some());
+        assert!(atlas_layer.allocate(40, 40).is_none());
+        assert!(atlas_layer.allocate(20, 20).is_some());
+    }
+
+    #[test]
+    fn space_is_reclaimed_once_every_texture_is_freed() {
+        let mut atlas_layer = AtlasLayer::new(0, 64, 64);
+        atlas_layer.allocate(60, 30).unwrap();
+        atlas_layer.allocate(60, 30).unwrap();
+        assert!(atlas_layer.allocate(60, 30).is_none());
+        assert!(!atlas_layer.free());
+        assert!(atlas_layer.allocate(60, 30).is_none(), "holes in a shelf are not re-used");
+        assert!(atlas_layer.free());
+        assert_eq!(atlas_layer.allocate(60, 30), Some(TextureRect::new(0, 0, 60, 30)));
+    }
+
+    #[test]
+    fn crops_past_the_texture_are_rejected() {
+        let texture = TextureHandle::new(0, TextureRect::new(64, 64, 32, 16), (1024, 1024));
+        assert!(texture.contains_crop((0, 0, 32, 16)));
+        assert!(texture.contains_crop((16, 8, 16, 8)));
+        assert!(!texture.contains_crop((1, 0, 32, 16)));
+        assert!(!texture.contains_crop((0, 0, 32, 17)));
+        // x + w and y + h overflow a u32
+        assert!(!texture.contains_crop((u32::MAX, 0, 2, 1)));
+        assert!(!texture.contains_crop((0, 1, 1, u32::MAX)));
+    }
+}
@@ -0,0 +1,237 @@
+//! Compressed textures (BC1, BC3, ETC2) loaded from DDS or KTX2 files, which take 4 to 8 times
+//! less VRAM than RGBA textures.
+//!
+//! They are not packed in the RGBA texture array: every format and size has its own array, bound
+//! to its own texture unit by `RenderStorage::set_active`, from `FIRST_COMPRESSED_TEXTURE_UNIT`.
+
+use std::convert::TryFrom;
+
+use crate::backend::gl::types::*;
+
+use crate::error::SprowlError;
+use super::texture::{TextureArrayLayer, TextureFormat};
+
+/// Compressed arrays are bound from this texture unit on, after the RGBA (0) and grayscale (1)
+/// arrays, the palettes (2) and the other RGBA tiers.
+pub const FIRST_COMPRESSED_TEXTURE_UNIT: GLuint = super::tiers::FIRST_RGBA_TIER_TEXTURE_UNIT + super::tiers::MAX_RGBA_TIERS as GLuint - 1;
+
+/// A texture added by `RenderStorage::add_compressed_texture`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressedTextureHandle {
+    /// index in `RenderStorage::compressed_arrays`
+    pub (crate) array: usize,
+    pub layer: TextureArrayLayer,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CompressedTextureHandle {
+    /// The texture unit the array of this texture is bound to: give it to the sampler of your shader.
+    pub fn texture_unit(&self) -> GLuint {
+        FIRST_COMPRESSED_TEXTURE_UNIT + self.array as GLuint
+    }
+}
+
+/// A compressed image and its mipmaps, borrowed from the file.
+pub (crate) struct CompressedImage<'a> {
+    pub (crate) format: TextureFormat,
+    pub (crate) width: u32,
+    pub (crate) height: u32,
+    /// mipmaps, starting with the full size image
+    pub (crate) levels: Vec<&'a [u8]>,
+}
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const KTX2_IDENTIFIER: &[u8] = &[0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+
+fn invalid(reason: &'static str) -> SprowlError {
+    SprowlError::InvalidCompressedTexture(reason)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, SprowlError> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("truncated header"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, SprowlError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+/// Parse a DDS or KTX2 file, depending on its magic bytes.
+pub (crate) fn parse(bytes: &[u8]) -> Result<CompressedImage<'_>, SprowlError> {
+    if bytes.starts_with(DDS_MAGIC) {
+        parse_dds(bytes)
+    } else if bytes.starts_with(KTX2_IDENTIFIER) {
+        parse_ktx2(bytes)
+    } else {
+        Err(invalid("not a DDS or KTX2 file"))
+    }
+}
+
+/// Check the number of mipmaps read from a header: a full chain has `log2(max(w, h)) + 1` of
+/// them, more than that is a corrupted file.
+fn check_level_count(width: u32, height: u32, level_count: u32) -> Result<u32, SprowlError> {
+    let max_level_count = 32 - width.max(height).max(1).leading_zeros();
+    if level_count > max_level_count {
+        return Err(invalid("more mipmap levels than the dimensions allow"));
+    }
+    Ok(level_count)
+}
+
+/// An empty texture can't be allocated, and would make every level empty.
+fn check_dimensions(width: u32, height: u32) -> Result<(), SprowlError> {
+    if width == 0 || height == 0 {
+        return Err(invalid("width and height must not be 0"));
+    }
+    Ok(())
+}
+
+/// Size in bytes of the mipmap `level`.
+fn level_size(format: TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let level_width = width.checked_shr(level).unwrap_or(0).max(1);
+    let level_height = height.checked_shr(level).unwrap_or(0).max(1);
+    format.image_size(level_width, level_height)
+}
+
+/// Split the data following the header in mipmaps.
+fn split_levels(format: TextureFormat, width: u32, height: u32, level_count: u32, mut data: &[u8]) -> Result<Vec<&[u8]>, SprowlError> {
+    let level_count = check_level_count(width, height, level_count)?;
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for level in 0..level_count {
+        let size = level_size(format, width, height, level);
+        if data.len() < size {
+            return Err(invalid("truncated image data"));
+        }
+        let (level_data, rest) = data.split_at(size);
+        levels.push(level_data);
+        data = rest;
+    }
+    Ok(levels)
+}
+
+fn parse_dds(bytes: &[u8]) -> Result<CompressedImage<'_>, SprowlError> {
+    // the header is 124 bytes after the magic, optionally followed by the 20 bytes of DX10.
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    check_dimensions(width, height)?;
+    let level_count = read_u32(bytes, 28)?.max(1);
+    let four_cc = bytes.get(84..88).ok_or_else(|| invalid("truncated header"))?;
+    let (format, data_offset) = match four_cc {
+        b"DXT1" => (TextureFormat::Bc1, 128),
+        b"DXT5" => (TextureFormat::Bc3, 128),
+        b"DX10" => {
+            let format = match read_u32(bytes, 128)? {
+                // BC1_UNORM, BC1_UNORM_SRGB
+                71 | 72 => TextureFormat::Bc1,
+                // BC3_UNORM, BC3_UNORM_SRGB
+                77 | 78 => TextureFormat::Bc3,
+                _ => return Err(invalid("unsupported DXGI format")),
+            };
+            (format, 148)
+        },
+        _ => return Err(invalid("unsupported DDS format, only DXT1 and DXT5 are")),
+    };
+    let levels = split_levels(format, width, height, level_count, bytes.get(data_offset..).unwrap_or(&[]))?;
+    Ok(CompressedImage { format, width, height, levels })
+}
+
+fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage<'_>, SprowlError> {
+    // sRGB formats are treated like their linear counterparts.
+    let format = match read_u32(bytes, 12)? {
+        // VK_FORMAT_BC1_RGB(A)_UNORM/SRGB_BLOCK
+        131..=134 => TextureFormat::Bc1,
+        // VK_FORMAT_BC3_UNORM/SRGB_BLOCK
+        137 | 138 => TextureFormat::Bc3,
+        // VK_FORMAT_ETC2_R8G8B8_UNORM/SRGB_BLOCK
+        147 | 148 => TextureFormat::Etc2Rgb,
+        // VK_FORMAT_ETC2_R8G8B8A8_UNORM/SRGB_BLOCK
+        151 | 152 => TextureFormat::Etc2Rgba,
+        _ => return Err(invalid("unsupported KTX2 format")),
+    };
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    check_dimensions(width, height)?;
+    // a depth, layer count or face count of 0 means that the texture is not 3D, not an array
+    // or not a cubemap.
+    if read_u32(bytes, 28)? != 0 {
+        return Err(invalid("3D KTX2 textures are not supported"));
+    }
+    if read_u32(bytes, 32)? > 1 {
+        return Err(invalid("KTX2 texture arrays are not supported"));
+    }
+    if read_u32(bytes, 36)? > 1 {
+        return Err(invalid("KTX2 cubemaps are not supported"));
+    }
+    // 0 means that the mipmaps should be generated, which compressed textures can't do.
+    let level_count = check_level_count(width, height, read_u32(bytes, 40)?.max(1))?;
+    if read_u32(bytes, 44)? != 0 {
+        return Err(invalid("supercompressed KTX2 files are not supported"));
+    }
+    // the level index comes right after the header, with (offset, length, uncompressed length)
+    // for every level.
+    let levels = (0..level_count).map(|level| {
+        let index = 80 + level as usize * 24;
+        let offset = usize::try_from(read_u64(bytes, index)?).map_err(|_| invalid("truncated image data"))?;
+        let length = usize::try_from(read_u64(bytes, index + 8)?).map_err(|_| invalid("truncated image data"))?;
+        let expected = level_size(format, width, height, level);
+        match offset.checked_add(length).and_then(|end| bytes.get(offset..end)) {
+            Some(level_data) if length >= expected => Ok(level_data),
+            _ => Err(invalid("truncated image data")),
+        }
+    }).collect::<Result<Vec<&[u8]>, SprowlError>>()?;
+    Ok(CompressedImage { format, width, height, levels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DXT1 file of `width` x `height` without mipmaps.
+    fn dds(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[0..4].copy_from_slice(DDS_MAGIC);
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DXT1");
+        bytes.resize(128 + TextureFormat::Bc1.image_size(width, height), 0);
+        bytes
+    }
+
+    /// A BC1 KTX2 file of `width` x `height` without mipmaps, with the given layer and face count.
+    fn ktx2(width: u32, height: u32, layer_count: u32, face_count: u32) -> Vec<u8> {
+        let data_offset = 80 + 24;
+        let data_size = TextureFormat::Bc1.image_size(width, height);
+        let mut bytes = vec![0; data_offset + data_size];
+        bytes[0..12].copy_from_slice(KTX2_IDENTIFIER);
+        for (offset, value) in [(12, 131), (20, width), (24, height), (32, layer_count), (36, face_count), (40, 1)] {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[80..88].copy_from_slice(&(data_offset as u64).to_le_bytes());
+        bytes[88..96].copy_from_slice(&(data_size as u64).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn valid_files_are_parsed() {
+        for bytes in &[dds(8, 4), ktx2(8, 4, 0, 1)] {
+            let image = parse(bytes).unwrap();
+            assert_eq!((image.format, image.width, image.height), (TextureFormat::Bc1, 8, 4));
+            assert_eq!(image.levels.len(), 1);
+            assert_eq!(image.levels[0].len(), 16);
+        }
+    }
+
+    #[test]
+    fn empty_textures_are_invalid() {
+        for bytes in &[dds(0, 4), dds(4, 0), ktx2(0, 4, 0, 1), ktx2(4, 0, 0, 1)] {
+            assert!(matches!(parse(bytes), Err(SprowlError::InvalidCompressedTexture(_))));
+        }
+    }
+
+    #[test]
+    fn ktx2_arrays_and_cubemaps_are_invalid() {
+        assert!(matches!(parse(&ktx2(4, 4, 2, 1)), Err(SprowlError::InvalidCompressedTexture(_))));
+        assert!(matches!(parse(&ktx2(4, 4, 0, 6)), Err(SprowlError::InvalidCompressedTexture(_))));
+    }
+}
@@ -0,0 +1,129 @@
+//! Dimensions, layer counts and filtering of the texture arrays of a `RenderStorage`.
+//!
+//! The formats are fixed by the kind of the array: RGBA for `TextureKind::RGBA`, one channel for
+//! `TextureKind::Grayscale`. Compressed textures live in arrays of their own, see `compressed`.
+
+use super::TextureKind;
+use super::texture::TextureArraySettings;
+use crate::backend::gl;
+use crate::color::AlphaMode;
+use crate::error::SprowlError;
+use crate::gl_utils::gl_get_int;
+
+/// Size and filtering of one texture array.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureArrayConfig {
+    /// Size of every layer, in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Layers allocated upfront. The array still grows when they are all used.
+    pub layers: u32,
+    pub settings: TextureArraySettings,
+}
+
+impl TextureArrayConfig {
+    pub fn new(width: u32, height: u32, layers: u32) -> TextureArrayConfig {
+        TextureArrayConfig {
+            width,
+            height,
+            layers,
+            settings: TextureArraySettings::default(),
+        }
+    }
+
+    pub fn with_settings(mut self, settings: TextureArraySettings) -> TextureArrayConfig {
+        self.settings = settings;
+        self
+    }
+
+    /// See `RenderStorageConfig::validate`.
+    pub (crate) fn validate_rgba(&self) -> Result<(), SprowlError> {
+        let (max_size, max_layers) = gl_limits();
+        self.validate(TextureKind::RGBA, max_size, max_layers)
+    }
+
+    fn validate(&self, kind: TextureKind, max_size: u32, max_layers: u32) -> Result<(), SprowlError> {
+        if self.width == 0 || self.height == 0 || self.layers == 0 {
+            return Err(SprowlError::InvalidStorageConfig(format!("{:?} array: {}x{}x{} is empty", kind, self.width, self.height, self.layers)));
+        }
+        if self.width > max_size || self.height > max_size {
+            return Err(SprowlError::InvalidStorageConfig(format!("{:?} array: {}x{} is bigger than GL_MAX_TEXTURE_SIZE ({})", kind, self.width, self.height, max_size)));
+        }
+        if self.layers > max_layers {
+            return Err(SprowlError::InvalidStorageConfig(format!("{:?} array: {} layers is more than GL_MAX_ARRAY_TEXTURE_LAYERS ({})", kind, self.layers, max_layers)));
+        }
+        Ok(())
+    }
+}
+
+/// Texture arrays of a `RenderStorage`, see `RenderStorage::with_config`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderStorageConfig {
+    /// "Normal" textures, small ones being packed together in atlas layers.
+    pub rgba: TextureArrayConfig,
+    /// Mostly text: the glyph atlas takes a whole layer of this array.
+    pub grayscale: TextureArrayConfig,
+    /// Whether RGBA textures are premultiplied when uploaded. The renderers must be built with
+    /// the same mode, see `RendererBuilder::with_alpha_mode`.
+    pub alpha_mode: AlphaMode,
+}
+
+impl Default for RenderStorageConfig {
+    /// What `RenderStorage::new` uses: 32 layers of 1024x1024 in RGBA with NEAREST filtering,
+    /// 16 layers of 2048x2048 in grayscale with linear filtering.
+    fn default() -> RenderStorageConfig {
+        RenderStorageConfig {
+            rgba: TextureArrayConfig::new(1024, 1024, 32),
+            grayscale: TextureArrayConfig::new(2048, 2048, 16).with_settings(TextureArraySettings::linear()),
+            alpha_mode: AlphaMode::Straight,
+        }
+    }
+}
+
+impl RenderStorageConfig {
+    pub fn new() -> RenderStorageConfig {
+        RenderStorageConfig::default()
+    }
+
+    /// Smaller arrays for mobile GPUs: 8 layers of 1024x1024 in RGBA, 2 layers of 1024x1024 in
+    /// grayscale.
+    pub fn small() -> RenderStorageConfig {
+        RenderStorageConfig {
+            rgba: TextureArrayConfig::new(1024, 1024, 8),
+            grayscale: TextureArrayConfig::new(1024, 1024, 2).with_settings(TextureArraySettings::linear()),
+            alpha_mode: AlphaMode::Straight,
+        }
+    }
+
+    pub fn with_rgba(mut self, rgba: TextureArrayConfig) -> RenderStorageConfig {
+        self.rgba = rgba;
+        self
+    }
+
+    pub fn with_grayscale(mut self, grayscale: TextureArrayConfig) -> RenderStorageConfig {
+        self.grayscale = grayscale;
+        self
+    }
+
+    /// Premultiply the RGBA textures (images and color glyphs, not MSDF glyphs) when they are
+    /// uploaded. Grayscale textures are coverage, and are left as is.
+    pub fn with_alpha_mode(mut self, alpha_mode: AlphaMode) -> RenderStorageConfig {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Checks the config against the limits of the current GL context.
+    ///
+    /// Returns an error if an array is empty, if its layers are bigger than `GL_MAX_TEXTURE_SIZE`
+    /// or if it has more layers than `GL_MAX_ARRAY_TEXTURE_LAYERS`.
+    pub fn validate(&self) -> Result<(), SprowlError> {
+        let (max_size, max_layers) = gl_limits();
+        self.rgba.validate(TextureKind::RGBA, max_size, max_layers)?;
+        self.grayscale.validate(TextureKind::Grayscale, max_size, max_layers)
+    }
+}
+
+/// GL_MAX_TEXTURE_SIZE and GL_MAX_ARRAY_TEXTURE_LAYERS of the current context.
+fn gl_limits() -> (u32, u32) {
+    (gl_get_int(gl::MAX_TEXTURE_SIZE).max(0) as u32, gl_get_int(gl::MAX_ARRAY_TEXTURE_LAYERS).max(0) as u32)
+}
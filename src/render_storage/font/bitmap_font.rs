@@ -0,0 +1,297 @@
+//! Hand-drawn fonts in the AngelCode BMFont format (.fnt), as exported by BMFont, Hiero,
+//! Littera or ShoeBox: pixel fonts keep their exact glyphs and kerning instead of being
+//! rasterized from a TTF.
+//!
+//! ```ignore
+//! let font_id = storage.add_bitmap_font_from_path("fonts/pixel.fnt")?;
+//! // the same as with any other font
+//! renderer.draw_text(&mut storage, font_id, "Hello!", 16.0, position, &TextOptions::new())?;
+//! ```
+//!
+//! Only the text format of .fnt is read, not the XML or binary ones. The pages are stored in the
+//! RGBA textures like any other image.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use cgmath::Vector2;
+use rusttype::{Scale as FontScale, VMetrics};
+
+use crate::error::SprowlError;
+use crate::render_storage::FontId;
+use crate::render_storage::atlas::TextureHandle;
+use super::{FontStemDrawCall, LayoutFont};
+
+/// A character of a `BitmapFont`, in pixels at the size of the font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitmapGlyph {
+    /// (x, y, w, h), relative to its page.
+    pub crop: (u32, u32, u32, u32),
+    /// From the pen position to the top-left corner of the glyph, the top of the line being y=0.
+    pub offset: (i32, i32),
+    pub x_advance: i32,
+    pub page: u32,
+}
+
+/// The content of a .fnt file, before its pages are loaded.
+#[derive(Debug, Clone)]
+pub struct FntFile {
+    /// Size the glyphs were drawn at, in pixels.
+    pub size: f32,
+    /// Distance between two baselines, in pixels.
+    pub line_height: f32,
+    /// Distance between the top of a line and its baseline, in pixels.
+    pub base: f32,
+    /// File names of the pages, by page id.
+    pub pages: Vec<String>,
+    pub glyphs: HashMap<char, BitmapGlyph>,
+    pub kernings: HashMap<(char, char), i32>,
+}
+
+fn invalid<S: Into<String>>(reason: S) -> SprowlError {
+    SprowlError::InvalidBitmapFont(reason.into())
+}
+
+/// Split a line of a .fnt file into its tag and its `key=value` pairs, values being possibly
+/// quoted (`file="my font.png"`).
+fn parse_line(line: &str) -> (&str, Vec<(&str, &str)>) {
+    let line = line.trim();
+    let (tag, mut rest) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim_start()),
+        None => (line, ""),
+    };
+    let mut pairs = Vec::new();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        let after = &rest[eq + 1..];
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else {
+            match after.find(char::is_whitespace) {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            }
+        };
+        pairs.push((key, value));
+        rest = next.trim_start();
+    }
+    (tag, pairs)
+}
+
+fn field<T: std::str::FromStr>(pairs: &[(&str, &str)], tag: &str, key: &str) -> Result<T, SprowlError> {
+    let value = pairs.iter().find(|(k, _)| *k == key).map(|(_, value)| *value)
+        .ok_or_else(|| invalid(format!("\"{}\" line without {:?}", tag, key)))?;
+    value.parse().map_err(|_| invalid(format!("invalid {:?} in a \"{}\" line: {:?}", key, tag, value)))
+}
+
+fn char_field(pairs: &[(&str, &str)], tag: &str, key: &str) -> Result<Option<char>, SprowlError> {
+    // -1 is the "invalid character" glyph of BMFont, which has no char.
+    let id: i64 = field(pairs, tag, key)?;
+    Ok(u32::try_from(id).ok().and_then(char::from_u32))
+}
+
+/// Read a .fnt file in the text format. Nothing is loaded, see `RenderStorage::add_bitmap_font`
+/// for that.
+pub fn parse_fnt(fnt: &str) -> Result<FntFile, SprowlError> {
+    let mut size = None;
+    let mut common = None;
+    let mut pages = Vec::new();
+    let mut glyphs = HashMap::new();
+    let mut kernings = HashMap::new();
+    for line in fnt.lines() {
+        let (tag, pairs) = parse_line(line);
+        match tag {
+            // negative when the font was exported with "match char height"
+            "info" => size = Some(field::<f32>(&pairs, tag, "size")?.abs()),
+            "common" => {
+                if field::<u32>(&pairs, tag, "packed").unwrap_or(0) != 0 {
+                    return Err(invalid("glyphs packed in the color channels are not supported"));
+                }
+                common = Some((field::<f32>(&pairs, tag, "lineHeight")?, field::<f32>(&pairs, tag, "base")?));
+            },
+            "page" => {
+                let id: usize = field(&pairs, tag, "id")?;
+                let file: String = field(&pairs, tag, "file")?;
+                if pages.len() <= id {
+                    pages.resize(id + 1, String::new());
+                }
+                pages[id] = file;
+            },
+            "char" => if let Some(c) = char_field(&pairs, tag, "id")? {
+                glyphs.insert(c, BitmapGlyph {
+                    crop: (field(&pairs, tag, "x")?, field(&pairs, tag, "y")?, field(&pairs, tag, "width")?, field(&pairs, tag, "height")?),
+                    offset: (field(&pairs, tag, "xoffset")?, field(&pairs, tag, "yoffset")?),
+                    x_advance: field(&pairs, tag, "xadvance")?,
+                    page: field(&pairs, tag, "page")?,
+                });
+            },
+            "kerning" => {
+                let first = char_field(&pairs, tag, "first")?;
+                let second = char_field(&pairs, tag, "second")?;
+                if let (Some(first), Some(second)) = (first, second) {
+                    kernings.insert((first, second), field(&pairs, tag, "amount")?);
+                }
+            },
+            _ => {},
+        }
+    }
+    let (line_height, base) = common.ok_or_else(|| invalid("no \"common\" line"))?;
+    if let Some(glyph) = glyphs.values().find(|glyph| glyph.page as usize >= pages.len()) {
+        return Err(invalid(format!("a char is on page {}, which is not listed", glyph.page)));
+    }
+    Ok(FntFile {
+        // without "info", the glyphs are considered drawn for a line as high as `lineHeight`
+        size: size.unwrap_or(line_height),
+        line_height,
+        base,
+        pages,
+        glyphs,
+        kernings,
+    })
+}
+
+/// A font loaded from a .fnt file and its pages, see `RenderStorage::add_bitmap_font`.
+///
+/// It has the same methods as `FontRenderer` to lay out and draw text, and the functions which
+/// take a `FontId` (`Renderer::draw_text`, `RenderStorage::word_to_draw_call`,
+/// `AdvancedLayout::new_rich`...) accept both kinds of fonts.
+///
+/// Glyphs are drawn scaled by `font_size / size`: draw them at `size`, or at a multiple of it,
+/// to keep them pixel perfect. Characters missing from the font are skipped.
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    pub (crate) font_id: FontId,
+    pub (crate) fnt: FntFile,
+    /// by page id
+    pub (crate) pages: Vec<TextureHandle>,
+}
+
+impl BitmapFont {
+    /// # Panics
+    ///
+    /// Panics if there isn't a texture for every page of `fnt`.
+    pub fn new(fnt: FntFile, pages: Vec<TextureHandle>, font_id: FontId) -> BitmapFont {
+        assert_eq!(fnt.pages.len(), pages.len(), "a bitmap font needs a texture for each of its pages");
+        BitmapFont {
+            font_id,
+            fnt,
+            pages,
+        }
+    }
+
+    #[inline]
+    pub fn font_id(&self) -> FontId {
+        self.font_id
+    }
+
+    /// Size the glyphs were drawn at, in pixels.
+    #[inline]
+    pub fn size(&self) -> f32 {
+        self.fnt.size
+    }
+
+    #[inline]
+    pub fn fnt(&self) -> &FntFile {
+        &self.fnt
+    }
+
+    /// The textures of the pages, by page id.
+    #[inline]
+    pub fn pages(&self) -> &[TextureHandle] {
+        &self.pages
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&BitmapGlyph> {
+        self.fnt.glyphs.get(&c)
+    }
+
+    fn ratio(&self, font_size: f32) -> f32 {
+        font_size / self.fnt.size
+    }
+
+    pub fn y_length(&self, font_size: f32) -> f32 {
+        let v_metrics = self.v_metrics(font_size);
+        v_metrics.ascent - v_metrics.descent
+    }
+
+    /// Ascent (above the baseline, positive), descent (below the baseline, negative) and line gap
+    /// of the font, in pixels. The line gap is always 0: `lineHeight` is split between the ascent
+    /// and the descent.
+    pub fn v_metrics(&self, font_size: f32) -> VMetrics {
+        let ratio = self.ratio(font_size);
+        VMetrics {
+            ascent: self.fnt.base * ratio,
+            descent: (self.fnt.base - self.fnt.line_height) * ratio,
+            line_gap: 0.0,
+        }
+    }
+
+    /// Distance between the baselines of two lines of a plain text in this font.
+    pub fn line_height(&self, font_size: f32) -> f32 {
+        self.fnt.line_height * self.ratio(font_size)
+    }
+
+    /// Width of `text` on a single line, kerning included.
+    pub fn text_width(&self, text: &str, font_size: f32) -> f32 {
+        let scale = FontScale::uniform(font_size);
+        let mut last_char = None;
+        text.chars().map(|c| {
+            let pair_kerning = last_char.map_or(0.0, |prev_char| LayoutFont::pair_kerning(self, scale, prev_char, c));
+            last_char = Some(c);
+            self.advance_width(scale, c) + pair_kerning
+        }).sum()
+    }
+
+    /// Returns where to draw every glyph of `text`, like `FontRenderer::word_to_draw_call`. The
+    /// glyphs are already in the RGBA textures, so this never fails and every draw call has
+    /// `color` set.
+    pub fn word_to_draw_call(&self, text: &str, font_size: f32) -> Vec<FontStemDrawCall> {
+        let ratio = self.ratio(font_size);
+        let mut pen_x = 0;
+        let mut last_char = None;
+        let mut results = Vec::with_capacity(text.len());
+        for (i, c) in text.chars().enumerate() {
+            if let Some(kerning) = last_char.and_then(|prev_char| self.fnt.kernings.get(&(prev_char, c))) {
+                pen_x += kerning;
+            }
+            last_char = Some(c);
+            let glyph = match self.glyph(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            let (_, _, w, h) = glyph.crop;
+            if w > 0 && h > 0 {
+                let page = &self.pages[glyph.page as usize];
+                let (x, y, w, h) = page.crop_in_layer(glyph.crop);
+                results.push(FontStemDrawCall {
+                    source_crop: (x as f32, y as f32, w as f32, h as f32),
+                    dest_origin: Vector2::new((pen_x + glyph.offset.0) as f32, glyph.offset.1 as f32) * ratio,
+                    dest_size: Vector2::new(w as f32, h as f32) * ratio,
+                    texture_layer: page.layer,
+                    character_index: i,
+                    padding: 0.0,
+                    color: true,
+                });
+            }
+            pen_x += glyph.x_advance;
+        }
+        results
+    }
+}
+
+impl LayoutFont for BitmapFont {
+    fn v_metrics(&self, scale: FontScale) -> VMetrics {
+        BitmapFont::v_metrics(self, scale.y)
+    }
+
+    fn advance_width(&self, scale: FontScale, c: char) -> f32 {
+        self.glyph(c).map_or(0.0, |glyph| glyph.x_advance as f32 * self.ratio(scale.x))
+    }
+
+    fn pair_kerning(&self, scale: FontScale, first: char, second: char) -> f32 {
+        self.fnt.kernings.get(&(first, second)).map_or(0.0, |amount| *amount as f32 * self.ratio(scale.x))
+    }
+}
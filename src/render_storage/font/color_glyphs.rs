@@ -0,0 +1,106 @@
+//! Color glyphs (emoji) from the bitmap tables of color fonts: `CBDT` (Noto Color Emoji) or
+//! `sbix` (Apple Color Emoji).
+//!
+//! These glyphs have no outline for rusttype to rasterize. Their PNG images are decoded and packed
+//! into the **RGBA** texture array instead, like small textures, and their `FontStemDrawCall`s
+//! have `color` set: draw them from the RGBA array, untinted.
+
+use cgmath::Vector2;
+use hashbrown::HashMap;
+
+use crate::color::{AlphaMode, premultiply_alpha};
+use crate::render_storage::{FontId, atlas::{self, AtlasLayer, TextureHandle}};
+use crate::render_storage::texture::Texture2DArray;
+
+/// Whether the font has a table of color bitmaps, by looking at its table directory.
+pub (crate) fn has_color_bitmaps(bytes: &[u8]) -> bool {
+    let num_tables = match bytes.get(4..6) {
+        Some(num_tables) => u16::from_be_bytes([num_tables[0], num_tables[1]]) as usize,
+        None => return false,
+    };
+    (0..num_tables).any(|i| {
+        let record = 12 + 16 * i;
+        matches!(bytes.get(record..record + 4), Some(b"CBDT") | Some(b"sbix"))
+    })
+}
+
+/// A color glyph, packed in the RGBA texture array.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorGlyph {
+    pub handle: TextureHandle,
+    /// Offset of the top-left corner of the image from the origin of the glyph on the baseline,
+    /// in pixels at `pixels_per_em`.
+    pub offset: Vector2<f32>,
+    /// Size the image was made for: it must be scaled by `font_size / pixels_per_em`.
+    pub pixels_per_em: f32,
+}
+
+/// The color glyphs of all the fonts, see the module documentation.
+#[derive(Debug, Default)]
+pub struct ColorGlyphAtlas {
+    // by font, glyph and requested pixels per em. None for glyphs without a color image
+    pub (crate) glyphs: HashMap<(FontId, u16, u16), Option<ColorGlyph>>,
+}
+
+impl ColorGlyphAtlas {
+    pub fn new() -> ColorGlyphAtlas {
+        ColorGlyphAtlas::default()
+    }
+
+    /// Number of glyphs looked up so far, with or without an image.
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+}
+
+/// A `ColorGlyphAtlas` along with the RGBA texture array it writes to, part of a `GlyphAtlasRef`.
+pub struct ColorGlyphAtlasRef<'a> {
+    pub (crate) atlas: &'a mut ColorGlyphAtlas,
+    pub (crate) texture_array: &'a mut Texture2DArray,
+    pub (crate) atlas_layers: &'a mut Vec<AtlasLayer>,
+    pub (crate) alpha_mode: AlphaMode,
+}
+
+impl<'a> ColorGlyphAtlasRef<'a> {
+    /// Returns the color image of the glyph in the strike closest to `pixels_per_em`, decoding
+    /// and uploading it first if needed. Returns None if the glyph has no PNG image.
+    pub (crate) fn glyph(&mut self, font_id: FontId, font_bytes: &[u8], glyph_id: u16, pixels_per_em: u16) -> Option<ColorGlyph> {
+        let ColorGlyphAtlasRef { atlas, texture_array, atlas_layers, alpha_mode } = self;
+        *atlas.glyphs.entry((font_id, glyph_id, pixels_per_em)).or_insert_with(|| {
+            let face = ttf_parser::Face::from_slice(font_bytes, 0).ok()?;
+            let image = face.glyph_raster_image(ttf_parser::GlyphId(glyph_id), pixels_per_em)?;
+            if image.format != ttf_parser::RasterImageFormat::PNG {
+                return None;
+            }
+            let rgba = match image::load_from_memory_with_format(image.data, image::ImageFormat::Png) {
+                Ok(decoded) => decoded.into_rgba8(),
+                Err(e) => {
+                    log::warn!("could not decode color glyph {} of font {}: {}", glyph_id, font_id, e);
+                    return None;
+                }
+            };
+            let (width, height) = rgba.dimensions();
+            let mut pixels = rgba.into_raw();
+            if *alpha_mode == AlphaMode::Premultiplied {
+                premultiply_alpha(&mut pixels);
+            }
+            let handle = match atlas::pack_texture(texture_array, atlas_layers, &pixels, (width, height)) {
+                Ok(handle) => handle,
+                Err(e) => {
+                    log::warn!("no room for color glyph {} of font {}: {}", glyph_id, font_id, e);
+                    return None;
+                }
+            };
+            Some(ColorGlyph {
+                handle,
+                // the image's y goes up from the baseline to its bottom edge
+                offset: Vector2::new(image.x as f32, -(image.y as f32 + height as f32)),
+                pixels_per_em: image.pixels_per_em.max(1) as f32,
+            })
+        })
+    }
+}
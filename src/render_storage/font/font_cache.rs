@@ -10,6 +10,8 @@ use hashbrown::{HashMap, HashSet, hash_map::DefaultHashBuilder as HashBuilder};
 /// as well as the pixel-space (integer) coordinates that this region should be
 /// drawn at.
 pub type TextureCoords = (Rect<f32>, Rect<i32>);
+/// Same as `TextureCoords`, with the pixel-space coordinates not rounded, see `Cache::rect_for_unrounded`.
+pub type UnroundedTextureCoords = (Rect<f32>, Rect<f32>);
 type FontId = usize;
 
 /// Indicates where a glyph texture is stored in the cache
@@ -105,16 +107,16 @@ struct GlyphTexInfo {
 }
 
 trait PaddingAware {
-    fn unpadded(self) -> Self;
+    fn unpadded(self, padding: u32) -> Self;
 }
 
 impl PaddingAware for Rect<u32> {
-    /// A padded texture has 1 extra pixel on all sides
-    fn unpadded(mut self) -> Self {
-        self.min.x += 1;
-        self.min.y += 1;
-        self.max.x -= 1;
-        self.max.y -= 1;
+    /// A padded texture has `padding` extra pixels on all sides
+    fn unpadded(mut self, padding: u32) -> Self {
+        self.min.x += padding;
+        self.min.y += padding;
+        self.max.x -= padding;
+        self.max.y -= padding;
         self
     }
 }
@@ -132,8 +134,9 @@ pub struct Cache {
     /// Mapping of row gaps top -> bottom
     space_end_for_start: HashMap<u32, u32>,
     all_glyphs: HashMap<LossyGlyphInfo, TextureRowGlyphIndex>,
-    pad_glyphs: bool,
+    glyph_padding: u32,
     align_4x4: bool,
+    multithread: bool,
 }
 
 /// Builder & rebuilder for `Cache`.
@@ -162,8 +165,9 @@ pub struct CacheBuilder {
     dimensions: (u32, u32),
     scale_tolerance: f32,
     position_tolerance: f32,
-    pad_glyphs: bool,
+    glyph_padding: u32,
     align_4x4: bool,
+    multithread: bool,
 }
 
 impl Default for CacheBuilder {
@@ -172,8 +176,9 @@ impl Default for CacheBuilder {
             dimensions: (256, 256),
             scale_tolerance: 0.1,
             position_tolerance: 0.1,
-            pad_glyphs: true,
+            glyph_padding: 1,
             align_4x4: false,
+            multithread: true,
         }
     }
 }
@@ -266,7 +271,21 @@ impl CacheBuilder {
     /// let cache = Cache::builder().pad_glyphs(true).build();
     /// ```
     pub fn pad_glyphs(mut self, pad_glyphs: bool) -> Self {
-        self.pad_glyphs = pad_glyphs;
+        self.glyph_padding = if pad_glyphs { 1 } else { 0 };
+        self
+    }
+    /// Pack glyphs in texture with `glyph_padding` zero alpha pixels on every
+    /// side, so that shaders can sample around the glyph to draw an outline,
+    /// a shadow or a glow. `pad_glyphs(true)` is a padding of 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use rusttype::gpu_cache::Cache;
+    /// let cache = Cache::builder().glyph_padding(4).build();
+    /// ```
+    pub fn glyph_padding(mut self, glyph_padding: u32) -> Self {
+        self.glyph_padding = glyph_padding;
         self
     }
     /// Align glyphs in texture to 4x4 texel boundaries.
@@ -285,6 +304,23 @@ impl CacheBuilder {
         self
     }
 
+    /// When multiple CPU cores are available, spread rasterization of uncached
+    /// glyphs across them on the rayon thread pool. Uploads are still made by
+    /// the thread calling `cache_glyphs`, which owns the GL context.
+    ///
+    /// Only has an effect with the `rayon` feature.
+    ///
+    /// # Example (set to default value)
+    ///
+    /// ```
+    /// # use rusttype::gpu_cache::Cache;
+    /// let cache = Cache::builder().multithread(true).build();
+    /// ```
+    pub fn multithread(mut self, multithread: bool) -> Self {
+        self.multithread = multithread;
+        self
+    }
+
     fn validated(self) -> Self {
         assert!(self.scale_tolerance >= 0.0);
         assert!(self.position_tolerance >= 0.0);
@@ -316,8 +352,9 @@ impl CacheBuilder {
             dimensions: (width, height),
             scale_tolerance,
             position_tolerance,
-            pad_glyphs,
+            glyph_padding,
             align_4x4,
+            multithread,
         } = self.validated();
 
         Cache {
@@ -337,8 +374,9 @@ impl CacheBuilder {
                 m
             },
             all_glyphs: HashMap::default(),
-            pad_glyphs,
+            glyph_padding,
             align_4x4,
+            multithread,
         }
     }
 
@@ -363,16 +401,18 @@ impl CacheBuilder {
             dimensions: (width, height),
             scale_tolerance,
             position_tolerance,
-            pad_glyphs,
+            glyph_padding,
             align_4x4,
+            multithread,
         } = self.validated();
 
         cache.width = width;
         cache.height = height;
         cache.scale_tolerance = scale_tolerance;
         cache.position_tolerance = position_tolerance;
-        cache.pad_glyphs = pad_glyphs;
+        cache.glyph_padding = glyph_padding;
         cache.align_4x4 = align_4x4;
+        cache.multithread = multithread;
         cache.clear();
     }
 }
@@ -473,6 +513,35 @@ impl Cache {
         (self.width, self.height)
     }
 
+    /// Returns the number of empty pixels around every glyph in the texture.
+    #[inline]
+    pub fn glyph_padding(&self) -> u32 {
+        self.glyph_padding
+    }
+
+    /// Returns whether glyphs are rasterized on the rayon thread pool, see
+    /// `CacheBuilder::multithread`.
+    #[inline]
+    pub fn multithread(&self) -> bool {
+        self.multithread
+    }
+
+    /// Same as `CacheBuilder::multithread`, without clearing the cache.
+    pub fn set_multithread(&mut self, multithread: bool) {
+        self.multithread = multithread;
+    }
+
+    /// Returns the number of glyphs in the cache.
+    pub fn glyph_count(&self) -> usize {
+        self.all_glyphs.len()
+    }
+
+    /// Returns the fraction of the texture used by glyphs, from 0.0 to 1.0.
+    pub fn occupancy(&self) -> f32 {
+        let used: u64 = self.rows.values().map(|row| row.width as u64 * row.height as u64).sum();
+        used as f32 / (self.width as u64 * self.height as u64).max(1) as f32
+    }
+
     // /// Queue a glyph for caching by the next call to `cache_queued`. `font_id`
     // /// is used to disambiguate glyphs from different fonts. The user should
     // /// ensure that `font_id` is unique to the font the glyph is from.
@@ -498,8 +567,9 @@ impl Cache {
             dimensions: (self.width, self.height),
             position_tolerance: self.position_tolerance,
             scale_tolerance: self.scale_tolerance,
-            pad_glyphs: self.pad_glyphs,
+            glyph_padding: self.glyph_padding,
             align_4x4: self.align_4x4,
+            multithread: self.multithread,
         }
     }
 
@@ -539,9 +609,22 @@ impl Cache {
     /// previously cached glyph textures.
     pub fn cache_glyphs<'a, I, F: FnMut(Rect<u32>, &[u8])>(
         &mut self,
+        font_id: FontId,
         glyphs: I,
-        mut uploader: F,
+        uploader: F,
     ) -> Result<CachedBy, CacheWriteErr> where I: Iterator<Item=&'a PositionedGlyph<'a>> + ExactSizeIterator + Clone {
+        self.cache_glyphs_of_fonts(glyphs.map(move |glyph| (font_id, glyph)), uploader)
+    }
+
+    /// Same as `cache_glyphs`, for glyphs of several fonts: `(font_id, glyph)`.
+    ///
+    /// Since caching may evict the glyphs of a previous call, the glyphs of a text using several
+    /// fonts must be cached by a single call.
+    pub fn cache_glyphs_of_fonts<'a, I, F: FnMut(Rect<u32>, &[u8])>(
+        &mut self,
+        glyphs: I,
+        mut uploader: F,
+    ) -> Result<CachedBy, CacheWriteErr> where I: Iterator<Item=(FontId, &'a PositionedGlyph<'a>)> + ExactSizeIterator + Clone {
         let mut queue_success = true;
         let from_empty = self.all_glyphs.is_empty();
 
@@ -553,11 +636,11 @@ impl Cache {
 
                 // divide glyphs into texture rows where a matching glyph texture
                 // already exists & glyphs where new textures must be cached
-                for glyph in glyphs.clone() {
+                for (font_id, glyph) in glyphs.clone() {
                     if glyph.pixel_bounding_box().is_none() {
                         continue;
                     }
-                    let glyph_info = self.lossy_info_for(0usize, glyph);
+                    let glyph_info = self.lossy_info_for(font_id, glyph);
                     if let Some((row, ..)) = self.all_glyphs.get(&glyph_info) {
                         in_use_rows.insert(*row);
                     } else {
@@ -589,11 +672,7 @@ impl Cache {
                 // Not cached, so add it:
                 let (unaligned_width, unaligned_height) = {
                     let bb = glyph.pixel_bounding_box().unwrap();
-                    if self.pad_glyphs {
-                        (bb.width() as u32 + 2, bb.height() as u32 + 2)
-                    } else {
-                        (bb.width() as u32, bb.height() as u32)
-                    }
+                    (bb.width() as u32 + 2 * self.glyph_padding, bb.height() as u32 + 2 * self.glyph_padding)
                 };
                 let (aligned_width, aligned_height) = if self.align_4x4 {
                     // align to the next 4x4 texel boundary
@@ -714,11 +793,7 @@ impl Cache {
             }
 
             if queue_success {
-                // single thread rasterization
-                for (tex_coords, glyph) in draw_and_upload {
-                    let pixels = draw_glyph(tex_coords, &glyph, self.pad_glyphs);
-                    uploader(tex_coords, pixels.as_slice());
-                }
+                rasterize_and_upload(draw_and_upload, self.glyph_padding, self.multithread, &mut uploader);
             }
         }
 
@@ -727,7 +802,7 @@ impl Cache {
         } else {
             // clear the cache then try again with optimal packing
             self.clear();
-            self.cache_glyphs(glyphs, uploader).map(|_| CachedBy::Reordering)
+            self.cache_glyphs_of_fonts(glyphs, uploader).map(|_| CachedBy::Reordering)
         }
     }
 
@@ -744,18 +819,33 @@ impl Cache {
     /// shape, and thus no rect to return).
     ///
     /// Ensure that `font_id` matches the `font_id` that was passed to
-    /// `queue_glyph` with this `glyph`.
+    /// `cache_glyphs` with this `glyph`.
     pub fn rect_for(
         &self,
+        font_id: FontId,
         glyph: &PositionedGlyph,
     ) -> Result<Option<TextureCoords>, CacheReadErr> {
+        Ok(self.rect_for_unrounded(font_id, glyph)?.map(|(uv_rect, rect)| {
+            let min = point(rect.min.x.round() as i32, rect.min.y.round() as i32);
+            let size = vector(rect.width() as i32, rect.height() as i32);
+            (uv_rect, Rect { min, max: min + size })
+        }))
+    }
+
+    /// Same as `rect_for`, but the pixel-space coordinates are not rounded: the region is drawn
+    /// exactly where the glyph is, for sub-pixel positioning with linear filtering.
+    pub fn rect_for_unrounded(
+        &self,
+        font_id: FontId,
+        glyph: &PositionedGlyph,
+    ) -> Result<Option<UnroundedTextureCoords>, CacheReadErr> {
         if glyph.pixel_bounding_box().is_none() {
             return Ok(None);
         }
 
         let (row, index) = self
             .all_glyphs
-            .get(&self.lossy_info_for(0usize, glyph))
+            .get(&self.lossy_info_for(font_id, glyph))
             .ok_or(CacheReadErr::GlyphNotCached)?;
 
         let (tex_width, tex_height) = (self.width as f32, self.height as f32);
@@ -765,8 +855,8 @@ impl Cache {
             offset: tex_offset,
             ..
         } = self.rows[&row].glyphs[*index as usize];
-        if self.pad_glyphs {
-            tex_rect = tex_rect.unpadded();
+        if self.glyph_padding > 0 {
+            tex_rect = tex_rect.unpadded(self.glyph_padding);
         }
         let uv_rect = Rect {
             min: point(
@@ -788,30 +878,64 @@ impl Cache {
         let min_from_origin =
             point(local_bb.min.x as f32, local_bb.min.y as f32) - (point(0.0, 0.0) + tex_offset);
         let ideal_min = min_from_origin + glyph.position();
-        let min = point(ideal_min.x.round() as i32, ideal_min.y.round() as i32);
-        let bb_offset = min - local_bb.min;
+        let size = vector(local_bb.width() as f32, local_bb.height() as f32);
         let bb = Rect {
-            min,
-            max: local_bb.max + bb_offset,
+            min: ideal_min,
+            max: ideal_min + size,
         };
         Ok(Some((uv_rect, bb)))
     }
 }
 
-#[inline]
-fn draw_glyph(tex_coords: Rect<u32>, glyph: &PositionedGlyph<'_>, pad_glyphs: bool) -> ByteArray2d {
-    let mut pixels = ByteArray2d::zeros(tex_coords.height() as usize, tex_coords.width() as usize);
-    if pad_glyphs {
-        glyph.draw(|x, y, v| {
-            let v = (v * 255.0).round().max(0.0).min(255.0) as u8;
-            // `+ 1` accounts for top/left glyph padding
-            pixels[(y as usize + 1, x as usize + 1)] = v;
-        });
+/// Rasterize the glyphs, on the rayon thread pool if `multithread`, and upload them from the
+/// calling thread.
+#[cfg(feature = "rayon")]
+fn rasterize_and_upload<F: FnMut(Rect<u32>, &[u8])>(
+    draw_and_upload: Vec<(Rect<u32>, &PositionedGlyph<'_>)>,
+    glyph_padding: u32,
+    multithread: bool,
+    uploader: &mut F,
+) {
+    use rayon::prelude::*;
+
+    if multithread && draw_and_upload.len() > 1 {
+        let rasterized: Vec<(Rect<u32>, ByteArray2d)> = draw_and_upload
+            .into_par_iter()
+            .map(|(tex_coords, glyph)| (tex_coords, draw_glyph(tex_coords, glyph, glyph_padding)))
+            .collect();
+        for (tex_coords, pixels) in rasterized {
+            uploader(tex_coords, pixels.as_slice());
+        }
     } else {
-        glyph.draw(|x, y, v| {
-            let v = (v * 255.0).round().max(0.0).min(255.0) as u8;
-            pixels[(y as usize, x as usize)] = v;
-        });
+        for (tex_coords, glyph) in draw_and_upload {
+            let pixels = draw_glyph(tex_coords, glyph, glyph_padding);
+            uploader(tex_coords, pixels.as_slice());
+        }
     }
+}
+
+/// Single thread rasterization, `multithread` needs the `rayon` feature.
+#[cfg(not(feature = "rayon"))]
+fn rasterize_and_upload<F: FnMut(Rect<u32>, &[u8])>(
+    draw_and_upload: Vec<(Rect<u32>, &PositionedGlyph<'_>)>,
+    glyph_padding: u32,
+    _multithread: bool,
+    uploader: &mut F,
+) {
+    for (tex_coords, glyph) in draw_and_upload {
+        let pixels = draw_glyph(tex_coords, glyph, glyph_padding);
+        uploader(tex_coords, pixels.as_slice());
+    }
+}
+
+#[inline]
+fn draw_glyph(tex_coords: Rect<u32>, glyph: &PositionedGlyph<'_>, glyph_padding: u32) -> ByteArray2d {
+    let mut pixels = ByteArray2d::zeros(tex_coords.height() as usize, tex_coords.width() as usize);
+    let padding = glyph_padding as usize;
+    glyph.draw(|x, y, v| {
+        let v = (v * 255.0).round().max(0.0).min(255.0) as u8;
+        // `+ padding` accounts for top/left glyph padding
+        pixels[(y as usize + padding, x as usize + padding)] = v;
+    });
     pixels
 }
\ No newline at end of file
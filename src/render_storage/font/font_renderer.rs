@@ -1,90 +1,313 @@
-use rusttype::{Font, Scale as FontScale};
+use rusttype::{Font, PositionedGlyph, Scale as FontScale, VMetrics};
 use cgmath::Vector2;
-use crate::render_storage::font::Cache as FontCache;
+use std::borrow::Cow;
+use crate::error::SprowlError;
+use crate::render_storage::{FontId, font::{CacheWriteErr, GlyphAtlasRef, msdf::{MsdfAtlasRef, MSDF_GLYPH_SIZE}}};
+use crate::render_storage::texture::TextureArrayLayer;
 
-use crate::render_storage::texture::{TextureArrayLayer, TextureArrayLayerRef};
+/// Smallest size glyphs are rasterized at when they don't fit in the glyph atlas.
+pub const MIN_FALLBACK_FONT_SIZE: f32 = 8.0;
 
-/// FontRenderer represents a font with a GPU caching system.
+/// FontRenderer represents a font, whose glyphs are cached in the `GlyphAtlas` shared by all fonts.
 pub struct FontRenderer {
-    pub (crate) font_cache: FontCache,
-    pub (crate) texture_layer: TextureArrayLayer,
+    pub (crate) font_id: FontId,
     pub (crate) font: Font<'static>,
+    /// bytes of the font if it has color glyphs, which rusttype can't read
+    pub (crate) color_font_bytes: Option<Cow<'static, [u8]>>,
 }
 
+#[derive(Debug, Clone)]
 pub struct FontStemDrawCall {
     // in pixels
     pub source_crop: (f32, f32, f32, f32),
     pub dest_origin: Vector2<f32>,
+    /// Size to draw the glyph at. Usually the same as the size of `source_crop`, unless the glyph
+    /// had to be rasterized at a smaller size to fit in the glyph atlas.
+    pub dest_size: Vector2<f32>,
     pub texture_layer: TextureArrayLayer,
     pub character_index: usize,
+    /// Empty pixels of the texture around `source_crop`, see `GlyphAtlas::set_glyph_padding`.
+    pub padding: f32,
+    /// The glyph is a color image (an emoji) in the **RGBA** texture array, to draw as it is
+    /// instead of as the alpha of the text color. See `color_glyphs`.
+    pub color: bool,
+}
+
+impl FontStemDrawCall {
+    /// `source_crop` grown by `padding` on every side: the part of the texture to draw for an
+    /// outline, a shadow or a glow.
+    pub fn padded_crop(&self) -> (f32, f32, f32, f32) {
+        let (x, y, w, h) = self.source_crop;
+        (x - self.padding, y - self.padding, w + 2.0 * self.padding, h + 2.0 * self.padding)
+    }
+
+    /// Where to draw `padded_crop`: its origin and its size, in pixels.
+    pub fn padded_dest(&self) -> (Vector2<f32>, Vector2<f32>) {
+        let ratio = if self.source_crop.2 > 0.0 { self.dest_size.x / self.source_crop.2 } else { 1.0 };
+        let padding = Vector2::new(self.padding, self.padding) * ratio;
+        (self.dest_origin - padding, self.dest_size + padding * 2.0)
+    }
 }
 
 impl FontRenderer {
-    pub fn new(font: Font<'static>, texture_layer: TextureArrayLayer) -> FontRenderer {
-        const CACHE_WIDTH: usize = 2048;
+    pub fn new(font: Font<'static>, font_id: FontId) -> FontRenderer {
         FontRenderer {
-            font_cache: FontCache::builder()
-                .dimensions(CACHE_WIDTH as u32, CACHE_WIDTH as u32)
-                .pad_glyphs(true)
-                .align_4x4(true)
-                .position_tolerance(1.0)
-                .scale_tolerance(0.5)
-                .build(),
-            texture_layer,
+            font_id,
             font,
+            color_font_bytes: None,
         }
     }
 
+    /// Whether the font has color glyphs, see `color_glyphs`.
+    pub fn has_color_glyphs(&self) -> bool {
+        self.color_font_bytes.is_some()
+    }
+
     #[inline]
     pub fn font(&self) -> &Font<'static> {
         &self.font
     }
 
     #[inline]
-    pub fn texture_layer(&self) -> TextureArrayLayer {
-        self.texture_layer
+    pub fn font_id(&self) -> FontId {
+        self.font_id
     }
 
     pub fn y_length(&self, font_size: f32) -> f32 {
-        let scale = FontScale::uniform(font_size);
-
-        let v_metrics = self.font().v_metrics(scale);
+        let v_metrics = self.v_metrics(font_size);
         v_metrics.ascent - v_metrics.descent
     }
 
-    pub fn word_to_draw_call(&mut self, tex_ref: &mut TextureArrayLayerRef<'_>, text: &str, font_size: f32) -> Vec<FontStemDrawCall> {
-        let scale = FontScale::uniform(font_size);
+    /// Ascent (above the baseline, positive), descent (below the baseline, negative) and line gap
+    /// of the font, in pixels.
+    pub fn v_metrics(&self, font_size: f32) -> VMetrics {
+        self.font().v_metrics(FontScale::uniform(font_size))
+    }
+
+    /// Distance between the baselines of two lines of a plain text in this font.
+    pub fn line_height(&self, font_size: f32) -> f32 {
+        let v_metrics = self.v_metrics(font_size);
+        v_metrics.ascent - v_metrics.descent + v_metrics.line_gap
+    }
+
+    /// Same as `word_to_draw_call`, but panics on error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the glyphs can't fit in the glyph atlas, even at a smaller size.
+    pub fn word_to_draw_call_unchecked(&self, atlas_ref: &mut GlyphAtlasRef<'_>, text: &str, font_size: f32) -> Vec<FontStemDrawCall> {
+        self.word_to_draw_call(atlas_ref, text, font_size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[deprecated(note = "word_to_draw_call returns a Result now")]
+    pub fn try_word_to_draw_call(&self, atlas_ref: &mut GlyphAtlasRef<'_>, text: &str, font_size: f32) -> Result<Vec<FontStemDrawCall>, SprowlError> {
+        self.word_to_draw_call(atlas_ref, text, font_size)
+    }
+
+    /// Returns where to draw every glyph of `text`, after caching them in the glyph atlas.
+    ///
+    /// If the glyphs are too big for the atlas (huge font size, or too many different glyphs at
+    /// once), the atlas grows if `GlyphAtlas::set_max_size` allows it. Otherwise they are
+    /// rasterized at a smaller size and scaled up instead: see `FontStemDrawCall::dest_size`.
+    /// The text is then blurrier, but at least it's there.
+    ///
+    /// Returns an error if the glyphs still don't fit at `MIN_FALLBACK_FONT_SIZE`.
+    pub fn word_to_draw_call(&self, atlas_ref: &mut GlyphAtlasRef<'_>, text: &str, font_size: f32) -> Result<Vec<FontStemDrawCall>, SprowlError> {
+        self.words_to_draw_calls(atlas_ref, &[text], font_size)
+            .map(|mut draw_calls| draw_calls.pop().unwrap_or_default())
+    }
+
+    /// Same as `word_to_draw_call` for several words, whose glyphs are cached together: caching
+    /// the glyphs of the last words can't evict the ones of the first words, as long as they are
+    /// all drawn before the next call.
+    ///
+    /// If several words don't fit in the atlas at once even after growing it, this returns an
+    /// error instead of rasterizing them at a smaller size: draw them in smaller batches, as
+    /// `Renderer::draw_text` does.
+    pub fn words_to_draw_calls<S: AsRef<str>>(&self, atlas_ref: &mut GlyphAtlasRef<'_>, words: &[S], font_size: f32) -> Result<Vec<Vec<FontStemDrawCall>>, SprowlError> {
+        let mut raster_size = font_size;
+        loop {
+            match self.words_to_draw_calls_at(atlas_ref, words, font_size, raster_size) {
+                Ok(draw_calls) => return Ok(draw_calls),
+                Err(_) if raster_size == font_size && atlas_ref.grow() => {},
+                Err(e) if words.len() == 1 && raster_size / 2.0 >= MIN_FALLBACK_FONT_SIZE => {
+                    log::debug!("glyph atlas: {} for font size {}, retrying at {}", e, raster_size, raster_size / 2.0);
+                    raster_size /= 2.0;
+                },
+                Err(e) => return Err(SprowlError::GlyphCache(e)),
+            }
+        }
+    }
+
+    /// Cache the glyphs of `chars` at every size of `sizes` in the glyph atlas ahead of time, to
+    /// avoid rasterizing them the first time they are drawn (at startup: ASCII plus the
+    /// characters of your game).
+    ///
+    /// The atlas still evicts the glyphs which were not used for the longest time when it's
+    /// full, prewarmed or not: prewarm what fits. Color glyphs are not prewarmed.
+    ///
+    /// Returns an error if the glyphs of one of the sizes don't fit in the atlas at once.
+    pub fn prewarm<I: IntoIterator<Item = char>>(&self, atlas_ref: &mut GlyphAtlasRef<'_>, chars: I, sizes: &[f32]) -> Result<(), SprowlError> {
+        let mut chars: Vec<char> = chars.into_iter().collect();
+        chars.sort_unstable();
+        chars.dedup();
+        let font_id = self.font_id as usize;
+        let GlyphAtlasRef { atlas, texture: tex_ref, .. } = atlas_ref;
+        for &font_size in sizes {
+            let scale = FontScale::uniform(font_size);
+            // the atlas ignores the subpixel position of glyphs, any position will do. With
+            // `GlyphAtlas::set_subpixel_positioning`, only the glyphs at a whole pixel are cached.
+            let glyphs: Vec<PositionedGlyph<'_>> = chars.iter()
+                .map(|c| self.font.glyph(*c).scaled(scale).positioned(rusttype::point(0.0, 0.0)))
+                .collect();
+            atlas.cache.cache_glyphs(font_id, glyphs.iter(), |rect, data| {
+                let rusttype::Point { x, y } = rect.min;
+                tex_ref.update(data, x as i32, y as i32, rect.width(), rect.height());
+            }).map_err(SprowlError::GlyphCache)?;
+        }
+        Ok(())
+    }
+
+    /// Lays out the words at `font_size`, but rasterizes the glyphs at `raster_size`.
+    fn words_to_draw_calls_at<S: AsRef<str>>(&self, atlas_ref: &mut GlyphAtlasRef<'_>, words: &[S], font_size: f32, raster_size: f32) -> Result<Vec<Vec<FontStemDrawCall>>, CacheWriteErr> {
+        let scale = FontScale::uniform(raster_size);
+        // how much bigger the glyphs should be drawn than they are rasterized
+        let ratio = font_size / raster_size;
 
         let v_metrics = self.font().v_metrics(scale);
         // represents the distance between the top most pixel possible for this font, and the baseline
         let ascent = v_metrics.ascent;
-        let glyphs = self.font.layout(text, scale, rusttype::point(0.0, 0.0)).enumerate().collect::<Vec<_>>();
+        let words_glyphs = words.iter()
+            .map(|text| self.font.layout(text.as_ref(), scale, rusttype::point(0.0, 0.0)).enumerate().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let all_glyphs = words_glyphs.iter().flatten().map(|(_, glyph)| glyph).collect::<Vec<_>>();
 
+        let font_id = self.font_id as usize;
+        let GlyphAtlasRef { atlas, texture: tex_ref, color } = atlas_ref;
         let (tex_w, tex_h) = tex_ref.stats().size();
-        let r = self.font_cache.cache_glyphs(glyphs.iter().map(|(_, c)| c), |rect, data| {
+        atlas.cache.cache_glyphs(font_id, all_glyphs.iter().copied(), |rect, data| {
             let rusttype::Point { x, y } = rect.min;
             let width = rect.width();
             let height = rect.height();
             tex_ref.update(data, x as i32, y as i32, width, height);
-        });
-        r.expect("failed to write to font gpu cache");
+        })?;
 
         let (tex_w, tex_h) = (tex_w as f32, tex_h as f32);
 
-        let mut results: Vec<FontStemDrawCall> = Vec::with_capacity(glyphs.len());
-        for (i, glyph) in &glyphs {
-            if let Ok(Some((uv_rect, screen_rect))) = self.font_cache.rect_for(glyph) {
-                let source_crop = (
-                    (uv_rect.min.x * tex_w),
-                    (uv_rect.min.y * tex_h),
-                    (uv_rect.width() * tex_w),
-                    (uv_rect.height() * tex_h), 
-                );
+        let mut words_results = Vec::with_capacity(words_glyphs.len());
+        for glyphs in &words_glyphs {
+            let mut results: Vec<FontStemDrawCall> = Vec::with_capacity(glyphs.len());
+            for (i, glyph) in glyphs {
+                if let (Some(font_bytes), Some(color)) = (&self.color_font_bytes, color.as_mut()) {
+                    // strikes are chosen for the size the glyph is drawn at, not the raster size.
+                    let pixels_per_em = font_size.round().max(1.0) as u16;
+                    if let Some(color_glyph) = color.glyph(self.font_id, font_bytes, glyph.id().0, pixels_per_em) {
+                        let rect = color_glyph.handle.rect;
+                        let position = glyph.position();
+                        let image_scale = font_size / color_glyph.pixels_per_em;
+                        results.push(FontStemDrawCall {
+                            source_crop: (rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32),
+                            dest_origin: Vector2::new(position.x, position.y + ascent) * ratio + color_glyph.offset * image_scale,
+                            dest_size: Vector2::new(rect.width as f32, rect.height as f32) * image_scale,
+                            texture_layer: color_glyph.handle.layer,
+                            character_index: *i,
+                            padding: 0.0,
+                            color: true,
+                        });
+                        continue;
+                    }
+                }
+                if let Some((uv_rect, origin)) = atlas.glyph_rect(font_id, glyph) {
+                    let source_crop = (
+                        (uv_rect.min.x * tex_w),
+                        (uv_rect.min.y * tex_h),
+                        (uv_rect.width() * tex_w),
+                        (uv_rect.height() * tex_h), 
+                    );
+                    results.push(FontStemDrawCall {
+                        source_crop,
+                        dest_origin: Vector2::new(origin.x, origin.y + ascent) * ratio,
+                        dest_size: Vector2::new(source_crop.2, source_crop.3) * ratio,
+                        texture_layer: atlas.texture_layer,
+                        character_index: *i,
+                        padding: atlas.cache.glyph_padding() as f32,
+                        color: false,
+                    });
+                }
+            }
+            words_results.push(results);
+        }
+        Ok(words_results)
+    }
+
+    /// Rasterize `text` on the CPU, in white on a transparent background: returns the RGBA
+    /// pixels and their size. Lines are separated by `\n`, and aligned to the left.
+    ///
+    /// Color glyphs are drawn as their outline, if the font has one. See
+    /// `RenderStorage::bake_text`.
+    pub fn rasterize_text(&self, text: &str, font_size: f32) -> (Vec<u8>, (u32, u32)) {
+        let scale = FontScale::uniform(font_size);
+        let ascent = self.v_metrics(font_size).ascent;
+        let line_height = self.line_height(font_size);
+        let glyphs: Vec<PositionedGlyph<'_>> = text.split('\n').enumerate()
+            .flat_map(|(i, line)| self.font.layout(line, scale, rusttype::point(0.0, ascent + i as f32 * line_height)))
+            .collect();
+
+        // glyphs may overhang the origin (an italic 'j'), or the ascent of the font
+        let line_count = text.split('\n').count() as f32;
+        let (mut min_x, mut min_y) = (0, 0);
+        let (mut max_x, mut max_y) = (0, (line_count * line_height).ceil() as i32);
+        for bounding_box in glyphs.iter().filter_map(PositionedGlyph::pixel_bounding_box) {
+            min_x = min_x.min(bounding_box.min.x);
+            min_y = min_y.min(bounding_box.min.y);
+            max_x = max_x.max(bounding_box.max.x);
+            max_y = max_y.max(bounding_box.max.y);
+        }
+        let width = (max_x - min_x).max(1) as usize;
+        let height = (max_y - min_y).max(1) as usize;
+
+        let mut pixels = vec![0u8; width * height * 4];
+        for glyph in &glyphs {
+            let bounding_box = match glyph.pixel_bounding_box() {
+                Some(bounding_box) => bounding_box,
+                None => continue,
+            };
+            glyph.draw(|x, y, coverage| {
+                let x = (bounding_box.min.x - min_x) as usize + x as usize;
+                let y = (bounding_box.min.y - min_y) as usize + y as usize;
+                let i = (y * width + x) * 4;
+                let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                // glyphs which overlap (kerning, scripts) keep the most opaque of the two
+                pixels[i..i + 3].copy_from_slice(&[255, 255, 255]);
+                pixels[i + 3] = pixels[i + 3].max(alpha);
+            });
+        }
+        (pixels, (width as u32, height as u32))
+    }
+
+    /// Same as `word_to_draw_call`, but the glyphs come from the MSDF atlas, in the **RGBA**
+    /// texture array. They must be drawn with a shader using `msdf::MSDF_GLSL`.
+    ///
+    /// Glyphs are converted to MSDF the first time they are seen, whatever the font size.
+    pub fn word_to_draw_call_msdf(&self, msdf_ref: &mut MsdfAtlasRef<'_>, text: &str, font_size: f32) -> Vec<FontStemDrawCall> {
+        let scale = FontScale::uniform(font_size);
+        let ratio = font_size / MSDF_GLYPH_SIZE;
+        let ascent = self.font().v_metrics(scale).ascent;
+
+        let mut results: Vec<FontStemDrawCall> = Vec::new();
+        for (i, glyph) in self.font.layout(text, scale, rusttype::point(0.0, 0.0)).enumerate() {
+            let msdf_glyph = self.font.glyph(glyph.id()).scaled(FontScale::uniform(MSDF_GLYPH_SIZE));
+            if let Some(msdf_glyph_info) = msdf_ref.glyph(self.font_id, &msdf_glyph) {
+                let rect = msdf_glyph_info.handle.rect;
+                let position = glyph.position();
                 results.push(FontStemDrawCall {
-                    source_crop,
-                    dest_origin: Vector2::new(screen_rect.min.x as f32, screen_rect.min.y as f32 + ascent),
-                    texture_layer: self.texture_layer,
-                    character_index: *i,
+                    source_crop: (rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32),
+                    dest_origin: Vector2::new(position.x, position.y + ascent) + msdf_glyph_info.offset * ratio,
+                    dest_size: Vector2::new(rect.width as f32, rect.height as f32) * ratio,
+                    texture_layer: msdf_glyph_info.handle.layer,
+                    character_index: i,
+                    padding: 0.0,
+                    color: false,
                 });
             }
         }
@@ -0,0 +1,189 @@
+use rusttype::{point, Point, PositionedGlyph, Rect};
+
+use crate::backend::gl;
+use crate::debug::GlyphCacheStats;
+use crate::gl_utils::gl_get_int;
+use crate::render_storage::font::Cache as FontCache;
+use crate::render_storage::font::color_glyphs::ColorGlyphAtlasRef;
+use crate::render_storage::texture::{TextureArrayLayer, TextureArrayLayerRef, TextureLayerStats};
+
+/// A glyph cache shared by all the fonts of a `RenderStorage`, backed by a single grayscale layer.
+///
+/// Glyphs are keyed by (font, glyph id, scale, subpixel offset). When the layer is full, the
+/// glyphs which were not used for the longest time are evicted, whichever font they belong to.
+///
+/// When the glyphs of a single word don't fit even in an empty atlas, the atlas grows up to
+/// `set_max_size` if it can, and the glyphs are rasterized at a smaller size otherwise: see
+/// `FontRenderer::word_to_draw_call`.
+///
+/// By default glyphs are placed on whole pixels, see `set_subpixel_positioning` for slowly moving
+/// text.
+pub struct GlyphAtlas {
+    pub (crate) cache: FontCache,
+    pub (crate) texture_layer: TextureArrayLayer,
+    /// the atlas doesn't grow past this size, in pixels
+    pub (crate) max_size: u32,
+    /// see `set_subpixel_positioning`
+    pub (crate) subpixel: bool,
+}
+
+/// Subpixel offsets glyphs are rasterized at with `GlyphAtlas::set_subpixel_positioning`: a
+/// quarter of a pixel. Offsets are ignored otherwise.
+pub const SUBPIXEL_POSITION_TOLERANCE: f32 = 0.25;
+
+impl GlyphAtlas {
+    pub fn new(texture_layer: TextureArrayLayer, width: u32, height: u32) -> GlyphAtlas {
+        GlyphAtlas {
+            cache: FontCache::builder()
+                .dimensions(width, height)
+                .pad_glyphs(true)
+                .align_4x4(true)
+                .position_tolerance(1.0)
+                .scale_tolerance(0.5)
+                .build(),
+            texture_layer,
+            max_size: width.max(height),
+            subpixel: false,
+        }
+    }
+
+    #[inline]
+    pub fn texture_layer(&self) -> TextureArrayLayer {
+        self.texture_layer
+    }
+
+    /// Number of empty pixels around every glyph in the layer, 1 by default.
+    #[inline]
+    pub fn glyph_padding(&self) -> u32 {
+        self.cache.glyph_padding()
+    }
+
+    /// Rasterize glyphs with `padding` empty pixels on every side, enough room for the outline,
+    /// shadow or glow of `TextEffects::required_padding`. Clears the atlas.
+    pub fn set_glyph_padding(&mut self, padding: u32) {
+        if padding != self.cache.glyph_padding() {
+            self.cache.to_builder().glyph_padding(padding).rebuild(&mut self.cache);
+        }
+    }
+
+    /// Keep the fractional positions of the glyphs in the draw calls instead of rounding them to
+    /// whole pixels, so that slowly scrolling text glides instead of jittering. Glyphs are also
+    /// rasterized at every `SUBPIXEL_POSITION_TOLERANCE` of a pixel, which takes more room in the
+    /// atlas. Clears the atlas.
+    ///
+    /// Glyphs drawn between pixels need linear filtering: `RenderStorage::set_subpixel_text`
+    /// enables both.
+    pub fn set_subpixel_positioning(&mut self, subpixel: bool) {
+        if subpixel != self.subpixel {
+            let position_tolerance = if subpixel { SUBPIXEL_POSITION_TOLERANCE } else { 1.0 };
+            self.cache.to_builder().position_tolerance(position_tolerance).rebuild(&mut self.cache);
+            self.subpixel = subpixel;
+        }
+    }
+
+    #[inline]
+    pub fn subpixel_positioning(&self) -> bool {
+        self.subpixel
+    }
+
+    /// The texture coordinates of a cached glyph, and where to draw its top-left corner: rounded
+    /// to a whole pixel unless `set_subpixel_positioning`. None if the glyph is empty or isn't
+    /// cached.
+    pub (crate) fn glyph_rect(&self, font_id: usize, glyph: &PositionedGlyph<'_>) -> Option<(Rect<f32>, Point<f32>)> {
+        let (uv_rect, screen_rect) = self.cache.rect_for_unrounded(font_id, glyph).ok()??;
+        let origin = if self.subpixel {
+            screen_rect.min
+        } else {
+            point(screen_rect.min.x.round(), screen_rect.min.y.round())
+        };
+        Some((uv_rect, origin))
+    }
+
+    /// Rasterize the new glyphs of a word on the rayon thread pool, which helps with big CJK
+    /// paragraphs at large sizes. Uploads stay on the thread of the GL context. Enabled by
+    /// default, only has an effect with the `rayon` feature.
+    pub fn set_multithread(&mut self, multithread: bool) {
+        self.cache.set_multithread(multithread);
+    }
+
+    /// Let the atlas grow up to `max_size` x `max_size` pixels (and `GL_MAX_TEXTURE_SIZE`) when
+    /// the glyphs of a word don't fit, instead of rasterizing them at a smaller size. By default
+    /// the atlas never grows.
+    ///
+    /// Growing reallocates the whole grayscale array with bigger layers, and clears the atlas:
+    /// the draw calls returned before are not valid anymore, and crops must be normalized with
+    /// the new `RenderStorage::get_max_dims`.
+    pub fn set_max_size(&mut self, max_size: u32) {
+        self.max_size = max_size;
+    }
+
+    pub fn max_size(&self) -> u32 {
+        self.max_size
+    }
+
+    pub fn stats(&self) -> GlyphCacheStats {
+        GlyphCacheStats {
+            glyphs: self.cache.glyph_count(),
+            occupancy: self.cache.occupancy(),
+            size: self.cache.dimensions(),
+        }
+    }
+
+    /// Remove every glyph from the atlas. They will be rasterized again the next time they're drawn.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+/// A `GlyphAtlas` along with the texture layer it writes to, given by `RenderStorage::get_font_with_texture`.
+pub struct GlyphAtlasRef<'a> {
+    pub (crate) atlas: &'a mut GlyphAtlas,
+    pub (crate) texture: TextureArrayLayerRef<'a>,
+    /// where the glyphs of color fonts go, if any
+    pub (crate) color: Option<ColorGlyphAtlasRef<'a>>,
+}
+
+impl<'a> GlyphAtlasRef<'a> {
+    /// Without color glyphs: the glyphs of color fonts are skipped.
+    pub fn new(atlas: &'a mut GlyphAtlas, texture: TextureArrayLayerRef<'a>) -> GlyphAtlasRef<'a> {
+        GlyphAtlasRef {
+            atlas,
+            texture,
+            color: None,
+        }
+    }
+
+    pub fn with_color_glyphs(mut self, color: ColorGlyphAtlasRef<'a>) -> GlyphAtlasRef<'a> {
+        self.color = Some(color);
+        self
+    }
+
+    /// Occupancy of the atlas, to know when text is about to be evicted or shrunk.
+    pub fn stats(&self) -> GlyphCacheStats {
+        self.atlas.stats()
+    }
+
+    /// Double the size of the atlas, up to `GlyphAtlas::set_max_size`. Returns false if it is
+    /// already as big as it can be.
+    pub fn grow(&mut self) -> bool {
+        grow_glyph_atlas(self.atlas, &mut self.texture)
+    }
+}
+
+pub (crate) fn grow_glyph_atlas(atlas: &mut GlyphAtlas, texture: &mut TextureArrayLayerRef<'_>) -> bool {
+    let (width, height) = atlas.cache.dimensions();
+    let max_size = atlas.max_size.min(gl_get_int(gl::MAX_TEXTURE_SIZE).max(0) as u32);
+    let (new_width, new_height) = ((width * 2).min(max_size).max(width), (height * 2).min(max_size).max(height));
+    if (new_width, new_height) == (width, height) {
+        return false;
+    }
+    log::info!("glyph atlas is full, growing it from {}x{} to {}x{}", width, height, new_width, new_height);
+    let texture_array = &mut *texture.texture_array;
+    if let Err(e) = texture_array.grow_layers(texture_array.max_width.max(new_width), texture_array.max_height.max(new_height)) {
+        log::error!("could not grow the glyph atlas: {}", e);
+        return false;
+    }
+    texture_array.stats[texture.layer as usize] = TextureLayerStats::new(new_width, new_height);
+    atlas.cache.to_builder().dimensions(new_width, new_height).rebuild(&mut atlas.cache);
+    true
+}
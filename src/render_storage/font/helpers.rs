@@ -1,10 +1,45 @@
 //! Various functions and structs which will help you use this crate to its maximum.
 
-use rusttype::{Font, Scale as FontScale};
+use std::borrow::Cow;
+
+use rusttype::{Font, Scale as FontScale, VMetrics};
 use cgmath::Vector2;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_linebreak::linebreaks;
 
 use smallvec::SmallVec;
 
+use crate::error::SprowlError;
+use crate::render_storage::RenderStorage;
+use crate::render_storage::texture::TextureArrayLayer;
+use super::{FontStemDrawCall, RichText};
+
+/// What `AdvancedLayout` needs to know about a font, in pixels: implemented by the fonts of
+/// rusttype, and by `BitmapFont`.
+pub trait LayoutFont {
+    fn v_metrics(&self, scale: FontScale) -> VMetrics;
+
+    /// Horizontal distance from the origin of `c` to the origin of the next character.
+    fn advance_width(&self, scale: FontScale, c: char) -> f32;
+
+    /// Added to `advance_width` when `second` comes right after `first`, usually negative.
+    fn pair_kerning(&self, scale: FontScale, first: char, second: char) -> f32;
+}
+
+impl LayoutFont for Font<'static> {
+    fn v_metrics(&self, scale: FontScale) -> VMetrics {
+        Font::v_metrics(self, scale)
+    }
+
+    fn advance_width(&self, scale: FontScale, c: char) -> f32 {
+        self.glyph(c).scaled(scale).h_metrics().advance_width
+    }
+
+    fn pair_kerning(&self, scale: FontScale, first: char, second: char) -> f32 {
+        Font::pair_kerning(self, scale, first, second)
+    }
+}
+
 pub trait AdvancedText<'t> {
     type E;
 
@@ -25,17 +60,206 @@ impl<'t> AdvancedText<'t> for &'t str {
     }
 }
 
+/// Character to put in a text where an `InlineImage` should be inserted.
+///
+/// This is the unicode "OBJECT REPLACEMENT CHARACTER".
+pub const INLINE_IMAGE_CHAR: char = '\u{FFFC}';
+
+/// Whitespace between words, whose width is not part of any word. No-break spaces are part of the
+/// words they join.
+fn is_word_separator(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{A0}' | '\u{2007}' | '\u{202F}')
+}
+
+/// An image laid out within a text, like a button icon in "Press [A] to continue".
+///
+/// It replaces an `INLINE_IMAGE_CHAR` in the text, and is laid out as a word of its own: it may
+/// wrap to the next line apart from the words next to it, but is never split itself.
+#[derive(Debug, Clone, Copy)]
+pub struct InlineImage {
+    /// Layer of the RGBA texture array to draw.
+    pub texture: TextureArrayLayer,
+    /// Part of the layer to draw, in pixels: (x, y, w, h).
+    pub crop: (u32, u32, u32, u32),
+    /// Horizontal space taken by the image in the line, in pixels.
+    pub advance: f32,
+    /// Height of the image in pixels. The bottom of the image sits on the baseline.
+    pub height: f32,
+}
+
+/// Base direction of the paragraphs of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextDirection {
+    /// Guessed for every paragraph from its first strong character: right-to-left for arabic or
+    /// hebrew, left-to-right for latin, ...
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+impl TextDirection {
+    fn paragraph_level(self) -> Option<Level> {
+        match self {
+            TextDirection::Auto => None,
+            TextDirection::LeftToRight => Some(Level::ltr()),
+            TextDirection::RightToLeft => Some(Level::rtl()),
+        }
+    }
+}
+
+/// How the last line of a truncated `AdvancedLayout` ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextOverflow {
+    /// Words are kept as they are, even if they go past `max_width`.
+    #[default]
+    Clip,
+    /// The end of the line is replaced by "…", cutting the last word if needed.
+    ///
+    /// The ellipsis is a `WordPos` like the others, in the font of the last word. It is always put
+    /// on the right of the line.
+    Ellipsis,
+    /// Words are kept as they are, and `AdvancedLayout::fade_index` tells which one to fade out.
+    Fade,
+}
+
+/// What the y of the start of an `AdvancedLayout` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlign {
+    /// The top of the first line.
+    #[default]
+    Top,
+    /// The middle of the text, between the top of the first line and the bottom of the last one:
+    /// for a label centered in a button for instance.
+    Middle,
+    /// The bottom of the last line, descenders included.
+    Bottom,
+    /// The baseline of the first line, to align text to a baseline grid.
+    Baseline,
+}
+
+/// Optional settings of an `AdvancedLayout`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LayoutOptions<'i> {
+    /// Images replacing every `INLINE_IMAGE_CHAR` of the text, in order.
+    ///
+    /// If there are more `INLINE_IMAGE_CHAR` than images, the remaining ones are treated as
+    /// regular characters.
+    pub inline_images: &'i [InlineImage],
+    /// Base direction of the text. Words are reordered within their line with the unicode
+    /// bidirectional algorithm, so mixed-direction paragraphs are fine whatever this is.
+    ///
+    /// The alignment is not changed: right-to-left text usually wants to be aligned on the right.
+    pub base_direction: TextDirection,
+    /// Lines after this one are dropped, and the last one overflows according to `overflow`.
+    pub max_lines: Option<usize>,
+    /// What to do with the last line when lines are dropped, or when it's wider than `max_width`
+    /// (a single word too long to fit).
+    pub overflow: TextOverflow,
+    /// Where the text is placed relative to the y of `start`.
+    pub vertical_align: VerticalAlign,
+}
+
+impl<'i> LayoutOptions<'i> {
+    pub fn with_inline_images(mut self, inline_images: &'i [InlineImage]) -> Self {
+        self.inline_images = inline_images;
+        self
+    }
+
+    pub fn with_base_direction(mut self, base_direction: TextDirection) -> Self {
+        self.base_direction = base_direction;
+        self
+    }
+
+    pub fn with_max_lines(mut self, max_lines: usize, overflow: TextOverflow) -> Self {
+        self.max_lines = Some(max_lines);
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn with_vertical_align(mut self, vertical_align: VerticalAlign) -> Self {
+        self.vertical_align = vertical_align;
+        self
+    }
+}
+
+/// A part of the text laid out with the same font and size: the whole text for `new_str`, or a
+/// span of a `RichText`.
+#[derive(Clone)]
+pub (crate) struct LayoutRun<'f, 't> {
+    pub (crate) text: &'t str,
+    pub (crate) font: &'f dyn LayoutFont,
+    pub (crate) scale: FontScale,
+    /// index of the span in the `RichText`
+    pub (crate) span_index: usize,
+}
+
 #[derive(Clone)]
 pub struct AdvancedLayout<'f, 't, T: AdvancedText<'t>> {
-    pub (crate) font: &'f Font<'static>,
+    pub (crate) runs: SmallVec<[LayoutRun<'f, 't>; 1]>,
     pub (crate) original_str: T,
-    pub (crate) scale: FontScale,
     pub (crate) start: Vector2<f32>,
     /// 0 = center, -1 = left, 1 = right
     pub (crate) align: i8,
     pub (crate) max_width: u32,
 
     layout: SmallVec<[WordPos<'t>; 16]>,
+    /// index in `layout` of the first word of every line
+    lines: SmallVec<[usize; 4]>,
+    /// y of the bottom of every line, descenders included
+    line_bottoms: SmallVec<[f32; 4]>,
+    /// y of the top of the first line, and of its baseline
+    top: f32,
+    first_baseline: f32,
+    /// number of words placed so far, pieces of a word count as one
+    word_count: usize,
+    truncated: bool,
+    fade_index: Option<usize>,
+}
+
+/// The line currently being laid out.
+struct Line {
+    /// index in `layout` of the first word of the line
+    first_word_index: usize,
+    top: f32,
+    /// biggest metrics of the fonts of the line
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+    /// distance between the top of the words of the line and the baseline, in the same order as `layout`.
+    word_ascents: SmallVec<[f32; 16]>,
+}
+
+impl Line {
+    fn new(first_word_index: usize, top: f32, v_metrics: VMetrics) -> Line {
+        Line {
+            first_word_index,
+            top,
+            ascent: v_metrics.ascent,
+            descent: v_metrics.descent,
+            line_gap: v_metrics.line_gap,
+            word_ascents: SmallVec::new(),
+        }
+    }
+
+    fn fit_font(&mut self, v_metrics: VMetrics) {
+        self.ascent = self.ascent.max(v_metrics.ascent);
+        self.descent = self.descent.min(v_metrics.descent);
+        self.line_gap = self.line_gap.max(v_metrics.line_gap);
+    }
+
+    fn height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+/// A word, or the part of a word in a single span, waiting to be placed.
+struct WordPiece<'t> {
+    word: WordPos<'t>,
+    /// distance between the top of the word and the baseline
+    ascent: f32,
+    /// metrics of the font of the word, none for inline images
+    v_metrics: Option<VMetrics>,
 }
 
 impl<'a, 't> AdvancedLayout<'a, 't, &'t str> {
@@ -47,20 +271,77 @@ impl<'a, 't> AdvancedLayout<'a, 't, &'t str> {
     /// align < 0 => left
     /// align == 0 => center
     /// align > 0 => right
-    pub fn new_str(font: &'a Font<'static>, t: &'t str, size: f32, start: Vector2<f32>, align: i8, max_width: u32) -> AdvancedLayout<'a, 't, &'t str> {
-        let mut l = AdvancedLayout {
+    pub fn new_str(font: &'a dyn LayoutFont, t: &'t str, size: f32, start: Vector2<f32>, align: i8, max_width: u32) -> AdvancedLayout<'a, 't, &'t str> {
+        Self::new_str_with_options(font, t, size, start, align, max_width, &LayoutOptions::default())
+    }
+
+    /// Same as `new_str`, but with additional options such as inline images.
+    pub fn new_str_with_options(font: &'a dyn LayoutFont, t: &'t str, size: f32, start: Vector2<f32>, align: i8, max_width: u32, options: &LayoutOptions<'_>) -> AdvancedLayout<'a, 't, &'t str> {
+        let run = LayoutRun {
+            text: t,
             font,
-            original_str: t,
             scale: FontScale::uniform(size),
+            span_index: 0,
+        };
+        let mut l = AdvancedLayout {
+            runs: smallvec::smallvec![run],
+            original_str: t,
             start,
             align,
             max_width,
             layout: Default::default(),
+            lines: Default::default(),
+            line_bottoms: Default::default(),
+            top: start.y,
+            first_baseline: start.y,
+            word_count: 0,
+            truncated: false,
+            fade_index: None,
         };
-        l.compute();
+        l.compute(t, options);
         l
     }
+}
+
+impl<'a, 't> AdvancedLayout<'a, 't, &'t RichText<'t>> {
+    /// Same as `new_str_with_options`, but every span of the text has its own font and size.
+    ///
+    /// Spans are laid out as a single paragraph: a word split between two spans (like
+    /// "**bold**ly") stays on one line. The span of every word is given by `WordPos::span_index`,
+    /// see `RenderStorage::rich_word_to_draw_call` to draw it.
+    ///
+    /// Returns an error if the font of a span doesn't exist.
+    pub fn new_rich(render_storage: &'a RenderStorage, t: &'t RichText<'t>, start: Vector2<f32>, align: i8, max_width: u32, options: &LayoutOptions<'_>) -> Result<AdvancedLayout<'a, 't, &'t RichText<'t>>, SprowlError> {
+        let runs = t.spans().iter().enumerate().map(|(span_index, span)| {
+            let font = render_storage.layout_font(span.font_id).ok_or(SprowlError::MissingFontId(span.font_id))?;
+            Ok(LayoutRun {
+                text: span.text,
+                font,
+                scale: FontScale::uniform(span.font_size),
+                span_index,
+            })
+        }).collect::<Result<SmallVec<_>, SprowlError>>()?;
+        let mut l = AdvancedLayout {
+            runs,
+            original_str: t,
+            start,
+            align,
+            max_width,
+            layout: Default::default(),
+            lines: Default::default(),
+            line_bottoms: Default::default(),
+            top: start.y,
+            first_baseline: start.y,
+            word_count: 0,
+            truncated: false,
+            fade_index: None,
+        };
+        l.compute(&t.to_plain_string(), options);
+        Ok(l)
+    }
+}
 
+impl<'a, 't, T: AdvancedText<'t>> AdvancedLayout<'a, 't, T> {
     fn line_size(&self, beg_line_word_index: usize, last_index: Option<usize>) -> f32 {
         let first_of_line = if let Some(word) = self.layout.get(beg_line_word_index) {
             word
@@ -76,9 +357,69 @@ impl<'a, 't> AdvancedLayout<'a, 't, &'t str> {
         (last_of_line.size.x + last_of_line.origin.x) - first_of_line.origin.x
     }
 
+    /// Reorders the words of the line from their logical order to the order they should be
+    /// displayed in, following their bidi levels (rule L2 of the unicode bidi algorithm).
+    fn reorder_line(&mut self, first_line_word_index: usize) {
+        let line = &mut self.layout[first_line_word_index..];
+        let min_odd_level = line.iter().map(|word| word.bidi_level).filter(|level| level % 2 == 1).min();
+        let max_level = line.iter().map(|word| word.bidi_level).max();
+        let (min_odd_level, max_level) = match (min_odd_level, max_level) {
+            (Some(min_odd_level), Some(max_level)) => (min_odd_level, max_level),
+            // only left-to-right words, nothing to do
+            _ => return,
+        };
+        // space between logically consecutive words, re-used in the same order between the
+        // visually consecutive ones.
+        let gaps: SmallVec<[f32; 16]> = line.windows(2)
+            .map(|w| w[1].origin.x - (w[0].origin.x + w[0].size.x))
+            .collect();
+        let line_start = line[0].origin.x;
+
+        // from the highest level to the lowest odd one, reverse every run of words at this
+        // level or higher.
+        for level in (min_odd_level..=max_level).rev() {
+            let mut i = 0;
+            while i < line.len() {
+                if line[i].bidi_level < level {
+                    i += 1;
+                    continue;
+                }
+                let run_start = i;
+                while i < line.len() && line[i].bidi_level >= level {
+                    i += 1;
+                }
+                line[run_start..i].reverse();
+            }
+        }
+
+        let mut x = line_start;
+        for (i, word) in line.iter_mut().enumerate() {
+            word.origin.x = x;
+            x += word.size.x + gaps.get(i).cloned().unwrap_or(0.0);
+        }
+    }
+
+    /// Puts the words of the line on a common baseline, reorders and realigns them, and starts
+    /// a new line below, whose font is initially `v_metrics`.
+    fn end_line(&mut self, line: &mut Line, pen_x: &mut f32, v_metrics: VMetrics) {
+        let baseline = line.top + line.ascent;
+        for (word, ascent) in self.layout[line.first_word_index..].iter_mut().zip(&line.word_ascents) {
+            word.origin.y = baseline - ascent;
+        }
+        self.reorder_line(line.first_word_index);
+        self.realign(line.first_word_index, None);
+        if self.lines.is_empty() {
+            self.first_baseline = baseline;
+        }
+        self.lines.push(line.first_word_index);
+        self.line_bottoms.push(baseline - line.descent);
+        *line = Line::new(self.layout.len(), line.top + line.height(), v_metrics);
+        *pen_x = self.start.x;
+    }
+
     fn realign(&mut self, first_line_word_index: usize, last_index: Option<usize>) {
-        if self.align < 0 {
-            // no need to do that if it's aligned on the left
+        if self.align < 0 || first_line_word_index >= self.layout.len() {
+            // no need to do that if it's aligned on the left, or if the line is empty
             return;
         }
         let line_size = self.line_size(first_line_word_index, last_index);
@@ -96,106 +437,303 @@ impl<'a, 't> AdvancedLayout<'a, 't, &'t str> {
         }
     }
 
-    fn compute(&mut self) {
-        let mut char_indices = self.original_str.char_indices();
+    /// Place the pieces of a word (or an inline image) at `pen_x`, or on the next line if they
+    /// don't fit in the current one.
+    fn place_word(&mut self, pieces: &mut SmallVec<[WordPiece<'t>; 2]>, line: &mut Line, pen_x: &mut f32, v_metrics: VMetrics) {
+        if pieces.is_empty() {
+            return;
+        }
+        let width: f32 = pieces.iter().map(|piece| piece.word.size.x).sum();
+        let words_in_line = self.layout.len() - line.first_word_index;
+        if words_in_line > 0 {
+            let line_start = self.layout[line.first_word_index].origin.x;
+            if *pen_x + width - line_start >= self.max_width as f32 {
+                // the word is too big to fit on the current line
+                self.end_line(line, pen_x, v_metrics);
+            }
+        }
+        let word_index = self.word_count;
+        self.word_count += 1;
+        for WordPiece { mut word, ascent, v_metrics } in pieces.drain(..) {
+            // the final y is only known once the whole line is there, see `end_line`.
+            word.origin = Vector2::new(*pen_x, line.top);
+            word.line_index = self.lines.len();
+            word.word_index = word_index;
+            *pen_x += word.size.x;
+            if let Some(v_metrics) = v_metrics {
+                line.fit_font(v_metrics);
+            }
+            line.word_ascents.push(ascent);
+            self.layout.push(word);
+        }
+    }
+
+    /// `bidi_text` is the text of all the runs, one after the other.
+    ///
+    /// Lines wrap where the unicode line breaking algorithm (UAX #14) allows it: between words,
+    /// but also between CJK characters or after a hyphen, and never at a no-break space. The
+    /// pieces between two break opportunities are the words of the layout.
+    fn compute(&mut self, bidi_text: &str, options: &LayoutOptions<'_>) {
+        let runs = self.runs.clone();
+        let mut v_metrics = match runs.first() {
+            Some(run) => run.font.v_metrics(run.scale),
+            None => return,
+        };
 
-        let v_metrics = self.font.v_metrics(self.scale);
-        let character_height = v_metrics.ascent - v_metrics.descent;
+        let mut inline_images = options.inline_images.iter();
 
-        // the index of the word in `layout` at the beginning of the line.
-        // used to realign stuff.
-        let mut beginning_line_word_index = 0;
+        // levels of every byte of the text: even for left-to-right, odd for right-to-left.
+        let bidi_info = BidiInfo::new(bidi_text, options.base_direction.paragraph_level());
+        // offsets in `bidi_text` before which a line can wrap, in increasing order.
+        let mut break_opportunities = linebreaks(bidi_text).map(|(offset, _)| offset).peekable();
 
-        let mut current_word_boundaries: Option<(usize, usize)> = None;
-        let mut origin = self.start;
-        let mut size = Vector2::new(0.0, character_height);
-        let mut last_char = None;
+        let mut line = Line::new(0, self.start.y, v_metrics);
+        let mut pen_x = self.start.x;
+        // the current word, split in several pieces when it spans several runs
+        let mut pieces: SmallVec<[WordPiece<'t>; 2]> = SmallVec::new();
 
-        while let Some((i, c)) = char_indices.next() {
-            let g = self.font.glyph(c).scaled(self.scale);
+        let mut run_offset = 0;
+        let mut char_count = 0;
+        for run in &runs {
+            let text = run.text;
+            v_metrics = run.font.v_metrics(run.scale);
+            let character_height = v_metrics.ascent - v_metrics.descent;
+            let bidi_level = |i: usize| bidi_info.levels[run_offset + i].number();
+            let text_piece = |beg: usize, end: usize, width: f32, first_char: usize| WordPiece {
+                word: WordPos {
+                    span_index: run.span_index,
+                    char_offset: first_char,
+                    ..WordPos::new(&text[beg..end], Vector2::new(width, character_height), bidi_level(beg))
+                },
+                ascent: v_metrics.ascent,
+                v_metrics: Some(v_metrics),
+            };
 
-            let words_in_line = self.layout.len() - beginning_line_word_index;
+            // (begin, end, width, index of its first char in the whole text) of the word currently
+            // being read
+            let mut current_word: Option<(usize, usize, f32, usize)> = None;
+            let mut last_char = None;
 
-            let pair_kerning = last_char
-                .map(|prev_char| self.font.pair_kerning(self.scale, prev_char, c))
-                .unwrap_or(0.0);
-            match (current_word_boundaries, c.is_whitespace()) {
-                (Some((beg, end)), true) => {
-                    self.layout.push(WordPos {
-                        word: &self.original_str[beg..end],
-                        origin,
-                        size,
-                    });
-                    current_word_boundaries = None;
-                    if self.line_size(beginning_line_word_index, None) >= self.max_width as f32 && words_in_line > 0 {
-                        origin = Vector2::new( self.start.x, origin.y + character_height + v_metrics.line_gap);
-                        self.layout.last_mut().unwrap().origin = origin;
+            for (i, c) in text.char_indices() {
+                let char_index = char_count;
+                char_count += 1;
+                if c == INLINE_IMAGE_CHAR {
+                    if let Some(image) = inline_images.next() {
+                        if let Some((beg, end, width, first_char)) = current_word.take() {
+                            pieces.push(text_piece(beg, end, width, first_char));
+                        }
+                        self.place_word(&mut pieces, &mut line, &mut pen_x, v_metrics);
+                        pieces.push(WordPiece {
+                            word: WordPos {
+                                inline_image: Some(*image),
+                                span_index: run.span_index,
+                                char_offset: char_index,
+                                ..WordPos::new(&text[i..i + c.len_utf8()], Vector2::new(image.advance, image.height), bidi_level(i))
+                            },
+                            ascent: image.height,
+                            v_metrics: None,
+                        });
+                        self.place_word(&mut pieces, &mut line, &mut pen_x, v_metrics);
+                        last_char = None;
+                        continue;
+                    }
+                }
+
+                let pair_kerning = last_char
+                    .map(|prev_char| run.font.pair_kerning(run.scale, prev_char, c))
+                    .unwrap_or(0.0);
+                let advance = run.font.advance_width(run.scale, c) + pair_kerning;
+                last_char = Some(c);
 
-                        origin.x += size.x;
-                        // len() - 2 is valid because we checked earlier that there were at least 1 word (before the insert)
-                        self.realign(beginning_line_word_index, Some(self.layout.len() - 2));
-                        beginning_line_word_index = self.layout.len() - 1;
-                        size.x = 0.0;
+                while break_opportunities.next_if(|offset| *offset < run_offset + i).is_some() {}
+                if break_opportunities.peek() == Some(&(run_offset + i)) {
+                    // the line can wrap before `c`, even without whitespace: end the current word.
+                    if let Some((beg, end, width, first_char)) = current_word.take() {
+                        pieces.push(text_piece(beg, end, width, first_char));
                     }
-                    if c == '\n' {
-                        // newline
-                        origin.x = self.start.x;
-                        origin.y += character_height + v_metrics.line_gap;
-                        self.realign(beginning_line_word_index, None);
-                        beginning_line_word_index = self.layout.len();
-                    } else {
-                        origin.x += size.x + g.h_metrics().advance_width + pair_kerning;
+                    self.place_word(&mut pieces, &mut line, &mut pen_x, v_metrics);
+                }
+
+                if is_word_separator(c) {
+                    if let Some((beg, end, width, first_char)) = current_word.take() {
+                        pieces.push(text_piece(beg, end, width, first_char));
                     }
-                    size.x = 0.0;
-                },
-                (None, true) => {
+                    self.place_word(&mut pieces, &mut line, &mut pen_x, v_metrics);
                     if c == '\n' {
                         // newline
-                        origin.x = self.start.x;
-                        origin.y += character_height + v_metrics.line_gap;
-                        self.realign(beginning_line_word_index, None);
-                        beginning_line_word_index = self.layout.len();
+                        self.end_line(&mut line, &mut pen_x, v_metrics);
                     } else {
-                        origin.x += g.h_metrics().advance_width + pair_kerning;
+                        pen_x += advance;
                     }
-                },
-                (Some((beg, end)), false) => {
-                    current_word_boundaries = Some((beg, end + c.len_utf8()));
-                    size.x += g.h_metrics().advance_width + pair_kerning;
-                },
-                (None, false) => {
-                    current_word_boundaries = Some((i, i + c.len_utf8()));
-                    size.x += g.h_metrics().advance_width + pair_kerning;
+                } else {
+                    current_word = match current_word {
+                        Some((beg, end, width, first_char)) => Some((beg, end + c.len_utf8(), width + advance, first_char)),
+                        None => Some((i, i + c.len_utf8(), advance, char_index)),
+                    };
                 }
-            };
-            last_char = Some(c);
-        }
-        if let Some((beg, end)) = current_word_boundaries {
-            self.layout.push(WordPos {
-                word: &self.original_str[beg..end],
-                origin,
-                size,
-            });
-        }
-        let words_in_line = self.layout.len() - beginning_line_word_index;
-        if self.line_size(beginning_line_word_index, None) >= self.max_width as f32 && words_in_line >= 2 {
-            // last word is too big to fit on current line
-            origin = Vector2::new( self.start.x, origin.y + character_height + v_metrics.line_gap);
-            self.layout.last_mut().unwrap().origin = origin;
-
-            // align 2nd last line
-            self.realign(beginning_line_word_index, Some(self.layout.len() - 2));
-
-            // align 1st last line
-            self.realign(self.layout.len() - 1, None);
-        } else {
-            self.realign(beginning_line_word_index, None);
+            }
+            // the word may continue in the next run, it is only placed at the next break opportunity.
+            if let Some((beg, end, width, first_char)) = current_word {
+                pieces.push(text_piece(beg, end, width, first_char));
+            }
+            run_offset += text.len();
         }
+        self.place_word(&mut pieces, &mut line, &mut pen_x, v_metrics);
+        self.end_line(&mut line, &mut pen_x, v_metrics);
+        self.truncate(options.max_lines, options.overflow);
+        self.align_vertically(options.vertical_align);
+    }
+
+    /// Moves the whole text so that `start.y` is where `vertical_align` says.
+    fn align_vertically(&mut self, vertical_align: VerticalAlign) {
+        let offset = match vertical_align {
+            VerticalAlign::Top => return,
+            VerticalAlign::Middle => -self.height() / 2.0,
+            VerticalAlign::Bottom => -self.height(),
+            VerticalAlign::Baseline => self.start.y - self.first_baseline,
+        };
+        for word in &mut self.layout {
+            word.origin.y += offset;
+        }
+        for bottom in &mut self.line_bottoms {
+            *bottom += offset;
+        }
+        self.top += offset;
+        self.first_baseline += offset;
+    }
+
+    /// Drops the lines after `max_lines`, and ends the last line according to `overflow` if it
+    /// overflows.
+    fn truncate(&mut self, max_lines: Option<usize>, overflow: TextOverflow) {
+        if let Some(max_lines) = max_lines {
+            if self.lines.len() > max_lines {
+                let first_dropped = self.lines[max_lines];
+                self.layout.truncate(first_dropped);
+                self.lines.truncate(max_lines);
+                self.line_bottoms.truncate(max_lines);
+                self.truncated = true;
+            }
+        }
+        let last_line = match self.lines.last() {
+            Some(last_line) => *last_line,
+            None => return,
+        };
+        if !self.truncated && self.line_size(last_line, None) <= self.max_width as f32 {
+            return;
+        }
+        self.truncated = true;
+        match overflow {
+            TextOverflow::Clip => {},
+            TextOverflow::Fade => self.fade_index = self.layout.len().checked_sub(1),
+            TextOverflow::Ellipsis => self.add_ellipsis(last_line),
+        }
+    }
+
+    /// Removes what doesn't fit at the end of the last line to put an ellipsis instead.
+    fn add_ellipsis(&mut self, first_line_word_index: usize) {
+        const ELLIPSIS: &str = "\u{2026}";
+        let (y, height, span_index) = match self.layout[first_line_word_index..].iter().rev().find(|word| word.inline_image.is_none()) {
+            Some(word) => (word.origin.y, word.size.y, word.span_index),
+            // no text in this line (or an empty line), nothing to cut.
+            None => return,
+        };
+        let run = &self.runs[span_index];
+        let (font, scale) = (run.font, run.scale);
+        let ellipsis_width = font.advance_width(scale, '\u{2026}');
+
+        // realigned at the end, go back to the left
+        let shift = self.start.x - self.layout[first_line_word_index].origin.x;
+        for word in &mut self.layout[first_line_word_index..] {
+            word.origin.x += shift;
+        }
+
+        let max_x = self.start.x + self.max_width as f32 - ellipsis_width;
+        while self.layout.len() > first_line_word_index {
+            let word = self.layout.last_mut().unwrap();
+            if word.origin.x + word.size.x <= max_x {
+                break;
+            }
+            if word.inline_image.is_none() && word.origin.x < max_x {
+                // keep the characters that fit
+                let run = &self.runs[word.span_index];
+                let mut width = 0.0;
+                let mut end = 0;
+                for (i, c) in word.word.char_indices() {
+                    let advance = run.font.advance_width(run.scale, c);
+                    if word.origin.x + width + advance > max_x {
+                        break;
+                    }
+                    width += advance;
+                    end = i + c.len_utf8();
+                }
+                if end > 0 {
+                    word.word = &word.word[..end];
+                    word.size.x = width;
+                    break;
+                }
+            }
+            self.layout.pop();
+        }
+
+        let x = self.layout[first_line_word_index..].last()
+            .map(|word| word.origin.x + word.size.x)
+            .unwrap_or(self.start.x);
+        // the ellipsis stands for the characters after the last one kept
+        let char_offset = self.layout[first_line_word_index..].iter()
+            .map(|word| word.char_offset + word.word.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.layout.push(WordPos {
+            origin: Vector2::new(x, y),
+            span_index,
+            char_offset,
+            line_index: self.lines.len() - 1,
+            word_index: self.word_count,
+            ..WordPos::new(ELLIPSIS, Vector2::new(ellipsis_width, height), 0)
+        });
+        self.realign(first_line_word_index, None);
     }
 
     pub fn iter(&self) -> impl Iterator<Item=&WordPos<'t>> {
         self.layout.iter()
     }
+
+    /// The text that was laid out.
+    pub fn text(&self) -> &T {
+        &self.original_str
+    }
+
+    /// Height of the text, from the top of the first line to the bottom of the last one.
+    pub fn height(&self) -> f32 {
+        self.line_bottoms.last().map(|bottom| bottom - self.top).unwrap_or(0.0)
+    }
+
+    /// y of the top of the first line, after the `VerticalAlign`.
+    pub fn top(&self) -> f32 {
+        self.top
+    }
+
+    /// y of the baseline of the first line.
+    pub fn baseline(&self) -> f32 {
+        self.first_baseline
+    }
+
+    /// Number of lines, after truncation.
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Whether lines were dropped because of `LayoutOptions::max_lines`, or the last line is
+    /// wider than `max_width`.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// With `TextOverflow::Fade`, the index in `iter()` of the word to fade out when the text is
+    /// truncated: the last one.
+    pub fn fade_index(&self) -> Option<usize> {
+        self.fade_index
+    }
 }
 
 /// Only to be consumed (it is returned by `AdvancedLayoutIter`), represents a size and a position
@@ -205,4 +743,166 @@ pub struct WordPos<'t> {
     pub word: &'t str,
     pub origin: Vector2<f32>,
     pub size: Vector2<f32>,
+    /// If set, this is not a word but an inline image, which should be drawn instead of `word`.
+    pub inline_image: Option<InlineImage>,
+    /// Embedding level given by the unicode bidi algorithm. Odd levels are right-to-left.
+    pub bidi_level: u8,
+    /// Index of the span of the `RichText` this word comes from, 0 for a plain string.
+    ///
+    /// A word written with several spans is split in several `WordPos`, one per span.
+    pub span_index: usize,
+    /// Index in the whole text (all the spans of a `RichText`) of the first character of `word`,
+    /// in characters rather than bytes.
+    pub char_offset: usize,
+    /// Index of the word in the text, in logical order. The pieces of a word split by spans share
+    /// the same index.
+    pub word_index: usize,
+    /// Index of the line of the word, from 0 for the first line.
+    pub line_index: usize,
+}
+
+/// A glyph of a laid out text, with where it comes from: to drive per-glyph effects (typewriter,
+/// wavy text...) from your instance data. See `WordPos::glyph_infos`.
+#[derive(Debug, Clone)]
+pub struct GlyphDrawInfo {
+    pub draw_call: FontStemDrawCall,
+    /// Index of the character in the whole text, in characters.
+    pub char_index: usize,
+    pub word_index: usize,
+    pub line_index: usize,
+}
+
+impl<'t> WordPos<'t> {
+    fn new(word: &'t str, size: Vector2<f32>, bidi_level: u8) -> WordPos<'t> {
+        WordPos {
+            word,
+            origin: Vector2::new(0.0, 0.0),
+            size,
+            inline_image: None,
+            bidi_level,
+            span_index: 0,
+            char_offset: 0,
+            word_index: 0,
+            line_index: 0,
+        }
+    }
+
+    /// Attach the indices of the character, the word and the line to the draw calls of this word,
+    /// as returned by `word_to_draw_call(.., &self.visual_word(), ..)`.
+    pub fn glyph_infos(&self, draw_calls: Vec<FontStemDrawCall>) -> impl Iterator<Item = GlyphDrawInfo> {
+        let char_count = self.word.chars().count();
+        let (char_offset, word_index, line_index, right_to_left) = (self.char_offset, self.word_index, self.line_index, self.is_right_to_left());
+        draw_calls.into_iter().map(move |draw_call| {
+            // the characters of a right-to-left word were reversed by `visual_word`.
+            let index_in_word = if right_to_left {
+                char_count.saturating_sub(draw_call.character_index + 1)
+            } else {
+                draw_call.character_index
+            };
+            GlyphDrawInfo {
+                char_index: char_offset + index_in_word,
+                word_index,
+                line_index,
+                draw_call,
+            }
+        })
+    }
+
+    #[inline]
+    pub fn is_right_to_left(&self) -> bool {
+        self.bidi_level % 2 == 1
+    }
+
+    /// The characters of the word in the order they should be drawn from left to right, to give
+    /// to `FontRenderer::word_to_draw_call`: reversed for right-to-left words.
+    ///
+    /// The `character_index` of the draw calls are then from the end of the word. Characters are
+    /// not mirrored and arabic letters are not joined, use `RenderStorage::shape_text` (with the
+    /// `shaping` feature) for that.
+    pub fn visual_word(&self) -> Cow<'t, str> {
+        if self.is_right_to_left() {
+            Cow::Owned(self.word.chars().rev().collect())
+        } else {
+            Cow::Borrowed(self.word)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every character is as wide as half the size of the font.
+    struct MonospaceFont;
+
+    impl LayoutFont for MonospaceFont {
+        fn v_metrics(&self, scale: FontScale) -> VMetrics {
+            VMetrics { ascent: 0.8 * scale.y, descent: -0.2 * scale.y, line_gap: 0.0 }
+        }
+
+        fn advance_width(&self, scale: FontScale, _c: char) -> f32 {
+            scale.x / 2.0
+        }
+
+        fn pair_kerning(&self, _scale: FontScale, _first: char, _second: char) -> f32 {
+            0.0
+        }
+    }
+
+    fn words<'t>(layout: &AdvancedLayout<'_, 't, &'t str>) -> Vec<(&'t str, f32, f32)> {
+        layout.iter().map(|word| (word.word, word.origin.x, word.origin.y)).collect()
+    }
+
+    #[test]
+    fn words_wrap_at_max_width() {
+        let layout = AdvancedLayout::new_str(&MonospaceFont, "aaa bbb ccc", 20.0, Vector2::new(0.0, 0.0), -1, 75);
+        assert_eq!(words(&layout), [("aaa", 0.0, 0.0), ("bbb", 40.0, 0.0), ("ccc", 0.0, 20.0)]);
+        assert_eq!(layout.line_count(), 2);
+        assert_eq!(layout.height(), 40.0);
+        assert!(!layout.is_truncated());
+
+        let layout = AdvancedLayout::new_str(&MonospaceFont, "aaa bbb ccc", 20.0, Vector2::new(0.0, 0.0), -1, u32::MAX);
+        assert_eq!(layout.line_count(), 1);
+    }
+
+    #[test]
+    fn lines_are_aligned_within_max_width() {
+        let layout = AdvancedLayout::new_str(&MonospaceFont, "aaa", 20.0, Vector2::new(10.0, 0.0), 0, 100);
+        assert_eq!(words(&layout), [("aaa", 45.0, 0.0)]);
+        let layout = AdvancedLayout::new_str(&MonospaceFont, "aaa", 20.0, Vector2::new(10.0, 0.0), 1, 100);
+        assert_eq!(words(&layout), [("aaa", 80.0, 0.0)]);
+    }
+
+    #[test]
+    fn newlines_end_the_line() {
+        let layout = AdvancedLayout::new_str(&MonospaceFont, "a\nb", 20.0, Vector2::new(0.0, 0.0), -1, u32::MAX);
+        assert_eq!(words(&layout), [("a", 0.0, 0.0), ("b", 0.0, 20.0)]);
+        assert_eq!(layout.iter().map(|word| word.line_index).collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[test]
+    fn max_lines_ends_with_an_ellipsis() {
+        let options = LayoutOptions::default().with_max_lines(1, TextOverflow::Ellipsis);
+        let layout = AdvancedLayout::new_str_with_options(&MonospaceFont, "aaa bbb ccc", 20.0, Vector2::new(0.0, 0.0), -1, 75, &options);
+        assert!(layout.is_truncated());
+        assert_eq!(layout.line_count(), 1);
+        // "bbb" is cut to leave room for the ellipsis
+        assert_eq!(words(&layout), [("aaa", 0.0, 0.0), ("bb", 40.0, 0.0), ("\u{2026}", 60.0, 0.0)]);
+        let ellipsis = layout.iter().last().unwrap();
+        assert_eq!(ellipsis.char_offset, 6);
+        assert!(ellipsis.origin.x + ellipsis.size.x <= 75.0);
+    }
+
+    #[test]
+    fn max_lines_fades_the_last_word() {
+        let options = LayoutOptions::default().with_max_lines(1, TextOverflow::Fade);
+        let layout = AdvancedLayout::new_str_with_options(&MonospaceFont, "aaa bbb ccc", 20.0, Vector2::new(0.0, 0.0), -1, 75, &options);
+        assert!(layout.is_truncated());
+        assert_eq!(layout.iter().count(), 2);
+        assert_eq!(layout.fade_index(), Some(1));
+
+        let layout = AdvancedLayout::new_str_with_options(&MonospaceFont, "aaa", 20.0, Vector2::new(0.0, 0.0), -1, 75, &options);
+        assert!(!layout.is_truncated());
+        assert_eq!(layout.fade_index(), None);
+    }
 }
@@ -1,7 +1,20 @@
+mod bitmap_font;
 mod font_cache;
 mod font_renderer;
+mod glyph_atlas;
 mod helpers;
+mod rich_text;
+mod text_effects;
+pub mod msdf;
+pub mod color_glyphs;
+#[cfg(feature = "shaping")]
+pub mod shaping;
 
+pub use bitmap_font::*;
 pub use font_renderer::*;
+pub use glyph_atlas::*;
 pub (crate) use font_cache::*;
-pub use helpers::*;
\ No newline at end of file
+pub use font_cache::CacheWriteErr;
+pub use helpers::*;
+pub use rich_text::*;
+pub use text_effects::*;
\ No newline at end of file
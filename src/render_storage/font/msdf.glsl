@@ -0,0 +1,15 @@
+// Reads a glyph generated by sprowl's MSDF text path.
+//
+// `msdf` is the color sampled from the RGBA texture array, and `screen_px_range` is
+// MSDF_RANGE * 2 * (font_size / MSDF_GLYPH_SIZE): the width of the field in screen pixels.
+//
+// Returns the opacity of the glyph. For an outline, call it again with a negative `offset`
+// (in texture pixels) and draw the difference with the outline color.
+float msdf_median(float r, float g, float b) {
+    return max(min(r, g), min(max(r, g), b));
+}
+
+float msdf_alpha(vec4 msdf, float screen_px_range, float offset) {
+    float signed_distance = msdf_median(msdf.r, msdf.g, msdf.b) - 0.5;
+    return clamp(signed_distance * screen_px_range + offset + 0.5, 0.0, 1.0);
+}
@@ -0,0 +1,372 @@
+//! Multi-channel signed distance fields (MSDF) for text.
+//!
+//! Instead of the coverage of every pixel, an MSDF stores the distance to the outline of the glyph,
+//! in 3 channels colored so that sharp corners survive the bilinear filtering. A glyph rasterized
+//! once at `MSDF_GLYPH_SIZE` can then be drawn crisp at any size, and outlines or glows are a
+//! matter of changing a threshold in the shader (see `MSDF_GLSL`).
+//!
+//! MSDF glyphs are stored in the RGBA texture array, packed like small textures.
+
+use cgmath::Vector2;
+use hashbrown::HashMap;
+use rusttype::{GlyphId, OutlineBuilder, ScaledGlyph};
+
+use crate::render_storage::{FontId, atlas::{self, AtlasLayer, TextureHandle}};
+use crate::render_storage::texture::Texture2DArray;
+
+/// Size (in pixels per em) at which glyphs are rasterized into MSDF.
+pub const MSDF_GLYPH_SIZE: f32 = 32.0;
+
+/// Distance (in pixels, at `MSDF_GLYPH_SIZE`) covered by the field on each side of the outline.
+pub const MSDF_RANGE: f32 = 4.0;
+
+/// A fragment shader function to read a glyph drawn from `word_to_draw_call_msdf`.
+///
+/// Sample the RGBA texture array as usual, and give the color to `msdf_alpha` along with the
+/// width of the field on screen: `MSDF_RANGE * 2 * font_size / MSDF_GLYPH_SIZE`.
+pub const MSDF_GLSL: &str = include_str!("msdf.glsl");
+
+// bit flags of the channels of an edge
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const WHITE: u8 = RED | GREEN | BLUE;
+const CYAN: u8 = GREEN | BLUE;
+const MAGENTA: u8 = RED | BLUE;
+const YELLOW: u8 = RED | GREEN;
+
+// number of lines curves are split into
+const QUAD_STEPS: usize = 8;
+const CUBIC_STEPS: usize = 12;
+
+// sin(3 radians): a join between two primitives is a corner if it turns more than this.
+const CORNER_CROSS_THRESHOLD: f32 = 0.141;
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    p0: Vector2<f32>,
+    p1: Vector2<f32>,
+    // index of the line, quad or curve this segment was flattened from
+    primitive: usize,
+    color: u8,
+    // whether this segment starts or ends an edge, where pseudo-distances should be used.
+    edge_start: bool,
+    edge_end: bool,
+}
+
+/// Collects the outline as contours of straight segments.
+#[derive(Debug)]
+struct Outline {
+    contours: Vec<Vec<Segment>>,
+    current: Vec<Segment>,
+    last: Vector2<f32>,
+    contour_start: Vector2<f32>,
+    primitive: usize,
+}
+
+impl Outline {
+    fn new() -> Outline {
+        Outline {
+            contours: Vec::new(),
+            current: Vec::new(),
+            last: Vector2::new(0.0, 0.0),
+            contour_start: Vector2::new(0.0, 0.0),
+            primitive: 0,
+        }
+    }
+
+    fn push_line(&mut self, to: Vector2<f32>) {
+        if to != self.last {
+            self.current.push(Segment { p0: self.last, p1: to, primitive: self.primitive, color: WHITE, edge_start: false, edge_end: false });
+        }
+        self.last = to;
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+        self.last = Vector2::new(x, y);
+        self.contour_start = self.last;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push_line(Vector2::new(x, y));
+        self.primitive += 1;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p0, p1, p2) = (self.last, Vector2::new(x1, y1), Vector2::new(x, y));
+        for i in 1..=QUAD_STEPS {
+            let t = i as f32 / QUAD_STEPS as f32;
+            let mt = 1.0 - t;
+            self.push_line(p0 * (mt * mt) + p1 * (2.0 * mt * t) + p2 * (t * t));
+        }
+        self.primitive += 1;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p0, p1, p2, p3) = (self.last, Vector2::new(x1, y1), Vector2::new(x2, y2), Vector2::new(x, y));
+        for i in 1..=CUBIC_STEPS {
+            let t = i as f32 / CUBIC_STEPS as f32;
+            let mt = 1.0 - t;
+            self.push_line(p0 * (mt * mt * mt) + p1 * (3.0 * mt * mt * t) + p2 * (3.0 * mt * t * t) + p3 * (t * t * t));
+        }
+        self.primitive += 1;
+    }
+
+    fn close(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        let start = self.contour_start;
+        self.push_line(start);
+        self.primitive += 1;
+        let contour = std::mem::take(&mut self.current);
+        self.contours.push(contour);
+    }
+}
+
+fn direction(s: &Segment) -> Vector2<f32> {
+    let d = s.p1 - s.p0;
+    let len = (d.x * d.x + d.y * d.y).sqrt();
+    d / len
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn dot(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+/// Assign a color to every segment, so that the two edges meeting at a corner never share
+/// more than one channel. This is the "simple" coloring of msdfgen.
+fn color_contour(contour: &mut [Segment]) {
+    // index of the segments starting an edge
+    let corners: Vec<usize> = (0..contour.len()).filter(|&i| {
+        let prev = &contour[(i + contour.len() - 1) % contour.len()];
+        let next = &contour[i];
+        if prev.primitive == next.primitive {
+            return false;
+        }
+        let (a, b) = (direction(prev), direction(next));
+        dot(a, b) <= 0.0 || cross(a, b).abs() > CORNER_CROSS_THRESHOLD
+    }).collect();
+
+    match corners.len() {
+        0 => {
+            // smooth contour, like an "o"
+            for segment in contour.iter_mut() {
+                segment.color = WHITE;
+            }
+        },
+        1 => {
+            // "teardrop": split the contour in three edges.
+            let corner = corners[0];
+            let len = contour.len();
+            for i in 0..len {
+                let segment = &mut contour[(corner + i) % len];
+                segment.color = [MAGENTA, WHITE, YELLOW][i * 3 / len];
+                segment.edge_start = i == 0;
+                segment.edge_end = i == len - 1;
+            }
+        },
+        corner_count => {
+            let len = contour.len();
+            for (edge, &corner) in corners.iter().enumerate() {
+                let next_corner = corners[(edge + 1) % corner_count];
+                let mut color = [CYAN, MAGENTA, YELLOW][edge % 3];
+                if edge == corner_count - 1 && corner_count % 3 == 1 {
+                    // the last edge would have the same color as the first one
+                    color = MAGENTA;
+                }
+                let mut i = corner;
+                loop {
+                    contour[i].color = color;
+                    contour[i].edge_start = i == corner;
+                    let next = (i + 1) % len;
+                    contour[i].edge_end = next == next_corner;
+                    i = next;
+                    if i == next_corner {
+                        break;
+                    }
+                }
+            }
+        },
+    }
+}
+
+/// Returns (distance, pseudo-distance) from `p` to the segment.
+///
+/// The pseudo-distance is the distance to the line extending the segment when the closest point
+/// is past the end of an edge, which is what keeps the corners sharp.
+fn segment_distance(s: &Segment, p: Vector2<f32>) -> (f32, f32) {
+    let d = s.p1 - s.p0;
+    let len2 = dot(d, d);
+    let t = dot(p - s.p0, d) / len2;
+    let closest = s.p0 + d * t.clamp(0.0, 1.0);
+    let diff = p - closest;
+    let distance = dot(diff, diff).sqrt();
+    if (t < 0.0 && s.edge_start) || (t > 1.0 && s.edge_end) {
+        let perpendicular = (cross(d, p - s.p0) / len2.sqrt()).abs();
+        (distance, perpendicular.min(distance))
+    } else {
+        (distance, distance)
+    }
+}
+
+/// Non-zero winding rule.
+fn is_inside(segments: &[Segment], p: Vector2<f32>) -> bool {
+    let mut winding = 0;
+    for s in segments {
+        if s.p0.y <= p.y {
+            if s.p1.y > p.y && cross(s.p1 - s.p0, p - s.p0) > 0.0 {
+                winding += 1;
+            }
+        } else if s.p1.y <= p.y && cross(s.p1 - s.p0, p - s.p0) < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding != 0
+}
+
+/// An MSDF bitmap of a glyph, in RGBA. The alpha channel holds the true distance field.
+#[derive(Debug, Clone)]
+pub struct MsdfBitmap {
+    pub width: u32,
+    pub height: u32,
+    /// Position of the top left corner of the bitmap, relative to the origin of the glyph
+    /// (on the baseline), in pixels at the glyph's scale.
+    pub offset: Vector2<f32>,
+    pub pixels: Vec<u8>,
+}
+
+/// Generate the MSDF of a glyph, at the scale of the glyph. `range` is the distance in pixels
+/// covered on each side of the outline.
+///
+/// Returns `None` for glyphs without an outline, like spaces.
+pub fn generate_msdf(glyph: &ScaledGlyph<'_>, range: f32) -> Option<MsdfBitmap> {
+    let bounding_box = glyph.exact_bounding_box()?;
+    let mut outline = Outline::new();
+    if !glyph.build_outline(&mut outline) {
+        return None;
+    }
+    outline.close();
+
+    let mut segments: Vec<Segment> = Vec::new();
+    for mut contour in outline.contours {
+        color_contour(&mut contour);
+        segments.extend(contour);
+    }
+    if segments.is_empty() {
+        return None;
+    }
+
+    let origin_x = (bounding_box.min.x - range).floor();
+    let origin_y = (bounding_box.min.y - range).floor();
+    let width = ((bounding_box.max.x + range).ceil() - origin_x) as u32;
+    let height = ((bounding_box.max.y + range).ceil() - origin_y) as u32;
+
+    let to_byte = |signed_distance: f32| -> u8 {
+        ((0.5 + signed_distance / (2.0 * range)).clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let p = Vector2::new(origin_x + x as f32 + 0.5, origin_y + y as f32 + 0.5);
+            let sign = if is_inside(&segments, p) { 1.0 } else { -1.0 };
+            // for each channel (r, g, b) and for the true distance: (closest distance, its pseudo-distance)
+            let mut closest = [(f32::INFINITY, f32::INFINITY); 4];
+            for s in &segments {
+                let (distance, pseudo_distance) = segment_distance(s, p);
+                for (channel, flag) in [RED, GREEN, BLUE].iter().enumerate() {
+                    if s.color & flag != 0 && distance < closest[channel].0 {
+                        closest[channel] = (distance, pseudo_distance);
+                    }
+                }
+                if distance < closest[3].0 {
+                    closest[3] = (distance, distance);
+                }
+            }
+            for &(distance, pseudo_distance) in &closest {
+                let d = if distance.is_finite() { pseudo_distance } else { closest[3].0 };
+                pixels.push(to_byte(sign * d));
+            }
+        }
+    }
+
+    Some(MsdfBitmap {
+        width,
+        height,
+        offset: Vector2::new(origin_x, origin_y),
+        pixels,
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub (crate) struct MsdfGlyph {
+    pub (crate) handle: TextureHandle,
+    pub (crate) offset: Vector2<f32>,
+}
+
+/// Remembers which glyphs have already been converted to MSDF, for all fonts.
+///
+/// Unlike the `GlyphAtlas`, glyphs are never evicted: since they don't depend on the font size,
+/// there are only as many as there are different characters in your texts.
+#[derive(Debug, Default)]
+pub struct MsdfAtlas {
+    // None for glyphs with nothing to draw
+    pub (crate) glyphs: HashMap<(FontId, GlyphId), Option<MsdfGlyph>>,
+}
+
+impl MsdfAtlas {
+    pub fn new() -> MsdfAtlas {
+        MsdfAtlas::default()
+    }
+
+    /// Number of glyphs converted so far.
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+}
+
+/// A `MsdfAtlas` along with the RGBA texture array it writes to, given by `RenderStorage::get_font_with_msdf`.
+pub struct MsdfAtlasRef<'a> {
+    pub (crate) atlas: &'a mut MsdfAtlas,
+    pub (crate) texture_array: &'a mut Texture2DArray,
+    pub (crate) atlas_layers: &'a mut Vec<AtlasLayer>,
+}
+
+impl<'a> MsdfAtlasRef<'a> {
+    /// Returns the glyph, generating and uploading it first if needed.
+    pub (crate) fn glyph(&mut self, font_id: FontId, glyph: &ScaledGlyph<'_>) -> Option<MsdfGlyph> {
+        let MsdfAtlasRef { atlas, texture_array, atlas_layers } = self;
+        let key = (font_id, glyph.id());
+        if let Some(msdf_glyph) = atlas.glyphs.get(&key) {
+            return *msdf_glyph;
+        }
+        let msdf_glyph = match generate_msdf(glyph, MSDF_RANGE) {
+            Some(bitmap) => match atlas::pack_texture(texture_array, atlas_layers, &bitmap.pixels, (bitmap.width, bitmap.height)) {
+                Ok(handle) => Some(MsdfGlyph {
+                    handle,
+                    offset: bitmap.offset,
+                }),
+                Err(e) => {
+                    // not cached, so that it's tried again once some room has been freed.
+                    log::warn!("could not add MSDF glyph {:?} of font {}: {}", key.1, font_id, e);
+                    return None;
+                }
+            },
+            None => None,
+        };
+        atlas.glyphs.insert(key, msdf_glyph);
+        msdf_glyph
+    }
+}
@@ -0,0 +1,81 @@
+use crate::color::Color;
+use crate::render_storage::FontId;
+use super::AdvancedText;
+
+/// A part of a `RichText` with its own font, size and color.
+#[derive(Debug, Clone, Copy)]
+pub struct TextSpan<'t> {
+    pub text: &'t str,
+    pub font_id: FontId,
+    pub font_size: f32,
+    /// Not used by the layout, it's only there for you to draw the span with.
+    pub color: Color<u8>,
+}
+
+/// A paragraph made of several spans, to be laid out as one with `AdvancedLayout::new_rich`.
+///
+/// ```ignore
+/// let text = RichText::new()
+///     .with_span("Press ", regular_font, 16.0, Color::white())
+///     .with_span("Start", bold_font, 16.0, Color::from_rgb(255, 200, 0))
+///     .with_span(" to continue", regular_font, 16.0, Color::white());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RichText<'t> {
+    spans: Vec<TextSpan<'t>>,
+}
+
+impl<'t> RichText<'t> {
+    pub fn new() -> RichText<'t> {
+        RichText::default()
+    }
+
+    pub fn push(&mut self, text: &'t str, font_id: FontId, font_size: f32, color: Color<u8>) {
+        self.spans.push(TextSpan { text, font_id, font_size, color });
+    }
+
+    pub fn with_span(mut self, text: &'t str, font_id: FontId, font_size: f32, color: Color<u8>) -> RichText<'t> {
+        self.push(text, font_id, font_size, color);
+        self
+    }
+
+    pub fn spans(&self) -> &[TextSpan<'t>] {
+        &self.spans
+    }
+
+    /// Returns the span at `span_index`, typically `WordPos::span_index`.
+    pub fn span(&self, span_index: usize) -> Option<&TextSpan<'t>> {
+        self.spans.get(span_index)
+    }
+
+    /// The text of all the spans, one after the other.
+    pub fn to_plain_string(&self) -> String {
+        self.spans.iter().map(|span| span.text).collect()
+    }
+}
+
+impl<'t> AdvancedText<'t> for &'t RichText<'t> {
+    type E = ();
+
+    fn len(&self) -> usize {
+        self.spans.iter().map(|span| span.text.len()).sum()
+    }
+
+    /// `begin` and `end` are byte offsets in the text of all the spans. If the range is in
+    /// several spans, returns what's in the first one, and the offset where it ends.
+    fn get(&self, begin: usize, end: usize) -> Result<&'t str, (&'t str, usize)> {
+        let mut span_start = 0;
+        for span in &self.spans {
+            let span_end = span_start + span.text.len();
+            if begin < span_end {
+                return if end <= span_end {
+                    Ok(&span.text[begin - span_start..end - span_start])
+                } else {
+                    Err((&span.text[begin - span_start..], span_end))
+                };
+            }
+            span_start = span_end;
+        }
+        Ok("")
+    }
+}
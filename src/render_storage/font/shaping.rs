@@ -0,0 +1,177 @@
+//! Text shaping with `rustybuzz`, a port of harfbuzz: ligatures, kerning, combining marks and
+//! complex scripts, with a chain of fallback fonts for the characters missing from a font.
+//!
+//! Only available with the `shaping` feature. See `RenderStorage::shape_text`.
+
+use std::borrow::Cow;
+
+use hashbrown::HashMap;
+use rusttype::{GlyphId, PositionedGlyph, Scale as FontScale};
+use cgmath::Vector2;
+
+use crate::error::SprowlError;
+use crate::render_storage::FontId;
+use crate::render_storage::texture::TextureArrayLayerRef;
+use super::{FontRenderer, FontStemDrawCall, GlyphAtlas};
+
+/// The raw bytes of the fonts, which rustybuzz needs (rusttype doesn't give them back), and the
+/// fallback chains.
+#[derive(Debug, Default)]
+pub (crate) struct ShapingFonts {
+    data: HashMap<FontId, Cow<'static, [u8]>>,
+    fallbacks: HashMap<FontId, Vec<FontId>>,
+}
+
+impl ShapingFonts {
+    pub (crate) fn add_font(&mut self, font_id: FontId, data: Cow<'static, [u8]>) {
+        self.data.insert(font_id, data);
+    }
+
+    pub (crate) fn set_fallbacks(&mut self, font_id: FontId, fallbacks: Vec<FontId>) {
+        self.fallbacks.insert(font_id, fallbacks);
+    }
+
+    /// The font itself, followed by its fallbacks.
+    fn chain(&self, font_id: FontId) -> Vec<FontId> {
+        let mut chain = vec![font_id];
+        if let Some(fallbacks) = self.fallbacks.get(&font_id) {
+            chain.extend(fallbacks.iter().filter(|fallback| **fallback != font_id));
+        }
+        chain
+    }
+}
+
+/// A glyph placed by the shaper, relative to the origin of the text.
+#[derive(Debug, Clone, Copy)]
+struct ShapedGlyph {
+    font_id: FontId,
+    glyph_id: u32,
+    x: f32,
+    y: f32,
+    /// byte offset of the first character of this glyph
+    cluster: usize,
+}
+
+/// What stays the same while shaping a text and the runs given to its fallback fonts.
+struct Shaper<'a> {
+    shaping_fonts: &'a ShapingFonts,
+    fonts: &'a HashMap<FontId, FontRenderer>,
+    font_size: f32,
+}
+
+impl<'a> Shaper<'a> {
+    /// Shapes `text` with the first font of `chain`, and shapes the runs of glyphs missing from it
+    /// with the next fonts. Returns the total advance.
+    fn shape_with_fallback(&self, chain: &[FontId], text: &str, byte_offset: usize, origin_x: f32, out: &mut Vec<ShapedGlyph>) -> f32 {
+        let font_id = chain[0];
+        let (font, data) = match (self.fonts.get(&font_id), self.shaping_fonts.data.get(&font_id)) {
+            (Some(font), Some(data)) => (font, data),
+            _ => return 0.0,
+        };
+        let face = match rustybuzz::Face::from_slice(data, 0) {
+            Some(face) => face,
+            None => return 0.0,
+        };
+        // rusttype scales fonts by their height, not by their units per em.
+        let v_metrics = font.font().v_metrics_unscaled();
+        let units_to_px = self.font_size / (v_metrics.ascent - v_metrics.descent);
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        let glyph_buffer = rustybuzz::shape(&face, &[], buffer);
+        let infos = glyph_buffer.glyph_infos();
+        let positions = glyph_buffer.glyph_positions();
+
+        let mut x = origin_x;
+        let mut i = 0;
+        while i < infos.len() {
+            // glyph 0 is .notdef: the font doesn't have this character.
+            if infos[i].glyph_id == 0 && chain.len() > 1 {
+                let run_start = i;
+                while i < infos.len() && infos[i].glyph_id == 0 {
+                    i += 1;
+                }
+                // clusters are in visual order, so they decrease in right-to-left text.
+                let run = &infos[run_start..i];
+                let start = run.iter().map(|info| info.cluster as usize).min().unwrap_or(0);
+                let last = run.iter().map(|info| info.cluster as usize).max().unwrap_or(0);
+                let end = infos.iter()
+                    .map(|info| info.cluster as usize)
+                    .filter(|cluster| *cluster > last)
+                    .min()
+                    .unwrap_or(text.len());
+                x += self.shape_with_fallback(&chain[1..], &text[start..end], byte_offset + start, x, out);
+                continue;
+            }
+            let position = positions[i];
+            out.push(ShapedGlyph {
+                font_id,
+                glyph_id: infos[i].glyph_id,
+                x: x + position.x_offset as f32 * units_to_px,
+                // y goes up in font units, and down on screen
+                y: -position.y_offset as f32 * units_to_px,
+                cluster: byte_offset + infos[i].cluster as usize,
+            });
+            x += position.x_advance as f32 * units_to_px;
+            i += 1;
+        }
+        x - origin_x
+    }
+}
+
+/// Shapes the text, caches the glyphs in the glyph atlas, and returns where to draw them.
+pub (crate) fn shape_text(shaping_fonts: &ShapingFonts, fonts: &HashMap<FontId, FontRenderer>, glyph_atlas: &mut GlyphAtlas, mut texture: TextureArrayLayerRef<'_>, font_id: FontId, text: &str, font_size: f32) -> Result<Vec<FontStemDrawCall>, SprowlError> {
+    let font = fonts.get(&font_id).ok_or(SprowlError::MissingFontId(font_id))?;
+    let scale = FontScale::uniform(font_size);
+    // all the fonts share the baseline of the main one.
+    let ascent = font.font().v_metrics(scale).ascent;
+
+    let mut shaped_glyphs = Vec::new();
+    let shaper = Shaper { shaping_fonts, fonts, font_size };
+    shaper.shape_with_fallback(&shaping_fonts.chain(font_id), text, 0, 0.0, &mut shaped_glyphs);
+
+    // cluster byte offsets to character indices
+    let char_indices: HashMap<usize, usize> = text.char_indices().enumerate().map(|(i, (byte, _))| (byte, i)).collect();
+
+    let (tex_w, tex_h) = texture.stats().size();
+    let (tex_w, tex_h) = (tex_w as f32, tex_h as f32);
+    let texture_layer = glyph_atlas.texture_layer;
+    let mut results: Vec<FontStemDrawCall> = Vec::with_capacity(shaped_glyphs.len());
+    // cached in a single call, so that caching the glyphs of a fallback font can't evict the ones
+    // of the fonts before it.
+    let glyphs: Vec<(FontId, usize, PositionedGlyph<'static>)> = shaped_glyphs.iter()
+        .filter_map(|glyph| {
+            let glyph_font = fonts.get(&glyph.font_id)?.font();
+            let character_index = char_indices.get(&glyph.cluster).cloned().unwrap_or(0);
+            let positioned = glyph_font.glyph(GlyphId(glyph.glyph_id as u16))
+                .scaled(scale)
+                .positioned(rusttype::point(glyph.x, glyph.y));
+            Some((glyph.font_id, character_index, positioned))
+        })
+        .collect();
+    glyph_atlas.cache.cache_glyphs_of_fonts(glyphs.iter().map(|(glyph_font_id, _, glyph)| (*glyph_font_id as usize, glyph)), |rect, data| {
+        let rusttype::Point { x, y } = rect.min;
+        texture.update(data, x as i32, y as i32, rect.width(), rect.height());
+    }).map_err(SprowlError::GlyphCache)?;
+    for (glyph_font_id, character_index, glyph) in &glyphs {
+        if let Some((uv_rect, origin)) = glyph_atlas.glyph_rect(*glyph_font_id as usize, glyph) {
+            let source_crop = (
+                uv_rect.min.x * tex_w,
+                uv_rect.min.y * tex_h,
+                uv_rect.width() * tex_w,
+                uv_rect.height() * tex_h,
+            );
+            results.push(FontStemDrawCall {
+                source_crop,
+                dest_origin: Vector2::new(origin.x, origin.y + ascent),
+                dest_size: Vector2::new(source_crop.2, source_crop.3),
+                texture_layer,
+                character_index: *character_index,
+                padding: glyph_atlas.cache.glyph_padding() as f32,
+                color: false,
+            });
+        }
+    }
+    Ok(results)
+}
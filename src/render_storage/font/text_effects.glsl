@@ -0,0 +1,78 @@
+// Draws a glyph of sprowl's grayscale glyph atlas with the outline, shadow and glow packed by
+// `TextEffects`.
+//
+// Draw `FontStemDrawCall::padded_crop`, and forward the 2 text effect attribs from the vertex
+// shader (`flat`), along with the padded crop in texture coordinates (min.xy, max.xy), also
+// `flat`. In the fragment shader, call `text_effects_color` with the texture coordinates of the
+// fragment, and the color of the text itself.
+//
+// The returned color is not premultiplied.
+vec4 text_effects_unpack_color(uint color) {
+    return vec4(float((color >> 24) & 0xFFu), float((color >> 16) & 0xFFu), float((color >> 8) & 0xFFu), float(color & 0xFFu)) / 255.0;
+}
+
+// coverage of the glyph at `uv`, 0.0 outside of the padded crop, where the neighbours are.
+float text_effects_coverage(sampler2DArray glyphs, vec3 uv, vec4 crop) {
+    if (uv.x < crop.x || uv.y < crop.y || uv.x > crop.z || uv.y > crop.w) {
+        return 0.0;
+    }
+    return texture(glyphs, uv).r;
+}
+
+// highest coverage within `radius` texels of `uv`, sampled on 2 rings of 8 directions.
+float text_effects_dilate(sampler2DArray glyphs, vec3 uv, vec4 crop, vec2 texel, float radius) {
+    float coverage = text_effects_coverage(glyphs, uv, crop);
+    if (radius <= 0.0) {
+        return coverage;
+    }
+    for (int i = 0; i < 8; i++) {
+        float angle = float(i) * 0.785398;
+        vec2 direction = vec2(cos(angle), sin(angle)) * texel;
+        coverage = max(coverage, text_effects_coverage(glyphs, uv + vec3(direction * radius, 0.0), crop));
+        coverage = max(coverage, text_effects_coverage(glyphs, uv + vec3(direction * radius * 0.5, 0.0), crop));
+    }
+    return clamp(coverage, 0.0, 1.0);
+}
+
+// `b` over `a`, both not premultiplied.
+vec4 text_effects_over(vec4 a, vec4 b) {
+    float alpha = b.a + a.a * (1.0 - b.a);
+    if (alpha <= 0.0) {
+        return vec4(0.0);
+    }
+    return vec4((b.rgb * b.a + a.rgb * a.a * (1.0 - b.a)) / alpha, alpha);
+}
+
+// params: outline width, shadow offset (x, y), glow radius, in texels.
+// colors: outline, shadow and glow colors.
+vec4 text_effects_color(sampler2DArray glyphs, vec3 uv, vec4 crop, vec4 params, uvec3 colors, vec4 text_color) {
+    vec2 texel = 1.0 / vec2(textureSize(glyphs, 0).xy);
+    float outline_width = params.x;
+    vec2 shadow_offset = params.yz;
+    float glow_radius = params.w;
+
+    vec4 color = vec4(0.0);
+    if (glow_radius > 0.0) {
+        // the glow fades out linearly with the distance
+        float glow = 0.0;
+        for (int i = 1; i <= 4; i++) {
+            float radius = outline_width + glow_radius * float(i) / 4.0;
+            glow += text_effects_dilate(glyphs, uv, crop, texel, radius) * 0.25;
+        }
+        vec4 glow_color = text_effects_unpack_color(colors.z);
+        color = vec4(glow_color.rgb, glow_color.a * glow);
+    }
+    if (shadow_offset != vec2(0.0)) {
+        vec3 shadow_uv = uv - vec3(shadow_offset * texel, 0.0);
+        vec4 shadow_color = text_effects_unpack_color(colors.y);
+        shadow_color.a *= text_effects_dilate(glyphs, shadow_uv, crop, texel, outline_width);
+        color = text_effects_over(color, shadow_color);
+    }
+    if (outline_width > 0.0) {
+        vec4 outline_color = text_effects_unpack_color(colors.x);
+        outline_color.a *= text_effects_dilate(glyphs, uv, crop, texel, outline_width);
+        color = text_effects_over(color, outline_color);
+    }
+    vec4 fill = vec4(text_color.rgb, text_color.a * text_effects_coverage(glyphs, uv, crop));
+    return text_effects_over(color, fill);
+}
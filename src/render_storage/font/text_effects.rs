@@ -0,0 +1,96 @@
+use cgmath::Vector2;
+
+use crate::color::Color;
+use crate::renderer::instance_data;
+
+/// A fragment shader function drawing a glyph of the grayscale glyph atlas with its
+/// `TextEffects`, see the comments in the source.
+pub const TEXT_EFFECTS_GLSL: &str = include_str!("text_effects.glsl");
+
+/// Outline, shadow and glow of grayscale text, drawn by `TEXT_EFFECTS_GLSL`.
+///
+/// Distances are in pixels of the glyph atlas. The glyphs need that much room around them: set
+/// the padding of the atlas to `required_padding` with `GlyphAtlas::set_glyph_padding`, and draw
+/// `FontStemDrawCall::padded_crop` at `FontStemDrawCall::padded_dest`.
+///
+/// As instance data, next to the fields of the glyph quad:
+///
+/// * "text_effect_params" (`vec4`), see `params`
+/// * "text_effect_colors" (`uvec3`), see `colors`
+#[derive(Debug, Clone, Copy)]
+pub struct TextEffects {
+    /// 0.0 for no outline.
+    pub outline_width: f32,
+    pub outline_color: Color<u8>,
+    /// The shadow is the glyph with its outline, moved by this offset. (0.0, 0.0) for no shadow.
+    pub shadow_offset: Vector2<f32>,
+    pub shadow_color: Color<u8>,
+    /// 0.0 for no glow.
+    pub glow_radius: f32,
+    pub glow_color: Color<u8>,
+}
+
+impl Default for TextEffects {
+    fn default() -> TextEffects {
+        TextEffects {
+            outline_width: 0.0,
+            outline_color: Color::black(),
+            shadow_offset: Vector2::new(0.0, 0.0),
+            shadow_color: Color::from_rgba(0, 0, 0, 128),
+            glow_radius: 0.0,
+            glow_color: Color::white(),
+        }
+    }
+}
+
+impl TextEffects {
+    /// No effect at all.
+    pub fn new() -> TextEffects {
+        TextEffects::default()
+    }
+
+    pub fn with_outline(mut self, width: f32, color: Color<u8>) -> TextEffects {
+        self.outline_width = width;
+        self.outline_color = color;
+        self
+    }
+
+    pub fn with_shadow(mut self, offset: Vector2<f32>, color: Color<u8>) -> TextEffects {
+        self.shadow_offset = offset;
+        self.shadow_color = color;
+        self
+    }
+
+    pub fn with_glow(mut self, radius: f32, color: Color<u8>) -> TextEffects {
+        self.glow_radius = radius;
+        self.glow_color = color;
+        self
+    }
+
+    /// Padding the glyphs need in the atlas for the effects not to be cut.
+    pub fn required_padding(&self) -> u32 {
+        let shadow = self.outline_width.max(0.0) + self.shadow_offset.x.abs().max(self.shadow_offset.y.abs());
+        let glow = self.outline_width.max(0.0) + self.glow_radius.max(0.0);
+        // 1 more pixel for the bilinear filtering
+        shadow.max(glow).ceil() as u32 + 1
+    }
+
+    /// Outline width, shadow offset (x, y) and glow radius, in the order of "text_effect_params".
+    pub fn params(&self) -> [f32; 4] {
+        [self.outline_width, self.shadow_offset.x, self.shadow_offset.y, self.glow_radius]
+    }
+
+    /// Outline, shadow and glow colors as `0xRRGGBBAA`, in the order of "text_effect_colors".
+    pub fn colors(&self) -> [u32; 3] {
+        [pack_color(self.outline_color), pack_color(self.shadow_color), pack_color(self.glow_color)]
+    }
+}
+
+instance_data!(impl TextEffects |effects| {
+    "text_effect_params": [f32; 4] = effects.params(),
+    "text_effect_colors": [u32; 3] = effects.colors(),
+});
+
+fn pack_color(color: Color<u8>) -> u32 {
+    u32::from_be_bytes([color.r, color.g, color.b, color.a])
+}
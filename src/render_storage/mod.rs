@@ -1,13 +1,40 @@
 pub mod texture;
 pub mod font;
+pub mod atlas;
+pub mod upload;
+pub mod registry;
+pub mod compressed;
+pub mod palette;
+pub mod watcher;
+pub mod config;
+pub mod tiers;
+pub mod repack;
+#[cfg(feature = "sheet-import")]
+pub mod sheet;
 
-use font::FontRenderer;
-use texture::{Texture2DArray, TextureFormat, TextureArrayLayer, TextureArrayLayerRef, TextureLayerStats};
+use font::{BitmapFont, FontRenderer, FontStemDrawCall, GlyphAtlas, GlyphAtlasRef, LayoutFont, RichText, WordPos, msdf::{MsdfAtlas, MsdfAtlasRef}};
+use font::color_glyphs::{self, ColorGlyphAtlas, ColorGlyphAtlasRef};
+use atlas::{TextureHandle, TextureRect, TextureRegion};
+use upload::{AsyncUploads, PendingUpload, TextureLoadHandle, TextureLoadState, UploadBudget, UploadPriority, UploadQueue, UploadStats};
+use registry::{AssetFlags, AssetRegistry, FontInfo, NamedAsset, TextureInfo};
+use texture::{Texture2DArray, TextureArraySettings, TextureFilter, TextureFormat, TextureArrayLayerRef};
+use compressed::{CompressedTextureHandle, FIRST_COMPRESSED_TEXTURE_UNIT};
+use palette::{PALETTE_TEXTURE_UNIT, PaletteId, PaletteTexture};
+use config::{RenderStorageConfig, TextureArrayConfig};
+use tiers::{MAX_RGBA_TIERS, RgbaTier};
 
 use rusttype::Font;
+#[cfg(feature = "shaping")]
+use font::shaping::{self, ShapingFonts};
 use image::GenericImageView;
 
 use hashbrown::HashMap;
+use std::path::Path;
+
+use crate::color::{AlphaMode, Color};
+use crate::debug::StorageStats;
+use crate::error::SprowlError;
+use crate::gl_state::GlStateGuard;
 
 pub type FontId = u32;
 
@@ -17,67 +44,396 @@ pub enum TextureKind {
     RGBA,
 }
 
+impl TextureKind {
+    /// The texture unit the array of this kind is bound to by `RenderStorage::set_active`.
+    pub fn texture_unit(self) -> u32 {
+        match self {
+            TextureKind::RGBA => 0,
+            TextureKind::Grayscale => 1,
+        }
+    }
+}
+
+/// How `RenderStorage::add_texture_from_image_bytes_with_options` converts an image to RGBA.
+///
+/// The options are kept along with the texture, and applied again when it's reloaded by
+/// `reload_texture_from_image_bytes` or an `AssetWatcher`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextureLoadOptions {
+    /// Pixels of this color (its alpha is ignored) become fully transparent, for legacy assets
+    /// which use magenta as transparency instead of an alpha channel. Their color is cleared as
+    /// well, so that it doesn't bleed into their neighbors when filtered.
+    pub color_key: Option<Color<u8>>,
+}
+
+impl TextureLoadOptions {
+    pub fn new() -> TextureLoadOptions {
+        TextureLoadOptions::default()
+    }
+
+    pub fn with_color_key(mut self, color_key: Color<u8>) -> Self {
+        self.color_key = Some(color_key);
+        self
+    }
+
+    /// Apply the options to RGBA pixels.
+    fn apply(&self, pixels: &mut [u8]) {
+        if let Some(key) = self.color_key {
+            for pixel in pixels.chunks_exact_mut(4) {
+                if pixel[..3] == [key.r, key.g, key.b] {
+                    pixel.copy_from_slice(&[0, 0, 0, 0]);
+                }
+            }
+        }
+    }
+}
+
 pub struct RenderStorage {
     current_font_id: FontId,
     pub fonts: HashMap<FontId, FontRenderer>,
-    // array grayscale holds textures of 2048/2048 in grayscale, and is made for fonts.
+    // fonts of .fnt files, whose ids are shared with `fonts`. Their pages are RGBA textures.
+    pub bitmap_fonts: HashMap<FontId, BitmapFont>,
+    // glyphs of all the fonts are cached in a single layer of texture_array_grayscale.
+    pub glyph_atlas: GlyphAtlas,
+    // glyphs converted to MSDF, stored in the first RGBA tier.
+    pub msdf_atlas: MsdfAtlas,
+    // glyphs of color fonts (emoji), stored in the first RGBA tier.
+    pub color_glyph_atlas: ColorGlyphAtlas,
+    // array grayscale holds textures of 2048/2048 in grayscale by default, and is made for fonts.
     pub texture_array_grayscale: Texture2DArray,
-    // array rgba is made for "normal" pixelperfect textures,
-    pub texture_array_rgba: Texture2DArray,
+    // arrays rgba are made for "normal" pixelperfect textures, one per size of layers. See `tiers`.
+    pub rgba_tiers: Vec<RgbaTier>,
+    // one array per format and size of compressed textures, bound from FIRST_COMPRESSED_TEXTURE_UNIT.
+    pub compressed_arrays: Vec<Texture2DArray>,
+    // one palette per row, bound to PALETTE_TEXTURE_UNIT.
+    pub palettes: PaletteTexture,
+    // textures waiting for `process_uploads`
+    uploads: UploadQueue,
+    // images decoded on a worker thread, then uploaded through PBOs by `process_uploads`
+    async_uploads: AsyncUploads,
+    // names and usage of the textures and fonts, for `iter_textures` and `iter_fonts`
+    registry: AssetRegistry,
+    // font bytes and fallback chains for `shape_text`
+    #[cfg(feature = "shaping")]
+    shaping_fonts: ShapingFonts,
 }
 
 impl RenderStorage {
+    /// Linear filtering for the text, NEAREST for everything else.
     pub fn new() -> RenderStorage {
-        let mut texture_array_grayscale = Texture2DArray::new(2048, 2048, 16, TextureFormat::Greyscale);
-        texture_array_grayscale.set_linear(true);
-        let texture_array_rgba = Texture2DArray::new(1024, 1024, 32, TextureFormat::RGBA);
+        Self::from_config(RenderStorageConfig::default())
+    }
+
+    /// Choose the filtering options of the RGBA textures, and of the grayscale ones (mostly text).
+    pub fn with_settings(rgba_settings: TextureArraySettings, grayscale_settings: TextureArraySettings) -> RenderStorage {
+        let default_config = RenderStorageConfig::default();
+        Self::from_config(RenderStorageConfig {
+            rgba: default_config.rgba.with_settings(rgba_settings),
+            grayscale: default_config.grayscale.with_settings(grayscale_settings),
+            ..default_config
+        })
+    }
+
+    /// Choose the size, the number of layers and the filtering of the texture arrays.
+    ///
+    /// The glyph atlas takes a whole layer of the grayscale array, so its size is the size of
+    /// that array.
+    ///
+    /// Returns an error if the config exceeds the limits of the GL context, see
+    /// `RenderStorageConfig::validate`.
+    pub fn with_config(config: RenderStorageConfig) -> Result<RenderStorage, SprowlError> {
+        config.validate()?;
+        Ok(Self::from_config(config))
+    }
+
+    fn from_config(config: RenderStorageConfig) -> RenderStorage {
+        let RenderStorageConfig { rgba, grayscale, alpha_mode } = config;
+        let new_array = |array: TextureArrayConfig, format: TextureFormat| {
+            Texture2DArray::with_settings(array.width, array.height, array.layers, format, array.settings)
+        };
+        let mut texture_array_grayscale = new_array(grayscale, TextureFormat::Greyscale);
+        let texture_array_rgba = new_array(rgba, TextureFormat::RGBA);
+        let glyph_layer = texture_array_grayscale.add_empty_texture(grayscale.width, grayscale.height).expect("new texture array has no room for the glyph atlas");
 
         let mut render_storage = RenderStorage {
             current_font_id: 0,
             fonts: Default::default(),
+            bitmap_fonts: Default::default(),
+            glyph_atlas: GlyphAtlas::new(glyph_layer, grayscale.width, grayscale.height),
+            msdf_atlas: MsdfAtlas::new(),
+            color_glyph_atlas: ColorGlyphAtlas::new(),
             texture_array_grayscale,
-            texture_array_rgba,
+            rgba_tiers: vec![RgbaTier::new(texture_array_rgba, alpha_mode)],
+            compressed_arrays: Vec::new(),
+            palettes: PaletteTexture::new(),
+            uploads: UploadQueue::default(),
+            async_uploads: AsyncUploads::default(),
+            registry: AssetRegistry::default(),
+            #[cfg(feature = "shaping")]
+            shaping_fonts: ShapingFonts::default(),
         };
         render_storage.set_active();
         render_storage
     }
 
-    /// Load a font from *static* bytes. There is currently no way to dynamically load a font, for convenience only.
+    /// Load a font from *static* bytes, typically with `include_bytes!`.
     ///
     /// Returns a number representing the ID of the font, which you can use later on in `draw(..)`
     ///
+    /// Returns an error if the bytes are not a valid font.
+    pub fn add_font_from_bytes(&mut self, bytes: &'static [u8]) -> Result<FontId, SprowlError> {
+        let font = Font::try_from_bytes(bytes).ok_or(SprowlError::InvalidFont)?;
+        let font_id = self.add_font(font);
+        if color_glyphs::has_color_bitmaps(bytes) {
+            self.fonts.get_mut(&font_id).unwrap().color_font_bytes = Some(std::borrow::Cow::Borrowed(bytes));
+        }
+        #[cfg(feature = "shaping")]
+        self.shaping_fonts.add_font(font_id, std::borrow::Cow::Borrowed(bytes));
+        Ok(font_id)
+    }
+
+    /// Same as `add_font_from_bytes`, for fonts known to be valid.
+    ///
     /// # Panics
     ///
-    /// Panics if there's more than one font
-    pub fn add_font_from_bytes(&mut self, bytes: &'static [u8]) -> FontId {
-        let font = Font::try_from_bytes(bytes).expect("wrong font added from static bytes");
+    /// Panics if the bytes are not a valid font
+    pub fn add_font_from_bytes_unchecked(&mut self, bytes: &'static [u8]) -> FontId {
+        self.add_font_from_bytes(bytes).expect("wrong font added from static bytes")
+    }
+
+    /// Load a font from owned bytes, for instance a font chosen by the user at runtime.
+    ///
+    /// Returns an error if the bytes are not a valid font.
+    pub fn add_font_from_vec(&mut self, bytes: Vec<u8>) -> Result<FontId, SprowlError> {
+        // rusttype doesn't give the bytes back, and the shaper needs them as well.
+        #[cfg(feature = "shaping")]
+        let shaping_bytes = bytes.clone();
+        let color_font_bytes = if color_glyphs::has_color_bitmaps(&bytes) { Some(bytes.clone()) } else { None };
+        let font = Font::try_from_vec(bytes).ok_or(SprowlError::InvalidFont)?;
+        let font_id = self.add_font(font);
+        if let Some(color_font_bytes) = color_font_bytes {
+            self.fonts.get_mut(&font_id).unwrap().color_font_bytes = Some(std::borrow::Cow::Owned(color_font_bytes));
+        }
+        #[cfg(feature = "shaping")]
+        self.shaping_fonts.add_font(font_id, std::borrow::Cow::Owned(shaping_bytes));
+        Ok(font_id)
+    }
+
+    /// Load a font from a file (ttf or otf).
+    ///
+    /// Returns an error if the file can't be read, or is not a valid font.
+    pub fn add_font_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<FontId, SprowlError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let font_id = self.add_font_from_vec(bytes)?;
+        if let Some(file_name) = path.file_name() {
+            self.set_font_name(font_id, file_name.to_string_lossy());
+        }
+        Ok(font_id)
+    }
+
+    /// Load a bitmap font from a .fnt file (in the text format) and the images of its pages
+    /// (PNG, ...), in the order of their page ids. See `BitmapFont`.
+    ///
+    /// The pages are added like `add_texture_from_image_bytes`, and must fit in the first RGBA
+    /// tier. The font gets a `FontId` like any other font.
+    ///
+    /// Returns an error if the .fnt file can't be read, if there isn't an image for every page,
+    /// if a page can't be added, or if a glyph goes past its page.
+    pub fn add_bitmap_font(&mut self, fnt: &str, pages: &[&[u8]]) -> Result<FontId, SprowlError> {
+        let fnt = font::parse_fnt(fnt)?;
+        if fnt.pages.len() != pages.len() {
+            return Err(SprowlError::InvalidBitmapFont(format!("{} pages given, expected {}", pages.len(), fnt.pages.len())));
+        }
+        let mut page_handles = Vec::with_capacity(pages.len());
+        for (page_name, bytes) in fnt.pages.iter().zip(pages) {
+            let page = self.add_texture_from_image_bytes(bytes, None).and_then(|page| {
+                if page.array != 0 {
+                    // `draw_text` only samples the first tier
+                    self.remove_texture(page);
+                    return Err(SprowlError::InvalidBitmapFont(format!("page {:?} is too big for the first RGBA tier", page_name)));
+                }
+                Ok(page)
+            });
+            match page {
+                Ok(page) => {
+                    self.set_texture_name(page, page_name.as_str());
+                    page_handles.push(page);
+                },
+                Err(e) => {
+                    for page in page_handles {
+                        self.remove_texture(page);
+                    }
+                    return Err(e);
+                },
+            }
+        }
+        let out_of_page = fnt.glyphs.iter()
+            .find(|(_, glyph)| !page_handles[glyph.page as usize].contains_crop(glyph.crop));
+        if let Some((c, glyph)) = out_of_page {
+            let error = SprowlError::InvalidBitmapFont(format!("char {:?} goes past page {}", c, glyph.page));
+            for page in page_handles {
+                self.remove_texture(page);
+            }
+            return Err(error);
+        }
+        let font_id = self.current_font_id;
+        self.current_font_id += 1;
+        self.bitmap_fonts.insert(font_id, BitmapFont::new(fnt, page_handles, font_id));
+        self.registry.add_font(font_id);
+        Ok(font_id)
+    }
 
-        let grayscale_layer = self.texture_array_grayscale.add_empty_texture(2048, 2048);
+    /// Load a bitmap font from a .fnt file, like `add_bitmap_font`. The pages are read next to
+    /// it, from the file names it lists.
+    ///
+    /// Returns an error if a file can't be read, or if the font can't be added.
+    pub fn add_bitmap_font_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<FontId, SprowlError> {
+        let path = path.as_ref();
+        let fnt = std::fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let pages = font::parse_fnt(&fnt)?.pages.iter()
+            .map(|page| std::fs::read(dir.join(page)))
+            .collect::<Result<Vec<Vec<u8>>, _>>()?;
+        let pages: Vec<&[u8]> = pages.iter().map(Vec::as_slice).collect();
+        let font_id = self.add_bitmap_font(&fnt, &pages)?;
+        if let Some(file_name) = path.file_name() {
+            self.set_font_name(font_id, file_name.to_string_lossy());
+        }
+        Ok(font_id)
+    }
 
-        let _v = self.fonts.insert(self.current_font_id, FontRenderer::new(font, grayscale_layer));
+    fn add_font(&mut self, font: Font<'static>) -> FontId {
+        let _v = self.fonts.insert(self.current_font_id, FontRenderer::new(font, self.current_font_id));
         debug_assert!(_v.is_none());
         let font_id = self.current_font_id;
         self.current_font_id += 1;
+        self.registry.add_font(font_id);
         font_id
     }
 
     /// Load a texture from bytes: you must specify the correct width and height of the texture.
     ///
+    /// Textures smaller than `ATLAS_MAX_SPRITE_SIZE` are packed with other small textures in a
+    /// shared layer, bigger textures get a layer for themselves. Either way, the returned handle
+    /// tells you where the texture has been put, including its tier: see `add_rgba_tier`.
+    ///
+    /// The texture array grows when it's full, see `Texture2DArray::set_growable`. Returns an
+    /// error if it can't.
+    ///
     /// # Panics
     ///
     /// * (debug only) if the size is incorrect (higher than the slice's)
-    /// * (debug only) if the amount of textures  recorded is higher than u32::MAX_VALUE
-    pub fn add_texture_from_raw_bytes(&mut self, bytes: &[u8], size: (u32, u32)) -> TextureArrayLayer {
-        self.texture_array_rgba.add_texture(bytes, size.0, size.1)
+    pub fn add_texture_from_raw_bytes(&mut self, bytes: &[u8], size: (u32, u32)) -> Result<TextureHandle, SprowlError> {
+        let handle = tiers::allocate_texture(&mut self.rgba_tiers, size)?;
+        self.rgba_tiers[handle.array as usize].upload(&handle, bytes);
+        self.registry.add_texture(handle);
+        Ok(handle)
     }
 
-    /// Load a texture from some bytes. Preferably, the image should be PNG with an alpha layer.
-    /// Returns a number representing the ID of the texture, which you can use later on in `draw(..)`
+    /// Reserve room for a texture now, but only upload it when the upload budget allows, during
+    /// one of the next `process_uploads`. `Critical` textures are uploaded right away.
+    ///
+    /// The returned handle can be used right away to compute your crops, but the texture should
+    /// not be drawn until `is_uploaded` returns true.
+    ///
+    /// Returns an error if there is no room left for the texture.
     ///
     /// # Panics
-    /// 
-    /// Panics if the image is not RGBA (the texture has no alpha layer)
-    pub fn add_texture_from_image_bytes(&mut self, bytes: &[u8], image_format: Option<image::ImageFormat>) -> image::ImageResult<u32> {
+    ///
+    /// * (debug only) if the size is incorrect (higher than the bytes')
+    pub fn queue_texture(&mut self, bytes: Vec<u8>, size: (u32, u32), priority: UploadPriority) -> Result<TextureHandle, SprowlError> {
+        debug_assert!(bytes.len() >= size.0 as usize * size.1 as usize * 4);
+        let handle = tiers::allocate_texture(&mut self.rgba_tiers, size)?;
+        if priority == UploadPriority::Critical {
+            self.rgba_tiers[handle.array as usize].upload(&handle, &bytes);
+        } else {
+            self.uploads.push(PendingUpload { handle, bytes }, priority);
+        }
+        self.registry.add_texture(handle);
+        Ok(handle)
+    }
+
+    /// Upload some of the textures queued with `queue_texture`, within the budget set by
+    /// `set_upload_budget`. Call this once per frame.
+    ///
+    /// Textures of `load_texture_async` move forward by one step, and their copy to a pixel buffer
+    /// object shares the same budget.
+    ///
+    /// Glyphs are never queued: they are always uploaded as soon as they are needed.
+    pub fn process_uploads(&mut self) -> UploadStats {
+        let rgba_tiers = &mut self.rgba_tiers;
+        let mut stats = self.uploads.process(|upload| {
+            rgba_tiers[upload.handle.array as usize].upload(&upload.handle, &upload.bytes);
+        });
+        let registry = &mut self.registry;
+        self.async_uploads.process(rgba_tiers, self.uploads.budget.max_bytes, &mut stats, |handle, options| {
+            registry.add_texture(handle);
+            if let Some(entry) = registry.texture_mut(&handle) {
+                entry.load_options = options;
+            }
+        });
+        stats
+    }
+
+    /// Decode an image (PNG, JPEG, ...) on a worker thread, and upload it over the next calls to
+    /// `process_uploads` without stalling: a decoded image is copied to a pixel buffer object,
+    /// the copy to the texture is issued the frame after, and it's ready once the GPU is done.
+    ///
+    /// Images are decoded one after the other, by a single worker thread started by the first
+    /// load. Images which are not RGBA are converted. Poll `texture_load_state` until the texture is
+    /// `Ready`, and call `finish_texture_load` to forget about the load.
+    pub fn load_texture_async(&mut self, bytes: Vec<u8>, image_format: Option<image::ImageFormat>) -> TextureLoadHandle {
+        self.load_texture_async_with_options(bytes, image_format, &TextureLoadOptions::default())
+    }
+
+    /// Same as `load_texture_async`, but the pixels are converted according to `options` once
+    /// decoded, see `add_texture_from_image_bytes_with_options`.
+    pub fn load_texture_async_with_options(&mut self, bytes: Vec<u8>, image_format: Option<image::ImageFormat>, options: &TextureLoadOptions) -> TextureLoadHandle {
+        self.async_uploads.load(bytes, image_format, *options)
+    }
+
+    pub fn texture_load_state(&self, load: TextureLoadHandle) -> TextureLoadState {
+        self.async_uploads.state(load)
+    }
+
+    /// Returns the texture or the error of a finished load, and forgets about it. Returns None if
+    /// the load is not finished, or was already forgotten.
+    pub fn finish_texture_load(&mut self, load: TextureLoadHandle) -> Option<Result<TextureHandle, SprowlError>> {
+        self.async_uploads.finish(load)
+    }
+
+    pub fn set_upload_budget(&mut self, budget: UploadBudget) {
+        self.uploads.budget = budget;
+    }
+
+    /// Returns false if the texture is still waiting in the upload queue.
+    pub fn is_uploaded(&self, handle: TextureHandle) -> bool {
+        !self.uploads.is_pending(&handle)
+    }
+
+    /// Number of textures waiting in the upload queue, or being loaded by `load_texture_async`.
+    pub fn pending_uploads(&self) -> usize {
+        self.uploads.len() + self.async_uploads.len()
+    }
+
+    /// Load a texture from some bytes (PNG, JPEG, ...). Images which are not RGBA (JPEG, paletted
+    /// or grayscale PNG, ...) are converted, and are fully opaque if they have no alpha layer.
+    /// Returns a handle representing the texture, which you can use later on in `draw(..)`
+    ///
+    /// Returns an error if the image can't be decoded, or if there is no room left for it.
+    pub fn add_texture_from_image_bytes(&mut self, bytes: &[u8], image_format: Option<image::ImageFormat>) -> Result<TextureHandle, SprowlError> {
+        self.add_texture_from_image_bytes_with_options(bytes, image_format, &TextureLoadOptions::default())
+    }
+
+    /// Same as `add_texture_from_image_bytes`, but the pixels are converted according to
+    /// `options` first.
+    ///
+    /// ```ignore
+    /// let options = TextureLoadOptions::new().with_color_key(Color::from_rgb(255, 0, 255));
+    /// let sprite = storage.add_texture_from_image_bytes_with_options(&bytes, None, &options)?;
+    /// ```
+    pub fn add_texture_from_image_bytes_with_options(&mut self, bytes: &[u8], image_format: Option<image::ImageFormat>, options: &TextureLoadOptions) -> Result<TextureHandle, SprowlError> {
         let opened_image = match image_format {
             Some(image_format) => image::load_from_memory_with_format(bytes, image_format),
             None => image::load_from_memory(bytes)
@@ -85,43 +441,660 @@ impl RenderStorage {
         let img_w = opened_image.width();
         let img_h = opened_image.height();
 
-        let color_data: Vec<u8> = if let image::DynamicImage::ImageRgba8(rgba_data) = opened_image {
-            rgba_data.into_raw()
-        } else {
-            panic!("image loaded from memory is not RGBA");
+        // no copy if the image is already RGBA.
+        let mut color_data: Vec<u8> = opened_image.into_rgba8().into_raw();
+        options.apply(&mut color_data);
+        let handle = self.add_texture_from_raw_bytes(color_data.as_slice(), (img_w, img_h))?;
+        if let Some(entry) = self.registry.texture_mut(&handle) {
+            entry.load_options = *options;
+        }
+        Ok(handle)
+    }
+
+    /// Load a texture from an image file, like `add_texture_from_image_bytes`. The format is
+    /// guessed from the content of the file.
+    ///
+    /// Returns an error if the file can't be read or decoded, or if there is no room left for it.
+    pub fn add_texture_from_image_path<P: AsRef<Path>>(&mut self, path: P) -> Result<TextureHandle, SprowlError> {
+        self.add_texture_from_image_path_with_options(path, &TextureLoadOptions::default())
+    }
+
+    /// Same as `add_texture_from_image_path`, but the pixels are converted according to `options`
+    /// first, see `add_texture_from_image_bytes_with_options`.
+    pub fn add_texture_from_image_path_with_options<P: AsRef<Path>>(&mut self, path: P, options: &TextureLoadOptions) -> Result<TextureHandle, SprowlError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let handle = self.add_texture_from_image_bytes_with_options(&bytes, None, options)?;
+        if let Some(file_name) = path.file_name() {
+            self.set_texture_name(handle, file_name.to_string_lossy());
+        }
+        Ok(handle)
+    }
+
+    /// Replace the pixels of a texture by the image in `bytes`, in place: the handle stays valid.
+    /// The `TextureLoadOptions` the texture was loaded with are applied again.
+    ///
+    /// Returns an error if the image can't be decoded, or if its size is not the size of the
+    /// texture.
+    pub fn reload_texture_from_image_bytes(&mut self, handle: TextureHandle, bytes: &[u8], image_format: Option<image::ImageFormat>) -> Result<(), SprowlError> {
+        let opened_image = match image_format {
+            Some(image_format) => image::load_from_memory_with_format(bytes, image_format),
+            None => image::load_from_memory(bytes)
+        }?;
+        let size = (opened_image.width(), opened_image.height());
+        if size != handle.rect.size() {
+            return Err(SprowlError::TextureSizeMismatch { expected: handle.rect.size(), found: size });
+        }
+        let mut color_data: Vec<u8> = opened_image.into_rgba8().into_raw();
+        if let Some(entry) = self.registry.texture_mut(&handle) {
+            entry.load_options.apply(&mut color_data);
+        }
+        // an upload still in the queue would overwrite the new pixels.
+        self.uploads.cancel(&handle);
+        self.rgba_tiers[handle.array as usize].upload(&handle, &color_data);
+        Ok(())
+    }
+
+    /// Same as `reload_texture_from_image_bytes`, with the content of a file. See also
+    /// `watcher::AssetWatcher` to reload textures when their file changes.
+    pub fn reload_texture_from_path<P: AsRef<Path>>(&mut self, handle: TextureHandle, path: P) -> Result<(), SprowlError> {
+        let bytes = std::fs::read(path)?;
+        self.reload_texture_from_image_bytes(handle, &bytes, None)
+    }
+
+    /// Load every image of a directory (not its sub-directories), like
+    /// `add_texture_from_image_path`. Files whose extension is not a known image format are
+    /// skipped.
+    ///
+    /// Returns the textures by file name without the extension ("player" for "player.png").
+    /// Returns an error if the directory or one of the images can't be read or decoded, or if
+    /// there is no room left: the textures loaded before that are kept.
+    pub fn add_textures_from_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<std::collections::HashMap<String, TextureHandle>, SprowlError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && image::ImageFormat::from_path(&path).is_ok() {
+                paths.push(path);
+            }
+        }
+        // the order of read_dir depends on the platform, and the order of the packing matters.
+        paths.sort();
+
+        let mut textures = std::collections::HashMap::with_capacity(paths.len());
+        for path in paths {
+            let handle = self.add_texture_from_image_path(&path)?;
+            if let Some(file_stem) = path.file_stem() {
+                textures.insert(file_stem.to_string_lossy().into_owned(), handle);
+            }
+        }
+        Ok(textures)
+    }
+
+    /// Load a compressed texture from a DDS (BC1/DXT1, BC3/DXT5) or KTX2 (BC1, BC3, ETC2) file,
+    /// along with its mipmaps if it has all of them.
+    ///
+    /// The texture is uploaded as is, in a texture array of its format and size created when
+    /// needed, and bound to `CompressedTextureHandle::texture_unit`. The filtering options are
+    /// the ones of the RGBA textures.
+    ///
+    /// Returns an error if the file can't be parsed, or if there is no room left for it.
+    pub fn add_compressed_texture(&mut self, bytes: &[u8]) -> Result<CompressedTextureHandle, SprowlError> {
+        let image = compressed::parse(bytes)?;
+        let settings = self.rgba_tiers[0].texture_array.settings();
+        let has_mipmaps = image.levels.len() as i32 >= settings.with_mipmaps(true).mip_levels(image.width, image.height);
+        let settings = settings.with_mipmaps(settings.mipmaps && has_mipmaps);
+
+        let existing = self.compressed_arrays.iter().position(|array| {
+            array.format == image.format
+                && (array.max_width, array.max_height) == (image.width, image.height)
+                && array.settings().mipmaps == settings.mipmaps
+        });
+        let array = match existing {
+            Some(array) => array,
+            None => {
+                let mut texture_array = Texture2DArray::with_settings(image.width, image.height, 4, image.format, settings);
+                texture_array.set_active(FIRST_COMPRESSED_TEXTURE_UNIT + self.compressed_arrays.len() as u32);
+                self.compressed_arrays.push(texture_array);
+                self.compressed_arrays.len() - 1
+            }
         };
-        Ok(self.add_texture_from_raw_bytes(color_data.as_slice(), (img_w, img_h)))
+        let layer = self.compressed_arrays[array].add_compressed_texture(&image.levels, image.width, image.height)?;
+        Ok(CompressedTextureHandle { array, layer, width: image.width, height: image.height })
+    }
+
+    /// Remove a texture added with `add_compressed_texture`, so that its layer can be re-used.
+    pub fn remove_compressed_texture(&mut self, handle: CompressedTextureHandle) {
+        self.compressed_arrays[handle.array].remove_texture(handle.layer);
+    }
+
+    /// Remove a texture previously added with one of the `add_texture_*` methods.
+    ///
+    /// The space is recycled by the next added textures, so you must not draw it afterwards.
+    /// For packed textures, the shared layer is only recycled once all of its textures are removed.
+    pub fn remove_texture(&mut self, handle: TextureHandle) {
+        self.uploads.cancel(&handle);
+        self.registry.remove_texture(&handle);
+        self.rgba_tiers[handle.array as usize].remove(&handle);
     }
 
     pub fn get_font(&mut self, font_id: FontId) -> Option<&mut FontRenderer> {
         self.fonts.get_mut(&font_id)
     }
 
-    pub fn get_font_with_texture<'a>(&'a mut self, font_id: FontId) -> Option<(&'a mut FontRenderer, TextureArrayLayerRef<'a>)> {
+    pub fn get_font_ref(&self, font_id: FontId) -> Option<&FontRenderer> {
+        self.fonts.get(&font_id)
+    }
+
+    pub fn get_bitmap_font(&self, font_id: FontId) -> Option<&BitmapFont> {
+        self.bitmap_fonts.get(&font_id)
+    }
+
+    /// The error of the functions which need the outlines of `font_id`, which it doesn't have.
+    pub (crate) fn no_font_error(&self, font_id: FontId) -> SprowlError {
+        if self.bitmap_fonts.contains_key(&font_id) {
+            SprowlError::UnsupportedForBitmapFont(font_id)
+        } else {
+            SprowlError::MissingFontId(font_id)
+        }
+    }
+
+    /// The font to give to `AdvancedLayout`, whether it's a bitmap font or not.
+    pub fn layout_font(&self, font_id: FontId) -> Option<&dyn LayoutFont> {
+        match self.fonts.get(&font_id) {
+            Some(font_renderer) => Some(font_renderer.font()),
+            None => self.bitmap_fonts.get(&font_id).map(|font| font as &dyn LayoutFont),
+        }
+    }
+
+    /// Returns where to draw every glyph of `text`, with `FontRenderer::word_to_draw_call` or
+    /// `BitmapFont::word_to_draw_call` depending on the font.
+    ///
+    /// Returns an error if the font doesn't exist, or if the glyphs don't fit in the glyph atlas.
+    pub fn word_to_draw_call(&mut self, font_id: FontId, text: &str, font_size: f32) -> Result<Vec<FontStemDrawCall>, SprowlError> {
+        if let Some(font) = self.bitmap_fonts.get(&font_id) {
+            self.registry.mark_font_used(font_id);
+            return Ok(font.word_to_draw_call(text, font_size));
+        }
+        let (font, mut atlas_ref) = self.get_font_with_texture(font_id).ok_or(SprowlError::MissingFontId(font_id))?;
+        font.word_to_draw_call(&mut atlas_ref, text, font_size)
+    }
+
+    /// Same as `word_to_draw_call` for several words cached at once, see
+    /// `FontRenderer::words_to_draw_calls`.
+    pub fn words_to_draw_calls<S: AsRef<str>>(&mut self, font_id: FontId, words: &[S], font_size: f32) -> Result<Vec<Vec<FontStemDrawCall>>, SprowlError> {
+        if let Some(font) = self.bitmap_fonts.get(&font_id) {
+            self.registry.mark_font_used(font_id);
+            return Ok(words.iter().map(|word| font.word_to_draw_call(word.as_ref(), font_size)).collect());
+        }
+        let (font, mut atlas_ref) = self.get_font_with_texture(font_id).ok_or(SprowlError::MissingFontId(font_id))?;
+        font.words_to_draw_calls(&mut atlas_ref, words, font_size)
+    }
+
+    /// Returns the font, along with the glyph atlas shared by all fonts to give to `word_to_draw_call`.
+    pub fn get_font_with_texture<'a>(&'a mut self, font_id: FontId) -> Option<(&'a mut FontRenderer, GlyphAtlasRef<'a>)> {
+        self.registry.mark_font_used(font_id);
         let texture_2d_array_ref = &mut self.texture_array_grayscale;
+        let glyph_atlas = &mut self.glyph_atlas;
+        let RgbaTier { texture_array, atlas_layers, alpha_mode } = &mut self.rgba_tiers[0];
+        let color_glyphs_ref = ColorGlyphAtlasRef {
+            atlas: &mut self.color_glyph_atlas,
+            texture_array,
+            atlas_layers,
+            alpha_mode: *alpha_mode,
+        };
         self.fonts.get_mut(&font_id).map(move |font_renderer| {
-            let texture_layer = font_renderer.texture_layer;
+            let texture_layer = glyph_atlas.texture_layer;
             (
                 font_renderer,
-                TextureArrayLayerRef::new(texture_2d_array_ref, texture_layer)
+                GlyphAtlasRef::new(glyph_atlas, TextureArrayLayerRef::new(texture_2d_array_ref, texture_layer))
+                    .with_color_glyphs(color_glyphs_ref)
             )
         })
     }
 
+    /// Cache the glyphs of `chars` at every size of `sizes` ahead of time, see
+    /// `FontRenderer::prewarm`.
+    ///
+    /// ```ignore
+    /// let ascii = (0x20u8..0x7F).map(char::from);
+    /// render_storage.prewarm_font(font_id, ascii.chain("éèàç".chars()), &[16.0, 24.0])?;
+    /// ```
+    ///
+    /// Does nothing for a bitmap font, whose glyphs are all in its pages already.
+    pub fn prewarm_font<I: IntoIterator<Item = char>>(&mut self, font_id: FontId, chars: I, sizes: &[f32]) -> Result<(), SprowlError> {
+        if self.bitmap_fonts.contains_key(&font_id) {
+            return Ok(());
+        }
+        let (font, mut atlas_ref) = self.get_font_with_texture(font_id).ok_or(SprowlError::MissingFontId(font_id))?;
+        font.prewarm(&mut atlas_ref, chars, sizes)
+    }
+
+    /// Place glyphs at fractional positions instead of whole pixels, so that slowly scrolling
+    /// text doesn't jitter: see `GlyphAtlas::set_subpixel_positioning`. Enabling it also makes
+    /// the grayscale array filter linearly (the default of `RenderStorage::new`), which glyphs
+    /// drawn between pixels need; disabling it leaves the filtering as it is.
+    ///
+    /// Clears the glyph atlas. Bitmap fonts, color and MSDF glyphs aren't affected.
+    pub fn set_subpixel_text(&mut self, subpixel: bool) {
+        self.glyph_atlas.set_subpixel_positioning(subpixel);
+        if subpixel {
+            let settings = self.texture_settings(TextureKind::Grayscale);
+            self.set_texture_settings(TextureKind::Grayscale, TextureArraySettings {
+                min_filter: TextureFilter::Linear,
+                mag_filter: TextureFilter::Linear,
+                ..settings
+            });
+        }
+    }
+
+    /// Returns the font, along with the MSDF atlas to give to `word_to_draw_call_msdf`.
+    ///
+    /// Returns None for a bitmap font, which has no outlines to compute distances from.
+    pub fn get_font_with_msdf<'a>(&'a mut self, font_id: FontId) -> Option<(&'a mut FontRenderer, MsdfAtlasRef<'a>)> {
+        self.registry.mark_font_used(font_id);
+        // MSDF glyphs are distances, never premultiplied.
+        let RgbaTier { texture_array, atlas_layers, .. } = &mut self.rgba_tiers[0];
+        let msdf_ref = MsdfAtlasRef {
+            atlas: &mut self.msdf_atlas,
+            texture_array,
+            atlas_layers,
+        };
+        self.fonts.get_mut(&font_id).map(move |font_renderer| (font_renderer, msdf_ref))
+    }
+
+    /// Returns where to draw the glyphs of a word laid out by `AdvancedLayout::new_rich`, with the
+    /// font and size of its span. The color is up to you: see `RichText::span`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the word doesn't come from this `rich_text`.
+    pub fn rich_word_to_draw_call(&mut self, rich_text: &RichText<'_>, word: &WordPos<'_>) -> Result<Vec<FontStemDrawCall>, SprowlError> {
+        let span = rich_text.spans()[word.span_index];
+        self.word_to_draw_call(span.font_id, &word.visual_word(), span.font_size)
+    }
+
+    /// Rasterize `text` once into the RGBA textures, so that text which never changes (HUD
+    /// labels, menus) is drawn as a single quad instead of one per glyph. The texture is white on
+    /// a transparent background: tint it with the color of the quad.
+    ///
+    /// Lines are separated by `\n`, and aligned to the left. The texture is named after the
+    /// text in `iter_textures`; call `remove_texture` once the text isn't needed anymore.
+    ///
+    /// Returns an error if the font doesn't exist or is a bitmap font, or if there is no room
+    /// left for the texture.
+    pub fn bake_text(&mut self, font_id: FontId, text: &str, font_size: f32) -> Result<TextureHandle, SprowlError> {
+        let font = self.fonts.get(&font_id).ok_or_else(|| self.no_font_error(font_id))?;
+        let (pixels, size) = font.rasterize_text(text, font_size);
+        self.registry.mark_font_used(font_id);
+        let handle = self.add_texture_from_raw_bytes(&pixels, size)?;
+        self.set_texture_name(handle, text);
+        Ok(handle)
+    }
+
+    /// Give a name to a texture, shown by `iter_textures`.
+    pub fn set_texture_name<S: Into<String>>(&mut self, handle: TextureHandle, name: S) {
+        if let Some(entry) = self.registry.texture_mut(&handle) {
+            entry.name = Some(name.into());
+        }
+    }
+
+    /// Give a name to a font, shown by `iter_fonts`.
+    pub fn set_font_name<S: Into<String>>(&mut self, font_id: FontId, name: S) {
+        if let Some(entry) = self.registry.fonts.get_mut(&font_id) {
+            entry.name = Some(name.into());
+        }
+    }
+
+    /// Load a texture like `add_texture_from_image_bytes`, and register it under `key`. The key is
+    /// also its name for `iter_textures`.
+    ///
+    /// Returns an error if the key is already taken (before loading anything), or if the texture
+    /// can't be loaded.
+    pub fn add_texture_named<S: Into<String>>(&mut self, key: S, bytes: &[u8], image_format: Option<image::ImageFormat>) -> Result<TextureHandle, SprowlError> {
+        let key = key.into();
+        if self.registry.keys.contains_key(&key) {
+            return Err(SprowlError::DuplicateAssetKey(key));
+        }
+        let handle = self.add_texture_from_image_bytes(bytes, image_format)?;
+        self.register_texture(key, handle)?;
+        Ok(handle)
+    }
+
+    /// Load a font like `add_font_from_vec`, and register it under `key`. The key is also its name
+    /// for `iter_fonts`.
+    ///
+    /// Returns an error if the key is already taken (before loading anything), or if the bytes are
+    /// not a valid font.
+    pub fn add_font_named<S: Into<String>>(&mut self, key: S, bytes: Vec<u8>) -> Result<FontId, SprowlError> {
+        let key = key.into();
+        if self.registry.keys.contains_key(&key) {
+            return Err(SprowlError::DuplicateAssetKey(key));
+        }
+        let font_id = self.add_font_from_vec(bytes)?;
+        self.register_font(key, font_id)?;
+        Ok(font_id)
+    }
+
+    /// Register a texture which was already added under `key`, to find it later with `layer_of`.
+    /// The key is also its name for `iter_textures`.
+    ///
+    /// The key is freed when the texture is removed. Returns an error if the key is already taken.
+    pub fn register_texture<S: Into<String>>(&mut self, key: S, handle: TextureHandle) -> Result<(), SprowlError> {
+        let key = key.into();
+        self.registry.register(key.clone(), NamedAsset::Texture(handle))?;
+        self.set_texture_name(handle, key);
+        Ok(())
+    }
+
+    /// Register a font which was already added under `key`, to find it later with `font_of`.
+    /// The key is also its name for `iter_fonts`.
+    ///
+    /// Returns an error if the key is already taken.
+    pub fn register_font<S: Into<String>>(&mut self, key: S, font_id: FontId) -> Result<(), SprowlError> {
+        let key = key.into();
+        self.registry.register(key.clone(), NamedAsset::Font(font_id))?;
+        self.set_font_name(font_id, key);
+        Ok(())
+    }
+
+    /// Register a part of `texture` under `key`, in pixels relative to the texture (x, y, w, h),
+    /// typically a sprite of a spritesheet whose crops come from a data file. Draw it with
+    /// `QuadInstance::with_region`, after finding it with `region_of`.
+    ///
+    /// The key is freed when the texture is removed. Returns an error if the key is already
+    /// taken, or if the crop goes past the texture.
+    pub fn define_region<S: Into<String>>(&mut self, texture: TextureHandle, key: S, crop: (u32, u32, u32, u32)) -> Result<TextureRegion, SprowlError> {
+        let key = key.into();
+        if !texture.contains_crop(crop) {
+            return Err(SprowlError::RegionOutOfBounds(key));
+        }
+        let region = texture.region(crop);
+        self.registry.register(key, NamedAsset::Region(region))?;
+        Ok(region)
+    }
+
+    /// Read the metadata of a spritesheet exported by TexturePacker or Aseprite, whose image was
+    /// added as `texture`, and register every frame as a region under `key_prefix` followed by
+    /// its name, see `define_region`. Its animations are returned as clips, see `sheet`.
+    ///
+    /// Returns an error if the metadata can't be read, or if one of the keys is already taken, in
+    /// which case nothing is registered.
+    #[cfg(feature = "sheet-import")]
+    pub fn import_sprite_sheet(&mut self, texture: TextureHandle, json: &str, key_prefix: &str) -> Result<sheet::SpriteSheet, SprowlError> {
+        let sheet = sheet::parse_sprite_sheet(texture, json)?;
+        let keys: Vec<String> = sheet.regions.keys().map(|name| format!("{}{}", key_prefix, name)).collect();
+        if let Some(key) = keys.iter().find(|key| self.registry.keys.contains_key(key.as_str())) {
+            return Err(SprowlError::DuplicateAssetKey(key.clone()));
+        }
+        for (key, region) in keys.into_iter().zip(sheet.regions.values()) {
+            self.registry.register(key, NamedAsset::Region(*region))?;
+        }
+        Ok(sheet)
+    }
+
+    /// Returns the region registered under `key` with `define_region`, or None if there is no
+    /// region under this key.
+    pub fn region_of(&self, key: &str) -> Option<TextureRegion> {
+        match self.registry.keys.get(key) {
+            Some(NamedAsset::Region(region)) => Some(*region),
+            _ => None,
+        }
+    }
+
+    /// Forget a key, without removing its asset. Returns the asset it was registered for, if any.
+    pub fn unregister(&mut self, key: &str) -> Option<NamedAsset> {
+        self.registry.keys.remove(key)
+    }
+
+    /// Returns the texture registered under `key`, with its layer and where it is in the layer.
+    ///
+    /// Returns None if there is no texture under this key.
+    pub fn layer_of(&self, key: &str) -> Option<TextureHandle> {
+        match self.registry.keys.get(key) {
+            Some(NamedAsset::Texture(handle)) => Some(*handle),
+            _ => None,
+        }
+    }
+
+    /// Returns the font registered under `key`, or None if there is no font under this key.
+    pub fn font_of(&self, key: &str) -> Option<FontId> {
+        match self.registry.keys.get(key) {
+            Some(NamedAsset::Font(font_id)) => Some(*font_id),
+            _ => None,
+        }
+    }
+
+    /// Iterate over the registered keys and their assets, in the order they were registered.
+    pub fn iter_named_assets(&self) -> impl Iterator<Item = (&str, NamedAsset)> + '_ {
+        self.registry.keys.iter().map(|(key, asset)| (key.as_str(), *asset))
+    }
+
+    /// Start a new frame, for the `last_used_frame` of `iter_textures` and `iter_fonts`.
+    /// Returns the new frame number.
+    pub fn next_frame(&mut self) -> u64 {
+        self.registry.frame += 1;
+        self.registry.frame
+    }
+
+    /// Record that the texture is drawn this frame. Textures are drawn by your own vertex data,
+    /// so sprowl can't know it by itself.
+    pub fn mark_texture_used(&mut self, handle: TextureHandle) {
+        let frame = self.registry.frame;
+        if let Some(entry) = self.registry.texture_mut(&handle) {
+            entry.last_used_frame = Some(frame);
+        }
+    }
+
+    /// Iterate over all the textures added by the user, in the order they were added.
+    ///
+    /// Glyphs (MSDF or not) are not included, see `iter_fonts` instead.
+    pub fn iter_textures(&self) -> impl Iterator<Item = TextureInfo<'_>> + '_ {
+        self.registry.textures.values().map(move |(handle, entry)| {
+            TextureInfo {
+                handle: *handle,
+                name: entry.name.as_deref(),
+                size: handle.rect.size(),
+                kind: TextureKind::RGBA,
+                layer: handle.layer,
+                flags: AssetFlags {
+                    packed: self.rgba_tiers[handle.array as usize].is_packed(handle),
+                    pending_upload: self.uploads.is_pending(handle),
+                    msdf: false,
+                },
+                last_used_frame: entry.last_used_frame,
+            }
+        })
+    }
+
+    /// Iterate over all the fonts, by increasing `FontId`.
+    pub fn iter_fonts(&self) -> impl Iterator<Item = FontInfo<'_>> + '_ {
+        (0..self.current_font_id).filter_map(move |font_id| {
+            let entry = self.registry.fonts.get(&font_id)?;
+            if let Some(bitmap_font) = self.bitmap_fonts.get(&font_id) {
+                let first_page = bitmap_font.pages().first();
+                return Some(FontInfo {
+                    font_id,
+                    name: entry.name.as_deref(),
+                    glyph_count: bitmap_font.fnt().glyphs.len(),
+                    kind: TextureKind::RGBA,
+                    layer: first_page.map_or(0, |page| page.layer),
+                    flags: AssetFlags {
+                        packed: first_page.is_some_and(|page| self.rgba_tiers[page.array as usize].is_packed(page)),
+                        pending_upload: bitmap_font.pages().iter().any(|page| self.uploads.is_pending(page)),
+                        msdf: false,
+                    },
+                    last_used_frame: entry.last_used_frame,
+                });
+            }
+            let font_renderer = self.fonts.get(&font_id)?;
+            Some(FontInfo {
+                font_id,
+                name: entry.name.as_deref(),
+                glyph_count: font_renderer.font().glyph_count(),
+                kind: TextureKind::Grayscale,
+                layer: self.glyph_atlas.texture_layer,
+                flags: AssetFlags {
+                    packed: true,
+                    pending_upload: false,
+                    msdf: self.msdf_atlas.glyphs.keys().any(|(glyph_font_id, _)| *glyph_font_id == font_id),
+                },
+                last_used_frame: entry.last_used_frame,
+            })
+        })
+    }
+
+    /// Occupancy of the texture arrays and of the glyph atlas, see `debug::DebugOverlay`.
+    pub fn storage_stats(&self) -> StorageStats {
+        StorageStats {
+            rgba: self.rgba_tiers.iter().map(|tier| tier.texture_array.array_stats()).collect(),
+            grayscale: self.texture_array_grayscale.array_stats(),
+            compressed: self.compressed_arrays.iter().map(Texture2DArray::array_stats).collect(),
+            glyph_cache: self.glyph_atlas.stats(),
+            msdf_glyphs: self.msdf_atlas.glyphs.len(),
+        }
+    }
+
+    /// Characters missing from `font_id` are looked for in `fallbacks`, in order, by `shape_text`.
+    #[cfg(feature = "shaping")]
+    pub fn set_fallback_fonts(&mut self, font_id: FontId, fallbacks: Vec<FontId>) {
+        self.shaping_fonts.set_fallbacks(font_id, fallbacks);
+    }
+
+    /// Like `FontRenderer::word_to_draw_call`, but the text is shaped first: ligatures,
+    /// kerning, combining marks and complex scripts are supported, and characters missing from
+    /// the font are taken from its fallback fonts.
+    ///
+    /// `character_index` of the results is the index of the first character of the glyph, since
+    /// a glyph can represent several characters (ligatures) and the other way around.
+    ///
+    /// A bitmap font isn't shaped: this is the same as `word_to_draw_call` with its kerning.
+    #[cfg(feature = "shaping")]
+    pub fn shape_text(&mut self, font_id: FontId, text: &str, font_size: f32) -> Result<Vec<FontStemDrawCall>, SprowlError> {
+        if self.bitmap_fonts.contains_key(&font_id) {
+            return self.word_to_draw_call(font_id, text, font_size);
+        }
+        self.registry.mark_font_used(font_id);
+        loop {
+            let texture = TextureArrayLayerRef::new(&mut self.texture_array_grayscale, self.glyph_atlas.texture_layer);
+            match shaping::shape_text(&self.shaping_fonts, &self.fonts, &mut self.glyph_atlas, texture, font_id, text, font_size) {
+                Err(SprowlError::GlyphCache(e)) => {
+                    let mut texture = TextureArrayLayerRef::new(&mut self.texture_array_grayscale, self.glyph_atlas.texture_layer);
+                    if !font::grow_glyph_atlas(&mut self.glyph_atlas, &mut texture) {
+                        return Err(SprowlError::GlyphCache(e));
+                    }
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Same as `set_active`, but the texture bindings (and the rest of the GL state) of the
+    /// application are restored when the returned guard is dropped, see `gl_state`.
+    pub fn set_active_scoped(&mut self) -> GlStateGuard {
+        let guard = GlStateGuard::new();
+        self.set_active();
+        guard
+    }
+
     pub fn set_active(&mut self) {
-        self.texture_array_rgba.set_active(0);
-        self.texture_array_grayscale.set_active(1);
+        for (tier, rgba_tier) in self.rgba_tiers.iter_mut().enumerate() {
+            rgba_tier.texture_array.set_active(tiers::rgba_tier_texture_unit(tier as u32));
+        }
+        self.texture_array_grayscale.set_active(TextureKind::Grayscale.texture_unit());
+        self.palettes.set_active(PALETTE_TEXTURE_UNIT);
+        for (i, texture_array) in self.compressed_arrays.iter_mut().enumerate() {
+            texture_array.set_active(FIRST_COMPRESSED_TEXTURE_UNIT + i as u32);
+        }
+    }
+
+    /// Add a palette of up to `MAX_PALETTE_COLORS` colors, see the `palette` module. Missing
+    /// colors are transparent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `MAX_PALETTE_COLORS` colors
+    pub fn add_palette(&mut self, colors: &[Color<u8>]) -> PaletteId {
+        self.palettes.add(colors)
+    }
+
+    /// Replace the colors of a palette, for instance to animate them.
+    ///
+    /// # Panics
+    ///
+    /// * if the palette doesn't exist
+    /// * if there are more than `MAX_PALETTE_COLORS` colors
+    pub fn set_palette(&mut self, palette: PaletteId, colors: &[Color<u8>]) {
+        self.palettes.set(palette, colors);
+    }
+
+    /// Returns the part of its layer used by the texture, in pixels.
+    pub fn get_stats(&self, handle: TextureHandle) -> TextureRect {
+        debug_assert!((handle.layer as usize) < self.rgba_tiers[handle.array as usize].texture_array.stats.len(), "layer {} was never allocated", handle.layer);
+        handle.rect
+    }
+
+    pub fn texture_settings(&self, texture_kind: TextureKind) -> TextureArraySettings {
+        match texture_kind {
+            TextureKind::Grayscale => self.texture_array_grayscale.settings(),
+            TextureKind::RGBA => self.rgba_tiers[0].texture_array.settings(),
+        }
     }
 
-    pub fn get_stats(&self, layer: TextureArrayLayer) -> TextureLayerStats {
-        self.texture_array_rgba.stats[layer as usize]
+    /// Change the filtering options of one of the texture arrays, see `Texture2DArray::set_settings`.
+    pub fn set_texture_settings(&mut self, texture_kind: TextureKind, settings: TextureArraySettings) {
+        match texture_kind {
+            TextureKind::Grayscale => self.texture_array_grayscale.set_settings(settings),
+            TextureKind::RGBA => for rgba_tier in &mut self.rgba_tiers {
+                rgba_tier.texture_array.set_settings(settings);
+            },
+        }
+    }
+
+    /// Change the mipmap level the GPU picks for the textures of one of the arrays, see
+    /// `TextureArraySettings::lod_bias` and `TextureArraySettings::lod_range`. Only useful if the
+    /// array has mipmaps.
+    ///
+    /// This changes every draw: to change it for some draws only, use a `Sampler` with these
+    /// settings, see `Renderer::with_sampler`.
+    pub fn set_texture_lod(&mut self, texture_kind: TextureKind, lod_bias: f32, lod_range: (f32, f32)) {
+        let settings = self.texture_settings(texture_kind);
+        self.set_texture_settings(texture_kind, settings.with_lod_bias(lod_bias).with_lod_range(lod_range.0, lod_range.1));
     }
 
     pub fn get_max_dims(&self, texture_kind: TextureKind) -> (u32, u32) {
         let t = match texture_kind {
             TextureKind::Grayscale => &self.texture_array_grayscale,
-            TextureKind::RGBA => &self.texture_array_rgba,
+            TextureKind::RGBA => &self.rgba_tiers[0].texture_array,
         };
         (t.max_width, t.max_height)
     }
+
+    /// Add an RGBA texture array with layers of another size, see `tiers`. Textures added
+    /// afterwards go to the tier with the smallest layers they fit in.
+    ///
+    /// Returns the index of the tier, or an error if there are already `MAX_RGBA_TIERS` tiers or
+    /// if the GL context can't allocate the array.
+    pub fn add_rgba_tier(&mut self, config: TextureArrayConfig) -> Result<u32, SprowlError> {
+        if self.rgba_tiers.len() >= MAX_RGBA_TIERS {
+            return Err(SprowlError::InvalidStorageConfig(format!("there are already {} RGBA tiers", MAX_RGBA_TIERS)));
+        }
+        config.validate_rgba()?;
+        let tier = self.rgba_tiers.len() as u32;
+        let mut texture_array = Texture2DArray::with_settings(config.width, config.height, config.layers, TextureFormat::RGBA, config.settings);
+        texture_array.set_active(tiers::rgba_tier_texture_unit(tier));
+        let alpha_mode = self.alpha_mode();
+        self.rgba_tiers.push(RgbaTier::new(texture_array, alpha_mode));
+        Ok(tier)
+    }
+
+    /// See `RenderStorageConfig::with_alpha_mode`.
+    #[inline]
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.rgba_tiers[0].alpha_mode
+    }
+
+    /// Size of the layers of every RGBA tier, by index.
+    pub fn rgba_tier_dims(&self) -> Vec<(u32, u32)> {
+        self.rgba_tiers.iter().map(RgbaTier::layer_dims).collect()
+    }
 }
\ No newline at end of file
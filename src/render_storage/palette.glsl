@@ -0,0 +1,11 @@
+// Remaps indexed colors with the palettes of `RenderStorage::add_palette`.
+//
+// `palettes` is the sampler2D bound to `PALETTE_TEXTURE_UNIT`, and `palette` the id of the
+// palette, typically a `flat uint` passed from an instanced attrib. The red channel of `indexed`
+// holds the index of the color in the palette, from 0 to 255: store indexed textures as RGBA with
+// the index in the red channel. The alpha of `indexed` multiplies the alpha of the palette color.
+vec4 palette_color(sampler2D palettes, uint palette, vec4 indexed) {
+    int index = int(indexed.r * 255.0 + 0.5);
+    vec4 color = texelFetch(palettes, ivec2(index, int(palette)), 0);
+    return vec4(color.rgb, color.a * indexed.a);
+}
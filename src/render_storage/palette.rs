@@ -0,0 +1,170 @@
+//! Palettes for palette swapping: indexed textures are drawn with the colors of a palette chosen
+//! per instance, so a single spritesheet can be used for every variant of a character.
+//!
+//! Every palette is a row of a small RGBA texture, bound to `PALETTE_TEXTURE_UNIT` by
+//! `RenderStorage::set_active`. Read it in your fragment shader with `PALETTE_GLSL`, and give the
+//! id of the palette as an instanced attrib, see `with_palette_field`.
+
+use crate::backend::gl::{self, types::*};
+use crate::color::Color;
+use crate::renderer::VertexLayout;
+use std::{
+    mem::MaybeUninit,
+    os::raw::c_void,
+};
+
+/// The palette texture is bound to this texture unit, after the RGBA (0) and grayscale (1) arrays.
+pub const PALETTE_TEXTURE_UNIT: GLuint = 2;
+
+/// A palette holds at most this many colors, indexed from 0.
+pub const MAX_PALETTE_COLORS: usize = 256;
+
+/// A shader function to remap indexed colors, see the comments in the source.
+pub const PALETTE_GLSL: &str = include_str!("palette.glsl");
+
+/// A palette added by `RenderStorage::add_palette`: the row of the palette texture.
+pub type PaletteId = u32;
+
+/// Add the field "palette", a `uint` holding a `PaletteId`, to `layout`.
+pub fn with_palette_field(layout: VertexLayout) -> VertexLayout {
+    layout.with_field("palette", 1, gl::UNSIGNED_INT)
+}
+
+/// Every palette, one per row of a `MAX_PALETTE_COLORS` wide texture.
+#[derive(Debug)]
+pub struct PaletteTexture {
+    id: GLuint,
+    /// RGBA colors of every palette, padded with transparent black
+    colors: Vec<u8>,
+    /// rows allocated on the GPU
+    capacity: u32,
+    active_unit: Option<GLuint>,
+}
+
+const ROW_SIZE: usize = MAX_PALETTE_COLORS * 4;
+
+impl PaletteTexture {
+    pub fn new() -> PaletteTexture {
+        let mut id: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let id = unsafe {
+            gl::GenTextures(1, id.as_mut_ptr());
+            id.assume_init()
+        };
+        let mut palette_texture = PaletteTexture {
+            id,
+            colors: Vec::new(),
+            capacity: 0,
+            active_unit: None,
+        };
+        palette_texture.reallocate(4);
+        palette_texture
+    }
+
+    /// Number of palettes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.colors.len() / ROW_SIZE
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if there are more than `MAX_PALETTE_COLORS` colors
+    pub fn add(&mut self, colors: &[Color<u8>]) -> PaletteId {
+        let palette = self.len() as PaletteId;
+        self.colors.resize(self.colors.len() + ROW_SIZE, 0);
+        if palette >= self.capacity {
+            self.reallocate(self.capacity * 2);
+        }
+        self.set(palette, colors);
+        palette
+    }
+
+    /// Replace the colors of a palette, for instance to animate them.
+    ///
+    /// # Panics
+    ///
+    /// * if the palette doesn't exist
+    /// * if there are more than `MAX_PALETTE_COLORS` colors
+    pub fn set(&mut self, palette: PaletteId, colors: &[Color<u8>]) {
+        assert!(colors.len() <= MAX_PALETTE_COLORS, "a palette holds at most {} colors, got {}", MAX_PALETTE_COLORS, colors.len());
+        assert!((palette as usize) < self.len(), "palette {} doesn't exist", palette);
+        let range = palette as usize * ROW_SIZE..(palette as usize + 1) * ROW_SIZE;
+        let row = &mut self.colors[range.clone()];
+        row.iter_mut().for_each(|b| *b = 0);
+        for (rgba, color) in row.chunks_exact_mut(4).zip(colors) {
+            rgba.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        unsafe {
+            self.bind();
+            let row = &self.colors[range];
+            gl::TexSubImage2D(gl::TEXTURE_2D, 0, 0, palette as GLint, MAX_PALETTE_COLORS as GLsizei, 1, gl::RGBA, gl::UNSIGNED_BYTE, row.as_ptr() as *const c_void);
+            self.unbind();
+        }
+    }
+
+    /// Returns the colors of the palette, padded to `MAX_PALETTE_COLORS` with transparent black.
+    pub fn colors(&self, palette: PaletteId) -> Option<Vec<Color<u8>>> {
+        let start = palette as usize * ROW_SIZE;
+        let row = self.colors.get(start..start + ROW_SIZE)?;
+        Some(row.chunks_exact(4).map(|c| Color::from_rgba(c[0], c[1], c[2], c[3])).collect())
+    }
+
+    /// Allocate `capacity` rows, and upload the palettes again.
+    fn reallocate(&mut self, capacity: u32) {
+        log::debug!("allocating palette texture for {} palettes", capacity);
+        self.capacity = capacity;
+        let mut pixels = self.colors.clone();
+        pixels.resize(capacity as usize * ROW_SIZE, 0);
+        unsafe {
+            self.bind();
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as GLint, MAX_PALETTE_COLORS as GLsizei, capacity as GLsizei, 0, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const c_void);
+            // colors are never interpolated, they are read with texelFetch.
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+            self.unbind();
+        }
+    }
+
+    /// Bind the texture to modify it: to its unit if it was made active, where it stays bound.
+    unsafe fn bind(&self) {
+        if let Some(unit) = self.active_unit {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+        }
+        gl::BindTexture(gl::TEXTURE_2D, self.id);
+    }
+
+    unsafe fn unbind(&self) {
+        if self.active_unit.is_none() {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Bind the texture to the texture unit `index`.
+    pub fn set_active(&mut self, index: GLuint) {
+        self.active_unit = Some(index);
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + index);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        }
+    }
+}
+
+impl Default for PaletteTexture {
+    fn default() -> PaletteTexture {
+        PaletteTexture::new()
+    }
+}
+
+impl Drop for PaletteTexture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Metadata about the textures and fonts of a `RenderStorage`.
+//!
+//! This is what `RenderStorage::iter_textures` and `RenderStorage::iter_fonts` return, so that
+//! asset browsers and debug UIs don't have to keep track of every asset on their own.
+//!
+//! Assets can also be registered under a unique key (`RenderStorage::add_texture_named`,
+//! `RenderStorage::register_font`, ...) and looked up by it, so that data files can refer to them
+//! by name instead of by handle.
+
+use linked_hash_map::LinkedHashMap;
+use hashbrown::HashMap;
+
+use super::{FontId, TextureKind, TextureLoadOptions};
+use super::atlas::{TextureHandle, TextureRect, TextureRegion};
+use super::texture::TextureArrayLayer;
+use super::repack::TextureRemap;
+use crate::error::SprowlError;
+
+/// What an asset is currently used for, and how it's stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AssetFlags {
+    /// The asset shares its layer with other assets (small textures, glyphs).
+    pub packed: bool,
+    /// The texture is still waiting in the upload queue, and must not be drawn yet.
+    pub pending_upload: bool,
+    /// Some glyphs of the font have been converted to MSDF.
+    pub msdf: bool,
+}
+
+/// A texture added with one of the `add_texture_*` methods, or with `queue_texture`.
+#[derive(Debug, Clone, Copy)]
+pub struct TextureInfo<'a> {
+    pub handle: TextureHandle,
+    /// Set with `RenderStorage::set_texture_name`, or the file name for
+    /// `add_texture_from_image_path` and `add_textures_from_dir`.
+    pub name: Option<&'a str>,
+    pub size: (u32, u32),
+    /// Textures are always stored in the RGBA array.
+    pub kind: TextureKind,
+    pub layer: TextureArrayLayer,
+    pub flags: AssetFlags,
+    /// Frame (as counted by `RenderStorage::next_frame`) of the last `mark_texture_used`, if any.
+    pub last_used_frame: Option<u64>,
+}
+
+/// A font added with one of the `add_font_*` or `add_bitmap_font*` methods.
+#[derive(Debug, Clone, Copy)]
+pub struct FontInfo<'a> {
+    pub font_id: FontId,
+    /// Set with `RenderStorage::set_font_name`, or the file name for `add_font_from_path`.
+    pub name: Option<&'a str>,
+    /// Number of glyphs in the font.
+    pub glyph_count: usize,
+    /// Glyphs are cached in the grayscale array, except for bitmap fonts whose pages are RGBA
+    /// textures.
+    pub kind: TextureKind,
+    /// The layer of the glyph atlas, shared by all fonts, or the layer of the first page of a
+    /// bitmap font.
+    pub layer: TextureArrayLayer,
+    pub flags: AssetFlags,
+    /// Frame (as counted by `RenderStorage::next_frame`) the font was last fetched to draw text, if any.
+    pub last_used_frame: Option<u64>,
+}
+
+/// An asset registered under a key, see `RenderStorage::iter_named_assets`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NamedAsset {
+    Texture(TextureHandle),
+    Font(FontId),
+    /// A part of a texture, see `RenderStorage::define_region`.
+    Region(TextureRegion),
+}
+
+#[derive(Debug)]
+pub (crate) struct AssetEntry {
+    pub (crate) name: Option<String>,
+    pub (crate) last_used_frame: Option<u64>,
+    /// textures only: the options it was loaded with, applied again when it's reloaded.
+    pub (crate) load_options: TextureLoadOptions,
+}
+
+impl AssetEntry {
+    fn new() -> AssetEntry {
+        AssetEntry {
+            name: None,
+            last_used_frame: None,
+            load_options: TextureLoadOptions::default(),
+        }
+    }
+}
+
+/// Textures are identified by where they are stored: two live textures can't overlap.
+pub (crate) type TextureKey = (u32, TextureArrayLayer, TextureRect);
+
+#[derive(Debug, Default)]
+pub (crate) struct AssetRegistry {
+    pub (crate) frame: u64,
+    // in insertion order, so that asset browsers don't shuffle their lists around.
+    pub (crate) textures: LinkedHashMap<TextureKey, (TextureHandle, AssetEntry)>,
+    pub (crate) fonts: HashMap<FontId, AssetEntry>,
+    /// unique keys given by the user, in insertion order as well.
+    pub (crate) keys: LinkedHashMap<String, NamedAsset>,
+}
+
+impl AssetRegistry {
+    pub (crate) fn add_texture(&mut self, handle: TextureHandle) {
+        self.textures.insert((handle.array, handle.layer, handle.rect), (handle, AssetEntry::new()));
+    }
+
+    pub (crate) fn remove_texture(&mut self, handle: &TextureHandle) {
+        self.textures.remove(&(handle.array, handle.layer, handle.rect));
+        // the key of the texture, and of the regions defined in it.
+        let keys: Vec<String> = self.keys.iter()
+            .filter(|(_, asset)| match asset {
+                NamedAsset::Texture(h) => (h.array, h.layer, h.rect) == (handle.array, handle.layer, handle.rect),
+                NamedAsset::Region(region) => region.is_in(handle),
+                NamedAsset::Font(_) => false,
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.keys.remove(&key);
+        }
+    }
+
+    /// Move the textures, and the keys and regions in them, to where `RenderStorage::repack`
+    /// put them. The order of insertion is kept.
+    pub (crate) fn remap_textures(&mut self, remap: &TextureRemap) {
+        let mut textures = std::mem::take(&mut self.textures);
+        while let Some((_, (handle, entry))) = textures.pop_front() {
+            let handle = remap.remap(handle);
+            self.textures.insert((handle.array, handle.layer, handle.rect), (handle, entry));
+        }
+        for (_, asset) in self.keys.iter_mut() {
+            match asset {
+                NamedAsset::Texture(handle) => *handle = remap.remap(*handle),
+                NamedAsset::Region(region) => *region = remap.remap_region(*region),
+                NamedAsset::Font(_) => {},
+            }
+        }
+    }
+
+    /// Returns an error if the key is already taken.
+    pub (crate) fn register(&mut self, key: String, asset: NamedAsset) -> Result<(), SprowlError> {
+        if self.keys.contains_key(&key) {
+            return Err(SprowlError::DuplicateAssetKey(key));
+        }
+        self.keys.insert(key, asset);
+        Ok(())
+    }
+
+    pub (crate) fn texture_mut(&mut self, handle: &TextureHandle) -> Option<&mut AssetEntry> {
+        self.textures.get_mut(&(handle.array, handle.layer, handle.rect)).map(|(_, entry)| entry)
+    }
+
+    pub (crate) fn add_font(&mut self, font_id: FontId) {
+        self.fonts.insert(font_id, AssetEntry::new());
+    }
+
+    pub (crate) fn mark_font_used(&mut self, font_id: FontId) {
+        let frame = self.frame;
+        if let Some(entry) = self.fonts.get_mut(&font_id) {
+            entry.last_used_frame = Some(frame);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Compacting the atlas layers of the RGBA tiers, fragmented by textures added and removed over
+//! time.
+//!
+//! The shelf packer of `atlas` only reclaims a layer once all of its textures are removed, so a
+//! layer where a few sprites out of a hundred survive still takes a whole layer.
+//! `RenderStorage::repack` moves the textures of such layers together, and tells where each of
+//! them went:
+//!
+//! ```ignore
+//! let remap = render_storage.repack();
+//! for sprite in &mut sprites {
+//!     sprite.texture = remap.remap(sprite.texture);
+//! }
+//! watcher.remap(&remap);
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::RenderStorage;
+use super::atlas::{AtlasLayer, TextureHandle, TextureRect, TextureRegion};
+use super::texture::TextureArrayLayer;
+
+/// Where the textures moved by `RenderStorage::repack` are now.
+#[derive(Debug, Clone, Default)]
+pub struct TextureRemap {
+    /// (old, new) handles
+    moves: Vec<(TextureHandle, TextureHandle)>,
+    /// index in `moves` by (array, layer, rect) of the old handle, since handles aren't `Hash`
+    by_old: HashMap<(u32, TextureArrayLayer, TextureRect), usize>,
+    freed_layers: usize,
+}
+
+impl TextureRemap {
+    /// The new handle of a texture, or None if it wasn't moved.
+    pub fn get(&self, handle: &TextureHandle) -> Option<TextureHandle> {
+        self.by_old.get(&(handle.array, handle.layer, handle.rect))
+            .map(|&index| self.moves[index].1)
+    }
+
+    /// The handle to use from now on: the new one if the texture was moved, `handle` otherwise.
+    pub fn remap(&self, handle: TextureHandle) -> TextureHandle {
+        self.get(&handle).unwrap_or(handle)
+    }
+
+    /// The region to use from now on, moved along with the texture it's a part of.
+    pub fn remap_region(&self, region: TextureRegion) -> TextureRegion {
+        match self.moves.iter().find(|(old, _)| region.is_in(old)) {
+            Some((old, new)) => {
+                let rect = region.rect;
+                new.region((rect.x - old.rect.x, rect.y - old.rect.y, rect.width, rect.height))
+            },
+            None => region,
+        }
+    }
+
+    /// The old and new handles of every moved texture.
+    pub fn iter(&self) -> impl Iterator<Item = (TextureHandle, TextureHandle)> + '_ {
+        self.moves.iter().copied()
+    }
+
+    /// Number of moved textures.
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Number of layers given back to the texture arrays.
+    pub fn freed_layers(&self) -> usize {
+        self.freed_layers
+    }
+
+    fn push(&mut self, old: TextureHandle, new: TextureHandle) {
+        self.by_old.insert((old.array, old.layer, old.rect), self.moves.len());
+        self.moves.push((old, new));
+    }
+}
+
+impl RenderStorage {
+    /// Move the packed textures of the RGBA tiers together, so that they take as few layers as
+    /// possible, and free the layers left empty. The pixels are copied on the GPU.
+    ///
+    /// The handles the storage knows about are updated: named textures and regions, the pages of
+    /// bitmap fonts, and finished `load_texture_async` loads. The ones you kept must be updated
+    /// with the returned `TextureRemap`, and so must the instances already added to a renderer.
+    ///
+    /// Layers are left untouched if they hold glyphs (color or MSDF), or a texture whose upload
+    /// isn't done. A tier is left untouched if repacking it wouldn't free any layer, or if there
+    /// is no room for the new layers.
+    pub fn repack(&mut self) -> TextureRemap {
+        let mut remap = TextureRemap::default();
+        for tier in 0..self.rgba_tiers.len() {
+            self.repack_tier(tier as u32, &mut remap);
+        }
+        if !remap.is_empty() {
+            self.registry.remap_textures(&remap);
+            for bitmap_font in self.bitmap_fonts.values_mut() {
+                for page in &mut bitmap_font.pages {
+                    *page = remap.remap(*page);
+                }
+            }
+            self.async_uploads.remap(&remap);
+        }
+        remap
+    }
+
+    fn repack_tier(&mut self, tier: u32, remap: &mut TextureRemap) {
+        let rgba_tier = &mut self.rgba_tiers[tier as usize];
+        let layer_dims = rgba_tier.layer_dims();
+        // the textures of every atlas layer, as far as the registry knows
+        let mut layers: Vec<(TextureArrayLayer, Vec<TextureHandle>)> = rgba_tier.atlas_layers.iter()
+            .map(|atlas_layer| (atlas_layer.layer, Vec::new()))
+            .collect();
+        for (handle, _) in self.registry.textures.values().filter(|(handle, _)| handle.array == tier) {
+            if let Some((_, textures)) = layers.iter_mut().find(|(layer, _)| *layer == handle.layer) {
+                textures.push(*handle);
+            }
+        }
+        // glyphs aren't in the registry: a layer with some of them has more live textures.
+        let (uploads, async_uploads, atlas_layers) = (&self.uploads, &self.async_uploads, &rgba_tier.atlas_layers);
+        layers.retain(|(layer, textures)| {
+            let live_textures = atlas_layers.iter()
+                .find(|atlas_layer| atlas_layer.layer == *layer)
+                .map_or(0, AtlasLayer::live_textures);
+            textures.len() == live_textures
+                && textures.iter().all(|handle| !uploads.is_pending(handle) && !async_uploads.is_uploading(handle))
+        });
+        if layers.len() < 2 {
+            return;
+        }
+
+        // the tallest first, so that shelves are filled with textures of similar heights
+        let mut textures: Vec<TextureHandle> = layers.iter().flat_map(|(_, textures)| textures.iter().copied()).collect();
+        textures.sort_by_key(|handle| Reverse((handle.rect.height, handle.rect.width)));
+        let mut new_layers: Vec<AtlasLayer> = Vec::new();
+        let mut placed = Vec::with_capacity(textures.len());
+        for handle in textures {
+            let (width, height) = handle.rect.size();
+            let allocated = new_layers.iter_mut().enumerate().find_map(|(index, atlas_layer)| {
+                atlas_layer.allocate(width, height).map(|rect| (index, rect))
+            });
+            let (index, rect) = match allocated {
+                Some(allocated) => allocated,
+                None => {
+                    let mut atlas_layer = AtlasLayer::new(0, layer_dims.0, layer_dims.1);
+                    let rect = atlas_layer.allocate(width, height).expect("a packed texture fits in an empty atlas layer");
+                    new_layers.push(atlas_layer);
+                    (new_layers.len() - 1, rect)
+                }
+            };
+            placed.push((handle, index, rect));
+        }
+        if new_layers.len() >= layers.len() {
+            return;
+        }
+
+        // new layers first, so that the copies never read and write the same layer
+        for index in 0..new_layers.len() {
+            match rgba_tier.texture_array.add_empty_texture(layer_dims.0, layer_dims.1) {
+                Ok(layer) => new_layers[index].layer = layer,
+                Err(e) => {
+                    log::warn!("RGBA tier {} not repacked: {}", tier, e);
+                    for atlas_layer in &new_layers[..index] {
+                        rgba_tier.texture_array.remove_texture(atlas_layer.layer);
+                    }
+                    return;
+                },
+            }
+        }
+        let mut copies = Vec::with_capacity(placed.len());
+        for (old, index, rect) in placed {
+            let layer = new_layers[index].layer;
+            let old_rect = old.rect;
+            copies.push((old.layer, (old_rect.x, old_rect.y, old_rect.width, old_rect.height), layer, (rect.x, rect.y)));
+            let mut new = TextureHandle::new(layer, rect, layer_dims);
+            new.array = tier;
+            remap.push(old, new);
+        }
+        rgba_tier.texture_array.copy_rects(&copies);
+
+        for (layer, _) in &layers {
+            rgba_tier.atlas_layers.retain(|atlas_layer| atlas_layer.layer != *layer);
+            rgba_tier.texture_array.remove_texture(*layer);
+        }
+        remap.freed_layers += layers.len() - new_layers.len();
+        rgba_tier.atlas_layers.extend(new_layers);
+    }
+}
@@ -0,0 +1,242 @@
+//! Importers for the metadata of spritesheets, with the `sheet-import` feature: the JSON of
+//! TexturePacker (hash or array) and of Aseprite (hash or array).
+//!
+//! Every frame becomes a `TextureRegion`, and the animations of the sheet (Aseprite tags, or the
+//! "animations" of TexturePacker) become `AnimationClip`s.
+//!
+//! ```ignore
+//! let texture = storage.add_texture_from_image_path("hero.png")?;
+//! let sheet = storage.import_sprite_sheet(texture, &std::fs::read_to_string("hero.json")?, "hero/")?;
+//! let mut animator = Animator::new(sheet.clip("walk").unwrap().clone());
+//! renderer.add_elem(&QuadInstance::new(position, size).with_region(&storage.region_of("hero/idle 0").unwrap()));
+//! ```
+
+use std::convert::TryFrom;
+
+use linked_hash_map::LinkedHashMap;
+use serde_json::Value;
+
+use crate::animation::{AnimationClip, AnimationFrame, LoopMode};
+use crate::error::SprowlError;
+use super::atlas::{TextureHandle, TextureRegion};
+
+/// Duration of the frames without one, in seconds: TexturePacker doesn't export any.
+pub const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+/// The frames and animations of a spritesheet, see `RenderStorage::import_sprite_sheet`.
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    pub texture: TextureHandle,
+    /// Every frame, by name, in the order of the file.
+    pub regions: LinkedHashMap<String, TextureRegion>,
+    /// The tags of Aseprite, or the "animations" of TexturePacker, in the order of the file.
+    pub clips: LinkedHashMap<String, AnimationClip>,
+}
+
+impl SpriteSheet {
+    pub fn region(&self, name: &str) -> Option<TextureRegion> {
+        self.regions.get(name).cloned()
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.get(name)
+    }
+}
+
+struct SheetFrame {
+    name: String,
+    /// in pixels, relative to the texture
+    crop: (u32, u32, u32, u32),
+    /// in seconds
+    duration: f32,
+}
+
+fn invalid<S: Into<String>>(reason: S) -> SprowlError {
+    SprowlError::InvalidSpriteSheet(reason.into())
+}
+
+fn parse_frame(name: &str, frame: &Value) -> Result<SheetFrame, SprowlError> {
+    // the frame would have to be drawn rotated by 90 degrees, which a quad can't do from its crop.
+    if frame.get("rotated").and_then(Value::as_bool) == Some(true) {
+        return Err(invalid(format!("frame {:?} is rotated, disable rotation in the export settings", name)));
+    }
+    let rect = frame.get("frame").ok_or_else(|| invalid(format!("frame {:?} has no rect", name)))?;
+    let field = |key: &str| {
+        let value = rect.get(key).and_then(Value::as_u64)
+            .ok_or_else(|| invalid(format!("the rect of frame {:?} has no {:?}", name, key)))?;
+        u32::try_from(value).map_err(|_| invalid(format!("the {:?} of frame {:?} is out of range", key, name)))
+    };
+    Ok(SheetFrame {
+        name: name.to_owned(),
+        crop: (field("x")?, field("y")?, field("w")?, field("h")?),
+        // in milliseconds, Aseprite only
+        duration: frame.get("duration").and_then(Value::as_f64).map_or(DEFAULT_FRAME_DURATION, |ms| ms as f32 / 1000.0),
+    })
+}
+
+fn parse_frames(root: &Value) -> Result<Vec<SheetFrame>, SprowlError> {
+    match root.get("frames") {
+        Some(Value::Object(frames)) => frames.iter().map(|(name, frame)| parse_frame(name, frame)).collect(),
+        Some(Value::Array(frames)) => frames.iter().enumerate().map(|(i, frame)| {
+            let name = frame.get("filename").and_then(Value::as_str).map_or_else(|| i.to_string(), str::to_owned);
+            parse_frame(&name, frame)
+        }).collect(),
+        _ => Err(invalid("no \"frames\" hash or array")),
+    }
+}
+
+fn clip(texture: &TextureHandle, frames: &[&SheetFrame], loop_mode: LoopMode) -> AnimationClip {
+    let frames = frames.iter().map(|frame| AnimationFrame {
+        texture_layer: texture.layer,
+        crop: texture.crop_in_layer(frame.crop),
+        duration: frame.duration,
+    }).collect();
+    AnimationClip::with_loop_mode(frames, loop_mode)
+}
+
+/// The "frameTags" of Aseprite: (name, from, to, direction), `to` included.
+fn parse_aseprite_tags(texture: &TextureHandle, root: &Value, frames: &[SheetFrame], clips: &mut LinkedHashMap<String, AnimationClip>) -> Result<(), SprowlError> {
+    let tags = match root.get("meta").and_then(|meta| meta.get("frameTags")).and_then(Value::as_array) {
+        Some(tags) => tags,
+        None => return Ok(()),
+    };
+    for tag in tags {
+        let name = tag.get("name").and_then(Value::as_str).ok_or_else(|| invalid("a frame tag has no name"))?;
+        let index = |key: &str| {
+            tag.get(key).and_then(Value::as_u64).map(|value| value as usize)
+                .filter(|index| *index < frames.len())
+                .ok_or_else(|| invalid(format!("frame tag {:?} has no valid {:?}", name, key)))
+        };
+        let (from, to) = (index("from")?, index("to")?);
+        if from > to {
+            return Err(invalid(format!("frame tag {:?} ends before it starts", name)));
+        }
+        let mut tag_frames: Vec<&SheetFrame> = frames[from..=to].iter().collect();
+        let direction = tag.get("direction").and_then(Value::as_str).unwrap_or("forward");
+        if direction.starts_with("reverse") || direction == "pingpong_reverse" {
+            tag_frames.reverse();
+        }
+        let loop_mode = if direction.starts_with("pingpong") { LoopMode::PingPong } else { LoopMode::Loop };
+        clips.insert(name.to_owned(), clip(texture, &tag_frames, loop_mode));
+    }
+    Ok(())
+}
+
+/// The "animations" of TexturePacker (for pixi.js and Phaser): the names of their frames, by name.
+fn parse_texture_packer_animations(texture: &TextureHandle, root: &Value, frames: &[SheetFrame], clips: &mut LinkedHashMap<String, AnimationClip>) -> Result<(), SprowlError> {
+    let animations = match root.get("animations").and_then(Value::as_object) {
+        Some(animations) => animations,
+        None => return Ok(()),
+    };
+    for (name, frame_names) in animations.iter() {
+        let frame_names = frame_names.as_array().filter(|frame_names| !frame_names.is_empty())
+            .ok_or_else(|| invalid(format!("animation {:?} has no frames", name)))?;
+        let anim_frames = frame_names.iter().map(|frame_name| {
+            let frame_name = frame_name.as_str().unwrap_or_default();
+            frames.iter().find(|frame| frame.name == frame_name)
+                .ok_or_else(|| invalid(format!("animation {:?} has an unknown frame {:?}", name, frame_name)))
+        }).collect::<Result<Vec<_>, _>>()?;
+        clips.insert(name.clone(), clip(texture, &anim_frames, LoopMode::Loop));
+    }
+    Ok(())
+}
+
+/// Read the JSON metadata of a spritesheet exported by TexturePacker or Aseprite, whose image
+/// was added as `texture`. Nothing is registered, see `RenderStorage::import_sprite_sheet` for
+/// that.
+///
+/// Frames must not be rotated. Trimmed frames are imported as they are in the sheet, without the
+/// transparent pixels trimmed around them.
+pub fn parse_sprite_sheet(texture: TextureHandle, json: &str) -> Result<SpriteSheet, SprowlError> {
+    let root: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+    let frames = parse_frames(&root)?;
+    let mut regions = LinkedHashMap::new();
+    for frame in &frames {
+        if !texture.contains_crop(frame.crop) {
+            return Err(SprowlError::RegionOutOfBounds(frame.name.clone()));
+        }
+        regions.insert(frame.name.clone(), texture.region(frame.crop));
+    }
+    let mut clips = LinkedHashMap::new();
+    parse_aseprite_tags(&texture, &root, &frames, &mut clips)?;
+    parse_texture_packer_animations(&texture, &root, &frames, &mut clips)?;
+    Ok(SpriteSheet {
+        texture,
+        regions,
+        clips,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_storage::atlas::TextureRect;
+
+    fn texture() -> TextureHandle {
+        TextureHandle::new(3, TextureRect::new(64, 0, 64, 32), (1024, 1024))
+    }
+
+    #[test]
+    fn texture_packer_hash() {
+        let json = r#"{
+            "frames": {
+                "idle": {"frame": {"x": 0, "y": 0, "w": 32, "h": 32}},
+                "walk": {"frame": {"x": 32, "y": 16, "w": 32, "h": 16}}
+            },
+            "animations": {"loop": ["walk", "idle"]}
+        }"#;
+        let sheet = parse_sprite_sheet(texture(), json).unwrap();
+        assert_eq!(sheet.regions.keys().collect::<Vec<_>>(), ["idle", "walk"]);
+        let walk = sheet.region("walk").unwrap();
+        assert_eq!((walk.layer, walk.rect), (3, TextureRect::new(96, 16, 32, 16)));
+        assert!(walk.is_in(&texture()));
+        let clip = sheet.clip("loop").unwrap();
+        let crops: Vec<_> = clip.frames().iter().map(|frame| frame.crop).collect();
+        assert_eq!(crops, [(96, 16, 32, 16), (64, 0, 32, 32)]);
+        assert_eq!(clip.frames()[0].duration, DEFAULT_FRAME_DURATION);
+    }
+
+    #[test]
+    fn aseprite_array_and_tags() {
+        let json = r#"{
+            "frames": [
+                {"filename": "a", "frame": {"x": 0, "y": 0, "w": 16, "h": 16}, "duration": 100},
+                {"filename": "b", "frame": {"x": 16, "y": 0, "w": 16, "h": 16}, "duration": 250},
+                {"frame": {"x": 32, "y": 0, "w": 16, "h": 16}, "duration": 100}
+            ],
+            "meta": {"frameTags": [{"name": "back", "from": 0, "to": 1, "direction": "pingpong_reverse"}]}
+        }"#;
+        let sheet = parse_sprite_sheet(texture(), json).unwrap();
+        assert_eq!(sheet.regions.keys().collect::<Vec<_>>(), ["a", "b", "2"]);
+        let clip = sheet.clip("back").unwrap();
+        assert_eq!(clip.loop_mode(), LoopMode::PingPong);
+        assert_eq!(clip.frames()[0].crop, (80, 0, 16, 16));
+        assert_eq!(clip.frames()[0].duration, 0.25);
+    }
+
+    #[test]
+    fn out_of_range_rects_are_invalid() {
+        let json = r#"{"frames": {"a": {"frame": {"x": 4294967299, "y": 0, "w": 1, "h": 1}}}}"#;
+        assert!(matches!(parse_sprite_sheet(texture(), json), Err(SprowlError::InvalidSpriteSheet(_))));
+    }
+
+    #[test]
+    fn crops_past_the_texture_are_rejected() {
+        let json = r#"{"frames": {"a": {"frame": {"x": 48, "y": 0, "w": 32, "h": 32}}}}"#;
+        assert!(matches!(parse_sprite_sheet(texture(), json), Err(SprowlError::RegionOutOfBounds(name)) if name == "a"));
+    }
+
+    #[test]
+    fn rotated_frames_are_invalid() {
+        let json = r#"{"frames": {"a": {"frame": {"x": 0, "y": 0, "w": 8, "h": 8}, "rotated": true}}}"#;
+        assert!(matches!(parse_sprite_sheet(texture(), json), Err(SprowlError::InvalidSpriteSheet(_))));
+    }
+
+    #[test]
+    fn broken_sheets_are_invalid() {
+        for json in &["not json", r#"{"frames": 3}"#, r#"{"frames": {"a": {}}}"#,
+            r#"{"frames": {"a": {"frame": {"x": 0, "y": 0, "w": 8, "h": 8}}}, "animations": {"run": ["b"]}}"#] {
+            assert!(matches!(parse_sprite_sheet(texture(), json), Err(SprowlError::InvalidSpriteSheet(_))), "{}", json);
+        }
+    }
+}
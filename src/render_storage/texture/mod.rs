@@ -1,26 +1,91 @@
-use gl::types::*;
+use crate::backend::gl::{self, types::*};
 use std::os::raw::c_void;
 
+use crate::debug::TextureArrayStats;
+use crate::error::SprowlError;
+
+mod settings;
+mod sampler;
+pub use settings::*;
+pub use sampler::*;
+
 pub type TextureArrayLayer = u32;
 
+/// A part of a layer copied to another one, see `Texture2DArray::copy_rects`:
+/// `(src_layer, (x, y, w, h), dst_layer, (x, y))`.
+pub (crate) type RectCopy = (TextureArrayLayer, (u32, u32, u32, u32), TextureArrayLayer, (u32, u32));
+
+// from GL_EXT_texture_compression_s3tc, which the gl crate doesn't generate.
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = 0x83F3;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum TextureFormat {
     RGBA,
     Greyscale,
+    /// BC1 (DXT1): RGB with 1 bit of alpha, 8 bytes per block of 4x4 pixels.
+    ///
+    /// Needs `GL_EXT_texture_compression_s3tc`, available on pretty much every desktop GPU.
+    Bc1,
+    /// BC3 (DXT5): RGBA, 16 bytes per block of 4x4 pixels.
+    Bc3,
+    /// ETC2 RGB, 8 bytes per block of 4x4 pixels. Core since GL 4.3 and GLES 3.0.
+    Etc2Rgb,
+    /// ETC2 RGBA (EAC alpha), 16 bytes per block of 4x4 pixels.
+    Etc2Rgba,
 }
 
 impl TextureFormat {
+    /// The format of the pixels given to GL; for compressed formats, the same as the internal
+    /// format.
     pub (crate) fn to_gl_format(self) -> gl::types::GLenum {
         match self {
             TextureFormat::Greyscale => gl::RED,
             TextureFormat::RGBA => gl::RGBA,
+            TextureFormat::Bc1 => COMPRESSED_RGBA_S3TC_DXT1_EXT,
+            TextureFormat::Bc3 => COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            TextureFormat::Etc2Rgb => gl::COMPRESSED_RGB8_ETC2,
+            TextureFormat::Etc2Rgba => gl::COMPRESSED_RGBA8_ETC2_EAC,
+        }
+    }
+
+    /// The sized format the texture is stored in. GLES 3.0 and WebGL2 don't accept the unsized
+    /// `RED` as an internal format.
+    pub (crate) fn to_gl_internal_format(self) -> gl::types::GLenum {
+        match self {
+            TextureFormat::Greyscale => gl::R8,
+            TextureFormat::RGBA => gl::RGBA8,
+            _ => self.to_gl_format(),
         }
     }
 
+    /// Bytes per pixel, only for uncompressed formats.
     pub (crate) fn bytes(self) -> usize {
         match self {
             TextureFormat::Greyscale => 1,
             TextureFormat::RGBA => 4,
+            _ => 0,
+        }
+    }
+
+    pub fn is_compressed(self) -> bool {
+        self.block_bytes().is_some()
+    }
+
+    /// Bytes per block of 4x4 pixels for compressed formats.
+    pub (crate) fn block_bytes(self) -> Option<usize> {
+        match self {
+            TextureFormat::Bc1 | TextureFormat::Etc2Rgb => Some(8),
+            TextureFormat::Bc3 | TextureFormat::Etc2Rgba => Some(16),
+            TextureFormat::RGBA | TextureFormat::Greyscale => None,
+        }
+    }
+
+    /// Size in bytes of an image of this format.
+    pub fn image_size(self, width: GLuint, height: GLuint) -> usize {
+        match self.block_bytes() {
+            Some(block_bytes) => (width as usize).div_ceil(4) * (height as usize).div_ceil(4) * block_bytes,
+            None => width as usize * height as usize * self.bytes(),
         }
     }
 }
@@ -71,11 +136,21 @@ impl TextureLayerStats {
 pub struct Texture2DArray {
     pub (crate) format: TextureFormat,
     pub (crate) id: GLuint,
+    // number of layers currently allocated on the GPU.
     pub (crate) max_layers: GLuint,
     pub (crate) max_width: GLuint,
     pub (crate) max_height: GLuint,
     // stores the dimension of every texture.
     pub (crate) stats: Vec<TextureLayerStats>,
+    // layers which have been removed, and can be re-used by the next `add_texture`.
+    pub (crate) free_layers: Vec<TextureArrayLayer>,
+    // whether to reallocate a bigger array instead of failing when all layers are used.
+    pub (crate) growable: bool,
+    pub (crate) settings: TextureArraySettings,
+    // true when textures changed since mipmaps were last generated.
+    pub (crate) mipmaps_dirty: bool,
+    // the texture unit given to the last `set_active`, to bind the new texture after growing.
+    pub (crate) active_unit: Option<GLuint>,
 }
 
 /// Represents an array of RGBA textures.
@@ -88,65 +163,279 @@ impl Texture2DArray {
         }
     }
 
-    pub fn new(width: GLuint, height: GLuint, max_layers: GLuint, format: TextureFormat) -> Texture2DArray {
+    /// Allocate the storage of a new texture array, and set its parameters.
+    fn allocate(width: GLuint, height: GLuint, layers: GLuint, format: TextureFormat, settings: &TextureArraySettings) -> GLuint {
         let id = Self::gen_texture();
+        let mip_levels = settings.mip_levels(width, height);
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, id);
-            // allocate the storage for the texture array
-            gl::TexImage3D(
-                gl::TEXTURE_2D_ARRAY,
-                // only use 1 level for the mipmap (so value=0)
-                0,
-                format.to_gl_format() as GLint,
-                width as GLint,
-                height as GLint,
-                max_layers as GLint,
-                // border must always be 0
-                0,
-                format.to_gl_format(),
-                gl::UNSIGNED_BYTE,
-                // fill with void
-                std::ptr::null()
-            );
+            // allocate the storage for the texture array, for every mipmap level
+            for level in 0..mip_levels {
+                if format.is_compressed() {
+                    let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+                    gl::CompressedTexImage3D(
+                        gl::TEXTURE_2D_ARRAY,
+                        level,
+                        format.to_gl_internal_format(),
+                        level_width as GLsizei,
+                        level_height as GLsizei,
+                        layers as GLsizei,
+                        0,
+                        (format.image_size(level_width, level_height) * layers as usize) as GLsizei,
+                        std::ptr::null()
+                    );
+                    continue;
+                }
+                gl::TexImage3D(
+                    gl::TEXTURE_2D_ARRAY,
+                    level,
+                    format.to_gl_internal_format() as GLint,
+                    (width >> level).max(1) as GLint,
+                    (height >> level).max(1) as GLint,
+                    layers as GLint,
+                    // border must always be 0
+                    0,
+                    format.to_gl_format(),
+                    gl::UNSIGNED_BYTE,
+                    // fill with void
+                    std::ptr::null()
+                );
+            }
 
-            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::MIRRORED_REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::MIRRORED_REPEAT as GLint);
+            settings.apply(mip_levels);
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
         }
+        id
+    }
+
+    /// Create a new texture array, with room for `max_layers` textures of `width` x `height`.
+    ///
+    /// The array grows when all the layers are used, unless `set_growable(false)` is called.
+    pub fn new(width: GLuint, height: GLuint, max_layers: GLuint, format: TextureFormat) -> Texture2DArray {
+        Self::with_settings(width, height, max_layers, format, TextureArraySettings::default())
+    }
+
+    /// Same as `new`, with mipmaps or filtering options.
+    pub fn with_settings(width: GLuint, height: GLuint, max_layers: GLuint, format: TextureFormat, settings: TextureArraySettings) -> Texture2DArray {
         Texture2DArray {
-            id,
+            id: Self::allocate(width, height, max_layers, format, &settings),
             max_layers,
             max_width: width,
             max_height: height,
             stats: Vec::with_capacity(max_layers as usize),
+            free_layers: Vec::new(),
+            growable: true,
+            settings,
+            mipmaps_dirty: false,
+            active_unit: None,
             format,
         }
     }
 
+    /// If false, adding a texture when all the layers are used returns an error, instead of
+    /// reallocating a bigger array.
+    pub fn set_growable(&mut self, growable: bool) {
+        self.growable = growable;
+    }
+
+    /// Number of layers currently allocated, used or not.
+    pub fn max_layers(&self) -> GLuint {
+        self.max_layers
+    }
+
+    /// Reallocate the array with twice as many layers.
+    fn grow(&mut self) -> Result<(), SprowlError> {
+        let gl_max_layers = unsafe {
+            let mut gl_max_layers: GLint = 0;
+            gl::GetIntegerv(gl::MAX_ARRAY_TEXTURE_LAYERS, &mut gl_max_layers);
+            gl_max_layers.max(0) as GLuint
+        };
+        if !self.growable || !self.can_reallocate() || self.max_layers >= gl_max_layers {
+            return Err(SprowlError::TextureArrayFull { max_layers: self.max_layers });
+        }
+        let new_max_layers = (self.max_layers * 2).max(1).min(gl_max_layers);
+        log::debug!("growing texture array {} from {} to {} layers", self.id, self.max_layers, new_max_layers);
+        self.reallocate(self.max_width, self.max_height, new_max_layers)
+    }
+
+    /// Reallocate the array with layers of `width` x `height`, keeping the content of the used
+    /// layers in their top-left corner. Handles of packed textures must be computed again, since
+    /// their uvs are relative to the size of the layers.
+    ///
+    /// Returns `SprowlError::CompressedCopyUnsupported` if the array is compressed and
+    /// `glCopyImageSubData` is not available, in which case the array is left as is.
+    ///
+    /// # Panics
+    ///
+    /// * if the layers would shrink
+    pub fn grow_layers(&mut self, width: GLuint, height: GLuint) -> Result<(), SprowlError> {
+        assert!(width >= self.max_width && height >= self.max_height, "texture array layers can only grow");
+        if (width, height) != (self.max_width, self.max_height) {
+            log::debug!("growing the layers of texture array {} from {}x{} to {}x{}", self.id, self.max_width, self.max_height, width, height);
+            self.reallocate(width, height, self.max_layers)?;
+        }
+        Ok(())
+    }
+
+    /// Returns false if the used layers couldn't be copied to a new array: compressed textures
+    /// can't be copied through a framebuffer.
+    fn can_reallocate(&self) -> bool {
+        !self.format.is_compressed() || gl::CopyImageSubData::is_loaded()
+    }
+
+    /// Reallocate the array with the current settings, and copy the existing layers into it.
+    /// Only the first mipmap level is copied, the others are generated again. Compressed
+    /// textures, whose mipmaps can't be generated, have all their levels copied.
+    ///
+    /// Uses `glCopyImageSubData` if available (GL 4.3), and a framebuffer copy otherwise. Compressed
+    /// arrays are not reallocated at all without `glCopyImageSubData`.
+    fn reallocate(&mut self, new_width: GLuint, new_height: GLuint, new_max_layers: GLuint) -> Result<(), SprowlError> {
+        if !self.can_reallocate() {
+            return Err(SprowlError::CompressedCopyUnsupported);
+        }
+        let new_id = Self::allocate(new_width, new_height, new_max_layers, self.format, &self.settings);
+        let (width, height) = (self.max_width as GLsizei, self.max_height as GLsizei);
+        let used_layers = self.stats.len() as GLsizei;
+        unsafe {
+            if gl::CopyImageSubData::is_loaded() {
+                let copied_levels = if self.format.is_compressed() {
+                    self.settings.mip_levels(self.max_width, self.max_height)
+                } else {
+                    1
+                };
+                for level in 0..copied_levels {
+                    gl::CopyImageSubData(
+                        self.id, gl::TEXTURE_2D_ARRAY, level, 0, 0, 0,
+                        new_id, gl::TEXTURE_2D_ARRAY, level, 0, 0, 0,
+                        (width >> level).max(1), (height >> level).max(1), used_layers
+                    );
+                }
+            } else {
+                // attach every layer of the old texture to a framebuffer, and copy it from there.
+                let mut previous_read_framebuffer: GLint = 0;
+                gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous_read_framebuffer);
+                let mut fbo = std::mem::MaybeUninit::uninit();
+                gl::GenFramebuffers(1, fbo.as_mut_ptr());
+                let fbo = fbo.assume_init();
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, new_id);
+                for layer in 0..used_layers {
+                    gl::FramebufferTextureLayer(gl::READ_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, self.id, 0, layer);
+                    gl::CopyTexSubImage3D(gl::TEXTURE_2D_ARRAY, 0, 0, 0, layer, 0, 0, width, height);
+                }
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read_framebuffer as GLuint);
+                gl::DeleteFramebuffers(1, &fbo);
+            }
+            gl::DeleteTextures(1, &self.id);
+        }
+        self.id = new_id;
+        self.max_width = new_width;
+        self.max_height = new_height;
+        self.max_layers = new_max_layers;
+        self.mipmaps_dirty = true;
+        if let Some(unit) = self.active_unit {
+            self.set_active(unit);
+        }
+        Ok(())
+    }
+
     /// Set the MIN and MAG filter to linear instead of NEAREST
+    ///
+    /// This changes the filtering of the whole array: to change it for some draws only, see `Sampler`.
     pub fn set_linear(&mut self, flag: bool) {
+        let filter = if flag { TextureFilter::Linear } else { TextureFilter::Nearest };
+        self.set_settings(TextureArraySettings {
+            min_filter: filter,
+            mag_filter: filter,
+            ..self.settings
+        });
+    }
+
+    pub fn settings(&self) -> TextureArraySettings {
+        self.settings
+    }
+
+    /// Change the filtering options. Turning mipmaps on or off reallocates the whole array.
+    ///
+    /// Compressed arrays keep their mipmaps setting if `glCopyImageSubData` is not available,
+    /// since reallocating them would lose their textures.
+    pub fn set_settings(&mut self, mut settings: TextureArraySettings) {
+        if settings.mipmaps != self.settings.mipmaps && !self.can_reallocate() {
+            log::warn!("glCopyImageSubData is not supported, mipmaps of compressed texture array {} can't be turned on or off", self.id);
+            settings.mipmaps = self.settings.mipmaps;
+        }
+        let reallocate = settings.mipmaps != self.settings.mipmaps;
+        self.settings = settings;
+        if reallocate {
+            // can_reallocate was checked above
+            let _ = self.reallocate(self.max_width, self.max_height, self.max_layers);
+        } else {
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+                settings.apply(settings.mip_levels(self.max_width, self.max_height));
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+            }
+        }
+    }
+
+    /// Generate the mipmaps again if textures changed since last time. Done by `set_active`.
+    ///
+    /// Mipmaps of compressed textures can't be generated: they are uploaded along with the
+    /// texture by `add_compressed_texture`.
+    pub fn generate_mipmaps(&mut self) {
+        if !self.settings.mipmaps || !self.mipmaps_dirty || self.format.is_compressed() {
+            return;
+        }
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
-            let flag = if flag { gl::LINEAR as GLint } else { gl::NEAREST as GLint };
-            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, flag);
-            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, flag);
+            gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
         }
+        self.mipmaps_dirty = false;
     }
 
-    pub fn set_active(&self, index: GLuint) {
+    /// Bind the array to the texture unit `index`, generating its mipmaps first if needed.
+    pub fn set_active(&mut self, index: GLuint) {
+        self.generate_mipmaps();
+        self.active_unit = Some(index);
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0 + index);
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
         }
     }
 
-    pub fn add_texture(&mut self, bytes: &[u8], width: GLuint, height: GLuint) -> TextureArrayLayer {
+    /// Returns the next layer to write to, and records its stats.
+    ///
+    /// Layers freed by `remove_texture` are re-used first, then the array is grown if needed.
+    fn next_layer(&mut self, width: GLuint, height: GLuint) -> Result<TextureArrayLayer, SprowlError> {
+        if width > self.max_width || height > self.max_height {
+            return Err(SprowlError::TextureTooLarge { width, height, max: (self.max_width, self.max_height) });
+        }
+        match self.free_layers.pop() {
+            Some(layer) => {
+                self.stats[layer as usize] = TextureLayerStats::new(width, height);
+                Ok(layer)
+            },
+            None => {
+                if self.stats.len() >= self.max_layers as usize {
+                    self.grow()?;
+                }
+                self.stats.push(TextureLayerStats::new(width, height));
+                Ok((self.stats.len() - 1) as TextureArrayLayer)
+            }
+        }
+    }
+
+    /// Add a texture in a new layer.
+    ///
+    /// Returns an error if all the layers are used and the array can't grow, or if the texture
+    /// is larger than a layer.
+    pub fn add_texture(&mut self, bytes: &[u8], width: GLuint, height: GLuint) -> Result<TextureArrayLayer, SprowlError> {
+        debug_assert!(!self.format.is_compressed(), "use add_compressed_texture for compressed formats");
         debug_assert!(bytes.len() >= width as usize * height as usize * self.format.bytes());
 
-        let next_layer = self.stats.len() as GLint;
+        let next_layer = self.next_layer(width, height)?;
 
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
@@ -155,7 +444,7 @@ impl Texture2DArray {
                 0, // mipmap 0
                 0, // xoffset = 0
                 0, // yoffset = 0
-                next_layer, // layer to update (create)
+                next_layer as GLint, // layer to update (create)
                 width as GLint,
                 height as GLint,
                 1, // only one depth to update
@@ -164,19 +453,86 @@ impl Texture2DArray {
             );
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
         }
+        self.mipmaps_dirty = true;
+
+        Ok(next_layer)
+    }
+
+    /// Add a compressed texture in a new layer. `levels` are the mipmaps of the texture, starting
+    /// with the full size image. If the array has mipmaps, it must have all of them.
+    ///
+    /// Returns an error if all the layers are used and the array can't grow.
+    ///
+    /// # Panics
+    ///
+    /// * (debug only) if the format of the array is not compressed, or a level is too small
+    pub fn add_compressed_texture(&mut self, levels: &[&[u8]], width: GLuint, height: GLuint) -> Result<TextureArrayLayer, SprowlError> {
+        debug_assert!(self.format.is_compressed(), "use add_texture for uncompressed formats");
+        let next_layer = self.next_layer(width, height)?;
+        let mip_levels = self.settings.mip_levels(self.max_width, self.max_height) as usize;
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+            for (level, bytes) in levels.iter().take(mip_levels).enumerate() {
+                let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+                let image_size = self.format.image_size(level_width, level_height);
+                debug_assert!(bytes.len() >= image_size);
+                gl::CompressedTexSubImage3D(
+                    gl::TEXTURE_2D_ARRAY,
+                    level as GLint,
+                    0,
+                    0,
+                    next_layer as GLint,
+                    level_width as GLsizei,
+                    level_height as GLsizei,
+                    1,
+                    self.format.to_gl_format(),
+                    image_size as GLsizei,
+                    bytes.as_ptr() as *const c_void
+                );
+            }
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+        Ok(next_layer)
+    }
+
+    /// Reserve a new layer, to be filled later with `update_texture`.
+    ///
+    /// Returns an error if all the layers are used and the array can't grow, or if the texture
+    /// is larger than a layer.
+    pub fn add_empty_texture(&mut self, width: GLuint, height: GLuint) -> Result<TextureArrayLayer, SprowlError> {
+        self.next_layer(width, height)
+    }
 
-        self.stats.push(TextureLayerStats::new(width, height));
+    /// Free a layer, so that it can be re-used by a later `add_texture`.
+    ///
+    /// The content of the layer is left untouched on the GPU, but the layer must not be drawn
+    /// anymore: it will be overwritten as soon as another texture is added.
+    ///
+    /// # Panics
+    ///
+    /// * (debug only) if the layer was never allocated, or has already been removed
+    pub fn remove_texture(&mut self, layer: TextureArrayLayer) {
+        debug_assert!((layer as usize) < self.stats.len(), "layer {} was never allocated", layer);
+        debug_assert!(!self.free_layers.contains(&layer), "layer {} was already removed", layer);
+        self.stats[layer as usize] = TextureLayerStats::new(0, 0);
+        self.free_layers.push(layer);
+    }
 
-        next_layer as u32
+    /// Returns the number of layers currently in use.
+    pub fn used_layers(&self) -> usize {
+        self.stats.len() - self.free_layers.len()
     }
 
-    pub fn add_empty_texture(&mut self, width: GLuint, height: GLuint) -> TextureArrayLayer {
-        let next_layer = self.stats.len() as GLint;
-        self.stats.push(TextureLayerStats::new(width, height));
-        next_layer as u32
+    pub fn array_stats(&self) -> TextureArrayStats {
+        TextureArrayStats {
+            used_layers: self.used_layers(),
+            allocated_layers: self.max_layers as usize,
+            layer_size: (self.max_width, self.max_height),
+        }
     }
 
     pub fn update_texture(&mut self, layer: TextureArrayLayer, bytes: &[u8], xoffset: GLint, yoffset: GLint, width: GLuint, height: GLuint) {
+        debug_assert!(!self.format.is_compressed(), "compressed textures can't be updated");
         debug_assert!(bytes.len() >= width as usize * height as usize * self.format.bytes());
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
@@ -194,6 +550,71 @@ impl Texture2DArray {
             );
             gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
         }
+        self.mipmaps_dirty = true;
+    }
+
+    /// Same as `update_texture`, but the pixels are read from the start of `buffer`, a
+    /// `GL_PIXEL_UNPACK_BUFFER`. The copy is done asynchronously by the driver.
+    pub (crate) fn update_texture_from_buffer(&mut self, layer: TextureArrayLayer, buffer: GLuint, xoffset: GLint, yoffset: GLint, width: GLuint, height: GLuint) {
+        debug_assert!(!self.format.is_compressed(), "compressed textures can't be updated");
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, buffer);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+            gl::TexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                xoffset,
+                yoffset,
+                layer as GLint,
+                width as GLint,
+                height as GLint,
+                1,
+                self.format.to_gl_format(),
+                // an offset in the bound buffer
+                gl::UNSIGNED_BYTE, std::ptr::null()
+            );
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+        }
+        self.mipmaps_dirty = true;
+    }
+
+    /// Copy parts of layers to other layers of the array, on the GPU. The source and the
+    /// destination of a copy must be different layers.
+    pub (crate) fn copy_rects(&mut self, copies: &[RectCopy]) {
+        debug_assert!(!self.format.is_compressed(), "compressed textures can't be copied by rect");
+        debug_assert!(copies.iter().all(|(src, _, dst, _)| src != dst), "a layer can't be copied to itself");
+        unsafe {
+            if gl::CopyImageSubData::is_loaded() {
+                for &(src_layer, (x, y, width, height), dst_layer, (dst_x, dst_y)) in copies {
+                    gl::CopyImageSubData(
+                        self.id, gl::TEXTURE_2D_ARRAY, 0, x as GLint, y as GLint, src_layer as GLint,
+                        self.id, gl::TEXTURE_2D_ARRAY, 0, dst_x as GLint, dst_y as GLint, dst_layer as GLint,
+                        width as GLsizei, height as GLsizei, 1
+                    );
+                }
+            } else {
+                // same as `reallocate`: attach the source layer to a framebuffer, and copy from there.
+                let mut previous_read_framebuffer: GLint = 0;
+                gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous_read_framebuffer);
+                let mut fbo = std::mem::MaybeUninit::uninit();
+                gl::GenFramebuffers(1, fbo.as_mut_ptr());
+                let fbo = fbo.assume_init();
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+                for &(src_layer, (x, y, width, height), dst_layer, (dst_x, dst_y)) in copies {
+                    gl::FramebufferTextureLayer(gl::READ_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, self.id, 0, src_layer as GLint);
+                    gl::CopyTexSubImage3D(
+                        gl::TEXTURE_2D_ARRAY, 0, dst_x as GLint, dst_y as GLint, dst_layer as GLint,
+                        x as GLint, y as GLint, width as GLsizei, height as GLsizei
+                    );
+                }
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read_framebuffer as GLuint);
+                gl::DeleteFramebuffers(1, &fbo);
+            }
+        }
+        self.mipmaps_dirty = true;
     }
 }
 
@@ -0,0 +1,50 @@
+use crate::backend::gl::{self, types::*};
+use std::mem::MaybeUninit;
+
+use super::TextureArraySettings;
+
+/// Filtering options which override the ones of a texture array while bound to its unit, to
+/// sample the same textures with another filtering: pixel perfect sprites and smooth UI from the
+/// same storage, for instance.
+///
+/// Bind it with `Renderer::set_sampler`. Mipmaps can only be used if the array has them, see
+/// `TextureArraySettings::mipmaps`.
+#[derive(Debug)]
+pub struct Sampler {
+    id: GLuint,
+    settings: TextureArraySettings,
+}
+
+impl Sampler {
+    pub fn new(settings: TextureArraySettings) -> Sampler {
+        let mut id: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let id = unsafe {
+            gl::GenSamplers(1, id.as_mut_ptr());
+            id.assume_init()
+        };
+        settings.apply_to_sampler(id);
+        Sampler { id, settings }
+    }
+
+    pub fn settings(&self) -> TextureArraySettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: TextureArraySettings) {
+        self.settings = settings;
+        settings.apply_to_sampler(self.id);
+    }
+
+    #[inline]
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::backend::gl::{self, types::*};
+use crate::shader::GlProfile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Pixel perfect, but shimmers when zoomed out.
+    Nearest,
+    Linear,
+}
+
+/// Filtering options of a `Texture2DArray`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureArraySettings {
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    /// Allocate mipmaps, and interpolate linearly between them when zoomed out.
+    ///
+    /// Mipmaps are generated again by `Texture2DArray::set_active` when textures have changed.
+    pub mipmaps: bool,
+    /// Level of anisotropic filtering (typically 2, 4, 8 or 16), only useful with mipmaps.
+    ///
+    /// Clamped to what the driver supports, and ignored if it doesn't support it at all.
+    pub anisotropy: Option<f32>,
+    /// Added to the mipmap level computed by the GPU, only useful with mipmaps: positive values
+    /// pick smaller levels (blurrier, less shimmer), negative ones bigger levels (sharper, more
+    /// shimmer).
+    ///
+    /// Not supported by OpenGL ES, where it is ignored.
+    pub lod_bias: f32,
+    /// The mipmap levels the GPU may pick, min and max, after the bias. Fractional levels blend
+    /// two levels with `LINEAR_MIPMAP_LINEAR`. (-1000.0, 1000.0) by default, no limit.
+    pub lod_range: (f32, f32),
+}
+
+/// How texture arrays are sampled outside of [0, 1], by the texture and by its samplers alike.
+const WRAP_MODE: GLenum = gl::MIRRORED_REPEAT;
+
+/// The `lod_range` of OpenGL by default.
+pub const DEFAULT_LOD_RANGE: (f32, f32) = (-1000.0, 1000.0);
+
+impl Default for TextureArraySettings {
+    /// NEAREST filtering, no mipmaps: what you want for pixel art.
+    fn default() -> TextureArraySettings {
+        TextureArraySettings {
+            min_filter: TextureFilter::Nearest,
+            mag_filter: TextureFilter::Nearest,
+            mipmaps: false,
+            anisotropy: None,
+            lod_bias: 0.0,
+            lod_range: DEFAULT_LOD_RANGE,
+        }
+    }
+}
+
+impl TextureArraySettings {
+    pub fn nearest() -> TextureArraySettings {
+        TextureArraySettings::default()
+    }
+
+    pub fn linear() -> TextureArraySettings {
+        TextureArraySettings {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            ..TextureArraySettings::default()
+        }
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> TextureArraySettings {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> TextureArraySettings {
+        self.anisotropy = Some(anisotropy);
+        self
+    }
+
+    pub fn with_lod_bias(mut self, lod_bias: f32) -> TextureArraySettings {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    /// Only let the GPU pick mipmap levels between `min_lod` and `max_lod`: `with_lod_range(0.0,
+    /// 2.0)` never goes below a quarter of the size of the textures, for instance.
+    pub fn with_lod_range(mut self, min_lod: f32, max_lod: f32) -> TextureArraySettings {
+        self.lod_range = (min_lod, max_lod);
+        self
+    }
+
+    /// Number of mipmap levels to allocate for a texture of this size.
+    pub (crate) fn mip_levels(&self, width: GLuint, height: GLuint) -> GLint {
+        if self.mipmaps {
+            (32 - width.max(height).max(1).leading_zeros()) as GLint
+        } else {
+            1
+        }
+    }
+
+    fn gl_min_filter(&self) -> GLenum {
+        match (self.min_filter, self.mipmaps) {
+            (TextureFilter::Nearest, false) => gl::NEAREST,
+            (TextureFilter::Linear, false) => gl::LINEAR,
+            (TextureFilter::Nearest, true) => gl::NEAREST_MIPMAP_LINEAR,
+            (TextureFilter::Linear, true) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    fn gl_mag_filter(&self) -> GLenum {
+        match self.mag_filter {
+            TextureFilter::Nearest => gl::NEAREST,
+            TextureFilter::Linear => gl::LINEAR,
+        }
+    }
+
+    /// The anisotropy to set, clamped to what the driver supports, if any. Without anisotropy,
+    /// this is 1.0, to turn it off on textures and samplers which had some before.
+    fn supported_anisotropy(&self) -> Option<GLfloat> {
+        // core in GL 4.6, same values as GL_EXT_texture_filter_anisotropic before that.
+        // Left to 0 if the driver doesn't know about it.
+        let mut max_anisotropy: GLfloat = 0.0;
+        unsafe {
+            gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+        }
+        match self.anisotropy {
+            _ if max_anisotropy < 1.0 => {
+                if self.anisotropy.is_some() {
+                    log::debug!("anisotropic filtering is not supported by this context");
+                }
+                None
+            },
+            Some(anisotropy) => Some(anisotropy.clamp(1.0, max_anisotropy)),
+            None => Some(1.0),
+        }
+    }
+
+    /// The bias to set, if the context supports it: GLES only has the bias argument of
+    /// `texture()` in the shaders.
+    fn supported_lod_bias(&self) -> Option<GLfloat> {
+        if !GlProfile::detect().is_es() {
+            Some(self.lod_bias)
+        } else {
+            if self.lod_bias != 0.0 {
+                log::debug!("the lod bias of textures is not supported by OpenGL ES");
+            }
+            None
+        }
+    }
+
+    /// Set the parameters of the texture array currently bound to `GL_TEXTURE_2D_ARRAY`.
+    pub (crate) fn apply(&self, mip_levels: GLint) {
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, self.gl_min_filter() as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, self.gl_mag_filter() as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAX_LEVEL, mip_levels - 1);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, WRAP_MODE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, WRAP_MODE as GLint);
+            if let Some(anisotropy) = self.supported_anisotropy() {
+                gl::TexParameterf(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAX_ANISOTROPY, anisotropy);
+            }
+            gl::TexParameterf(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_LOD, self.lod_range.0);
+            gl::TexParameterf(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAX_LOD, self.lod_range.1);
+            if let Some(lod_bias) = self.supported_lod_bias() {
+                gl::TexParameterf(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_LOD_BIAS, lod_bias);
+            }
+        }
+    }
+
+    /// Set the parameters of a sampler object. The number of mipmap levels is still the one of
+    /// the texture.
+    pub (crate) fn apply_to_sampler(&self, sampler: GLuint) {
+        unsafe {
+            gl::SamplerParameteri(sampler, gl::TEXTURE_MIN_FILTER, self.gl_min_filter() as GLint);
+            gl::SamplerParameteri(sampler, gl::TEXTURE_MAG_FILTER, self.gl_mag_filter() as GLint);
+            gl::SamplerParameteri(sampler, gl::TEXTURE_WRAP_S, WRAP_MODE as GLint);
+            gl::SamplerParameteri(sampler, gl::TEXTURE_WRAP_T, WRAP_MODE as GLint);
+            if let Some(anisotropy) = self.supported_anisotropy() {
+                gl::SamplerParameterf(sampler, gl::TEXTURE_MAX_ANISOTROPY, anisotropy);
+            }
+            gl::SamplerParameterf(sampler, gl::TEXTURE_MIN_LOD, self.lod_range.0);
+            gl::SamplerParameterf(sampler, gl::TEXTURE_MAX_LOD, self.lod_range.1);
+            if let Some(lod_bias) = self.supported_lod_bias() {
+                gl::SamplerParameterf(sampler, gl::TEXTURE_LOD_BIAS, lod_bias);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! RGBA texture arrays of different sizes ("tiers"), so that a 4096x4096 background doesn't force
+//! every layer of the RGBA textures to be that big.
+//!
+//! Tier 0 is the RGBA array of `RenderStorageConfig`, bound to `TextureKind::RGBA.texture_unit()`.
+//! Other tiers are added with `RenderStorage::add_rgba_tier`, and bound from
+//! `FIRST_RGBA_TIER_TEXTURE_UNIT`. Textures go to the tier with the smallest layers they fit in,
+//! and `TextureHandle::array` tells which one. `QuadInstance` carries it to the quad shader, which
+//! samples every tier; custom shaders need a sampler bound to `TextureHandle::texture_unit`.
+
+use crate::backend::gl::types::*;
+
+use crate::color::{AlphaMode, premultiply_alpha};
+use crate::error::SprowlError;
+use super::TextureKind;
+use super::atlas::{self, AtlasLayer, TextureHandle};
+use super::texture::Texture2DArray;
+
+/// Maximum number of RGBA arrays, tier 0 included.
+pub const MAX_RGBA_TIERS: usize = 4;
+
+/// Tiers after the first one are bound from this texture unit on, after the palettes (2).
+pub const FIRST_RGBA_TIER_TEXTURE_UNIT: GLuint = super::palette::PALETTE_TEXTURE_UNIT + 1;
+
+/// The texture unit the RGBA array of this tier is bound to by `RenderStorage::set_active`.
+pub fn rgba_tier_texture_unit(tier: u32) -> GLuint {
+    match tier {
+        0 => TextureKind::RGBA.texture_unit(),
+        tier => FIRST_RGBA_TIER_TEXTURE_UNIT + tier - 1,
+    }
+}
+
+/// An RGBA texture array, and the layers of it shared by several small textures.
+pub struct RgbaTier {
+    pub texture_array: Texture2DArray,
+    pub (crate) atlas_layers: Vec<AtlasLayer>,
+    /// the alpha mode of the storage, applied by `upload`
+    pub (crate) alpha_mode: AlphaMode,
+}
+
+impl RgbaTier {
+    pub (crate) fn new(texture_array: Texture2DArray, alpha_mode: AlphaMode) -> RgbaTier {
+        RgbaTier {
+            texture_array,
+            atlas_layers: Vec::new(),
+            alpha_mode,
+        }
+    }
+
+    pub fn layer_dims(&self) -> (u32, u32) {
+        (self.texture_array.max_width, self.texture_array.max_height)
+    }
+
+    fn fits(&self, size: (u32, u32)) -> bool {
+        let (width, height) = self.layer_dims();
+        size.0 <= width && size.1 <= height
+    }
+
+    /// See `atlas::allocate_texture`. The handle is tagged with `tier`.
+    pub (crate) fn allocate(&mut self, tier: u32, size: (u32, u32)) -> Result<TextureHandle, SprowlError> {
+        let mut handle = atlas::allocate_texture(&mut self.texture_array, &mut self.atlas_layers, size)?;
+        handle.array = tier;
+        Ok(handle)
+    }
+
+    pub (crate) fn upload(&mut self, handle: &TextureHandle, bytes: &[u8]) {
+        if self.alpha_mode == AlphaMode::Premultiplied {
+            let mut bytes = bytes.to_vec();
+            premultiply_alpha(&mut bytes);
+            atlas::upload_texture(&mut self.texture_array, handle, &bytes);
+        } else {
+            atlas::upload_texture(&mut self.texture_array, handle, bytes);
+        }
+    }
+
+    /// See `RenderStorage::remove_texture`.
+    pub (crate) fn remove(&mut self, handle: &TextureHandle) {
+        match self.atlas_layers.iter().position(|atlas_layer| atlas_layer.layer == handle.layer) {
+            Some(index) => {
+                if self.atlas_layers[index].free() {
+                    self.atlas_layers.swap_remove(index);
+                    self.texture_array.remove_texture(handle.layer);
+                }
+            },
+            None => self.texture_array.remove_texture(handle.layer),
+        }
+    }
+
+    pub (crate) fn is_packed(&self, handle: &TextureHandle) -> bool {
+        self.atlas_layers.iter().any(|atlas_layer| atlas_layer.layer == handle.layer)
+    }
+}
+
+/// The tier with the smallest layers a texture of this size fits in, or the one with the biggest
+/// layers if it fits nowhere.
+pub (crate) fn choose_tier(tiers: &[RgbaTier], size: (u32, u32)) -> u32 {
+    let area = |tier: &RgbaTier| {
+        let (width, height) = tier.layer_dims();
+        width as u64 * height as u64
+    };
+    let fitting = tiers.iter().enumerate()
+        .filter(|(_, tier)| tier.fits(size))
+        .min_by_key(|(_, tier)| area(tier));
+    let (index, _) = fitting
+        .or_else(|| tiers.iter().enumerate().max_by_key(|(_, tier)| area(tier)))
+        .expect("there is always at least one RGBA tier");
+    index as u32
+}
+
+/// Allocate a texture in the tier chosen by `choose_tier`.
+pub (crate) fn allocate_texture(tiers: &mut [RgbaTier], size: (u32, u32)) -> Result<TextureHandle, SprowlError> {
+    let tier = choose_tier(tiers, size);
+    tiers[tier as usize].allocate(tier, size)
+}
@@ -0,0 +1,356 @@
+//! Spreading texture uploads over several frames.
+//!
+//! Uploading a lot of textures at once (typically when a new scene is loaded) makes for a very
+//! long frame. Textures queued with `RenderStorage::queue_texture` are uploaded a few at a time
+//! by `RenderStorage::process_uploads`, which you should call once per frame.
+//!
+//! Images given to `RenderStorage::load_texture_async` are also decoded on a worker thread, one
+//! after the other, and copied to the texture through a pixel buffer object over the next calls to `process_uploads`,
+//! so that neither the decoding nor the copy stall a frame.
+
+use std::collections::VecDeque;
+use std::mem::MaybeUninit;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::backend::gl::{self, types::*};
+use crate::color::{AlphaMode, premultiply_alpha};
+use crate::error::SprowlError;
+use super::TextureLoadOptions;
+use super::atlas::TextureHandle;
+use super::repack::TextureRemap;
+use super::tiers::{self, RgbaTier};
+
+/// How urgent an upload is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UploadPriority {
+    /// Uploaded right away, ignoring the budget: the texture is needed for this frame.
+    Critical,
+    /// Uploaded as soon as the budget allows.
+    Normal,
+    /// Uploaded only once there are no `Normal` uploads left, e.g. textures of a level
+    /// which is still far away.
+    Prefetch,
+}
+
+/// How much can be uploaded in a single call to `process_uploads`.
+///
+/// At least one upload is always done per call, even if it's bigger than the budget, so that
+/// big textures don't wait forever.
+#[derive(Debug, Clone, Copy)]
+pub struct UploadBudget {
+    pub max_bytes: usize,
+    /// Note that this is measured on the CPU side: the driver might do the actual copy later.
+    pub max_time: Option<Duration>,
+}
+
+impl Default for UploadBudget {
+    /// 4MB per frame, for instance 4 textures of 512x512.
+    fn default() -> UploadBudget {
+        UploadBudget {
+            max_bytes: 4 * 1024 * 1024,
+            max_time: None,
+        }
+    }
+}
+
+/// What happened during a call to `process_uploads`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UploadStats {
+    pub uploaded_count: usize,
+    pub uploaded_bytes: usize,
+    /// Uploads left for the next frames.
+    pub pending_count: usize,
+}
+
+#[derive(Debug)]
+pub (crate) struct PendingUpload {
+    pub (crate) handle: TextureHandle,
+    pub (crate) bytes: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub (crate) struct UploadQueue {
+    pub (crate) budget: UploadBudget,
+    normal: VecDeque<PendingUpload>,
+    prefetch: VecDeque<PendingUpload>,
+}
+
+impl UploadQueue {
+    pub (crate) fn push(&mut self, upload: PendingUpload, priority: UploadPriority) {
+        match priority {
+            UploadPriority::Prefetch => self.prefetch.push_back(upload),
+            _ => self.normal.push_back(upload),
+        }
+    }
+
+    pub (crate) fn len(&self) -> usize {
+        self.normal.len() + self.prefetch.len()
+    }
+
+    pub (crate) fn is_pending(&self, handle: &TextureHandle) -> bool {
+        self.normal.iter().chain(self.prefetch.iter()).any(|u| u.handle == *handle)
+    }
+
+    /// Remove an upload which hasn't been done yet. Returns true if it was found.
+    pub (crate) fn cancel(&mut self, handle: &TextureHandle) -> bool {
+        for queue in [&mut self.normal, &mut self.prefetch].iter_mut() {
+            if let Some(index) = queue.iter().position(|u| u.handle == *handle) {
+                queue.remove(index);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Call `upload` for the next pending uploads, as long as the budget allows.
+    pub (crate) fn process<F: FnMut(&PendingUpload)>(&mut self, mut upload: F) -> UploadStats {
+        let start = Instant::now();
+        let mut stats = UploadStats::default();
+        while let Some(next_size) = self.normal.front().or_else(|| self.prefetch.front()).map(|next| next.bytes.len()) {
+            let over_budget = stats.uploaded_bytes + next_size > self.budget.max_bytes
+                || self.budget.max_time.map(|max_time| start.elapsed() >= max_time).unwrap_or(false);
+            if over_budget && stats.uploaded_count > 0 {
+                break;
+            }
+            let next = self.normal.pop_front().or_else(|| self.prefetch.pop_front()).unwrap();
+            upload(&next);
+            stats.uploaded_count += 1;
+            stats.uploaded_bytes += next_size;
+        }
+        stats.pending_count = self.len();
+        stats
+    }
+}
+
+/// A texture being loaded by `RenderStorage::load_texture_async`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureLoadHandle(u32);
+
+/// Where a `TextureLoadHandle` is at, see `RenderStorage::texture_load_state`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureLoadState {
+    /// The image is being decoded by a worker thread.
+    Decoding,
+    /// The image is decoded, and is being copied to the texture array.
+    Uploading,
+    /// The texture can be drawn.
+    Ready(TextureHandle),
+    /// The image could not be decoded, or there was no room for it: get the error with
+    /// `RenderStorage::finish_texture_load`.
+    Failed,
+    /// The handle was already given to `finish_texture_load`.
+    Unknown,
+}
+
+type DecodeResult = Result<(Vec<u8>, (u32, u32)), SprowlError>;
+
+/// An image for the worker thread to decode.
+type DecodeJob = (u32, Vec<u8>, Option<image::ImageFormat>, TextureLoadOptions);
+
+#[derive(Debug)]
+enum LoadStep {
+    Decoding(TextureLoadOptions),
+    /// Decoded, waiting for the budget to be staged.
+    Decoded { bytes: Vec<u8>, size: (u32, u32), options: TextureLoadOptions },
+    /// The bytes are in `pbo`, the copy to the texture is issued next frame.
+    Staged { handle: TextureHandle, pbo: GLuint },
+    /// The copy is issued, `pbo` can be deleted once the fence is signaled.
+    Copying { handle: TextureHandle, pbo: GLuint, fence: GLsync },
+    Done(Result<TextureHandle, SprowlError>),
+}
+
+/// Loads started by `RenderStorage::load_texture_async`, in the order they were started.
+#[derive(Debug)]
+pub (crate) struct AsyncUploads {
+    next_id: u32,
+    loads: Vec<(u32, LoadStep)>,
+    // images to decode, sent to the worker thread. None until the first load starts it.
+    jobs: Option<Sender<DecodeJob>>,
+    sender: Sender<(u32, DecodeResult)>,
+    receiver: Receiver<(u32, DecodeResult)>,
+}
+
+impl Default for AsyncUploads {
+    fn default() -> AsyncUploads {
+        let (sender, receiver) = mpsc::channel();
+        AsyncUploads {
+            next_id: 0,
+            loads: Vec::new(),
+            jobs: None,
+            sender,
+            receiver,
+        }
+    }
+}
+
+fn decode(bytes: &[u8], image_format: Option<image::ImageFormat>, options: &TextureLoadOptions) -> DecodeResult {
+    let image = match image_format {
+        Some(image_format) => image::load_from_memory_with_format(bytes, image_format),
+        None => image::load_from_memory(bytes),
+    }?.into_rgba8();
+    let size = image.dimensions();
+    let mut pixels = image.into_raw();
+    options.apply(&mut pixels);
+    Ok((pixels, size))
+}
+
+impl AsyncUploads {
+    /// Decode `bytes` on the worker thread, and convert the pixels according to `options`.
+    pub (crate) fn load(&mut self, bytes: Vec<u8>, image_format: Option<image::ImageFormat>, options: TextureLoadOptions) -> TextureLoadHandle {
+        let id = self.next_id;
+        self.next_id += 1;
+        let sender = &self.sender;
+        let jobs = self.jobs.get_or_insert_with(|| spawn_worker(sender.clone()));
+        // the worker only stops once `jobs` is dropped, so it can still receive.
+        let _ = jobs.send((id, bytes, image_format, options));
+        self.loads.push((id, LoadStep::Decoding(options)));
+        TextureLoadHandle(id)
+    }
+
+    pub (crate) fn state(&self, load: TextureLoadHandle) -> TextureLoadState {
+        match self.loads.iter().find(|(id, _)| *id == load.0) {
+            None => TextureLoadState::Unknown,
+            Some((_, LoadStep::Decoding(_))) => TextureLoadState::Decoding,
+            Some((_, LoadStep::Done(Ok(handle)))) => TextureLoadState::Ready(*handle),
+            Some((_, LoadStep::Done(Err(_)))) => TextureLoadState::Failed,
+            Some(_) => TextureLoadState::Uploading,
+        }
+    }
+
+    /// Forget a finished load, returning its result. Returns None if it's not finished.
+    pub (crate) fn finish(&mut self, load: TextureLoadHandle) -> Option<Result<TextureHandle, SprowlError>> {
+        let index = self.loads.iter().position(|(id, step)| *id == load.0 && matches!(step, LoadStep::Done(_)))?;
+        match self.loads.remove(index).1 {
+            LoadStep::Done(result) => Some(result),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Whether `handle` is being copied from a pixel buffer object.
+    pub (crate) fn is_uploading(&self, handle: &TextureHandle) -> bool {
+        self.loads.iter().any(|(_, step)| match step {
+            LoadStep::Staged { handle: h, .. } | LoadStep::Copying { handle: h, .. } => h == handle,
+            _ => false,
+        })
+    }
+
+    /// Update the handles of the finished loads, see `RenderStorage::repack`.
+    pub (crate) fn remap(&mut self, remap: &TextureRemap) {
+        for (_, step) in &mut self.loads {
+            if let LoadStep::Done(Ok(handle)) = step {
+                *handle = remap.remap(*handle);
+            }
+        }
+    }
+
+    /// Number of loads which are not done yet.
+    pub (crate) fn len(&self) -> usize {
+        self.loads.iter().filter(|(_, step)| !matches!(step, LoadStep::Done(_))).count()
+    }
+
+    /// Advance every load by one step: finish the copies whose fence is signaled, issue the copies
+    /// of the staged images, and stage the decoded images within `max_bytes`, adding them to
+    /// `stats`. `on_allocated` is called for every texture allocated in one of `tiers`, along with
+    /// the options it was loaded with.
+    pub (crate) fn process<F: FnMut(TextureHandle, TextureLoadOptions)>(&mut self, tiers: &mut [RgbaTier], max_bytes: usize, stats: &mut UploadStats, mut on_allocated: F) {
+        while let Ok((id, result)) = self.receiver.try_recv() {
+            if let Some((_, step)) = self.loads.iter_mut().find(|(load_id, _)| *load_id == id) {
+                let options = match step {
+                    LoadStep::Decoding(options) => *options,
+                    _ => continue,
+                };
+                *step = match result {
+                    Ok((bytes, size)) => LoadStep::Decoded { bytes, size, options },
+                    Err(e) => LoadStep::Done(Err(e)),
+                };
+            }
+        }
+
+        for (_, step) in &mut self.loads {
+            let next = match step {
+                LoadStep::Copying { handle, pbo, fence } => unsafe {
+                    match gl::ClientWaitSync(*fence, 0, 0) {
+                        gl::TIMEOUT_EXPIRED => continue,
+                        gl::WAIT_FAILED => log::error!("glClientWaitSync failed for the upload of texture layer {}", handle.layer),
+                        _ => {},
+                    }
+                    gl::DeleteSync(*fence);
+                    gl::DeleteBuffers(1, pbo);
+                    LoadStep::Done(Ok(*handle))
+                },
+                LoadStep::Staged { handle, pbo } => unsafe {
+                    let rect = handle.rect;
+                    tiers[handle.array as usize].texture_array.update_texture_from_buffer(handle.layer, *pbo, rect.x as GLint, rect.y as GLint, rect.width, rect.height);
+                    LoadStep::Copying { handle: *handle, pbo: *pbo, fence: gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) }
+                },
+                LoadStep::Decoded { bytes, size, options } => {
+                    let over_budget = stats.uploaded_bytes + bytes.len() > max_bytes;
+                    if over_budget && stats.uploaded_count > 0 {
+                        continue;
+                    }
+                    stats.uploaded_count += 1;
+                    stats.uploaded_bytes += bytes.len();
+                    match tiers::allocate_texture(tiers, *size) {
+                        Ok(handle) => {
+                            on_allocated(handle, *options);
+                            if tiers[handle.array as usize].alpha_mode == AlphaMode::Premultiplied {
+                                premultiply_alpha(bytes);
+                            }
+                            LoadStep::Staged { handle, pbo: stage(bytes) }
+                        },
+                        Err(e) => LoadStep::Done(Err(e)),
+                    }
+                },
+                LoadStep::Decoding(_) | LoadStep::Done(_) => continue,
+            };
+            *step = next;
+        }
+        stats.pending_count += self.len();
+    }
+}
+
+/// Start the thread decoding the images of `load_texture_async`, in the order they are sent. It
+/// stops when the returned sender is dropped, along with the storage.
+fn spawn_worker(results: Sender<(u32, DecodeResult)>) -> Sender<DecodeJob> {
+    let (jobs, pending_jobs) = mpsc::channel::<DecodeJob>();
+    thread::spawn(move || {
+        for (id, bytes, image_format, options) in pending_jobs {
+            if results.send((id, decode(&bytes, image_format, &options))).is_err() {
+                break;
+            }
+        }
+    });
+    jobs
+}
+
+/// Copy `bytes` to a new pixel buffer object.
+fn stage(bytes: &[u8]) -> GLuint {
+    let mut pbo: MaybeUninit<GLuint> = MaybeUninit::uninit();
+    unsafe {
+        gl::GenBuffers(1, pbo.as_mut_ptr());
+        let pbo = pbo.assume_init();
+        gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, pbo);
+        gl::BufferData(gl::PIXEL_UNPACK_BUFFER, bytes.len() as GLsizeiptr, bytes.as_ptr() as *const _, gl::STREAM_DRAW);
+        gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+        pbo
+    }
+}
+
+impl Drop for AsyncUploads {
+    fn drop(&mut self) {
+        for (_, step) in &self.loads {
+            unsafe {
+                match step {
+                    LoadStep::Staged { pbo, .. } => gl::DeleteBuffers(1, pbo),
+                    LoadStep::Copying { pbo, fence, .. } => {
+                        gl::DeleteSync(*fence);
+                        gl::DeleteBuffers(1, pbo);
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Reloading textures when their file changes on disk, to iterate on art while the game runs.
+//!
+//! Files are polled for their modification time: there is no dependency on a file notification
+//! library, and nothing happens between two calls to `AssetWatcher::poll`.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::error::SprowlError;
+use super::RenderStorage;
+use super::atlas::TextureHandle;
+use super::repack::TextureRemap;
+
+#[derive(Debug)]
+struct WatchedTexture {
+    path: PathBuf,
+    handle: TextureHandle,
+    /// None if the file could not be read when it was last checked.
+    modified: Option<SystemTime>,
+}
+
+/// Watches the files textures were loaded from, and reloads the textures whose file changed.
+///
+/// A texture is reloaded in place, so its handle (layer and crop) stays valid. The new image must
+/// have the same size as the old one: otherwise the texture is not reloaded, and you should
+/// remove it and add it again.
+#[derive(Debug)]
+pub struct AssetWatcher {
+    textures: Vec<WatchedTexture>,
+    interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl Default for AssetWatcher {
+    fn default() -> AssetWatcher {
+        AssetWatcher::new()
+    }
+}
+
+impl AssetWatcher {
+    /// A watcher checking the files at most twice a second.
+    pub fn new() -> AssetWatcher {
+        AssetWatcher::with_interval(Duration::from_millis(500))
+    }
+
+    /// A watcher checking the files at most once every `interval`, no matter how often `poll` is
+    /// called.
+    pub fn with_interval(interval: Duration) -> AssetWatcher {
+        AssetWatcher {
+            textures: Vec::new(),
+            interval,
+            last_poll: None,
+        }
+    }
+
+    /// Watch the file a texture was loaded from. The next changes of the file are reloaded by
+    /// `poll`.
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P, handle: TextureHandle) {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified_time(&path);
+        self.unwatch(handle);
+        self.textures.push(WatchedTexture { path, handle, modified });
+    }
+
+    /// Stop watching a texture, typically before removing it from the `RenderStorage`.
+    pub fn unwatch(&mut self, handle: TextureHandle) {
+        self.textures.retain(|watched| (watched.handle.layer, watched.handle.rect) != (handle.layer, handle.rect));
+    }
+
+    /// Follow the textures moved by `RenderStorage::repack`.
+    pub fn remap(&mut self, remap: &TextureRemap) {
+        for watched in &mut self.textures {
+            watched.handle = remap.remap(watched.handle);
+        }
+    }
+
+    /// Number of watched textures.
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    /// Reload the textures whose file changed since the last check, and returns them along with
+    /// the result of the reload. Does nothing if the last check is more recent than the interval.
+    ///
+    /// A file which fails to reload (because it's only half written, for instance) is tried again
+    /// the next time it changes.
+    pub fn poll(&mut self, render_storage: &mut RenderStorage) -> Vec<(TextureHandle, Result<(), SprowlError>)> {
+        let now = Instant::now();
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < self.interval {
+                return Vec::new();
+            }
+        }
+        self.last_poll = Some(now);
+
+        let mut reloaded = Vec::new();
+        for watched in &mut self.textures {
+            let modified = modified_time(&watched.path);
+            if modified.is_none() || modified == watched.modified {
+                continue;
+            }
+            watched.modified = modified;
+            let result = render_storage.reload_texture_from_path(watched.handle, &watched.path);
+            if let Err(e) = &result {
+                log::warn!("could not reload texture {}: {}", watched.path.display(), e);
+            }
+            reloaded.push((watched.handle, result));
+        }
+        reloaded
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,59 @@
+use super::Renderer;
+use crate::shader::Uniform;
+use crate::backend::gl::{self, types::*};
+use image::RgbaImage;
+
+impl<U: Uniform> Renderer<U> {
+    /// Read the pixels of a rectangle of the bound framebuffer, in screen coordinates with the
+    /// origin at the top left, like `push_clip_rect`.
+    ///
+    /// Elements added so far are drawn first. The rectangle is intersected with the viewport, so
+    /// the returned image may be smaller than asked, or even empty.
+    pub fn read_pixels(&mut self, rect: (i32, i32, u32, u32)) -> RgbaImage {
+        self.flush();
+        // in i64, so that the far corner can't overflow.
+        let (x, y, w, h) = (rect.0 as i64, rect.1 as i64, rect.2 as i64, rect.3 as i64);
+        let x1 = x.max(0);
+        let y1 = y.max(0);
+        let x2 = (x + w).min(self.viewport.0 as i64);
+        let y2 = (y + h).min(self.viewport.1 as i64);
+        let width = (x2 - x1).max(0) as u32;
+        let height = (y2 - y1).max(0) as u32;
+        let mut pixels: Vec<u8> = vec![0; width as usize * height as usize * 4];
+        if pixels.is_empty() {
+            return RgbaImage::from_raw(width, height, pixels).unwrap();
+        }
+        unsafe {
+            let mut pack_alignment: GLint = 0;
+            gl::GetIntegerv(gl::PACK_ALIGNMENT, &mut pack_alignment);
+            // rows are tightly packed in our buffer.
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            // glReadPixels has its origin at the bottom left of the viewport.
+            // the rect is not empty, so it is within the viewport.
+            let flipped_y = (self.viewport.1 as i64 - y2) as GLint;
+            gl::ReadPixels(x1 as GLint, flipped_y, width as GLsizei, height as GLsizei, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+            if pack_alignment > 0 {
+                gl::PixelStorei(gl::PACK_ALIGNMENT, pack_alignment);
+            }
+        }
+        // the first row we get is the bottom one.
+        let row_size = width as usize * 4;
+        let row_count = height as usize;
+        for row in 0..row_count / 2 {
+            let (top, bottom) = pixels.split_at_mut((row_count - row - 1) * row_size);
+            top[row * row_size..(row + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+        }
+        RgbaImage::from_raw(width, height, pixels).unwrap()
+    }
+
+    /// Read the whole viewport, see `read_pixels`.
+    ///
+    /// ```ignore
+    /// renderer.draw();
+    /// renderer.capture_frame().save("screenshot.png")?;
+    /// ```
+    pub fn capture_frame(&mut self) -> RgbaImage {
+        let (width, height) = self.viewport;
+        self.read_pixels((0, 0, width, height))
+    }
+}
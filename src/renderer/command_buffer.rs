@@ -0,0 +1,192 @@
+use std::ops::Range;
+
+use cgmath::Vector2;
+
+use crate::camera::Camera2D;
+use crate::color::Color;
+use crate::render_storage::{FontId, RenderStorage, font::FontStemDrawCall};
+use crate::shader::Uniform;
+use super::{AsVertexData, BlendMode, DepthMode, Renderer};
+
+/// Converts a glyph to your own vertex data, for `DrawCommandBuffer::draw_text`.
+///
+/// Receives the glyph, the origin of the text, and the instanced vertex buffer to add the
+/// instances to. Returns the number of instances added, like `AsVertexData::add_vertex_data`.
+pub type GlyphToVertexData = fn(&FontStemDrawCall, Vector2<f32>, &mut Vec<u8>) -> u32;
+
+/// A command recorded in a `DrawCommandBuffer`.
+#[derive(Debug, Clone)]
+pub enum DrawCommand<U: Uniform> {
+    Clear(Option<Color<u8>>),
+    /// Set the given mat4 uniform to the view matrix of the camera.
+    SetCamera { uniform: U, camera: Camera2D },
+    SetBlendMode(BlendMode),
+    SetDepthMode(DepthMode),
+    PushClipRect(i32, i32, u32, u32),
+    PopClipRect,
+    /// Draw instances whose data is in the given range of the buffer's instance data.
+    DrawInstances { instance_count: usize, data: Range<usize> },
+    DrawText {
+        font_id: FontId,
+        text: String,
+        font_size: f32,
+        origin: Vector2<f32>,
+        glyph_to_vertex_data: GlyphToVertexData,
+    },
+}
+
+/// Records draw commands without touching OpenGL, to execute them later with `Renderer::execute`.
+///
+/// Since no GL call is made while recording, buffers can be built on any thread (for instance
+/// one per job of your job system), merged with `append`, and sent to the GL thread. A buffer
+/// is not consumed by `execute`, so a frame can be replayed as many times as needed when debugging.
+#[derive(Debug, Clone)]
+pub struct DrawCommandBuffer<U: Uniform> {
+    commands: Vec<DrawCommand<U>>,
+    instance_data: Vec<u8>,
+}
+
+impl<U: Uniform> Default for DrawCommandBuffer<U> {
+    fn default() -> DrawCommandBuffer<U> {
+        DrawCommandBuffer {
+            commands: Vec::new(),
+            instance_data: Vec::new(),
+        }
+    }
+}
+
+impl<U: Uniform> DrawCommandBuffer<U> {
+    pub fn new() -> DrawCommandBuffer<U> {
+        DrawCommandBuffer::default()
+    }
+
+    pub fn clear(&mut self, clear_color: Option<Color<u8>>) {
+        self.commands.push(DrawCommand::Clear(clear_color));
+    }
+
+    pub fn set_camera(&mut self, uniform: U, camera: Camera2D) {
+        self.commands.push(DrawCommand::SetCamera { uniform, camera });
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.commands.push(DrawCommand::SetBlendMode(blend_mode));
+    }
+
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        self.commands.push(DrawCommand::SetDepthMode(depth_mode));
+    }
+
+    pub fn push_clip_rect(&mut self, x: i32, y: i32, w: u32, h: u32) {
+        self.commands.push(DrawCommand::PushClipRect(x, y, w, h));
+    }
+
+    pub fn pop_clip_rect(&mut self) {
+        self.commands.push(DrawCommand::PopClipRect);
+    }
+
+    /// Record the vertex data of all the elements, to be drawn in a single command.
+    pub fn draw_elems<E: AsVertexData>(&mut self, elems: &[E]) {
+        let start = self.instance_data.len();
+        let instance_count: usize = elems.iter()
+            .map(|e| e.add_vertex_data(&mut self.instance_data) as usize)
+            .sum();
+        let data = start..self.instance_data.len();
+        // consecutive draws are merged, to keep the list of commands short.
+        if let Some(DrawCommand::DrawInstances { instance_count: previous_count, data: previous_data }) = self.commands.last_mut() {
+            if previous_data.end == start {
+                *previous_count += instance_count;
+                previous_data.end = data.end;
+                return;
+            }
+        }
+        self.commands.push(DrawCommand::DrawInstances { instance_count, data });
+    }
+
+    /// Record some text to draw. The glyphs are only laid out and cached during `execute`,
+    /// since the glyph atlas lives on the GL thread.
+    pub fn draw_text(&mut self, font_id: FontId, text: &str, font_size: f32, origin: Vector2<f32>, glyph_to_vertex_data: GlyphToVertexData) {
+        self.commands.push(DrawCommand::DrawText {
+            font_id,
+            text: text.to_owned(),
+            font_size,
+            origin,
+            glyph_to_vertex_data,
+        });
+    }
+
+    /// Move all the commands of `other` at the end of this buffer.
+    pub fn append(&mut self, other: DrawCommandBuffer<U>) {
+        let offset = self.instance_data.len();
+        self.instance_data.extend_from_slice(&other.instance_data);
+        self.commands.extend(other.commands.into_iter().map(|command| match command {
+            DrawCommand::DrawInstances { instance_count, data } => DrawCommand::DrawInstances {
+                instance_count,
+                data: (data.start + offset)..(data.end + offset),
+            },
+            command => command,
+        }));
+    }
+
+    pub fn commands(&self) -> &[DrawCommand<U>] {
+        &self.commands
+    }
+
+    /// Returns the instance data of a `DrawCommand::DrawInstances`.
+    pub fn instance_data(&self, data: Range<usize>) -> &[u8] {
+        &self.instance_data[data]
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Remove all the commands, keeping the allocated memory.
+    pub fn reset(&mut self) {
+        self.commands.clear();
+        self.instance_data.clear();
+    }
+}
+
+impl<U: Uniform> Renderer<U> {
+    /// Execute all the commands of the buffer, and draw what's left at the end.
+    ///
+    /// Texts whose font doesn't exist or doesn't fit in the glyph atlas are skipped with a warning.
+    /// The renderer switches to the alpha mode of the storage.
+    pub fn execute(&mut self, buffer: &DrawCommandBuffer<U>, render_storage: &mut RenderStorage) {
+        self.set_alpha_mode(render_storage.alpha_mode());
+        for command in &buffer.commands {
+            match command {
+                DrawCommand::Clear(clear_color) => {
+                    // what was added before is below the clear, not above it
+                    self.flush();
+                    self.clear(*clear_color);
+                },
+                DrawCommand::SetCamera { uniform, camera } => {
+                    self.flush();
+                    self.shader.set_matrix4(*uniform, &camera.view_matrix());
+                },
+                DrawCommand::SetBlendMode(blend_mode) => self.set_blend_mode(*blend_mode),
+                DrawCommand::SetDepthMode(depth_mode) => self.set_depth_mode(*depth_mode),
+                DrawCommand::PushClipRect(x, y, w, h) => self.push_clip_rect(*x, *y, *w, *h),
+                DrawCommand::PopClipRect => self.pop_clip_rect(),
+                DrawCommand::DrawInstances { instance_count, data } => {
+                    self.add_raw_instances(&buffer.instance_data[data.clone()], *instance_count);
+                },
+                DrawCommand::DrawText { font_id, text, font_size, origin, glyph_to_vertex_data } => {
+                    match render_storage.word_to_draw_call(*font_id, text, *font_size) {
+                        Ok(draw_calls) => for draw_call in &draw_calls {
+                            let added_instances = glyph_to_vertex_data(draw_call, *origin, &mut self.temp_instanced_vb);
+                            self.add_instance_count(added_instances as usize);
+                        },
+                        Err(e) => log::warn!("could not draw text {:?}: {}", text, e),
+                    }
+                },
+            }
+        }
+        self.flush();
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Instant;
+
+use crate::color::AlphaMode;
+use crate::shader::{StandardGlobals, Uniform};
+use super::{Renderer, RendererBuilder};
+
+/// Where the `StandardGlobals` of a renderer come from, see `RendererBuilder::with_standard_globals`.
+#[derive(Debug)]
+pub (crate) struct GlobalsClock {
+    globals: StandardGlobals,
+    start: Instant,
+    /// the time of the previous frame, `None` before the first one
+    last_time: Option<f32>,
+    /// replaces the time since `start`, see `Renderer::set_globals_time`
+    fixed_time: Option<f32>,
+}
+
+impl GlobalsClock {
+    pub (crate) fn new() -> GlobalsClock {
+        GlobalsClock {
+            globals: StandardGlobals::default(),
+            start: Instant::now(),
+            last_time: None,
+            fixed_time: None,
+        }
+    }
+
+    /// Start a new frame.
+    fn next_frame(&mut self) {
+        let time = self.fixed_time.unwrap_or_else(|| self.start.elapsed().as_secs_f32());
+        self.globals = StandardGlobals {
+            resolution: self.globals.resolution,
+            time,
+            delta_time: self.last_time.map_or(0.0, |last_time| time - last_time),
+            frame: match self.last_time {
+                Some(_) => self.globals.frame.wrapping_add(1),
+                None => 0,
+            },
+        };
+        self.last_time = Some(time);
+    }
+}
+
+impl RendererBuilder {
+    /// Set the reserved uniforms of `StandardGlobals` (`u_resolution`, `u_time`, `u_delta_time`,
+    /// `u_frame`) on the shaders of the renderer on every `draw`. Disabled by default.
+    ///
+    /// Time and frames only move forward with `Renderer::begin_frame`.
+    pub fn with_standard_globals(mut self, standard_globals: bool) -> Self {
+        self.standard_globals = standard_globals;
+        self
+    }
+}
+
+impl<U: Uniform> Renderer<U> {
+    /// The values set on the shaders by the last `draw`, if the renderer was built
+    /// `with_standard_globals`.
+    pub fn standard_globals(&self) -> Option<&StandardGlobals> {
+        self.globals.as_ref().map(|clock| &clock.globals)
+    }
+
+    /// Use `time` as `StandardGlobals::time` from the next `begin_frame` on, instead of the
+    /// seconds since the renderer was built: to pause effects with the game, or to record at a
+    /// fixed rate. `None` goes back to the real time.
+    ///
+    /// Does nothing if the renderer wasn't built `with_standard_globals`.
+    pub fn set_globals_time(&mut self, time: Option<f32>) {
+        if let Some(clock) = &mut self.globals {
+            clock.fixed_time = time;
+        }
+    }
+
+    /// Start a new frame of `StandardGlobals`: `time`, `delta_time` and `frame` move forward, and
+    /// stay the same for every `draw` until the next call. Call it once per frame, before
+    /// drawing anything.
+    ///
+    /// Does nothing if the renderer wasn't built `with_standard_globals`.
+    pub fn begin_frame(&mut self) {
+        if let Some(clock) = &mut self.globals {
+            clock.next_frame();
+        }
+    }
+
+    /// Set the `StandardGlobals` of the current frame on `shader` and the mesh shader, along with
+    /// `GLOBAL_PREMULTIPLIED_ALPHA` (which a reloaded shader would have lost). `shader` is the one
+    /// in use afterwards.
+    pub (crate) fn update_globals(&mut self) {
+        let (_, _, width, height) = match &self.virtual_resolution {
+            Some(virtual_resolution) => virtual_resolution.viewport(),
+            None => (0, 0, self.viewport.0, self.viewport.1),
+        };
+        let globals = self.globals.as_mut().map(|clock| {
+            clock.globals.resolution = (width as f32, height as f32);
+            clock.globals
+        });
+        let premultiplied = self.alpha_mode == AlphaMode::Premultiplied;
+        if globals.is_none() && !premultiplied {
+            return;
+        }
+        self.for_each_shader(|shader| {
+            shader.use_program();
+            if let Some(globals) = &globals {
+                shader.set_standard_globals(globals);
+            }
+            if premultiplied {
+                shader.set_premultiplied_alpha(true);
+            }
+        });
+    }
+}
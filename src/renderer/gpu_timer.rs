@@ -0,0 +1,100 @@
+use crate::backend::gl::{self, types::*};
+use std::collections::VecDeque;
+use std::mem::MaybeUninit;
+use std::time::Duration;
+
+/// Results are read this many frames late at most: past that, reading them waits for the GPU.
+const MAX_FRAMES_IN_FLIGHT: usize = 4;
+
+/// Measures the GPU time of every `Renderer::draw` with `GL_TIME_ELAPSED` queries.
+///
+/// The results are only available a few frames later, so the queries of every frame are kept
+/// until they all are, and recycled afterwards.
+#[derive(Debug, Default)]
+pub (crate) struct GpuTimer {
+    /// queries of the current frame
+    current_frame: Vec<GLuint>,
+    /// queries of the previous frames, oldest first
+    in_flight: VecDeque<Vec<GLuint>>,
+    free: Vec<GLuint>,
+    /// set while a query is started, between `begin` and `end`
+    active: bool,
+}
+
+impl GpuTimer {
+    pub (crate) fn is_supported() -> bool {
+        gl::GenQueries::is_loaded() && gl::BeginQuery::is_loaded() && gl::GetQueryObjectui64v::is_loaded()
+    }
+
+    /// Start measuring. Only one query can be started at a time, so this does nothing if one is.
+    pub (crate) fn begin(&mut self) {
+        if self.active {
+            return;
+        }
+        let query = self.free.pop().unwrap_or_else(|| unsafe {
+            let mut query: MaybeUninit<GLuint> = MaybeUninit::uninit();
+            gl::GenQueries(1, query.as_mut_ptr());
+            query.assume_init()
+        });
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, query);
+        }
+        self.current_frame.push(query);
+        self.active = true;
+    }
+
+    pub (crate) fn end(&mut self) {
+        if self.active {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+            self.active = false;
+        }
+    }
+
+    /// Close the current frame, and returns the GPU time of the oldest frame whose results are
+    /// all available, if any. Waits for the oldest frame if there are too many in flight.
+    pub (crate) fn end_frame(&mut self) -> Option<Duration> {
+        self.end();
+        self.in_flight.push_back(std::mem::take(&mut self.current_frame));
+        let mut result = None;
+        while let Some(oldest) = self.in_flight.front() {
+            let available = oldest.iter().all(|query| unsafe {
+                let mut available: GLint = 0;
+                gl::GetQueryObjectiv(*query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+                available != 0
+            });
+            if !available && self.in_flight.len() <= MAX_FRAMES_IN_FLIGHT {
+                break;
+            }
+            let oldest = self.in_flight.pop_front().unwrap();
+            let mut nanos: u64 = 0;
+            for query in &oldest {
+                let mut elapsed: GLuint64 = 0;
+                unsafe {
+                    // blocks until the result is available
+                    gl::GetQueryObjectui64v(*query, gl::QUERY_RESULT, &mut elapsed);
+                }
+                nanos += elapsed;
+            }
+            self.free.extend(oldest);
+            result = Some(Duration::from_nanos(nanos));
+        }
+        result
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        let queries: Vec<GLuint> = self.free.iter()
+            .chain(self.current_frame.iter())
+            .chain(self.in_flight.iter().flatten())
+            .cloned()
+            .collect();
+        if !queries.is_empty() {
+            unsafe {
+                gl::DeleteQueries(queries.len() as GLsizei, queries.as_ptr());
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Named layers drawn in the order they are declared, each with its own blend mode, depth mode,
+//! scissor and clear: a background, the world, the UI...
+//!
+//! ```ignore
+//! let mut renderer = RendererBuilder::new(10000).standard_quad_layout()
+//!     .with_layer("world", LayerSettings::new())
+//!     .with_layer("lights", LayerSettings::new().with_blend_mode(BlendMode::Additive))
+//!     .with_layer("ui", LayerSettings::new().with_clear_depth())
+//!     .build_with(standard_quad_shader()?);
+//! let (world, ui) = (renderer.layer("world").unwrap(), renderer.layer("ui").unwrap());
+//! // in any order
+//! renderer.add_elem_to(ui, &health_bar);
+//! renderer.add_elem_to(world, &player);
+//! // the world, then the lights, then the UI
+//! renderer.draw();
+//! ```
+
+use crate::backend::gl;
+use crate::color::Color;
+use crate::shader::Uniform;
+use super::{AsVertexData, BlendMode, DepthMode, InstanceWriter, Renderer, RendererBuilder};
+
+/// A layer of a `LayerStack`, numbered in the order the layers were added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerId(usize);
+
+/// The state a layer is drawn with, see `LayerStack`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LayerSettings {
+    pub blend_mode: BlendMode,
+    /// The depth mode of the renderer if none.
+    pub depth_mode: Option<DepthMode>,
+    /// Only draw inside this rect (x, y, w, h), in screen coordinates with the origin at the top
+    /// left, instead of the current clip rect of the renderer.
+    pub scissor: Option<(i32, i32, u32, u32)>,
+    /// Clear the color within the scissor before drawing the layer.
+    pub clear_color: Option<Color<u8>>,
+    /// Clear the depth within the scissor before drawing the layer, so that it's drawn over the
+    /// previous layers whatever their depth.
+    pub clear_depth: bool,
+}
+
+impl LayerSettings {
+    /// Alpha blending, without any scissor nor clear.
+    pub fn new() -> LayerSettings {
+        LayerSettings::default()
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = Some(depth_mode);
+        self
+    }
+
+    pub fn with_scissor(mut self, x: i32, y: i32, w: u32, h: u32) -> Self {
+        self.scissor = Some((x, y, w, h));
+        self
+    }
+
+    pub fn with_clear_color(mut self, color: Color<u8>) -> Self {
+        self.clear_color = Some(color);
+        self
+    }
+
+    pub fn with_clear_depth(mut self) -> Self {
+        self.clear_depth = true;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Layer {
+    name: String,
+    settings: LayerSettings,
+    instanced_vb: Vec<u8>,
+    /// like `Renderer::temp_sort_keys`
+    sort_keys: Vec<f32>,
+    instance_count: usize,
+}
+
+/// The layers of a `Renderer`, see `Renderer::add_elem_to`.
+///
+/// Instances added to a layer wait until `Renderer::draw`, which draws the elements added with
+/// `add_elem` first as usual, then every layer which has instances, in the order the layers were
+/// added. Changing the blend mode, the depth mode or the clip rect of the renderer doesn't draw
+/// the layers, and doesn't change how they are drawn.
+///
+/// Layers don't have data of their own for the instance streams of the renderer.
+#[derive(Debug, Default)]
+pub struct LayerStack {
+    layers: Vec<Layer>,
+}
+
+impl LayerStack {
+    /// Add a layer, drawn after the ones already added.
+    ///
+    /// # Panics
+    ///
+    /// * if a layer with the same name was already added
+    pub fn add<S: Into<String>>(&mut self, name: S, settings: LayerSettings) -> LayerId {
+        let name = name.into();
+        assert!(self.id(&name).is_none(), "layer \"{}\" was already added", name);
+        self.layers.push(Layer {
+            name,
+            settings,
+            instanced_vb: Vec::new(),
+            sort_keys: Vec::new(),
+            instance_count: 0,
+        });
+        LayerId(self.layers.len() - 1)
+    }
+
+    pub fn id(&self, name: &str) -> Option<LayerId> {
+        self.layers.iter().position(|layer| layer.name == name).map(LayerId)
+    }
+
+    /// # Panics
+    ///
+    /// * if the layer comes from another renderer
+    pub fn name(&self, layer: LayerId) -> &str {
+        &self.layers[layer.0].name
+    }
+
+    /// # Panics
+    ///
+    /// * if the layer comes from another renderer
+    pub fn settings(&self, layer: LayerId) -> &LayerSettings {
+        &self.layers[layer.0].settings
+    }
+
+    /// Change how a layer is drawn, from the next `Renderer::draw` on.
+    ///
+    /// # Panics
+    ///
+    /// * if the layer comes from another renderer
+    pub fn settings_mut(&mut self, layer: LayerId) -> &mut LayerSettings {
+        &mut self.layers[layer.0].settings
+    }
+
+    /// Number of instances waiting to be drawn in the layer.
+    ///
+    /// # Panics
+    ///
+    /// * if the layer comes from another renderer
+    pub fn instance_count(&self, layer: LayerId) -> usize {
+        self.layers[layer.0].instance_count
+    }
+
+    /// The layers, in the order they are drawn.
+    pub fn iter(&self) -> impl Iterator<Item = (LayerId, &str)> + '_ {
+        self.layers.iter().enumerate().map(|(i, layer)| (LayerId(i), layer.name.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Drop the instances waiting in every layer, without drawing them.
+    pub fn discard_instances(&mut self) {
+        for layer in &mut self.layers {
+            layer.instanced_vb.clear();
+            layer.sort_keys.clear();
+            layer.instance_count = 0;
+        }
+    }
+
+    pub (crate) fn has_instances(&self) -> bool {
+        self.layers.iter().any(|layer| layer.instance_count > 0)
+    }
+}
+
+impl RendererBuilder {
+    /// Add a layer to the `LayerStack` of the renderer, see `LayerStack::add`.
+    ///
+    /// # Panics
+    ///
+    /// * if a layer with the same name was already added
+    pub fn with_layer<S: Into<String>>(mut self, name: S, settings: LayerSettings) -> Self {
+        self.layers.add(name, settings);
+        self
+    }
+}
+
+impl<U: Uniform> Renderer<U> {
+    /// Add a layer, drawn after the ones already added, see `LayerStack::add`.
+    ///
+    /// # Panics
+    ///
+    /// * if a layer with the same name was already added
+    pub fn add_layer<S: Into<String>>(&mut self, name: S, settings: LayerSettings) -> LayerId {
+        self.layers.add(name, settings)
+    }
+
+    /// The layer named `name`, if any.
+    pub fn layer(&self, name: &str) -> Option<LayerId> {
+        self.layers.id(name)
+    }
+
+    #[inline]
+    pub fn layers(&self) -> &LayerStack {
+        &self.layers
+    }
+
+    #[inline]
+    pub fn layers_mut(&mut self) -> &mut LayerStack {
+        &mut self.layers
+    }
+
+    /// Same as `add_elem`, but the instances wait in `layer` until `draw`.
+    ///
+    /// # Panics
+    ///
+    /// * if the layer comes from another renderer
+    pub fn add_elem_to<E: AsVertexData>(&mut self, layer: LayerId, e: &E) {
+        let layer = &mut self.layers.layers[layer.0];
+        let added_instances = e.add_vertex_data(&mut layer.instanced_vb) as usize;
+        if !layer.sort_keys.is_empty() {
+            layer.sort_keys.resize(layer.instance_count + added_instances, 0.0);
+        }
+        layer.instance_count += added_instances;
+    }
+
+    /// Same as `add_elem_with_key`, but the instances wait in `layer` until `draw`. They are only
+    /// sorted with the other instances of the layer.
+    ///
+    /// # Panics
+    ///
+    /// * if the layer comes from another renderer
+    pub fn add_elem_to_with_key<E: AsVertexData>(&mut self, layer: LayerId, e: &E, sort_key: f32) {
+        let layer = &mut self.layers.layers[layer.0];
+        layer.sort_keys.resize(layer.instance_count, 0.0);
+        let added_instances = e.add_vertex_data(&mut layer.instanced_vb) as usize;
+        layer.sort_keys.resize(layer.instance_count + added_instances, sort_key);
+        layer.instance_count += added_instances;
+    }
+
+    /// Same as `add_instance`, but the instance waits in `layer` until `draw`.
+    ///
+    /// # Panics
+    ///
+    /// * if the renderer was not built `with_vertex_layout`
+    /// * if the layer comes from another renderer
+    /// * if `f` panics, see `InstanceWriter`
+    pub fn add_instance_to<F: FnOnce(&mut InstanceWriter)>(&mut self, layer: LayerId, f: F) {
+        let vertex_layout = self.vertex_layout.as_ref().expect("add_instance_to needs a renderer built with_vertex_layout");
+        let layer = &mut self.layers.layers[layer.0];
+        f(&mut InstanceWriter::new(vertex_layout, &mut layer.instanced_vb));
+        if !layer.sort_keys.is_empty() {
+            layer.sort_keys.push(0.0);
+        }
+        layer.instance_count += 1;
+    }
+
+    /// Draw every layer which has instances, in order, each with its own state. The state of the
+    /// renderer is set back afterwards.
+    pub (crate) fn draw_layers(&mut self) {
+        let (blend_mode, depth_mode) = (self.blend_mode, self.depth_mode);
+        let mut layers = std::mem::take(&mut self.layers.layers);
+        for layer in layers.iter_mut().filter(|layer| layer.instance_count > 0) {
+            let settings = layer.settings;
+            self.blend_mode = settings.blend_mode;
+            self.apply_blend_mode();
+            self.depth_mode = settings.depth_mode.unwrap_or(depth_mode);
+            if let Some(scissor) = settings.scissor {
+                // replaces the current clip rect instead of being intersected with it
+                self.clip_stack.push(scissor);
+            }
+            self.apply_clip_rect();
+            self.clear_layer(&settings);
+            self.apply_depth_mode();
+
+            std::mem::swap(&mut self.temp_instanced_vb, &mut layer.instanced_vb);
+            std::mem::swap(&mut self.temp_sort_keys, &mut layer.sort_keys);
+            self.instance_count = layer.instance_count;
+            self.draw_pending();
+            // both are empty now, the layer gets the buffers back for the next frame
+            std::mem::swap(&mut self.temp_instanced_vb, &mut layer.instanced_vb);
+            std::mem::swap(&mut self.temp_sort_keys, &mut layer.sort_keys);
+            layer.instance_count = 0;
+
+            if settings.scissor.is_some() {
+                self.clip_stack.pop();
+            }
+        }
+        self.layers.layers = layers;
+        self.blend_mode = blend_mode;
+        self.depth_mode = depth_mode;
+        self.apply_blend_mode();
+        self.apply_depth_mode();
+        self.apply_clip_rect();
+    }
+
+    /// Clear what `settings` asks for, within the current clip rect.
+    fn clear_layer(&self, settings: &LayerSettings) {
+        let mut mask = 0;
+        unsafe {
+            if let Some(color) = settings.clear_color {
+                let color: Color<f32> = color.to_color_f32();
+                gl::ClearColor(color.r, color.g, color.b, color.a);
+                mask |= gl::COLOR_BUFFER_BIT;
+            }
+            if settings.clear_depth {
+                // glClear is affected by the depth mask, set again by `apply_depth_mode`.
+                gl::DepthMask(gl::TRUE);
+                if self.gl_profile.is_es() {
+                    gl::ClearDepthf(1.0);
+                } else {
+                    gl::ClearDepth(1.0);
+                }
+                mask |= gl::DEPTH_BUFFER_BIT;
+            }
+            if mask != 0 {
+                gl::Clear(mask);
+            }
+        }
+    }
+}
@@ -0,0 +1,444 @@
+use crate::backend::gl::{self, types::*};
+use crate::color::Color;
+use std::ffi::CString;
+
+use super::packing::{f32_to_f16, pack_snorm16};
+use super::stream::{align_up, attrib_alignment, attrib_size, component_size, instance_layout, is_packed_type};
+
+/// An instanced attribute of a `VertexLayout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexField {
+    /// Name of the attribute in the vertex shader.
+    pub name: &'static str,
+    /// Number of components in the attribute: 4 if vec4, 1 if uint, ect.
+    pub width: usize,
+    /// `gl::FLOAT`, `gl::INT`, `gl::UNSIGNED_INT`, one of the smaller integer types,
+    /// `gl::HALF_FLOAT`, or one of the packed 10.10.10.2 types.
+    pub gl_type: GLenum,
+    /// Whether the integers are read as floats by the shader, see
+    /// `RendererBuilder::with_normalized_instanced_vertex_attrib`.
+    pub normalized: bool,
+}
+
+impl VertexField {
+    /// Size of the field in an instance, in bytes.
+    pub fn size(&self) -> usize {
+        attrib_size(self.width, self.gl_type)
+    }
+
+    /// The offset of the field in an instance is a multiple of this, see
+    /// `RendererBuilder::with_instanced_vertex_attrib`.
+    pub fn alignment(&self) -> usize {
+        attrib_alignment(self.gl_type)
+    }
+
+    /// The type `glGetActiveAttrib` returns for an attribute declared with this field.
+    fn glsl_type(&self) -> Option<GLenum> {
+        let types = match self.gl_type {
+            _ if self.normalized || is_packed_type(self.gl_type) => [gl::FLOAT, gl::FLOAT_VEC2, gl::FLOAT_VEC3, gl::FLOAT_VEC4],
+            gl::FLOAT | gl::HALF_FLOAT => [gl::FLOAT, gl::FLOAT_VEC2, gl::FLOAT_VEC3, gl::FLOAT_VEC4],
+            gl::INT | gl::SHORT | gl::BYTE => [gl::INT, gl::INT_VEC2, gl::INT_VEC3, gl::INT_VEC4],
+            gl::UNSIGNED_INT | gl::UNSIGNED_SHORT | gl::UNSIGNED_BYTE => [gl::UNSIGNED_INT, gl::UNSIGNED_INT_VEC2, gl::UNSIGNED_INT_VEC3, gl::UNSIGNED_INT_VEC4],
+            _ => return None,
+        };
+        types.get(self.width.wrapping_sub(1)).cloned()
+    }
+}
+
+/// The instanced attributes of a renderer, described once and used both to configure the VAO
+/// (`RendererBuilder::with_vertex_layout`) and to fill instances (`InstanceWriter`).
+///
+/// Fields are laid out in order, and take consecutive locations.
+///
+/// ```ignore
+/// let layout = VertexLayout::new()
+///     .with_field("crop", 4, gl::FLOAT)
+///     .with_field("translation", 2, gl::FLOAT)
+///     .with_field("layer", 1, gl::UNSIGNED_INT)
+///     .with_normalized_field("color", 4, gl::UNSIGNED_BYTE);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VertexLayout {
+    fields: Vec<VertexField>,
+}
+
+impl VertexLayout {
+    pub fn new() -> VertexLayout {
+        VertexLayout::default()
+    }
+
+    /// Add an attribute named `name` in the shader, see `RendererBuilder::with_instanced_vertex_attrib`
+    /// for `width` and `gl_type`.
+    ///
+    /// # Panics
+    ///
+    /// * if a field with the same name was already added
+    /// * if `gl_type` is packed and `width` isn't 4
+    pub fn with_field(self, name: &'static str, width: usize, gl_type: GLenum) -> Self {
+        self.with(VertexField { name, width, gl_type, normalized: false })
+    }
+
+    /// Same as `with_field`, but the integers are read as floats by the shader, see
+    /// `RendererBuilder::with_normalized_instanced_vertex_attrib`.
+    ///
+    /// # Panics
+    ///
+    /// * if a field with the same name was already added
+    /// * if `gl_type` is packed and `width` isn't 4
+    pub fn with_normalized_field(self, name: &'static str, width: usize, gl_type: GLenum) -> Self {
+        self.with(VertexField { name, width, gl_type, normalized: true })
+    }
+
+    /// Add a field of `T`, see `instance_data!`.
+    pub (crate) fn with_instance_field<T: InstanceField>(self, name: &'static str) -> Self {
+        self.with(VertexField { name, width: T::WIDTH, gl_type: T::GL_TYPE, normalized: T::NORMALIZED })
+    }
+
+    fn with(mut self, field: VertexField) -> Self {
+        assert!(self.field(field.name).is_none(), "field \"{}\" was already added to the layout", field.name);
+        assert!(!is_packed_type(field.gl_type) || field.width == 4, "field \"{}\" has a packed type, its width must be 4", field.name);
+        self.fields.push(field);
+        self
+    }
+
+    pub fn fields(&self) -> &[VertexField] {
+        &self.fields
+    }
+
+    /// Returns the index of the field named `name`, its offset in bytes in an instance, and the field itself.
+    fn field(&self, name: &str) -> Option<(usize, usize, &VertexField)> {
+        let mut offset = 0;
+        for (i, field) in self.fields.iter().enumerate() {
+            offset = align_up(offset, field.alignment());
+            if field.name == name {
+                return Some((i, offset, field));
+            }
+            offset += field.size();
+        }
+        None
+    }
+
+    /// The offset of every field in an instance, in bytes. Every field is aligned, see
+    /// `VertexField::alignment`.
+    pub fn offsets(&self) -> Vec<usize> {
+        self.layout().0
+    }
+
+    /// Size of a single instance, in bytes, padding included: a multiple of 4 bytes at least.
+    pub fn stride(&self) -> usize {
+        self.layout().1
+    }
+
+    fn layout(&self) -> (Vec<usize>, usize) {
+        instance_layout(self.fields.iter().map(|field| (field.size(), field.alignment())))
+    }
+
+    /// Compare the fields with the active attributes of `program`, the first field being at
+    /// `first_location`. Returns every mismatch found.
+    ///
+    /// Attributes unused by the shader are optimized out by the driver, so a missing attribute is
+    /// only logged.
+    pub (crate) fn check_program(&self, program: GLuint, first_location: GLuint) -> Vec<String> {
+        let mut active_attribs: Vec<(String, GLenum)> = Vec::new();
+        unsafe {
+            let mut count: GLint = 0;
+            let mut max_length: GLint = 0;
+            gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count);
+            gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_length);
+            let mut name_buffer: Vec<u8> = vec![0; max_length.max(1) as usize];
+            for i in 0..count.max(0) as GLuint {
+                let mut length: GLsizei = 0;
+                let mut size: GLint = 0;
+                let mut glsl_type: GLenum = 0;
+                gl::GetActiveAttrib(program, i, name_buffer.len() as GLsizei, &mut length, &mut size, &mut glsl_type, name_buffer.as_mut_ptr() as *mut GLchar);
+                let name = String::from_utf8_lossy(&name_buffer[..length.max(0) as usize]).into_owned();
+                active_attribs.push((name, glsl_type));
+            }
+        }
+
+        let mut mismatches = Vec::new();
+        for (i, field) in self.fields.iter().enumerate() {
+            let expected_location = first_location + i as GLuint;
+            let glsl_type = match active_attribs.iter().find(|(name, _)| name == field.name) {
+                Some(&(_, glsl_type)) => glsl_type,
+                None => {
+                    log::warn!("attribute \"{}\" of the vertex layout is missing or unused in the shader", field.name);
+                    continue;
+                },
+            };
+            let cname = CString::new(field.name).unwrap_or_default();
+            let location = unsafe { gl::GetAttribLocation(program, cname.as_ptr()) };
+            if location != expected_location as GLint {
+                mismatches.push(format!("attribute \"{}\" is at location {} in the shader, but {} in the layout", field.name, location, expected_location));
+            }
+            if field.glsl_type() != Some(glsl_type) {
+                mismatches.push(format!("attribute \"{}\" has type 0x{:X} in the shader, which doesn't match width={} gl_type=0x{:X} normalized={}", field.name, glsl_type, field.width, field.gl_type, field.normalized));
+            }
+        }
+        mismatches
+    }
+}
+
+/// Fills a single instance laid out by a `VertexLayout`, field by field.
+///
+/// Fields which are not set are zeroed.
+///
+/// # Panics
+///
+/// Setters panic if the field doesn't exist, or if its width or type doesn't match.
+#[derive(Debug)]
+pub struct InstanceWriter<'a> {
+    layout: &'a VertexLayout,
+    data: &'a mut [u8],
+}
+
+impl<'a> InstanceWriter<'a> {
+    /// Append a zeroed instance to `instanced_vb`, and return a writer for it. Use it in your
+    /// `AsVertexData` implementations, or through `Renderer::add_instance`.
+    pub fn new(layout: &'a VertexLayout, instanced_vb: &'a mut Vec<u8>) -> InstanceWriter<'a> {
+        let start = instanced_vb.len();
+        instanced_vb.resize(start + layout.stride(), 0);
+        InstanceWriter {
+            layout,
+            data: &mut instanced_vb[start..],
+        }
+    }
+
+    fn set_bytes<B: AsRef<[u8]>>(&mut self, name: &str, gl_type: GLenum, values: impl ExactSizeIterator<Item=B>) -> &mut Self {
+        let (_, offset, field) = self.layout.field(name)
+            .unwrap_or_else(|| panic!("field \"{}\" is not in the vertex layout", name));
+        assert_eq!(field.gl_type, gl_type, "field \"{}\" has gl_type 0x{:X}, not 0x{:X}", name, field.gl_type, gl_type);
+        assert_eq!(field.width, values.len(), "field \"{}\" has a width of {}, not {}", name, field.width, values.len());
+        let component_size = component_size(gl_type);
+        for (i, bytes) in values.enumerate() {
+            let start = offset + i * component_size;
+            self.data[start..start + component_size].copy_from_slice(bytes.as_ref());
+        }
+        self
+    }
+
+    /// Set a `float`, `vec2`, `vec3` or `vec4` field.
+    pub fn set_f32s(&mut self, name: &str, values: &[f32]) -> &mut Self {
+        self.set_bytes(name, gl::FLOAT, values.iter().map(|v| v.to_ne_bytes()))
+    }
+
+    /// Set a `uint`, `uvec2`, `uvec3` or `uvec4` field.
+    pub fn set_u32s(&mut self, name: &str, values: &[u32]) -> &mut Self {
+        self.set_bytes(name, gl::UNSIGNED_INT, values.iter().map(|v| v.to_ne_bytes()))
+    }
+
+    /// Set an `int`, `ivec2`, `ivec3` or `ivec4` field.
+    pub fn set_i32s(&mut self, name: &str, values: &[i32]) -> &mut Self {
+        self.set_bytes(name, gl::INT, values.iter().map(|v| v.to_ne_bytes()))
+    }
+
+    /// Set a field of `gl::UNSIGNED_SHORT`s.
+    pub fn set_u16s(&mut self, name: &str, values: &[u16]) -> &mut Self {
+        self.set_bytes(name, gl::UNSIGNED_SHORT, values.iter().map(|v| v.to_ne_bytes()))
+    }
+
+    /// Set a field of `gl::SHORT`s.
+    pub fn set_i16s(&mut self, name: &str, values: &[i16]) -> &mut Self {
+        self.set_bytes(name, gl::SHORT, values.iter().map(|v| v.to_ne_bytes()))
+    }
+
+    /// Set a field of `gl::HALF_FLOAT`s, read as a `float`, `vec2`, `vec3` or `vec4`: the values
+    /// are converted with `packing::f32_to_f16`.
+    pub fn set_f16s(&mut self, name: &str, values: &[f32]) -> &mut Self {
+        self.set_bytes(name, gl::HALF_FLOAT, values.iter().map(|v| f32_to_f16(*v).to_ne_bytes()))
+    }
+
+    /// Set a field of normalized `gl::SHORT`s from values between -1.0 and 1.0, converted with
+    /// `packing::pack_snorm16`.
+    pub fn set_snorm16s(&mut self, name: &str, values: &[f32]) -> &mut Self {
+        self.set_bytes(name, gl::SHORT, values.iter().map(|v| pack_snorm16(*v).to_ne_bytes()))
+    }
+
+    /// Set a field of `gl::INT_2_10_10_10_REV` or `gl::UNSIGNED_INT_2_10_10_10_REV`, packed by
+    /// `packing::pack_snorm_10_10_10_2` or `packing::pack_unorm_10_10_10_2`.
+    pub fn set_10_10_10_2(&mut self, name: &str, packed: u32) -> &mut Self {
+        let (_, offset, field) = self.layout.field(name)
+            .unwrap_or_else(|| panic!("field \"{}\" is not in the vertex layout", name));
+        assert!(is_packed_type(field.gl_type), "field \"{}\" has gl_type 0x{:X}, not a packed 10.10.10.2 type", name, field.gl_type);
+        self.data[offset..offset + 4].copy_from_slice(&packed.to_ne_bytes());
+        self
+    }
+
+    /// Set a field of `gl::UNSIGNED_BYTE`s.
+    pub fn set_u8s(&mut self, name: &str, values: &[u8]) -> &mut Self {
+        self.set_bytes(name, gl::UNSIGNED_BYTE, values.iter().map(|v| [*v]))
+    }
+
+    /// Set a field of `gl::BYTE`s.
+    pub fn set_i8s(&mut self, name: &str, values: &[i8]) -> &mut Self {
+        self.set_bytes(name, gl::BYTE, values.iter().map(|v| v.to_ne_bytes()))
+    }
+
+    /// Set a field of 4 normalized `gl::UNSIGNED_BYTE`s, read as a `vec4` from 0.0 to 1.0.
+    pub fn set_color(&mut self, name: &str, color: Color<u8>) -> &mut Self {
+        self.set_u8s(name, &[color.r, color.g, color.b, color.a])
+    }
+
+    pub fn set_f32(&mut self, name: &str, value: f32) -> &mut Self {
+        self.set_f32s(name, &[value])
+    }
+
+    pub fn set_u32(&mut self, name: &str, value: u32) -> &mut Self {
+        self.set_u32s(name, &[value])
+    }
+
+    pub fn set_i32(&mut self, name: &str, value: i32) -> &mut Self {
+        self.set_i32s(name, &[value])
+    }
+}
+
+/// The Rust type of a field declared by `instance_data!`, with its layout.
+pub (crate) trait InstanceField {
+    const WIDTH: usize;
+    const GL_TYPE: GLenum;
+    const NORMALIZED: bool = false;
+    const ALIGNMENT: usize = attrib_alignment(Self::GL_TYPE);
+
+    fn write(&self, instance: &mut InstanceWriter, name: &str);
+
+    /// Append the field in the native byte order, like the GPU expects it.
+    fn push_to(&self, instanced_vb: &mut Vec<u8>);
+}
+
+impl InstanceField for f32 {
+    const WIDTH: usize = 1;
+    const GL_TYPE: GLenum = gl::FLOAT;
+
+    fn write(&self, instance: &mut InstanceWriter, name: &str) {
+        instance.set_f32(name, *self);
+    }
+
+    fn push_to(&self, instanced_vb: &mut Vec<u8>) {
+        instanced_vb.extend_from_slice(&self.to_ne_bytes());
+    }
+}
+
+impl<const N: usize> InstanceField for [f32; N] {
+    const WIDTH: usize = N;
+    const GL_TYPE: GLenum = gl::FLOAT;
+
+    fn write(&self, instance: &mut InstanceWriter, name: &str) {
+        instance.set_f32s(name, self);
+    }
+
+    fn push_to(&self, instanced_vb: &mut Vec<u8>) {
+        super::push_f32s(instanced_vb, self);
+    }
+}
+
+impl InstanceField for u32 {
+    const WIDTH: usize = 1;
+    const GL_TYPE: GLenum = gl::UNSIGNED_INT;
+
+    fn write(&self, instance: &mut InstanceWriter, name: &str) {
+        instance.set_u32(name, *self);
+    }
+
+    fn push_to(&self, instanced_vb: &mut Vec<u8>) {
+        instanced_vb.extend_from_slice(&self.to_ne_bytes());
+    }
+}
+
+impl<const N: usize> InstanceField for [u32; N] {
+    const WIDTH: usize = N;
+    const GL_TYPE: GLenum = gl::UNSIGNED_INT;
+
+    fn write(&self, instance: &mut InstanceWriter, name: &str) {
+        instance.set_u32s(name, self);
+    }
+
+    fn push_to(&self, instanced_vb: &mut Vec<u8>) {
+        for v in self {
+            instanced_vb.extend_from_slice(&v.to_ne_bytes());
+        }
+    }
+}
+
+/// Read as a `uint`.
+impl InstanceField for u16 {
+    const WIDTH: usize = 1;
+    const GL_TYPE: GLenum = gl::UNSIGNED_SHORT;
+
+    fn write(&self, instance: &mut InstanceWriter, name: &str) {
+        instance.set_u16s(name, &[*self]);
+    }
+
+    fn push_to(&self, instanced_vb: &mut Vec<u8>) {
+        instanced_vb.extend_from_slice(&self.to_ne_bytes());
+    }
+}
+
+/// 4 normalized bytes in RGBA order, read as a `vec4`.
+impl InstanceField for Color<u8> {
+    const WIDTH: usize = 4;
+    const GL_TYPE: GLenum = gl::UNSIGNED_BYTE;
+    const NORMALIZED: bool = true;
+
+    fn write(&self, instance: &mut InstanceWriter, name: &str) {
+        instance.set_color(name, *self);
+    }
+
+    fn push_to(&self, instanced_vb: &mut Vec<u8>) {
+        instanced_vb.extend_from_slice(&[self.r, self.g, self.b, self.a]);
+    }
+}
+
+/// Append zeroes to the instance starting at `start` in `instanced_vb`, until its size is a
+/// multiple of `alignment`: the padding before a field, or at the end of the instance.
+pub (crate) fn pad_instance(instanced_vb: &mut Vec<u8>, start: usize, alignment: usize) {
+    let size = instanced_vb.len() - start;
+    instanced_vb.resize(start + align_up(size, alignment), 0);
+}
+
+/// Declares `with_fields`, `write` and `push_to` of an instance data type from a single list of
+/// `"name": Type = value` fields, so the three can't disagree on the order or the types. The
+/// types are the ones implementing `InstanceField`, and the values read the instance through the
+/// name given between pipes.
+///
+/// Starting with `impl AsVertexData for` also implements `AsVertexData`, one instance per element.
+macro_rules! instance_data {
+    (impl AsVertexData for $ty:ident |$this:ident| { $($fields:tt)* }) => {
+        instance_data!(impl $ty |$this| { $($fields)* });
+
+        impl $crate::renderer::AsVertexData for $ty {
+            fn add_vertex_data(&self, instanced_vb: &mut Vec<u8>) -> u32 {
+                self.push_to(instanced_vb);
+                1
+            }
+        }
+    };
+    (impl $ty:ident |$this:ident| { $($name:literal: $field:ty = $value:expr),* $(,)? }) => {
+        impl $ty {
+            #[doc = concat!("Add the fields listed in the documentation of `", stringify!($ty), "` to `layout`.")]
+            pub fn with_fields(layout: $crate::renderer::VertexLayout) -> $crate::renderer::VertexLayout {
+                layout $(.with_instance_field::<$field>($name))*
+            }
+
+            #[doc = concat!("Set the fields of this `", stringify!($ty), "` in an instance laid out by `with_fields`.")]
+            pub fn write(&self, instance: &mut $crate::renderer::InstanceWriter) {
+                let $this = self;
+                $(<$field as $crate::renderer::InstanceField>::write(&$value, instance, $name);)*
+            }
+
+            #[doc = concat!("Append this `", stringify!($ty), "` to `instanced_vb`, laid out by `with_fields`: numbers in the native byte order, colors as 4 bytes in RGBA order.")]
+            pub fn push_to(&self, instanced_vb: &mut Vec<u8>) {
+                let $this = self;
+                let start = instanced_vb.len();
+                let mut alignment = 4;
+                $(
+                    alignment = alignment.max(<$field as $crate::renderer::InstanceField>::ALIGNMENT);
+                    $crate::renderer::pad_instance(instanced_vb, start, <$field as $crate::renderer::InstanceField>::ALIGNMENT);
+                    <$field as $crate::renderer::InstanceField>::push_to(&$value, instanced_vb);
+                )*
+                $crate::renderer::pad_instance(instanced_vb, start, alignment);
+            }
+        }
+    };
+}
+
+pub (crate) use instance_data;
@@ -0,0 +1,211 @@
+//! Textured triangles drawn between the instanced quads, for what a quad can't do: skewed
+//! platforms, trails, sprites distorted by a grid of vertices...
+//!
+//! ```ignore
+//! let mut renderer = RendererBuilder::new(10000).standard_quad_layout()
+//!     .build_with(standard_quad_shader()?);
+//! renderer.set_mesh_shader(standard_mesh_shader()?);
+//! // every frame, the uniforms are set on both shaders
+//! renderer.for_each_shader(|shader| {
+//!     shader.use_program();
+//!     shader.set_matrix4(QuadUniform::View, &camera.view_matrix());
+//! });
+//! renderer.add_elem(&background);
+//! renderer.add_mesh(&[
+//!     MeshVertex::textured([0.0, 0.0], &texture, [0.0, 0.0]),
+//!     MeshVertex::textured([120.0, 10.0], &texture, [1.0, 0.0]),
+//!     MeshVertex::textured([0.0, 50.0], &texture, [0.0, 1.0]),
+//! ], texture.layer);
+//! // drawn over the mesh
+//! renderer.add_elem(&player);
+//! renderer.draw();
+//! ```
+
+use crate::backend::gl::{self, types::*};
+use crate::color::AlphaMode;
+use crate::shader::{Shader, Uniform};
+use crate::render_storage::texture::TextureArrayLayer;
+use crate::sprites::{MeshVertex, MESH_VERTEX_SIZE};
+use std::{
+    mem::MaybeUninit,
+    ptr,
+    os::raw::c_void,
+};
+use super::Renderer;
+
+/// The shader, VAO and VBO of the meshes of a `Renderer`, see `Renderer::set_mesh_shader`.
+#[derive(Debug)]
+pub (crate) struct MeshPipeline<U: Uniform> {
+    shader: Shader<U>,
+    vao: GLuint,
+    vbo: GLuint,
+    /// size of the storage of `vbo`, in bytes
+    capacity: usize,
+    /// vertices waiting to be drawn, laid out by `MeshVertex::push_to`
+    vertices: Vec<u8>,
+    vertex_count: usize,
+}
+
+impl<U: Uniform> MeshPipeline<U> {
+    fn new(shader: Shader<U>) -> MeshPipeline<U> {
+        let mut vao: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let mut vbo: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let (vao, vbo) = unsafe {
+            gl::GenVertexArrays(1, vao.as_mut_ptr());
+            gl::GenBuffers(1, vbo.as_mut_ptr());
+            (vao.assume_init(), vbo.assume_init())
+        };
+        let stride = MESH_VERTEX_SIZE as GLsizei;
+        unsafe {
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            for location in 0..5 {
+                gl::EnableVertexAttribArray(location);
+            }
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, ptr::null::<c_void>());
+            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, 8 as *const c_void);
+            gl::VertexAttribPointer(2, 4, gl::UNSIGNED_BYTE, gl::TRUE, stride, 16 as *const c_void);
+            gl::VertexAttribIPointer(3, 1, gl::UNSIGNED_INT, stride, 20 as *const c_void);
+            gl::VertexAttribIPointer(4, 1, gl::UNSIGNED_INT, stride, 24 as *const c_void);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        MeshPipeline {
+            shader,
+            vao,
+            vbo,
+            capacity: 0,
+            vertices: Vec::new(),
+            vertex_count: 0,
+        }
+    }
+}
+
+impl<U: Uniform> Drop for MeshPipeline<U> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+        }
+    }
+}
+
+impl<U: Uniform> Renderer<U> {
+    /// Set the shader meshes are drawn with, typically `sprites::standard_mesh_shader`. Its
+    /// vertex attributes must be the ones of `MeshVertex`. Pending meshes are drawn first, with
+    /// the previous shader.
+    ///
+    /// It's a separate program: its uniforms must be set like the ones of `shader`, see
+    /// `for_each_shader`.
+    pub fn set_mesh_shader(&mut self, mut shader: Shader<U>) {
+        self.flush();
+        if self.alpha_mode == AlphaMode::Premultiplied {
+            shader.use_program();
+            shader.set_premultiplied_alpha(true);
+            self.shader.use_program();
+        }
+        self.mesh = Some(MeshPipeline::new(shader));
+    }
+
+    /// The shader given to `set_mesh_shader`, if any.
+    pub fn mesh_shader(&self) -> Option<&Shader<U>> {
+        self.mesh.as_ref().map(|mesh| &mesh.shader)
+    }
+
+    pub fn mesh_shader_mut(&mut self) -> Option<&mut Shader<U>> {
+        self.mesh.as_mut().map(|mesh| &mut mesh.shader)
+    }
+
+    /// Call `f` on the mesh shader if any, then on `shader`: to set the uniforms they share, after
+    /// `use_program`. `shader` is the one in use afterwards.
+    pub fn for_each_shader<F: FnMut(&mut Shader<U>)>(&mut self, mut f: F) {
+        if let Some(mesh) = &mut self.mesh {
+            f(&mut mesh.shader);
+        }
+        f(&mut self.shader);
+        self.shader.use_program();
+    }
+
+    /// Add a list of triangles, every 3 vertices being one, drawn with the texture `layer`.
+    ///
+    /// Meshes are drawn in the order they are added with the other elements: the elements added
+    /// before are below it, the ones added after above it. Several meshes added in a row are drawn
+    /// in one call. Meshes can't be added to a layer, and aren't sorted by `add_elem_with_key`.
+    ///
+    /// # Panics
+    ///
+    /// * if `set_mesh_shader` wasn't called
+    /// * if the number of vertices isn't a multiple of 3
+    pub fn add_mesh(&mut self, vertices: &[MeshVertex], layer: TextureArrayLayer) {
+        self.add_mesh_with_flags(vertices, layer, 0);
+    }
+
+    /// Same as `add_mesh`, with flags for the shader (`QUAD_FLAG_*` for the standard one).
+    ///
+    /// # Panics
+    ///
+    /// * if `set_mesh_shader` wasn't called
+    /// * if the number of vertices isn't a multiple of 3
+    pub fn add_mesh_with_flags(&mut self, vertices: &[MeshVertex], layer: TextureArrayLayer, flags: u32) {
+        assert_eq!(vertices.len() % 3, 0, "a mesh is a list of triangles, {} vertices were given", vertices.len());
+        self.push_mesh_vertices(vertices.iter(), layer, flags);
+    }
+
+    /// Same as `add_mesh`, but every 3 indices in `vertices` are a triangle: a grid of vertices
+    /// doesn't have to repeat the ones its cells share.
+    ///
+    /// # Panics
+    ///
+    /// * if `set_mesh_shader` wasn't called
+    /// * if the number of indices isn't a multiple of 3
+    /// * if an index is out of `vertices`
+    pub fn add_indexed_mesh(&mut self, vertices: &[MeshVertex], indices: &[u16], layer: TextureArrayLayer) {
+        assert_eq!(indices.len() % 3, 0, "a mesh is a list of triangles, {} indices were given", indices.len());
+        self.push_mesh_vertices(indices.iter().map(|i| &vertices[*i as usize]), layer, 0);
+    }
+
+    fn push_mesh_vertices<'a, I: ExactSizeIterator<Item = &'a MeshVertex>>(&mut self, vertices: I, layer: TextureArrayLayer, flags: u32) {
+        assert!(self.mesh.is_some(), "add_mesh needs a mesh shader, see set_mesh_shader");
+        // the quads added before are below the mesh
+        if self.instance_count > 0 {
+            self.flush();
+        }
+        let mesh = self.mesh.as_mut().unwrap();
+        mesh.vertex_count += vertices.len();
+        mesh.vertices.reserve(vertices.len() * MESH_VERTEX_SIZE);
+        for vertex in vertices {
+            vertex.push_to(&mut mesh.vertices, layer, flags);
+        }
+    }
+
+    pub (crate) fn has_pending_meshes(&self) -> bool {
+        self.mesh.as_ref().is_some_and(|mesh| mesh.vertex_count > 0)
+    }
+
+    /// Draw the pending meshes in one call, with the mesh shader.
+    pub (crate) fn draw_meshes(&mut self) {
+        let mesh = match &mut self.mesh {
+            Some(mesh) if mesh.vertex_count > 0 => mesh,
+            _ => return,
+        };
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.bytes_uploaded += mesh.vertices.len();
+        mesh.shader.use_program();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vbo);
+            if mesh.vertices.len() > mesh.capacity {
+                gl::BufferData(gl::ARRAY_BUFFER, mesh.vertices.len() as isize, mesh.vertices.as_ptr() as *const _, gl::DYNAMIC_DRAW);
+                mesh.capacity = mesh.vertices.len();
+            } else {
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, mesh.vertices.len() as isize, mesh.vertices.as_ptr() as *const _);
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(mesh.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, mesh.vertex_count as GLsizei);
+            gl::BindVertexArray(0);
+        }
+        mesh.vertices.clear();
+        mesh.vertex_count = 0;
+        self.shader.use_program();
+    }
+}
@@ -1,25 +1,90 @@
-use crate::shader::{Shader, Uniform};
-use crate::color::Color;
-use gl::types::*;
+mod persistent;
+mod gpu_timer;
+mod command_buffer;
+mod stream;
+mod capture;
+mod layout;
+mod static_batch;
+mod virtual_resolution;
+mod text;
+mod layers;
+mod mesh;
+mod globals;
+pub mod packing;
+
+pub use command_buffer::*;
+pub use layout::*;
+pub use static_batch::*;
+pub use virtual_resolution::*;
+pub use text::*;
+pub use layers::*;
+
+use crate::shader::{GlProfile, Shader, Uniform};
+use cgmath::Vector2;
+use crate::color::{AlphaMode, Color};
+use crate::render_storage::RenderStorage;
+use crate::render_storage::texture::Sampler;
+use crate::debug::FrameStats;
+use crate::camera::Camera2D;
+use crate::gl_state::GlState;
+use hashbrown::HashMap;
+use crate::backend::gl::{self, types::*};
 use std::{
     mem::{MaybeUninit, size_of},
     ptr,
     os::raw::c_void,
 };
+use self::persistent::PersistentBuffer;
+use self::gpu_timer::GpuTimer;
+use self::mesh::MeshPipeline;
+use self::globals::GlobalsClock;
+use self::stream::{InstanceStream, InstancedAttrib, attribs_layout, is_packed_type, log_attribs, vertex_attrib_pointers};
+
 #[derive(Debug)]
 pub struct RendererBuilder {
     pub (crate) vao: GLuint,
     pub (crate) quad_vbo: GLuint,
     pub (crate) instanced_vbo: GLuint,
     pub (crate) next_vertex_attrib: GLuint,
-    /// vec of (attrib_index, width in 4bytes, type)
-    ///
-    /// type may be gl::FLOAT, gl::INT, gl::UNSIGNED_INT
-    pub (crate) instanced_attribs: Vec<(GLuint, usize, GLenum)>,
+    pub (crate) instanced_attribs: Vec<InstancedAttrib>,
+    pub (crate) instance_streams: Vec<InstanceStream>,
+    /// set by `with_vertex_layout`, with the location of its first field
+    pub (crate) vertex_layout: Option<(GLuint, VertexLayout)>,
     pub (crate) max_instances: usize,
+    pub (crate) use_index_buffer: bool,
+    pub (crate) use_persistent_mapping: bool,
+    pub (crate) gl_profile: GlProfile,
+    pub (crate) growth_policy: GrowthPolicy,
+    pub (crate) blend_mode: BlendMode,
+    pub (crate) depth_mode: DepthMode,
+    /// added with `with_layer`
+    pub (crate) layers: LayerStack,
+    pub (crate) standard_globals: bool,
+    pub (crate) alpha_mode: AlphaMode,
+}
+
+/// What to do when more than `max_instances` elements are added before a `draw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthPolicy {
+    /// Draw the elements added so far as soon as `max_instances` is reached. This is the default.
+    Flush,
+    /// Reallocate the instanced VBO (doubling its size) until it can hold `max_instances`
+    /// elements, then behave like `Flush`.
+    ///
+    /// Not supported with `with_persistent_mapping`, which always behaves like `Flush`.
+    Grow { max_instances: usize },
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for GrowthPolicy {
+    fn default() -> GrowthPolicy {
+        GrowthPolicy::Flush
+    }
 }
 
 const VERTICES_PER_ELEM: usize = 6;
+/// Unique vertices of a quad when an index buffer is used.
+const UNIQUE_VERTICES_PER_ELEM: usize = 4;
 impl RendererBuilder {
     /// Build a new renderer.
     ///
@@ -47,35 +112,236 @@ impl RendererBuilder {
             instanced_vbo,
             next_vertex_attrib: 1,
             instanced_attribs: vec!(),
+            instance_streams: vec!(),
+            vertex_layout: None,
             max_instances,
+            use_index_buffer: false,
+            use_persistent_mapping: false,
+            gl_profile: GlProfile::detect(),
+            growth_policy: GrowthPolicy::default(),
+            blend_mode: BlendMode::default(),
+            depth_mode: DepthMode::default(),
+            layers: LayerStack::default(),
+            standard_globals: false,
+            alpha_mode: AlphaMode::Straight,
         }
     }
 
+    /// Draw quads from 4 unique vertices and an index buffer (EBO) with `glDrawElementsInstanced`,
+    /// instead of 6 vertices with `glDrawArraysInstanced`.
+    ///
+    /// Your vertex shader is not affected: attrib 0 is still a `vec2` going from (0, 0) to (1, 1).
+    pub fn with_index_buffer(mut self, use_index_buffer: bool) -> Self {
+        self.use_index_buffer = use_index_buffer;
+        self
+    }
+
+    /// Stream the instance data through a persistently mapped buffer (GL 4.4 or
+    /// `GL_ARB_buffer_storage`), split in 3 regions guarded by fences, instead of `glBufferSubData`.
+    ///
+    /// This avoids implicit synchronization with the GPU when drawing several times per frame.
+    /// Falls back to the usual path if the context doesn't support it.
+    pub fn with_persistent_mapping(mut self, use_persistent_mapping: bool) -> Self {
+        self.use_persistent_mapping = use_persistent_mapping;
+        self
+    }
+
+    /// Only use what the given flavor of OpenGL supports, see `shader::GlProfile`. Detected from
+    /// the current context by default.
+    ///
+    /// On `GlProfile::Es`, persistent mapping and GPU timing are never used. Shaders are
+    /// converted according to the context, not to this setting.
+    pub fn with_gl_profile(mut self, gl_profile: GlProfile) -> Self {
+        self.gl_profile = gl_profile;
+        self
+    }
+
+    /// Choose what happens when more than `max_instances` elements are added, see `GrowthPolicy`.
+    pub fn with_growth_policy(mut self, growth_policy: GrowthPolicy) -> Self {
+        self.growth_policy = growth_policy;
+        self
+    }
+
+    /// The blend mode of the renderer until `Renderer::set_blend_mode` is called. `Alpha` by default.
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// The depth mode of the renderer until `Renderer::set_depth_mode` is called. Disabled by default.
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    /// Draw textures premultiplied by a `RenderStorage` built with the same mode, typically
+    /// `storage.alpha_mode()`. `BlendMode::Alpha` and `BlendMode::Additive` then blend
+    /// premultiplied colors, and the shaders which declare `u_premultiplied_alpha` (see
+    /// `shader::GLOBAL_PREMULTIPLIED_ALPHA`) get it set to true.
+    ///
+    /// `draw_text` and `execute` switch to the mode of the storage they are given.
+    pub fn with_alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// `with_alpha_mode(storage.alpha_mode())`: the mode the textures of `storage` are in.
+    pub fn with_alpha_mode_of(self, storage: &RenderStorage) -> Self {
+        self.with_alpha_mode(storage.alpha_mode())
+    }
+
     /// Add a vertex attrib
     ///
-    /// `width` is the number of f32/u32/i32 in the attribute: 4 if vec4, 1 if uint, ect.
+    /// `width` is the number of components in the attribute: 4 if vec4, 1 if uint, ect.
+    ///
+    /// `gl_type` is the type of a component, typically `gl::FLOAT` or `gl::UNSIGNED_INT`.
+    /// `gl::UNSIGNED_SHORT`, `gl::SHORT`, `gl::UNSIGNED_BYTE` and `gl::BYTE` take 2 and 1 bytes,
+    /// and are read as `uint`s or `int`s by the shader. `gl::HALF_FLOAT` takes 2 bytes and is
+    /// read as a float.
+    ///
+    /// `gl::INT_2_10_10_10_REV` and `gl::UNSIGNED_INT_2_10_10_10_REV` pack 4 components in 4
+    /// bytes, and are read as a `vec4`: `width` must be 4. They are usually normalized, see
+    /// `with_normalized_instanced_vertex_attrib`. The `packing` module converts floats to all
+    /// of these formats.
+    ///
+    /// Every attrib starts at a multiple of the size of its components (4 bytes for packed
+    /// types), and every instance at a multiple of 4 bytes at least: the padding must be written
+    /// too, which `VertexLayout` and `InstanceWriter` take care of. Group the small attribs, so
+    /// that little padding is needed.
     ///
-    /// `gl_type` is the type, typically `gl::FLOAT` or `gl::UNSIGNED_INT`.
+    /// `divisor` is the number of instances sharing a value: 1 for an attrib per instance, more
+    /// for an attrib which changes every few instances, like a palette index shared by groups of
+    /// sprites (0 is read as 1). Attribs with a divisor other than 1 are sourced from the instance
+    /// stream of that divisor, added as by `with_instance_stream` if there is none yet, and get
+    /// their data from `Renderer::add_stream_data`.
     ///
     /// # Example
     ///
-    /// `width` = 4, `gl_type` = `gl::FLOAT` will add a `vec4` attrib to the VAO, that you can
-    /// use from your shader.
+    /// `width` = 4, `gl_type` = `gl::FLOAT`, `divisor` = 1 will add a `vec4` attrib to the VAO,
+    /// that you can use from your shader.
     ///
     /// Note that the first location is reserved for the vertex attrib, so the first instanced
     /// vertex atttrib should start from 1.
-    pub fn with_instanced_vertex_attrib(mut self, width: usize, gl_type: GLenum) -> Self {
-        self.instanced_attribs.push((self.next_vertex_attrib, width, gl_type));
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gl_type` is packed and `width` isn't 4.
+    pub fn with_instanced_vertex_attrib(self, width: usize, gl_type: GLenum, divisor: u32) -> Self {
+        if divisor <= 1 {
+            return self.with_attrib(width, gl_type, false);
+        }
+        match self.instance_streams.iter().position(|stream| stream.divisor == divisor) {
+            Some(stream) => self.with_stream_vertex_attrib(stream, width, gl_type),
+            None => {
+                let stream = self.instance_streams.len();
+                self.with_instance_stream(divisor).with_stream_vertex_attrib(stream, width, gl_type)
+            },
+        }
+    }
+
+    /// Same as `with_instanced_vertex_attrib`, but the integers are read as floats by the shader,
+    /// from 0.0 to 1.0 (or -1.0 to 1.0 if signed).
+    ///
+    /// # Example
+    ///
+    /// `width` = 4, `gl_type` = `gl::UNSIGNED_BYTE` is a `vec4` color in 4 bytes instead of 16.
+    pub fn with_normalized_instanced_vertex_attrib(self, width: usize, gl_type: GLenum) -> Self {
+        self.with_attrib(width, gl_type, true)
+    }
+
+    fn with_attrib(mut self, width: usize, gl_type: GLenum, normalized: bool) -> Self {
+        assert!(!is_packed_type(gl_type) || width == 4, "attribs of packed types must have a width of 4");
+        self.instanced_attribs.push(InstancedAttrib { index: self.next_vertex_attrib, width, gl_type, normalized });
         self.next_vertex_attrib += 1;
         self
     }
 
-    pub fn build_with<U: Uniform>(self, shader: Shader<U>) -> Renderer<U> {
+    /// Add a stream of instanced attribs, sourced from its own VBO, which advance every `divisor`
+    /// instances instead of every instance. For instance a palette index shared by groups of
+    /// `divisor` sprites.
+    ///
+    /// Streams are numbered from 0 in the order they are added. Add attribs to them with
+    /// `with_stream_vertex_attrib`, and data with `Renderer::add_stream_data`.
+    ///
+    /// `max_instances` should be a multiple of `divisor`, since batches too big to be drawn at
+    /// once are split every `max_instances` instances.
+    pub fn with_instance_stream(mut self, divisor: u32) -> Self {
+        self.instance_streams.push(InstanceStream::new(divisor));
+        self
+    }
+
+    /// Same as `with_instanced_vertex_attrib`, but the attrib is sourced from the given stream.
+    ///
+    /// Locations are shared by all the attribs: an attrib added here takes the next location, as
+    /// if it were added by `with_instanced_vertex_attrib`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream was not added by `with_instance_stream`, or if `gl_type` is packed
+    /// and `width` isn't 4.
+    pub fn with_stream_vertex_attrib(mut self, stream: usize, width: usize, gl_type: GLenum) -> Self {
+        assert!(!is_packed_type(gl_type) || width == 4, "attribs of packed types must have a width of 4");
+        self.instance_streams[stream].attribs.push(InstancedAttrib { index: self.next_vertex_attrib, width, gl_type, normalized: false });
+        self.next_vertex_attrib += 1;
+        self
+    }
+
+    /// Add an instanced vertex attrib for every field of `layout`, in order, as if they were added
+    /// with `with_instanced_vertex_attrib`. Fill instances with `Renderer::add_instance`.
+    ///
+    /// In debug builds, `build_with` checks the locations and types of the fields against the
+    /// attributes of the shader.
+    ///
+    /// # Panics
+    ///
+    /// * if a layout was already given
+    pub fn with_vertex_layout(mut self, layout: VertexLayout) -> Self {
+        assert!(self.vertex_layout.is_none(), "the renderer already has a vertex layout");
+        let first_location = self.next_vertex_attrib;
+        for field in layout.fields() {
+            self = self.with_attrib(field.width, field.gl_type, field.normalized);
+        }
+        self.vertex_layout = Some((first_location, layout));
+        self
+    }
+
+    /// Use the layout of `sprites::QuadInstance`, to draw with `sprites::standard_quad_shader`.
+    ///
+    /// # Panics
+    ///
+    /// * if a layout was already given
+    /// * (debug only, in `build_with`) if other instanced attribs were added before: the shaders
+    ///   expect the quad fields from location 1 on
+    pub fn standard_quad_layout(self) -> Self {
+        self.with_vertex_layout(crate::sprites::QuadInstance::with_fields(VertexLayout::new()))
+    }
+
+    /// Use the layout of `sprites::TemplateInstance`, to draw with `sprites::template_quad_shader`.
+    ///
+    /// # Panics
+    ///
+    /// Same as `standard_quad_layout`.
+    pub fn template_quad_layout(self) -> Self {
+        self.with_vertex_layout(crate::sprites::TemplateInstance::with_fields(VertexLayout::new()))
+    }
+
+    /// # Panics
+    ///
+    /// * (debug only) if the vertex layout doesn't match the attributes of the shader
+    pub fn build_with<U: Uniform>(mut self, shader: Shader<U>) -> Renderer<U> {
+        #[cfg(debug_assertions)]
+        {
+            if let Some((first_location, layout)) = &self.vertex_layout {
+                let mismatches = layout.check_program(shader.program_id(), *first_location);
+                assert!(mismatches.is_empty(), "vertex layout doesn't match the shader:\n{}", mismatches.join("\n"));
+            }
+        }
+
         // the total size of the vbo to cotnain "max_elements".
         let tot_width_quad_vbo: usize = 2;
-        let tot_width_instanced_vbo: usize = self.instanced_attribs.iter().map(|(_, s, _)| s).sum();
+        let (_, instance_size) = attribs_layout(&self.instanced_attribs);
 
-        let all_elems_size_instanced_vbo = tot_width_instanced_vbo * self.max_instances * (size_of::<f32>());
+        let all_elems_size_instanced_vbo = instance_size * self.max_instances;
 
         unsafe {
             // allocate both buffers
@@ -89,14 +355,63 @@ impl RendererBuilder {
                 1.0, 0.0,
                 1.0, 1.0
             ];
+            const VERTICES8: [f32; 8] =
+            [
+                0.0, 0.0,
+                1.0, 0.0,
+                1.0, 1.0,
+                0.0, 1.0,
+            ];
             gl::BindBuffer(gl::ARRAY_BUFFER, self.quad_vbo);
-            gl::BufferData(gl::ARRAY_BUFFER, (tot_width_quad_vbo * VERTICES_PER_ELEM * size_of::<f32>()) as isize, &VERTICES12 as *const _ as *const c_void, gl::DYNAMIC_DRAW);
+            if self.use_index_buffer {
+                gl::BufferData(gl::ARRAY_BUFFER, (tot_width_quad_vbo * UNIQUE_VERTICES_PER_ELEM * size_of::<f32>()) as isize, &VERTICES8 as *const _ as *const c_void, gl::STATIC_DRAW);
+            } else {
+                gl::BufferData(gl::ARRAY_BUFFER, (tot_width_quad_vbo * VERTICES_PER_ELEM * size_of::<f32>()) as isize, &VERTICES12 as *const _ as *const c_void, gl::DYNAMIC_DRAW);
+            }
 
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.instanced_vbo);
-            gl::BufferData(gl::ARRAY_BUFFER, all_elems_size_instanced_vbo as isize, ptr::null(), gl::DYNAMIC_DRAW);
         }
 
-        
+        // the base instance of the persistent path would offset the attribs of the streams too.
+        let use_persistent_mapping = self.use_persistent_mapping && self.instance_streams.is_empty() && !self.gl_profile.is_es();
+        if self.use_persistent_mapping && !use_persistent_mapping {
+            log::debug!("persistent mapping is not supported with instance streams or on OpenGL ES, falling back to glBufferSubData");
+        }
+        let persistent = if use_persistent_mapping && PersistentBuffer::is_supported() {
+            PersistentBuffer::new(&mut self.instanced_vbo, all_elems_size_instanced_vbo)
+        } else {
+            if use_persistent_mapping {
+                log::debug!("persistent mapping is not supported by this context, falling back to glBufferSubData");
+            }
+            None
+        };
+        if persistent.is_some() {
+            if let GrowthPolicy::Grow { .. } = self.growth_policy {
+                log::debug!("persistent mapped buffers can't grow, instances will be flushed instead");
+            }
+        } else {
+            unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.instanced_vbo);
+                gl::BufferData(gl::ARRAY_BUFFER, all_elems_size_instanced_vbo as isize, ptr::null(), gl::DYNAMIC_DRAW);
+            }
+        }
+
+        let ebo = if self.use_index_buffer {
+            const INDICES: [u8; VERTICES_PER_ELEM] = [0, 1, 2, 0, 2, 3];
+            let mut ebo: MaybeUninit<GLuint> = MaybeUninit::uninit();
+            unsafe {
+                gl::GenBuffers(1, ebo.as_mut_ptr());
+                let ebo = ebo.assume_init();
+                // the VAO keeps track of the bound element buffer, so it must be bound first.
+                gl::BindVertexArray(self.vao);
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+                gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, size_of::<[u8; VERTICES_PER_ELEM]>() as isize, &INDICES as *const _ as *const c_void, gl::STATIC_DRAW);
+                gl::BindVertexArray(0);
+                Some(ebo)
+            }
+        } else {
+            None
+        };
+
         unsafe {
             gl::BindVertexArray(self.vao);
             gl::BindBuffer(gl::ARRAY_BUFFER, self.quad_vbo);
@@ -111,41 +426,16 @@ impl RendererBuilder {
             );
 
 
-            let mut current_stride: usize = 0;
             gl::BindBuffer(gl::ARRAY_BUFFER, self.instanced_vbo);
-            for (i, widthof_attrib, gl_type) in self.instanced_attribs {
-                gl::EnableVertexAttribArray(i);
-                log::debug!("enabled vertex attrib instanced i={} width={} gl_type={} current_stride={} tot_width_instanced_vbo={}",
-                    i, widthof_attrib, gl_type, current_stride, tot_width_instanced_vbo);
-                if gl_type != gl::FLOAT {
-                    gl::VertexAttribIPointer(
-                        i, widthof_attrib as GLint, gl_type,
-                        // f32, i32, u32 are all 4 bytes
-                        (tot_width_instanced_vbo * 4) as GLint,
-                        // f32, i32, u32 are all 4 bytes
-                        ptr::null::<c_void>().offset((current_stride * 4) as isize)
-                    );
-                } else {
-                    gl::VertexAttribPointer(
-                        i, widthof_attrib as GLint, gl_type, gl::FALSE,
-                        // f32, i32, u32 are all 4 bytes
-                        (tot_width_instanced_vbo * 4) as GLint,
-                        // f32, i32, u32 are all 4 bytes
-                        ptr::null::<c_void>().offset((current_stride * 4) as isize)
-                    );
-                }
-                // only necessary for instanced arrays
-                gl::VertexAttribDivisor(i, 1);
-                current_stride += widthof_attrib;
+            vertex_attrib_pointers(&self.instanced_attribs, 1, 0);
+            for stream in &self.instance_streams {
+                stream.bind_attribs(0);
             }
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
-
-        // general init for the renderer:
-        unsafe {
-            // enable alpha blending
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        log_attribs(&self.instanced_attribs, 1);
+        for stream in &self.instance_streams {
+            log_attribs(&stream.attribs, stream.divisor);
         }
 
         // unsafe {
@@ -155,16 +445,57 @@ impl RendererBuilder {
             // gl::Disable(gl::MULTISAMPLE_ARB);
         // }
 
-        Renderer {
+        // the viewport of the context when the renderer is built, until `set_viewport` is called.
+        let viewport = unsafe {
+            let mut viewport: [GLint; 4] = [0; 4];
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            (viewport[2].max(0) as u32, viewport[3].max(0) as u32)
+        };
+
+        let mut renderer = Renderer {
             vao: self.vao,
             instanced_vbo: self.instanced_vbo,
             quad_vbo: self.quad_vbo,
+            ebo,
+            persistent,
+            gl_profile: self.gl_profile,
             max_instances: self.max_instances,
+            instance_size,
+            instanced_attribs: self.instanced_attribs,
+            growth_policy: self.growth_policy,
             shader,
 
             instance_count: 0,
-            temp_instanced_vb: Vec::with_capacity(all_elems_size_instanced_vbo as usize),
+            temp_instanced_vb: Vec::with_capacity(all_elems_size_instanced_vbo),
+            temp_sort_keys: Vec::new(),
+            temp_sorted_vb: Vec::new(),
+            temp_stream_vbs: self.instance_streams.iter().map(|_| Vec::new()).collect(),
+            instance_streams: self.instance_streams,
+            vertex_layout: self.vertex_layout.map(|(_, layout)| layout),
+            world_wrap: None,
+            cull_bounds: None,
+            viewport,
+            virtual_resolution: None,
+            clip_stack: Vec::new(),
+            frame_stats: FrameStats::default(),
+            gpu_timer: None,
+            static_batches: HashMap::new(),
+            next_batch_id: 0,
+            blend_mode: self.blend_mode,
+            depth_mode: self.depth_mode,
+            preserve_gl_state: false,
+            layers: self.layers,
+            mesh: None,
+            globals: if self.standard_globals { Some(GlobalsClock::new()) } else { None },
+            alpha_mode: self.alpha_mode,
+        };
+        if renderer.alpha_mode == AlphaMode::Premultiplied {
+            renderer.shader.use_program();
+            renderer.shader.set_premultiplied_alpha(true);
         }
+        renderer.apply_blend_mode();
+        renderer.apply_depth_mode();
+        renderer
     }
 }
 
@@ -173,56 +504,791 @@ pub struct Renderer<U: Uniform> {
     pub (crate) vao: GLuint,
     pub (crate) quad_vbo: GLuint,
     pub (crate) instanced_vbo: GLuint,
+    /// only set if the renderer was built `with_index_buffer`
+    pub (crate) ebo: Option<GLuint>,
+    /// only set if the renderer was built `with_persistent_mapping`, and the context supports it
+    pub (crate) persistent: Option<PersistentBuffer>,
+    pub (crate) gl_profile: GlProfile,
+    pub (crate) instance_streams: Vec<InstanceStream>,
+    pub (crate) vertex_layout: Option<VertexLayout>,
     pub (crate) max_instances: usize,
+    /// size of the instanced data of a single instance, in bytes
+    pub (crate) instance_size: usize,
+    /// the attribs of `instanced_vbo`, to set up the VAOs of `StaticBatch`es
+    pub (crate) instanced_attribs: Vec<InstancedAttrib>,
+    pub (crate) growth_policy: GrowthPolicy,
     pub shader: Shader<U>,
 
     // temp values, reset after every draw
     pub (crate) temp_instanced_vb: Vec<u8>,
+    /// sort key of every instance of `temp_instanced_vb`, empty if `add_elem_with_key` wasn't used
+    pub (crate) temp_sort_keys: Vec<f32>,
+    /// `temp_instanced_vb` sorted by key, kept around to avoid reallocating it every frame
+    pub (crate) temp_sorted_vb: Vec<u8>,
+    /// data of every instance stream
+    pub (crate) temp_stream_vbs: Vec<Vec<u8>>,
 
     pub (crate) instance_count: usize,
+
+    pub (crate) world_wrap: Option<WorldWrap>,
+    /// world rect (x, y, w, h) outside of which `add_elem_with_bounds` skips elements
+    pub (crate) cull_bounds: Option<(f32, f32, f32, f32)>,
+
+    /// (width, height), needed to flip the y axis of clip rects.
+    pub (crate) viewport: (u32, u32),
+    /// when set, `gl::Viewport` is its letterboxed viewport instead of the whole window
+    pub (crate) virtual_resolution: Option<VirtualResolution>,
+    /// clip rects in screen coordinates (x, y, w, h), the last one being the current one.
+    pub (crate) clip_stack: Vec<(i32, i32, u32, u32)>,
+
+    /// counted since the last `reset_frame_stats`
+    pub (crate) frame_stats: FrameStats,
+    /// only set after `set_gpu_timing(true)`, if the context supports it
+    pub (crate) gpu_timer: Option<GpuTimer>,
+
+    /// batches created with `create_static_batch`
+    pub (crate) static_batches: HashMap<BatchId, StaticBatch>,
+    pub (crate) next_batch_id: u32,
+
+    pub (crate) blend_mode: BlendMode,
+    pub (crate) depth_mode: DepthMode,
+
+    /// save and restore the GL state of the application around every `draw`
+    pub (crate) preserve_gl_state: bool,
+
+    /// instances waiting in their layer until `draw`
+    pub (crate) layers: LayerStack,
+
+    /// only set after `set_mesh_shader`
+    pub (crate) mesh: Option<MeshPipeline<U>>,
+
+    /// only set if the renderer was built `with_standard_globals`
+    pub (crate) globals: Option<GlobalsClock>,
+    pub (crate) alpha_mode: AlphaMode,
+}
+
+/// How the color of an element is combined with what's behind it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    /// The usual alpha blending, set when the renderer is built.
+    #[default]
+    Alpha,
+    /// For textures whose colors are already multiplied by their alpha.
+    PremultipliedAlpha,
+    /// Adds the color to the background: lights, fire, particles...
+    Additive,
+    /// Darkens the background: shadows, tints...
+    ///
+    /// With straight alpha, the alpha of the element is ignored: the parts which should leave
+    /// the background untouched must be white.
+    Multiply,
+    /// No blending: the color replaces what's behind it, alpha included.
+    None,
+    /// Any `glBlendFunc` and `glBlendEquation`, for instance `gl::ONE`, `gl::ONE` and
+    /// `gl::FUNC_REVERSE_SUBTRACT` to subtract the color from the background.
+    Custom { src: GLenum, dst: GLenum, equation: GLenum },
+}
+
+/// Whether elements are hidden by the elements with a lower depth, drawn before or after them.
+///
+/// The context must have a depth buffer. With the standard layout, the depth is
+/// `QuadInstance::depth`: opaque sprites can then be drawn in any order instead of being sorted
+/// on the CPU, while transparent ones should still be sorted and drawn afterwards with `TEST_ONLY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct DepthMode {
+    /// Discard the fragments behind what's already drawn (`GL_LEQUAL`).
+    pub test: bool,
+    /// Write the depth of the drawn fragments to the depth buffer.
+    pub write: bool,
+}
+
+impl DepthMode {
+    /// No depth test nor write, the default: elements are drawn in the order they are added.
+    pub const DISABLED: DepthMode = DepthMode { test: false, write: false };
+    /// For opaque sprites: they hide and are hidden by each other.
+    pub const OPAQUE: DepthMode = DepthMode { test: true, write: true };
+    /// For transparent sprites drawn after the opaque ones: they are hidden, but don't hide.
+    pub const TEST_ONLY: DepthMode = DepthMode { test: true, write: false };
+}
+
+/// Describes a world which wraps around on one or both axes, like in Asteroids.
+///
+/// When set on a `Renderer`, elements added with `add_elem_wrapped` close to a seam are drawn
+/// a second time on the other side of the world.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WorldWrap {
+    /// Width of the world, if it wraps horizontally. The world spans from 0 to `width`.
+    pub width: Option<f32>,
+    /// Height of the world, if it wraps vertically. The world spans from 0 to `height`.
+    pub height: Option<f32>,
+    /// Elements closer than `margin` to a seam are duplicated as well.
+    ///
+    /// With a margin of 0, only elements crossing a seam are duplicated. If your camera can show
+    /// both sides of a seam at once, set this to half the size of the view.
+    pub margin: f32,
+}
+
+impl WorldWrap {
+    /// Returns the offset to apply to draw a copy on the other side of the seam, if any.
+    fn seam_offset(position: f32, size: f32, extent: Option<f32>, margin: f32) -> Option<f32> {
+        let extent = extent?;
+        if position < margin {
+            Some(extent)
+        } else if position + size > extent - margin {
+            Some(-extent)
+        } else {
+            None
+        }
+    }
 }
 
 impl<U: Uniform> Renderer<U> {
-    /// Clear the screen with a solid color.
+    /// Clear the screen with a solid color, and the depth buffer if there is one.
     /// 
     /// Default clear color is black, just like your soul.
     pub fn clear(&mut self, clear_color: Option<Color<u8>>) {
         let clear_color: Color<f32> = clear_color.unwrap_or_else(|| Color::<u8>::from_rgb(0, 0, 0)).to_color_f32();
         unsafe {
+            // glClear is affected by the scissor test, but we always want to clear everything.
+            gl::Disable(gl::SCISSOR_TEST);
             gl::ClearColor(clear_color.r, clear_color.g, clear_color.b, 1.0f32);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+            // and by the depth mask.
+            gl::DepthMask(gl::TRUE);
+            if self.gl_profile.is_es() {
+                gl::ClearDepthf(1.0);
+            } else {
+                gl::ClearDepth(1.0);
+            }
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        self.apply_clip_rect();
+        self.apply_depth_mode();
+    }
+
+    /// Call this when the window is resized, with its new size in pixels.
+    ///
+    /// With a `VirtualResolution`, only its letterboxed part of the window is drawn to.
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = (width, height);
+        if let Some(virtual_resolution) = &mut self.virtual_resolution {
+            virtual_resolution.set_window_size(width, height);
         }
+        self.apply_viewport();
+        self.apply_clip_rect();
+    }
+
+    /// Set the GL viewport of the current window size and virtual resolution, after something
+    /// else changed it.
+    pub (crate) fn apply_viewport(&self) {
+        let (width, height) = self.viewport;
+        let (x, y, w, h) = match &self.virtual_resolution {
+            Some(virtual_resolution) => virtual_resolution.viewport(),
+            None => (0, 0, width, height),
+        };
+        unsafe {
+            // glViewport has its origin at the bottom left of the window.
+            gl::Viewport(x, height as i32 - (y + h as i32), w as i32, h as i32);
+        }
+    }
+
+    /// Save the GL state of the application before every `draw` and `draw_batch`, and restore it
+    /// afterwards. The program, blend and depth modes, viewport and clip rect of the renderer are
+    /// set again before drawing, in case the application changed them in the meantime. See
+    /// `gl_state`.
+    ///
+    /// This costs a few dozen glGet per draw: meant for a few draws per frame, within a larger
+    /// GL application. Disabled by default.
+    pub fn set_preserve_gl_state(&mut self, preserve: bool) {
+        self.preserve_gl_state = preserve;
+    }
+
+    #[inline]
+    pub fn preserve_gl_state(&self) -> bool {
+        self.preserve_gl_state
+    }
+
+    /// Draw at a fixed resolution scaled to the window, see `VirtualResolution`. Elements added
+    /// before this call are drawn right away, with the previous viewport.
+    ///
+    /// Clip rects and `read_pixels` are still in pixels of the window.
+    pub fn set_virtual_resolution(&mut self, virtual_resolution: Option<VirtualResolution>) {
+        self.flush();
+        self.virtual_resolution = virtual_resolution;
+        let (width, height) = self.viewport;
+        self.set_viewport(width, height);
     }
 
     #[inline]
-    pub fn set_viewport(&self, width: u32, height: u32) {
+    pub fn virtual_resolution(&self) -> Option<&VirtualResolution> {
+        self.virtual_resolution.as_ref()
+    }
+
+    /// Converts a position in the window (e.g. the mouse) to virtual coordinates, or returns it
+    /// as is without a virtual resolution.
+    pub fn window_to_virtual(&self, position: Vector2<f32>) -> Vector2<f32> {
+        match &self.virtual_resolution {
+            Some(virtual_resolution) => virtual_resolution.window_to_virtual(position),
+            None => position,
+        }
+    }
+
+    /// Only draw inside the given rectangle, in screen coordinates with the origin at the top left.
+    ///
+    /// The rectangle is intersected with the current one, so children can't draw outside of their
+    /// parents. Elements added before this call are drawn right away, with the previous clip rect.
+    pub fn push_clip_rect(&mut self, x: i32, y: i32, w: u32, h: u32) {
+        self.flush();
+        let rect = match self.clip_stack.last() {
+            Some(&(px, py, pw, ph)) => {
+                let x1 = x.max(px);
+                let y1 = y.max(py);
+                // in i64, so that the far corners can't overflow.
+                let x2 = (x as i64 + w as i64).min(px as i64 + pw as i64);
+                let y2 = (y as i64 + h as i64).min(py as i64 + ph as i64);
+                (x1, y1, (x2 - x1 as i64).max(0) as u32, (y2 - y1 as i64).max(0) as u32)
+            },
+            None => (x, y, w, h),
+        };
+        self.clip_stack.push(rect);
+        self.apply_clip_rect();
+    }
+
+    /// Go back to the clip rect before the last `push_clip_rect`. Elements added before this call
+    /// are drawn right away, with the clip rect being removed.
+    ///
+    /// # Panics
+    ///
+    /// * (debug only) if there is no clip rect to pop
+    pub fn pop_clip_rect(&mut self) {
+        debug_assert!(!self.clip_stack.is_empty(), "pop_clip_rect called without push_clip_rect");
+        self.flush();
+        self.clip_stack.pop();
+        self.apply_clip_rect();
+    }
+
+    /// Returns the current clip rect, if any.
+    pub fn clip_rect(&self) -> Option<(i32, i32, u32, u32)> {
+        self.clip_stack.last().cloned()
+    }
+
+    /// Draw the pending elements and meshes, if any, but not the layers.
+    pub (crate) fn flush(&mut self) {
+        if self.instance_count > 0 || self.has_pending_meshes() {
+            self.draw_with_layers(false);
+        }
+    }
+
+    pub (crate) fn apply_clip_rect(&self) {
         unsafe {
-            gl::Viewport(0, 0, width as i32, height as i32);
+            match self.clip_stack.last() {
+                Some(&(x, y, w, h)) => {
+                    // glScissor has its origin at the bottom left of the viewport.
+                    let flipped_y = self.viewport.1 as i64 - (y as i64 + h as i64);
+                    let flipped_y = flipped_y.clamp(i32::MIN as i64, i32::MAX as i64) as GLint;
+                    let (w, h) = (w.min(i32::MAX as u32) as GLsizei, h.min(i32::MAX as u32) as GLsizei);
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(x, flipped_y, w, h);
+                },
+                None => gl::Disable(gl::SCISSOR_TEST),
+            }
         }
     }
 
     pub fn add_elem<E: AsVertexData>(&mut self, e: &E) {
         let added_instances = e.add_vertex_data(&mut self.temp_instanced_vb);
-        self.instance_count += added_instances as usize;
+        self.add_instance_count(added_instances as usize);
     }
 
-    pub fn draw(&mut self) {
-        assert!(self.max_instances >= self.instance_count);
+    /// Same as `add_elem`, but the instances are sorted by `sort_key` before being drawn: the lowest
+    /// keys are drawn first, and end up below the others. Use it for transparent elements, which
+    /// must be drawn back to front.
+    ///
+    /// Elements added with `add_elem` have a key of 0, and the ones with the same key are drawn in
+    /// the order they were added.
+    ///
+    /// Only the instances drawn at once are sorted together: calling `draw`, changing the blend
+    /// mode or the clip rect draws right away, and so does reaching `max_instances` with
+    /// `GrowthPolicy::Flush`.
+    pub fn add_elem_with_key<E: AsVertexData>(&mut self, e: &E, sort_key: f32) {
+        self.temp_sort_keys.resize(self.instance_count, 0.0);
+        let added_instances = e.add_vertex_data(&mut self.temp_instanced_vb) as usize;
+        self.temp_sort_keys.resize(self.instance_count + added_instances, sort_key);
+        self.add_instance_count(added_instances);
+    }
+
+    /// Add a single instance laid out by the `VertexLayout` given to the builder.
+    ///
+    /// ```ignore
+    /// renderer.add_instance(|instance| {
+    ///     instance.set_f32s("crop", &[0.0, 0.0, 1.0, 1.0])
+    ///         .set_f32s("translation", &[x, y])
+    ///         .set_u32("layer", layer);
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// * if the renderer was not built `with_vertex_layout`
+    /// * if `f` panics, see `InstanceWriter`
+    pub fn add_instance<F: FnOnce(&mut InstanceWriter)>(&mut self, f: F) {
+        let layout = self.vertex_layout.as_ref().expect("add_instance needs a renderer built with_vertex_layout");
+        debug_assert_eq!(layout.stride(), self.instance_size, "attribs were added outside of the vertex layout");
+        f(&mut InstanceWriter::new(layout, &mut self.temp_instanced_vb));
+        self.add_instance_count(1);
+    }
+
+    /// The layout given to `RendererBuilder::with_vertex_layout`, if any.
+    pub fn vertex_layout(&self) -> Option<&VertexLayout> {
+        self.vertex_layout.as_ref()
+    }
+
+    /// Add data to a stream added with `RendererBuilder::with_instance_stream`: one entry for every
+    /// `divisor` instances added with `add_elem`, laid out like the attribs of the stream.
+    ///
+    /// The data is drawn with the instances on the next `draw`. Instances sorted by
+    /// `add_elem_with_key` are sorted on their own, without this data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream doesn't exist.
+    pub fn add_stream_data(&mut self, stream: usize, data: &[u8]) {
+        self.temp_stream_vbs[stream].extend_from_slice(data);
+    }
+
+    /// Add instances whose vertex data is already laid out, for instance by a `DrawCommandBuffer`.
+    pub (crate) fn add_raw_instances(&mut self, instanced_vb: &[u8], instance_count: usize) {
+        self.temp_instanced_vb.extend_from_slice(instanced_vb);
+        self.add_instance_count(instance_count);
+    }
+
+    /// To call after adding instances to `temp_instanced_vb`.
+    fn add_instance_count(&mut self, added_instances: usize) {
+        self.instance_count += added_instances;
+        if self.instance_count >= self.instance_limit() {
+            self.flush();
+        }
+    }
+
+    /// Change how the next elements are blended with what's already drawn. Elements added before
+    /// this call are drawn right away, with the previous blend mode.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.flush();
+        self.blend_mode = blend_mode;
+        self.apply_blend_mode();
+    }
+
+    /// Sample the textures bound to `texture_unit` with `sampler` instead of their own settings,
+    /// or with their own settings again if `None`. Elements added before this call are drawn
+    /// right away, with the previous sampler.
+    ///
+    /// See `TextureKind::texture_unit` for the units of the `RenderStorage`.
+    pub fn set_sampler(&mut self, texture_unit: GLuint, sampler: Option<&Sampler>) {
+        self.flush();
+        unsafe {
+            gl::BindSampler(texture_unit, sampler.map_or(0, Sampler::id));
+        }
+    }
+
+    /// Draw the elements added by `f` with `sampler` bound to `texture_unit`, then set the
+    /// textures of that unit back to their own settings. Elements added before are drawn right
+    /// away, without it.
+    ///
+    /// ```ignore
+    /// // the world is zoomed out: smaller mipmaps, for less shimmer
+    /// let far = Sampler::new(TextureArraySettings::nearest().with_mipmaps(true).with_lod_bias(0.5));
+    /// renderer.with_sampler(TextureKind::RGBA.texture_unit(), &far, |renderer| {
+    ///     for sprite in &world {
+    ///         renderer.add_elem(sprite);
+    ///     }
+    /// });
+    /// ```
+    pub fn with_sampler<F: FnOnce(&mut Self)>(&mut self, texture_unit: GLuint, sampler: &Sampler, f: F) {
+        self.set_sampler(texture_unit, Some(sampler));
+        f(self);
+        self.set_sampler(texture_unit, None);
+    }
+
+    #[inline]
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Draw textures of a `RenderStorage` with this alpha mode from now on, see
+    /// `RendererBuilder::with_alpha_mode`. Elements added before this call are drawn right away,
+    /// with the previous mode.
+    pub fn set_alpha_mode(&mut self, alpha_mode: AlphaMode) {
+        if alpha_mode == self.alpha_mode {
+            return;
+        }
+        self.flush();
+        self.alpha_mode = alpha_mode;
+        let premultiplied = alpha_mode == AlphaMode::Premultiplied;
+        self.for_each_shader(|shader| {
+            shader.use_program();
+            shader.set_premultiplied_alpha(premultiplied);
+        });
+        self.apply_blend_mode();
+    }
+
+    #[inline]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// The profile given to `RendererBuilder::with_gl_profile`, or the one of the context.
+    #[inline]
+    pub fn gl_profile(&self) -> GlProfile {
+        self.gl_profile
+    }
+
+    /// Change the depth test and write of the next elements, see `DepthMode`. Elements added
+    /// before this call are drawn right away, with the previous depth mode.
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        self.flush();
+        self.depth_mode = depth_mode;
+        self.apply_depth_mode();
+    }
+
+    #[inline]
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    /// Set the GL state of the current depth mode, after something else changed it.
+    pub (crate) fn apply_depth_mode(&self) {
         unsafe {
-            // fill instanced_vbo from temp
+            if self.depth_mode.test || self.depth_mode.write {
+                // writing needs the test to be enabled: ALWAYS writes without discarding anything.
+                gl::Enable(gl::DEPTH_TEST);
+                gl::DepthFunc(if self.depth_mode.test { gl::LEQUAL } else { gl::ALWAYS });
+            } else {
+                gl::Disable(gl::DEPTH_TEST);
+            }
+            gl::DepthMask(if self.depth_mode.write { gl::TRUE } else { gl::FALSE });
+        }
+    }
+
+    /// Set everything the renderer draws with: its program, blend mode, depth mode, viewport and
+    /// clip rect, after something else changed them.
+    pub (crate) fn apply_draw_state(&self) {
+        self.shader.use_program();
+        self.apply_blend_mode();
+        self.apply_depth_mode();
+        self.apply_viewport();
+        self.apply_clip_rect();
+    }
+
+    /// Set the GL state of the current blend mode, after something else changed it.
+    pub (crate) fn apply_blend_mode(&self) {
+        let premultiplied = self.alpha_mode == AlphaMode::Premultiplied;
+        let (src, dst, equation) = match self.blend_mode {
+            // the shaders output premultiplied colors with premultiplied textures
+            BlendMode::Alpha if premultiplied => (gl::ONE, gl::ONE_MINUS_SRC_ALPHA, gl::FUNC_ADD),
+            BlendMode::Alpha => (gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::FUNC_ADD),
+            BlendMode::PremultipliedAlpha => (gl::ONE, gl::ONE_MINUS_SRC_ALPHA, gl::FUNC_ADD),
+            BlendMode::Additive if premultiplied => (gl::ONE, gl::ONE, gl::FUNC_ADD),
+            BlendMode::Additive => (gl::SRC_ALPHA, gl::ONE, gl::FUNC_ADD),
+            // dst * src + dst * (1 - a) is only dst where a = 0 if src is premultiplied.
+            BlendMode::Multiply if premultiplied => (gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA, gl::FUNC_ADD),
+            BlendMode::Multiply => (gl::DST_COLOR, gl::ZERO, gl::FUNC_ADD),
+            BlendMode::None => {
+                unsafe { gl::Disable(gl::BLEND); }
+                return;
+            },
+            BlendMode::Custom { src, dst, equation } => (src, dst, equation),
+        };
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendEquation(equation);
+            gl::BlendFunc(src, dst);
+        }
+    }
+
+    /// Number of instances after which `add_elem` draws right away.
+    fn instance_limit(&self) -> usize {
+        match self.growth_policy {
+            GrowthPolicy::Grow { max_instances } if self.persistent.is_none() => max_instances.max(self.max_instances),
+            _ => self.max_instances,
+        }
+    }
+
+    /// Reallocate the instanced VBO so that it can hold `instance_count` instances, within the
+    /// limit of the growth policy.
+    fn grow(&mut self) {
+        let new_max_instances = self.instance_count.next_power_of_two().min(self.instance_limit());
+        if new_max_instances <= self.max_instances {
+            return;
+        }
+        log::debug!("growing instanced vbo from max_instances={} to {}", self.max_instances, new_max_instances);
+        unsafe {
+            // the vertex attribs refer to the buffer by name, so they don't need to be set again.
             gl::BindBuffer(gl::ARRAY_BUFFER, self.instanced_vbo);
-            gl::BufferSubData(gl::ARRAY_BUFFER, 0, self.temp_instanced_vb.len() as isize, self.temp_instanced_vb.as_ptr() as *const _);
-            // note that temp VBs are used instead of copying 1 by 1, because we never know how long an opengl call might take,
-            // every implementation might take a short or long time. Since we have to do this call several times (up to multiple thousands) per frame,
-            // i found it best to regroup it into one single call, using a temporary buffer on the heap.
+            gl::BufferData(gl::ARRAY_BUFFER, (new_max_instances * self.instance_size) as isize, ptr::null(), gl::DYNAMIC_DRAW);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.max_instances = new_max_instances;
+    }
 
-            gl::BindVertexArray(self.vao);
-            gl::DrawArraysInstanced(gl::TRIANGLES, 0, VERTICES_PER_ELEM as GLint, self.instance_count as GLint);
-            gl::BindVertexArray(0);
+    /// Set (or unset) the size of a world that wraps around, used by `add_elem_wrapped`.
+    pub fn set_world_wrap(&mut self, world_wrap: Option<WorldWrap>) {
+        self.world_wrap = world_wrap;
+    }
+
+    /// Add an element, along with copies on the other side of the world if it is near a seam.
+    ///
+    /// Behaves exactly like `add_elem` if no `WorldWrap` has been set.
+    pub fn add_elem_wrapped<E: WrappableVertexData>(&mut self, e: &E) {
+        self.add_elem(e);
+        let world_wrap = match self.world_wrap {
+            Some(world_wrap) => world_wrap,
+            None => return,
+        };
+        let (x, y, w, h) = e.bounds();
+        let offset_x = WorldWrap::seam_offset(x, w, world_wrap.width, world_wrap.margin);
+        let offset_y = WorldWrap::seam_offset(y, h, world_wrap.height, world_wrap.margin);
+        if let Some(dx) = offset_x {
+            self.add_elem(&e.translated(dx, 0.0));
         }
+        if let Some(dy) = offset_y {
+            self.add_elem(&e.translated(0.0, dy));
+        }
+        if let (Some(dx), Some(dy)) = (offset_x, offset_y) {
+            // close to a corner: the element must be visible from the 4 corners.
+            self.add_elem(&e.translated(dx, dy));
+        }
+    }
+
+    /// Set (or unset) the part of the world which is visible, used by `add_elem_with_bounds`:
+    /// (x, y, w, h), in the coordinates of the elements.
+    pub fn set_cull_bounds(&mut self, bounds: Option<(f32, f32, f32, f32)>) {
+        self.cull_bounds = bounds;
+    }
+
+    /// Cull the elements outside of what `camera` sees. Call it again when the camera moves.
+    pub fn set_cull_camera(&mut self, camera: &Camera2D) {
+        self.cull_bounds = Some(camera.visible_bounds());
+    }
+
+    #[inline]
+    pub fn cull_bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        self.cull_bounds
+    }
+
+    /// Add an element only if `bounds` (x, y, w, h) intersects the cull bounds, and return
+    /// whether it was added. Skipped elements are counted in `FrameStats::culled`.
+    ///
+    /// Behaves exactly like `add_elem` if no cull bounds have been set. The bounds must contain
+    /// everything the element draws, rotations included.
+    pub fn add_elem_with_bounds<E: AsVertexData>(&mut self, e: &E, bounds: (f32, f32, f32, f32)) -> bool {
+        if let Some((cull_x, cull_y, cull_w, cull_h)) = self.cull_bounds {
+            let (x, y, w, h) = bounds;
+            if x > cull_x + cull_w || x + w < cull_x || y > cull_y + cull_h || y + h < cull_y {
+                self.frame_stats.culled += 1;
+                return false;
+            }
+        }
+        self.add_elem(e);
+        true
+    }
+
+    /// Draw the elements added so far, then the instances of the layers in order (see
+    /// `LayerStack`).
+    ///
+    /// If there are more than `max_instances` of them, the instanced VBO is grown according to
+    /// the `GrowthPolicy`, and the rest is drawn in several calls.
+    ///
+    /// With `RendererBuilder::with_standard_globals`, the `StandardGlobals` of the frame started
+    /// by `begin_frame` are set before anything is drawn, including the elements drawn before
+    /// `draw` (when the blend mode changes for instance) and static batches.
+    pub fn draw(&mut self) {
+        self.draw_with_layers(true);
+    }
+
+    fn draw_with_layers(&mut self, layers: bool) {
+        self.with_draw_state(false, |renderer| {
+            // pending meshes were added after the elements drawn before them, and before the pending ones
+            renderer.draw_meshes();
+            renderer.draw_pending();
+            if layers && renderer.layers.has_instances() {
+                renderer.draw_layers();
+            }
+        });
+    }
+
+    /// Run `draw` with the state of the renderer, applied if `apply` is set, and its current
+    /// `StandardGlobals`. With `set_preserve_gl_state`, the state is always applied, and the one of
+    /// the application is restored afterwards.
+    pub (crate) fn with_draw_state<F: FnOnce(&mut Self)>(&mut self, apply: bool, draw: F) {
+        let saved_state = if self.preserve_gl_state {
+            Some(GlState::capture())
+        } else {
+            None
+        };
+        if apply || saved_state.is_some() {
+            self.apply_draw_state();
+        }
+        // the viewport may have changed since the last draw, and the frame since `begin_frame`.
+        self.update_globals();
+        draw(self);
+        if let Some(saved_state) = saved_state {
+            saved_state.restore();
+        }
+    }
+
+    /// Draw the instances of `temp_instanced_vb`.
+    pub (crate) fn draw_pending(&mut self) {
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin();
+        }
+        if self.instance_count > self.max_instances {
+            self.grow();
+        }
+        if !self.temp_sort_keys.is_empty() {
+            self.sort_instances();
+        }
+        self.upload_streams();
+        let instanced_vb = std::mem::take(&mut self.temp_instanced_vb);
+        let chunk_size = self.max_instances * self.instance_size;
+        let mut drawn = 0;
+        while drawn < self.instance_count {
+            if drawn > 0 && !self.instance_streams.is_empty() {
+                unsafe {
+                    gl::BindVertexArray(self.vao);
+                    for stream in &self.instance_streams {
+                        stream.bind_attribs(drawn);
+                    }
+                    gl::BindVertexArray(0);
+                }
+            }
+            let instance_count = (self.instance_count - drawn).min(self.max_instances);
+            let offset = drawn * self.instance_size;
+            let chunk_end = (offset + chunk_size).min(instanced_vb.len());
+            self.draw_instances(&instanced_vb[offset..chunk_end], instance_count);
+            drawn += instance_count;
+        }
+        if drawn > self.max_instances && !self.instance_streams.is_empty() {
+            unsafe {
+                gl::BindVertexArray(self.vao);
+                for stream in &self.instance_streams {
+                    stream.bind_attribs(0);
+                }
+                gl::BindVertexArray(0);
+            }
+        }
+        self.temp_instanced_vb = instanced_vb;
         self.instance_count = 0;
         self.temp_instanced_vb.clear();
+        self.temp_sort_keys.clear();
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end();
+        }
+    }
+
+    /// Instances, draw calls and bytes submitted since the last `reset_frame_stats`.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    /// Start counting from 0 again, typically once per frame.
+    ///
+    /// With `set_gpu_timing`, this also closes the frame for the GPU timer, and reads the time of
+    /// the most recent frame whose results are available.
+    pub fn reset_frame_stats(&mut self) {
+        let gpu_time = match &mut self.gpu_timer {
+            Some(gpu_timer) => gpu_timer.end_frame().or(self.frame_stats.gpu_time),
+            None => None,
+        };
+        self.frame_stats = FrameStats { gpu_time, ..FrameStats::default() };
+    }
+
+    /// Measure the GPU time of every `draw` with `GL_TIME_ELAPSED` queries, reported by
+    /// `FrameStats::gpu_time`. Call `reset_frame_stats` once per frame.
+    ///
+    /// Returns false if the context doesn't support timer queries, in which case nothing is
+    /// measured. This is always the case with `GlProfile::Es`.
+    pub fn set_gpu_timing(&mut self, enabled: bool) -> bool {
+        if !enabled {
+            self.gpu_timer = None;
+            self.frame_stats.gpu_time = None;
+        } else if self.gpu_timer.is_none() && !self.gl_profile.is_es() && GpuTimer::is_supported() {
+            self.gpu_timer = Some(GpuTimer::default());
+        }
+        self.gpu_timer.is_some() == enabled
+    }
+
+    /// Upload the data of the instance streams to their VBOs.
+    fn upload_streams(&mut self) {
+        for (stream, data) in self.instance_streams.iter().zip(&mut self.temp_stream_vbs) {
+            if data.is_empty() {
+                continue;
+            }
+            unsafe {
+                // orphans the previous storage, which may still be in use by the GPU.
+                gl::BindBuffer(gl::ARRAY_BUFFER, stream.vbo);
+                gl::BufferData(gl::ARRAY_BUFFER, data.len() as isize, data.as_ptr() as *const _, gl::STREAM_DRAW);
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            }
+            self.frame_stats.bytes_uploaded += data.len();
+            data.clear();
+        }
+    }
+
+    /// Sort the instances of `temp_instanced_vb` by their sort key.
+    fn sort_instances(&mut self) {
+        // instances added with add_elem after the last add_elem_with_key
+        self.temp_sort_keys.resize(self.instance_count, 0.0);
+        let keys = &self.temp_sort_keys;
+        let mut order: Vec<usize> = (0..self.instance_count).collect();
+        // stable, so that instances with the same key keep their order.
+        order.sort_by(|a, b| keys[*a].total_cmp(&keys[*b]));
+
+        self.temp_sorted_vb.clear();
+        for i in order {
+            let offset = i * self.instance_size;
+            self.temp_sorted_vb.extend_from_slice(&self.temp_instanced_vb[offset..offset + self.instance_size]);
+        }
+        std::mem::swap(&mut self.temp_instanced_vb, &mut self.temp_sorted_vb);
+    }
+
+    /// Draw `instance_count` instances, whose data is `instanced_vb`, in one call.
+    fn draw_instances(&mut self, instanced_vb: &[u8], instance_count: usize) {
+        assert!(self.max_instances >= instance_count);
+        self.frame_stats.instances += instance_count;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.bytes_uploaded += instanced_vb.len();
+        if let Some(persistent) = &mut self.persistent {
+            persistent.write(instanced_vb);
+            // every region holds max_instances instances, so the attribs are offset by whole instances.
+            let base_instance = (persistent.current_region * self.max_instances) as GLuint;
+            unsafe {
+                gl::BindVertexArray(self.vao);
+                if self.ebo.is_some() {
+                    gl::DrawElementsInstancedBaseInstance(gl::TRIANGLES, VERTICES_PER_ELEM as GLint, gl::UNSIGNED_BYTE, ptr::null(), instance_count as GLint, base_instance);
+                } else {
+                    gl::DrawArraysInstancedBaseInstance(gl::TRIANGLES, 0, VERTICES_PER_ELEM as GLint, instance_count as GLint, base_instance);
+                }
+                gl::BindVertexArray(0);
+            }
+            persistent.fence_and_advance();
+        } else {
+            unsafe {
+                // fill instanced_vbo from temp
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.instanced_vbo);
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, instanced_vb.len() as isize, instanced_vb.as_ptr() as *const _);
+                // note that temp VBs are used instead of copying 1 by 1, because we never know how long an opengl call might take,
+                // every implementation might take a short or long time. Since we have to do this call several times (up to multiple thousands) per frame,
+                // i found it best to regroup it into one single call, using a temporary buffer on the heap.
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+                gl::BindVertexArray(self.vao);
+                if self.ebo.is_some() {
+                    gl::DrawElementsInstanced(gl::TRIANGLES, VERTICES_PER_ELEM as GLint, gl::UNSIGNED_BYTE, ptr::null(), instance_count as GLint);
+                } else {
+                    gl::DrawArraysInstanced(gl::TRIANGLES, 0, VERTICES_PER_ELEM as GLint, instance_count as GLint);
+                }
+                gl::BindVertexArray(0);
+            }
+        }
     }
 }
 
@@ -231,7 +1297,18 @@ impl<U: Uniform> Drop for Renderer<U> {
         unsafe {
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteBuffers(1, &self.quad_vbo);
+            if self.persistent.is_some() {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.instanced_vbo);
+                gl::UnmapBuffer(gl::ARRAY_BUFFER);
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            }
             gl::DeleteBuffers(1, &self.instanced_vbo);
+            if let Some(ebo) = self.ebo {
+                gl::DeleteBuffers(1, &ebo);
+            }
+            for stream in &self.instance_streams {
+                gl::DeleteBuffers(1, &stream.vbo);
+            }
         }
     }
 }
@@ -245,4 +1322,31 @@ pub trait AsVertexData {
     /// should add as many vertex as you want to this buffer, respecting of course
     /// your config. You should return the number of instances you have added.
     fn add_vertex_data(&self, instanced_vb: &mut Vec<u8>) -> u32;
-}
\ No newline at end of file
+}
+/// Append `values` to an instanced vertex buffer, to use in your `AsVertexData` implementations
+/// instead of transmuting.
+#[inline]
+pub fn push_f32s(instanced_vb: &mut Vec<u8>, values: &[f32]) {
+    instanced_vb.reserve(std::mem::size_of_val(values));
+    for v in values {
+        instanced_vb.extend_from_slice(&v.to_ne_bytes());
+    }
+}
+
+/// Append `values` as `gl::HALF_FLOAT`s, see `packing::f32_to_f16`.
+#[inline]
+pub fn push_f16s(instanced_vb: &mut Vec<u8>, values: &[f32]) {
+    instanced_vb.reserve(values.len() * 2);
+    for v in values {
+        instanced_vb.extend_from_slice(&packing::f32_to_f16(*v).to_ne_bytes());
+    }
+}
+
+/// Elements which can be drawn in a wrapping world, see `Renderer::add_elem_wrapped`.
+pub trait WrappableVertexData: AsVertexData + Sized {
+    /// Returns the bounding box of the element in world coordinates: (x, y, w, h).
+    fn bounds(&self) -> (f32, f32, f32, f32);
+
+    /// Returns a copy of this element, moved by (dx, dy).
+    fn translated(&self, dx: f32, dy: f32) -> Self;
+}
@@ -0,0 +1,117 @@
+//! Conversions to the small formats instanced attributes can be declared with, to shrink the
+//! instances: `gl::HALF_FLOAT`, normalized bytes and shorts, and the 10.10.10.2 packed types.
+//!
+//! ```ignore
+//! let layout = VertexLayout::new()
+//!     .with_field("position", 2, gl::FLOAT)
+//!     .with_field("rotation", 1, gl::HALF_FLOAT)
+//!     .with_normalized_field("direction", 4, gl::INT_2_10_10_10_REV)
+//!     .with_normalized_field("color", 4, gl::UNSIGNED_BYTE);
+//! renderer.add_instance(|instance| {
+//!     instance.set_f32s("position", &[x, y])
+//!         .set_f16s("rotation", &[rotation])
+//!         .set_10_10_10_2("direction", pack_snorm_10_10_10_2([dx, dy, 0.0, 1.0]))
+//!         .set_u8s("color", &pack_unorm8x4([r, g, b, a]));
+//! });
+//! ```
+//!
+//! Signed normalized values follow the conversion of GL 4.2 and GLES 3.0, where -1.0, 0.0 and 1.0
+//! are exact. Older drivers read them slightly differently, by half a step at most.
+
+/// Convert to a half float (IEEE 754 binary16), rounding to the nearest. Values too big become
+/// infinities, and values too small become subnormals or zeros.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        // infinity, or NaN which must stay one
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        // subnormal: the implicit 1 of the mantissa becomes explicit
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = remainder > halfway || (remainder == halfway && half_mantissa & 1 == 1);
+        return sign | (half_mantissa + round_up as u32) as u16;
+    }
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    // a carry out of the mantissa increments the exponent, up to an infinity
+    sign | (half + round_up as u32) as u16
+}
+
+/// Convert a half float (IEEE 754 binary16) back, exactly.
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // subnormal, which is a normal f32: move the highest bit to the implicit 1
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((127 - 15 + 1 - shift) << 23) | (((mantissa << shift) & 0x3ff) << 13)
+        },
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// A value from 0.0 to 1.0, as a normalized `gl::UNSIGNED_BYTE`. Out of range values are clamped.
+pub fn pack_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// 4 values from 0.0 to 1.0 (typically a color), as 4 normalized `gl::UNSIGNED_BYTE`s.
+pub fn pack_unorm8x4(values: [f32; 4]) -> [u8; 4] {
+    [pack_unorm8(values[0]), pack_unorm8(values[1]), pack_unorm8(values[2]), pack_unorm8(values[3])]
+}
+
+/// A value from -1.0 to 1.0, as a normalized `gl::SHORT`. Out of range values are clamped.
+pub fn pack_snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+/// A value from 0.0 to 1.0, as a normalized `gl::UNSIGNED_SHORT`. Out of range values are clamped.
+pub fn pack_unorm16(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+/// 4 values from -1.0 to 1.0 in a normalized `gl::INT_2_10_10_10_REV`: x, y and z get 10 bits,
+/// w only 2 (-1.0, 0.0 or 1.0). Out of range values are clamped.
+///
+/// Typically a direction or a normal, in 4 bytes instead of 12.
+pub fn pack_snorm_10_10_10_2(values: [f32; 4]) -> u32 {
+    let snorm = |value: f32, bits: u32| {
+        let max = ((1 << (bits - 1)) - 1) as f32;
+        let value = (value.clamp(-1.0, 1.0) * max).round() as i32;
+        (value as u32) & ((1 << bits) - 1)
+    };
+    snorm(values[0], 10) | (snorm(values[1], 10) << 10) | (snorm(values[2], 10) << 20) | (snorm(values[3], 2) << 30)
+}
+
+/// 4 values from 0.0 to 1.0 in a normalized `gl::UNSIGNED_INT_2_10_10_10_REV`: x, y and z get 10
+/// bits, w only 2. Out of range values are clamped.
+///
+/// Typically a color with more precision than 8 bits, but a coarse alpha.
+pub fn pack_unorm_10_10_10_2(values: [f32; 4]) -> u32 {
+    let unorm = |value: f32, bits: u32| {
+        let max = ((1 << bits) - 1) as f32;
+        (value.clamp(0.0, 1.0) * max).round() as u32
+    };
+    unorm(values[0], 10) | (unorm(values[1], 10) << 10) | (unorm(values[2], 10) << 20) | (unorm(values[3], 2) << 30)
+}
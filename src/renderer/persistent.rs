@@ -0,0 +1,103 @@
+use crate::backend::gl::{self, types::*};
+use std::ptr;
+
+/// Number of regions of the buffer: the CPU writes to one while the GPU may still read the others.
+pub (crate) const REGION_COUNT: usize = 3;
+
+/// An instanced VBO mapped once and for all (GL 4.4 / `GL_ARB_buffer_storage`), split into
+/// `REGION_COUNT` regions used in turn, each protected by a fence.
+#[derive(Debug)]
+pub (crate) struct PersistentBuffer {
+    ptr: *mut u8,
+    pub (crate) region_size: usize,
+    fences: [GLsync; REGION_COUNT],
+    pub (crate) current_region: usize,
+}
+
+impl PersistentBuffer {
+    /// Returns true if the current context can use persistent mapping.
+    pub (crate) fn is_supported() -> bool {
+        gl::BufferStorage::is_loaded()
+            && gl::MapBufferRange::is_loaded()
+            && gl::FenceSync::is_loaded()
+            && gl::DrawArraysInstancedBaseInstance::is_loaded()
+            && gl::DrawElementsInstancedBaseInstance::is_loaded()
+    }
+
+    /// Allocate the storage of `vbo`, and map it. Returns None if mapping failed.
+    ///
+    /// `vbo` must not have been allocated with `glBufferData` before. If mapping fails, `vbo` is
+    /// replaced by a new buffer without storage: the storage of the old one is immutable, so it
+    /// couldn't be allocated with `glBufferData` anymore.
+    pub (crate) fn new(vbo: &mut GLuint, region_size: usize) -> Option<PersistentBuffer> {
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        let total_size = (region_size * REGION_COUNT) as GLsizeiptr;
+        let ptr = unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, *vbo);
+            gl::BufferStorage(gl::ARRAY_BUFFER, total_size, ptr::null(), flags);
+            let ptr = gl::MapBufferRange(gl::ARRAY_BUFFER, 0, total_size, flags);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            ptr as *mut u8
+        };
+        if ptr.is_null() {
+            log::debug!("could not map the instanced buffer, falling back to glBufferSubData");
+            unsafe {
+                gl::DeleteBuffers(1, vbo);
+                gl::GenBuffers(1, vbo);
+            }
+            return None;
+        }
+        Some(PersistentBuffer {
+            ptr,
+            region_size,
+            fences: [ptr::null(); REGION_COUNT],
+            current_region: 0,
+        })
+    }
+
+    /// Wait until the GPU is done with the current region, and copy `data` into it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is bigger than a region
+    pub (crate) fn write(&mut self, data: &[u8]) {
+        assert!(data.len() <= self.region_size);
+        let fence = self.fences[self.current_region];
+        unsafe {
+            if !fence.is_null() {
+                // 1 second at a time; only a lost context could make us wait longer.
+                loop {
+                    let result = gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000_000);
+                    if result != gl::TIMEOUT_EXPIRED {
+                        if result == gl::WAIT_FAILED {
+                            log::error!("glClientWaitSync failed for instanced buffer region {}", self.current_region);
+                        }
+                        break;
+                    }
+                }
+                gl::DeleteSync(fence);
+                self.fences[self.current_region] = ptr::null();
+            }
+            ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.add(self.current_region * self.region_size), data.len());
+        }
+    }
+
+    /// Mark the current region as used by the draw calls issued so far, and move to the next one.
+    pub (crate) fn fence_and_advance(&mut self) {
+        unsafe {
+            self.fences[self.current_region] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+        }
+        self.current_region = (self.current_region + 1) % REGION_COUNT;
+    }
+}
+
+impl Drop for PersistentBuffer {
+    fn drop(&mut self) {
+        // the buffer itself is unmapped and deleted by the renderer.
+        for fence in &self.fences {
+            if !fence.is_null() {
+                unsafe { gl::DeleteSync(*fence) };
+            }
+        }
+    }
+}
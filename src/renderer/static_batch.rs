@@ -0,0 +1,193 @@
+use crate::backend::gl::{self, types::*};
+use crate::error::SprowlError;
+use crate::shader::Uniform;
+use std::{
+    mem::MaybeUninit,
+    ptr,
+    os::raw::c_void,
+};
+use super::{AsVertexData, Renderer, VERTICES_PER_ELEM};
+use super::stream::vertex_attrib_pointers;
+
+/// A `StaticBatch` owned by a `Renderer`, see `Renderer::create_static_batch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BatchId(u32);
+
+/// Instances uploaded once and kept on the GPU, to draw things which rarely change (a chunk of a
+/// tilemap, a background...) without sending their data every frame.
+///
+/// A batch has its own VAO and VBO, laid out like the instanced attribs of the `Renderer` it was
+/// created from, and is drawn with `Renderer::draw_static_batch` and the same shader. Attribs of
+/// instance streams are not part of the layout.
+#[derive(Debug)]
+pub struct StaticBatch {
+    vao: GLuint,
+    vbo: GLuint,
+    instance_size: usize,
+    instance_count: usize,
+    /// size of the storage of `vbo`, in bytes
+    capacity: usize,
+    indexed: bool,
+}
+
+impl StaticBatch {
+    /// Create an empty batch for the layout of `renderer`.
+    pub fn new<U: Uniform>(renderer: &Renderer<U>) -> StaticBatch {
+        let mut vao: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let mut vbo: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let (vao, vbo) = unsafe {
+            gl::GenVertexArrays(1, vao.as_mut_ptr());
+            gl::GenBuffers(1, vbo.as_mut_ptr());
+            (vao.assume_init(), vbo.assume_init())
+        };
+        unsafe {
+            gl::BindVertexArray(vao);
+            if let Some(ebo) = renderer.ebo {
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            }
+            // the quad is shared with the renderer.
+            gl::BindBuffer(gl::ARRAY_BUFFER, renderer.quad_vbo);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, (2 * 4) as GLint, ptr::null::<c_void>());
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            vertex_attrib_pointers(&renderer.instanced_attribs, 1, 0);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        StaticBatch {
+            vao,
+            vbo,
+            instance_size: renderer.instance_size,
+            instance_count: 0,
+            capacity: 0,
+            indexed: renderer.ebo.is_some(),
+        }
+    }
+
+    /// Replace the content of the batch by `instance_count` instances, whose data is `instanced_vb`.
+    ///
+    /// # Panics
+    ///
+    /// * (debug only) if the size of `instanced_vb` doesn't match `instance_count`
+    pub fn set_raw_instances(&mut self, instanced_vb: &[u8], instance_count: usize) {
+        debug_assert_eq!(instanced_vb.len(), instance_count * self.instance_size, "instanced_vb doesn't hold instance_count instances");
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            if instanced_vb.len() > self.capacity {
+                gl::BufferData(gl::ARRAY_BUFFER, instanced_vb.len() as isize, instanced_vb.as_ptr() as *const _, gl::STATIC_DRAW);
+                self.capacity = instanced_vb.len();
+            } else if !instanced_vb.is_empty() {
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, instanced_vb.len() as isize, instanced_vb.as_ptr() as *const _);
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.instance_count = instance_count;
+    }
+
+    /// Replace the content of the batch by the given elements.
+    pub fn set_elems<'a, E: AsVertexData + 'a, I: IntoIterator<Item = &'a E>>(&mut self, elems: I) {
+        let mut instanced_vb = Vec::new();
+        let mut instance_count = 0;
+        for e in elems {
+            instance_count += e.add_vertex_data(&mut instanced_vb) as usize;
+        }
+        self.set_raw_instances(&instanced_vb, instance_count);
+    }
+
+    #[inline]
+    pub fn instance_count(&self) -> usize {
+        self.instance_count
+    }
+}
+
+impl Drop for StaticBatch {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+        }
+    }
+}
+
+impl<U: Uniform> Renderer<U> {
+    /// Draw every instance of `batch` in one call, with the shader, blend mode, depth mode,
+    /// viewport and clip rect of the renderer, whatever was bound before. Elements added before
+    /// this call are drawn first. With `set_preserve_gl_state`, the state of the application is
+    /// restored afterwards.
+    ///
+    /// # Panics
+    ///
+    /// * (debug only) if `batch` was created for a renderer with another layout
+    pub fn draw_static_batch(&mut self, batch: &StaticBatch) {
+        debug_assert_eq!(batch.instance_size, self.instance_size, "the batch was created for another layout");
+        debug_assert_eq!(batch.indexed, self.ebo.is_some(), "the batch was created for another renderer");
+        self.draw_batch_instances(batch.vao, batch.instance_count);
+    }
+
+    fn draw_batch_instances(&mut self, vao: GLuint, instance_count: usize) {
+        self.flush();
+        if instance_count == 0 {
+            return;
+        }
+        // batches are often drawn on their own, after a pass which used its own program and state.
+        self.with_draw_state(true, |renderer| {
+            if let Some(gpu_timer) = &mut renderer.gpu_timer {
+                gpu_timer.begin();
+            }
+            renderer.frame_stats.instances += instance_count;
+            renderer.frame_stats.draw_calls += 1;
+            unsafe {
+                gl::BindVertexArray(vao);
+                if renderer.ebo.is_some() {
+                    gl::DrawElementsInstanced(gl::TRIANGLES, VERTICES_PER_ELEM as GLint, gl::UNSIGNED_BYTE, ptr::null(), instance_count as GLint);
+                } else {
+                    gl::DrawArraysInstanced(gl::TRIANGLES, 0, VERTICES_PER_ELEM as GLint, instance_count as GLint);
+                }
+                gl::BindVertexArray(0);
+            }
+            if let Some(gpu_timer) = &mut renderer.gpu_timer {
+                gpu_timer.end();
+            }
+        });
+    }
+
+    /// Upload `elems` once to a `StaticBatch` kept by the renderer, to draw them every frame with
+    /// `draw_batch` without sending their data again.
+    pub fn create_static_batch<'a, E: AsVertexData + 'a, I: IntoIterator<Item = &'a E>>(&mut self, elems: I) -> BatchId {
+        let mut batch = StaticBatch::new(self);
+        batch.set_elems(elems);
+        let id = BatchId(self.next_batch_id);
+        self.next_batch_id += 1;
+        self.static_batches.insert(id, batch);
+        id
+    }
+
+    /// Replace the elements of a batch, for the occasional change.
+    ///
+    /// Returns an error if the batch was removed.
+    pub fn update_static_batch<'a, E: AsVertexData + 'a, I: IntoIterator<Item = &'a E>>(&mut self, id: BatchId, elems: I) -> Result<(), SprowlError> {
+        let batch = self.static_batches.get_mut(&id).ok_or(SprowlError::MissingBatchId(id.0))?;
+        batch.set_elems(elems);
+        Ok(())
+    }
+
+    /// Draw every instance of a batch in one call, see `draw_static_batch`.
+    ///
+    /// Returns an error if the batch was removed.
+    pub fn draw_batch(&mut self, id: BatchId) -> Result<(), SprowlError> {
+        let batch = self.static_batches.get(&id).ok_or(SprowlError::MissingBatchId(id.0))?;
+        let (vao, instance_count) = (batch.vao, batch.instance_count);
+        self.draw_batch_instances(vao, instance_count);
+        Ok(())
+    }
+
+    pub fn static_batch(&self, id: BatchId) -> Option<&StaticBatch> {
+        self.static_batches.get(&id)
+    }
+
+    /// Remove a batch from the renderer. Its GPU buffers are freed when the returned batch is
+    /// dropped.
+    pub fn remove_static_batch(&mut self, id: BatchId) -> Option<StaticBatch> {
+        self.static_batches.remove(&id)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::backend::gl::{self, types::*};
+use std::{
+    mem::MaybeUninit,
+    ptr,
+    os::raw::c_void,
+};
+
+/// An instanced attribute, see `RendererBuilder::with_instanced_vertex_attrib`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub (crate) struct InstancedAttrib {
+    pub (crate) index: GLuint,
+    /// number of components: 4 for a vec4, 1 for a uint
+    pub (crate) width: usize,
+    pub (crate) gl_type: GLenum,
+    /// integers read as floats from 0.0 to 1.0 (-1.0 to 1.0 if signed) by the shader
+    pub (crate) normalized: bool,
+}
+
+/// Size in bytes of a single component of `gl_type`.
+pub (crate) const fn component_size(gl_type: GLenum) -> usize {
+    match gl_type {
+        gl::BYTE | gl::UNSIGNED_BYTE => 1,
+        gl::SHORT | gl::UNSIGNED_SHORT | gl::HALF_FLOAT => 2,
+        gl::DOUBLE => 8,
+        _ => 4,
+    }
+}
+
+/// Whether `gl_type` packs all the components of an attribute in a single 32-bit integer, see
+/// `RendererBuilder::with_instanced_vertex_attrib`.
+pub (crate) const fn is_packed_type(gl_type: GLenum) -> bool {
+    matches!(gl_type, gl::INT_2_10_10_10_REV | gl::UNSIGNED_INT_2_10_10_10_REV)
+}
+
+/// Size in bytes of an attribute of `width` components of `gl_type`.
+pub (crate) fn attrib_size(width: usize, gl_type: GLenum) -> usize {
+    if is_packed_type(gl_type) {
+        4
+    } else {
+        width * component_size(gl_type)
+    }
+}
+
+/// Offset an attribute of `gl_type` must start at a multiple of: the size of its components, 4
+/// bytes for packed types. WebGL2 rejects misaligned attributes, and some drivers are slow with
+/// them.
+pub (crate) const fn attrib_alignment(gl_type: GLenum) -> usize {
+    if is_packed_type(gl_type) {
+        4
+    } else {
+        component_size(gl_type)
+    }
+}
+
+/// `offset` rounded up to a multiple of `alignment`, a power of 2.
+pub (crate) fn align_up(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+/// The offset of every attribute in an instance, and the size of the instance, from the size and
+/// the alignment of its attributes in order. Every attribute is aligned, and so is every
+/// instance, to 4 bytes at least.
+pub (crate) fn instance_layout(attribs: impl Iterator<Item = (usize, usize)>) -> (Vec<usize>, usize) {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    let mut instance_alignment = 4;
+    for (size, alignment) in attribs {
+        offset = align_up(offset, alignment);
+        offsets.push(offset);
+        offset += size;
+        instance_alignment = instance_alignment.max(alignment);
+    }
+    (offsets, align_up(offset, instance_alignment))
+}
+
+/// `instance_layout` of `attribs`.
+pub (crate) fn attribs_layout(attribs: &[InstancedAttrib]) -> (Vec<usize>, usize) {
+    instance_layout(attribs.iter().map(|attrib| (attrib.size(), attrib_alignment(attrib.gl_type))))
+}
+
+impl InstancedAttrib {
+    /// Size of the attribute, in bytes.
+    pub (crate) fn size(&self) -> usize {
+        attrib_size(self.width, self.gl_type)
+    }
+
+    /// Whether the shader reads the attribute as a float, a vec2, ... rather than as integers.
+    /// Packed types can only be read as floats, normalized or not.
+    pub (crate) fn is_float(&self) -> bool {
+        self.normalized || matches!(self.gl_type, gl::FLOAT | gl::HALF_FLOAT) || is_packed_type(self.gl_type)
+    }
+}
+
+/// Instanced attributes sourced from their own VBO, see `RendererBuilder::with_instance_stream`.
+#[derive(Debug)]
+pub (crate) struct InstanceStream {
+    pub (crate) vbo: GLuint,
+    /// the attributes advance every `divisor` instances
+    pub (crate) divisor: GLuint,
+    /// like `RendererBuilder::instanced_attribs`
+    pub (crate) attribs: Vec<InstancedAttrib>,
+}
+
+impl InstanceStream {
+    pub (crate) fn new(divisor: GLuint) -> InstanceStream {
+        let mut vbo: MaybeUninit<GLuint> = MaybeUninit::uninit();
+        let vbo = unsafe {
+            gl::GenBuffers(1, vbo.as_mut_ptr());
+            vbo.assume_init()
+        };
+        InstanceStream {
+            vbo,
+            // a divisor of 0 would make them per-vertex attributes.
+            divisor: divisor.max(1),
+            attribs: Vec::new(),
+        }
+    }
+
+    /// Size of the attributes of a single entry, in bytes.
+    pub (crate) fn stride(&self) -> usize {
+        attribs_layout(&self.attribs).1
+    }
+
+    /// Point the attributes of the stream to its VBO, starting at the entry of `first_instance`.
+    ///
+    /// The VAO must be bound.
+    pub (crate) unsafe fn bind_attribs(&self, first_instance: usize) {
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        let first_entry = first_instance / self.divisor as usize;
+        vertex_attrib_pointers(&self.attribs, self.divisor, first_entry * self.stride());
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    }
+}
+
+/// Enable and point instanced attribs to the VBO bound to `GL_ARRAY_BUFFER`, with the data of
+/// every attrib interleaved, starting from `offset` bytes.
+pub (crate) unsafe fn vertex_attrib_pointers(attribs: &[InstancedAttrib], divisor: GLuint, offset: usize) {
+    let (offsets, stride) = attribs_layout(attribs);
+    for (attrib, attrib_offset) in attribs.iter().zip(offsets) {
+        let i = attrib.index;
+        gl::EnableVertexAttribArray(i);
+        let pointer = ptr::null::<c_void>().add(offset + attrib_offset);
+        if attrib.is_float() {
+            let normalized = if attrib.normalized { gl::TRUE } else { gl::FALSE };
+            gl::VertexAttribPointer(i, attrib.width as GLint, attrib.gl_type, normalized, stride as GLint, pointer);
+        } else {
+            gl::VertexAttribIPointer(i, attrib.width as GLint, attrib.gl_type, stride as GLint, pointer);
+        }
+        // only necessary for instanced arrays
+        gl::VertexAttribDivisor(i, divisor);
+    }
+}
+
+/// Log the layout set by `vertex_attrib_pointers`, once when the renderer is built: the pointers
+/// of streams are set again on every draw.
+pub (crate) fn log_attribs(attribs: &[InstancedAttrib], divisor: GLuint) {
+    let (offsets, stride) = attribs_layout(attribs);
+    for (attrib, offset) in attribs.iter().zip(offsets) {
+        log::debug!("enabled vertex attrib instanced i={} width={} gl_type={} normalized={} offset={} stride={} divisor={}",
+            attrib.index, attrib.width, attrib.gl_type, attrib.normalized, offset, stride, divisor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribs_are_aligned_to_their_components() {
+        let attribs = [
+            (attrib_size(1, gl::UNSIGNED_BYTE), attrib_alignment(gl::UNSIGNED_BYTE)),
+            (attrib_size(1, gl::FLOAT), attrib_alignment(gl::FLOAT)),
+            (attrib_size(3, gl::UNSIGNED_BYTE), attrib_alignment(gl::UNSIGNED_BYTE)),
+            (attrib_size(1, gl::UNSIGNED_SHORT), attrib_alignment(gl::UNSIGNED_SHORT)),
+            (attrib_size(4, gl::INT_2_10_10_10_REV), attrib_alignment(gl::INT_2_10_10_10_REV)),
+            (attrib_size(1, gl::BYTE), attrib_alignment(gl::BYTE)),
+        ];
+        let (offsets, stride) = instance_layout(attribs.iter().cloned());
+        assert_eq!(offsets, vec![0, 4, 8, 12, 16, 20]);
+        assert_eq!(stride, 24);
+    }
+
+    #[test]
+    fn instances_are_aligned_to_their_largest_attrib() {
+        assert_eq!(instance_layout(std::iter::empty()), (vec![], 0));
+        assert_eq!(instance_layout([(2, 2), (1, 1)].iter().cloned()), (vec![0, 2], 4));
+        assert_eq!(instance_layout([(1, 1), (8, 8)].iter().cloned()), (vec![0, 8], 16));
+    }
+}
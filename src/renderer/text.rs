@@ -0,0 +1,184 @@
+use cgmath::Vector2;
+
+use crate::color::Color;
+use crate::error::SprowlError;
+use crate::render_storage::{FontId, RenderStorage, TextureKind};
+use crate::render_storage::font::{AdvancedLayout, LayoutOptions, VerticalAlign, WordPos};
+use crate::shader::Uniform;
+use crate::sprites::{QuadInstance, QUAD_FLAG_GRAYSCALE, QUAD_FLAG_TEXT_GAMMA};
+use super::{Renderer, VertexLayout};
+
+/// How `Renderer::draw_text` lays out and draws a text.
+#[derive(Debug, Clone, Copy)]
+pub struct TextOptions<'i> {
+    pub color: Color<u8>,
+    /// Lines wrap past this width. Without it, lines only break on '\n'.
+    pub max_width: Option<u32>,
+    /// align < 0 => left, align == 0 => center, align > 0 => right, within `max_width`. Ignored
+    /// without a `max_width`.
+    pub align: i8,
+    /// Inline images, direction, truncation...
+    pub layout: LayoutOptions<'i>,
+    /// Pixels of the glyph atlas drawn around every glyph, see `QuadInstance::with_pad`. `None`
+    /// draws all the padding the glyphs have (see `GlyphAtlas::set_glyph_padding`), which an
+    /// outline or a glow needs; 0.0 draws the glyphs tight, to overdraw less without effects.
+    /// Never more than the padding of the glyphs.
+    pub pad: Option<f32>,
+    /// Draw the glyphs with `QUAD_FLAG_TEXT_GAMMA`, so that dark and light text have the same
+    /// weight. Only for the glyphs of the grayscale atlas, not for bitmap fonts or emoji.
+    pub gamma_correction: bool,
+}
+
+impl<'i> Default for TextOptions<'i> {
+    fn default() -> TextOptions<'i> {
+        TextOptions {
+            color: Color::<u8>::white(),
+            max_width: None,
+            align: -1,
+            layout: LayoutOptions::default(),
+            pad: None,
+            gamma_correction: false,
+        }
+    }
+}
+
+impl<'i> TextOptions<'i> {
+    pub fn new() -> TextOptions<'i> {
+        TextOptions::default()
+    }
+
+    pub fn with_color(mut self, color: Color<u8>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: u32, align: i8) -> Self {
+        self.max_width = Some(max_width);
+        self.align = align;
+        self
+    }
+
+    /// Where the text is placed relative to the y of the position, see `VerticalAlign`.
+    pub fn with_vertical_align(mut self, vertical_align: VerticalAlign) -> Self {
+        self.layout.vertical_align = vertical_align;
+        self
+    }
+
+    pub fn with_layout(mut self, layout: LayoutOptions<'i>) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_pad(mut self, pad: f32) -> Self {
+        self.pad = Some(pad);
+        self
+    }
+
+    pub fn with_gamma_correction(mut self, gamma_correction: bool) -> Self {
+        self.gamma_correction = gamma_correction;
+        self
+    }
+}
+
+impl<U: Uniform> Renderer<U> {
+    /// Lay out `text`, cache its glyphs and add a `QuadInstance` for every glyph and inline image,
+    /// `position` being the top-left corner of the text (see `TextOptions::with_vertical_align`
+    /// for other options).
+    ///
+    /// The renderer must use `RendererBuilder::standard_quad_layout`. Glyphs are drawn with
+    /// `QUAD_FLAG_GRAYSCALE` (and `QUAD_FLAG_TEXT_GAMMA` with `TextOptions::gamma_correction`)
+    /// and the color of `options`; inline images and color glyphs (emoji) are drawn as they are.
+    /// The glyphs of a `BitmapFont` are multiplied by the color of `options`, which leaves them as
+    /// they are when it's white. The renderer switches to the alpha mode of the storage.
+    ///
+    /// When the glyphs of the whole text don't fit in the glyph atlas at once, the text is drawn in
+    /// several draw calls, each with the glyphs of a part of the words.
+    ///
+    /// Returns an error if the font is missing, or if the glyphs of a single word can't fit in the
+    /// glyph atlas. Words before the error are still drawn.
+    ///
+    /// # Panics
+    ///
+    /// * (debug only) if the renderer doesn't use the standard quad layout
+    pub fn draw_text(&mut self, render_storage: &mut RenderStorage, font_id: FontId, text: &str, size: f32, position: Vector2<f32>, options: &TextOptions<'_>) -> Result<(), SprowlError> {
+        debug_assert!(
+            self.vertex_layout() == Some(&QuadInstance::with_fields(VertexLayout::new())),
+            "draw_text needs a renderer built with standard_quad_layout"
+        );
+        self.set_alpha_mode(render_storage.alpha_mode());
+        // without a max width, the alignment would be relative to an infinitely wide line.
+        let (max_width, align) = match options.max_width {
+            Some(max_width) => (max_width, options.align),
+            None => (u32::MAX, -1),
+        };
+        let font = render_storage.layout_font(font_id).ok_or(SprowlError::MissingFontId(font_id))?;
+        let words = AdvancedLayout::new_str_with_options(font, text, size, position, align, max_width, &options.layout)
+            .iter()
+            .cloned()
+            .collect::<Vec<WordPos<'_>>>();
+        self.draw_words(render_storage, font_id, &words, size, options)
+    }
+
+    /// Add the quads of `words`, whose glyphs are cached all at once. If they don't fit in the
+    /// glyph atlas together, each half is drawn on its own: the glyphs of the second half may
+    /// take the place of the ones of the first half in the atlas, so the first half is drawn
+    /// before caching them.
+    fn draw_words(&mut self, render_storage: &mut RenderStorage, font_id: FontId, words: &[WordPos<'_>], size: f32, options: &TextOptions<'_>) -> Result<(), SprowlError> {
+        let visual_words = words.iter()
+            .filter(|word_pos| word_pos.inline_image.is_none())
+            .map(WordPos::visual_word)
+            .collect::<Vec<_>>();
+        let words_draw_calls = match render_storage.words_to_draw_calls(font_id, &visual_words, size) {
+            Err(SprowlError::GlyphCache(e)) if visual_words.len() > 1 => {
+                log::debug!("glyph atlas: {} for {} words, drawing them in two batches", e, visual_words.len());
+                let (first_half, second_half) = words.split_at(words.len() / 2);
+                for half in &[first_half, second_half] {
+                    // what's pending may use glyphs this half evicts
+                    self.flush();
+                    self.draw_words(render_storage, font_id, half, size, options)?;
+                }
+                return Ok(());
+            },
+            result => result?,
+        };
+
+        let (glyph_w, glyph_h) = render_storage.get_max_dims(TextureKind::Grayscale);
+        let (image_w, image_h) = render_storage.get_max_dims(TextureKind::RGBA);
+        let is_bitmap_font = render_storage.get_bitmap_font(font_id).is_some();
+        let glyph_flags = if options.gamma_correction { QUAD_FLAG_GRAYSCALE | QUAD_FLAG_TEXT_GAMMA } else { QUAD_FLAG_GRAYSCALE };
+        let mut words_draw_calls = words_draw_calls.into_iter();
+        for word_pos in words {
+            if let Some(image) = word_pos.inline_image {
+                let (x, y, w, h) = image.crop;
+                let crop = (x as f32 / image_w as f32, y as f32 / image_h as f32, w as f32 / image_w as f32, h as f32 / image_h as f32);
+                self.add_elem(&QuadInstance::new([word_pos.origin.x, word_pos.origin.y], [word_pos.size.x, word_pos.size.y])
+                    .with_uv(crop, image.texture));
+                continue;
+            }
+            let draw_calls = words_draw_calls.next().unwrap_or_default();
+            for glyph in &draw_calls {
+                let (x, y, w, h) = glyph.source_crop;
+                let origin = word_pos.origin + glyph.dest_origin;
+                let quad = QuadInstance::new([origin.x, origin.y], [glyph.dest_size.x, glyph.dest_size.y]);
+                let pad = options.pad.map_or(glyph.padding, |pad| pad.min(glyph.padding));
+                let quad = if glyph.color {
+                    let crop = (x / image_w as f32, y / image_h as f32, w / image_w as f32, h / image_h as f32);
+                    // emoji keep their colors, only the alpha of the text applies. Bitmap fonts
+                    // are tinted: white glyphs take the color of the text.
+                    let color = if is_bitmap_font { options.color } else { Color::<u8>::white().with_alpha(options.color.a) };
+                    quad.with_uv(crop, glyph.texture_layer)
+                        .with_pad(pad, (image_w, image_h))
+                        .with_color(color)
+                } else {
+                    let crop = (x / glyph_w as f32, y / glyph_h as f32, w / glyph_w as f32, h / glyph_h as f32);
+                    quad.with_uv(crop, glyph.texture_layer)
+                        .with_pad(pad, (glyph_w, glyph_h))
+                        .with_color(options.color)
+                        .with_flags(glyph_flags)
+                };
+                self.add_elem(&quad);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use cgmath::{Matrix4, Vector2};
+
+/// A fixed internal resolution (640x360 for instance), scaled to fit the window with black bars
+/// around it. Set it with `Renderer::set_virtual_resolution`.
+///
+/// Draw in virtual coordinates, from (0, 0) at the top left to (`width`, `height`), with
+/// `matrix()` as your projection. If you use a `Camera2D`, give it the virtual size as its
+/// viewport size instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualResolution {
+    pub width: u32,
+    pub height: u32,
+    /// Only scale by whole numbers (when the window is big enough), to keep pixel art crisp.
+    pub integer_scaling: bool,
+    window_size: (u32, u32),
+}
+
+impl VirtualResolution {
+    pub fn new(width: u32, height: u32) -> VirtualResolution {
+        VirtualResolution {
+            width,
+            height,
+            integer_scaling: false,
+            window_size: (width, height),
+        }
+    }
+
+    pub fn with_integer_scaling(mut self, integer_scaling: bool) -> VirtualResolution {
+        self.integer_scaling = integer_scaling;
+        self
+    }
+
+    /// Done by `Renderer::set_viewport`.
+    pub fn set_window_size(&mut self, width: u32, height: u32) {
+        self.window_size = (width, height);
+    }
+
+    #[inline]
+    pub fn window_size(&self) -> (u32, u32) {
+        self.window_size
+    }
+
+    /// Size of a virtual pixel, in pixels of the window.
+    pub fn scale(&self) -> f32 {
+        let scale = (self.window_size.0 as f32 / self.width.max(1) as f32).min(self.window_size.1 as f32 / self.height.max(1) as f32);
+        if self.integer_scaling && scale >= 1.0 {
+            scale.floor()
+        } else {
+            scale
+        }
+    }
+
+    /// The part of the window showing the virtual area, in pixels (x, y, w, h) with the origin at
+    /// the top left.
+    pub fn viewport(&self) -> (i32, i32, u32, u32) {
+        let scale = self.scale();
+        let width = (self.width as f32 * scale).round() as u32;
+        let height = (self.height as f32 * scale).round() as u32;
+        let x = (self.window_size.0 as i32 - width as i32) / 2;
+        let y = (self.window_size.1 as i32 - height as i32) / 2;
+        (x, y, width, height)
+    }
+
+    /// The projection from virtual coordinates to clip space, with the y axis pointing down.
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from(cgmath::Ortho {
+            left: 0.0,
+            right: self.width as f32,
+            bottom: self.height as f32,
+            top: 0.0,
+            near: -1.0,
+            far: 1.0,
+        })
+    }
+
+    /// Converts a position in the window (e.g. the mouse) in pixels, to virtual coordinates.
+    ///
+    /// Positions on the black bars are outside of (0, 0) - (`width`, `height`), see `contains`.
+    pub fn window_to_virtual(&self, position: Vector2<f32>) -> Vector2<f32> {
+        let (x, y, _, _) = self.viewport();
+        (position - Vector2::new(x as f32, y as f32)) / self.scale()
+    }
+
+    /// Converts a position in virtual coordinates to a position in the window, in pixels.
+    pub fn virtual_to_window(&self, position: Vector2<f32>) -> Vector2<f32> {
+        let (x, y, _, _) = self.viewport();
+        position * self.scale() + Vector2::new(x as f32, y as f32)
+    }
+
+    /// Whether a position in virtual coordinates is inside of the virtual area.
+    pub fn contains(&self, position: Vector2<f32>) -> bool {
+        position.x >= 0.0 && position.y >= 0.0 && position.x < self.width as f32 && position.y < self.height as f32
+    }
+}
@@ -0,0 +1,156 @@
+//! Window and GL context setup with SDL2, with the `sdl2` feature.
+//!
+//! `sdl2_init` creates the window and its context with the attributes sprowl needs (3.3 core or
+//! ES 3.0, a depth and stencil buffer for `DepthMode` and the shadows of `lighting`), loads the
+//! functions of the `gl` crate and logs what the driver reports. See `glutin_window` for the
+//! same with glutin.
+//!
+//! ```ignore
+//! let sdl = sprowl::sdl2_init(&WindowSettings::new("game", 1280, 720))?;
+//! let mut renderer = RendererBuilder::new(16384).standard_quad_layout().build_with(standard_quad_shader()?);
+//! sdl.fit_viewport(&mut renderer);
+//! let mut event_pump = sdl.event_pump()?;
+//! 'running: loop {
+//!     for event in event_pump.poll_iter() {
+//!         if let Event::Quit { .. } = event {
+//!             break 'running;
+//!         }
+//!         sdl.handle_event(&event, &mut renderer);
+//!     }
+//!     renderer.clear(None);
+//!     // ...
+//!     renderer.draw();
+//!     sdl.swap();
+//! }
+//! ```
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::video::{GLContext, GLProfile, SwapInterval, VideoSubsystem, Window};
+
+use crate::error::SprowlError;
+use crate::renderer::Renderer;
+use crate::shader::{GlProfile, Uniform};
+use crate::window::{after_context_creation, window_error};
+
+pub use crate::window::WindowSettings;
+
+/// A window whose GL context is current, created by `sdl2_init`.
+///
+/// The context lives as long as this struct: drop your renderers and your `RenderStorage`
+/// first.
+pub struct SdlContext {
+    pub sdl: sdl2::Sdl,
+    pub video: VideoSubsystem,
+    pub window: Window,
+    gl_context: GLContext,
+    gl_profile: GlProfile,
+}
+
+/// Create a window and make its GL context current, as described by `settings`, then load the
+/// functions of the `gl` crate.
+pub fn sdl2_init(settings: &WindowSettings) -> Result<SdlContext, SprowlError> {
+    let sdl = sdl2::init().map_err(window_error)?;
+    let video = sdl.video().map_err(window_error)?;
+
+    let gl_attr = video.gl_attr();
+    match settings.gl_profile {
+        GlProfile::Core => {
+            gl_attr.set_context_profile(GLProfile::Core);
+            gl_attr.set_context_version(3, 3);
+        },
+        GlProfile::Es => {
+            gl_attr.set_context_profile(GLProfile::GLES);
+            gl_attr.set_context_version(3, 0);
+        },
+    }
+    {
+        // macOS only gives core contexts which are forward compatible
+        let mut flags = gl_attr.set_context_flags();
+        if settings.gl_profile == GlProfile::Core {
+            flags.forward_compatible();
+        }
+        if settings.debug_context {
+            flags.debug();
+        }
+        flags.set();
+    }
+    gl_attr.set_double_buffer(true);
+    gl_attr.set_depth_size(24);
+    gl_attr.set_stencil_size(8);
+    gl_attr.set_multisample_buffers(if settings.multisample_samples > 0 { 1 } else { 0 });
+    gl_attr.set_multisample_samples(settings.multisample_samples);
+
+    let mut builder = video.window(&settings.title, settings.width, settings.height);
+    builder.opengl().allow_highdpi().position_centered();
+    if settings.resizable {
+        builder.resizable();
+    }
+    let window = builder.build().map_err(window_error)?;
+    let gl_context = window.gl_create_context().map_err(window_error)?;
+    window.gl_make_current(&gl_context).map_err(window_error)?;
+    ::gl::load_with(|name| video.gl_get_proc_address(name) as *const _);
+
+    let interval = if settings.vsync { SwapInterval::VSync } else { SwapInterval::Immediate };
+    if let Err(e) = video.gl_set_swap_interval(interval) {
+        log::warn!("could not set the swap interval: {}", e);
+    }
+
+    after_context_creation(settings);
+
+    Ok(SdlContext {
+        sdl,
+        video,
+        window,
+        gl_context,
+        gl_profile: GlProfile::detect(),
+    })
+}
+
+impl SdlContext {
+    /// The profile of the context which was created, which may not be the one asked for.
+    pub fn gl_profile(&self) -> GlProfile {
+        self.gl_profile
+    }
+
+    pub fn gl_context(&self) -> &GLContext {
+        &self.gl_context
+    }
+
+    pub fn event_pump(&self) -> Result<sdl2::EventPump, SprowlError> {
+        self.sdl.event_pump().map_err(window_error)
+    }
+
+    /// The size of the window in pixels, which is bigger than its size on high-DPI displays.
+    pub fn drawable_size(&self) -> (u32, u32) {
+        self.window.drawable_size()
+    }
+
+    /// Set the viewport of `renderer` to the whole window.
+    pub fn fit_viewport<U: Uniform>(&self, renderer: &mut Renderer<U>) {
+        let (width, height) = self.drawable_size();
+        renderer.set_viewport(width, height);
+    }
+
+    /// Follow the resizes of the window: returns its new drawable size if `event` resized it,
+    /// after updating the viewport of `renderer`.
+    pub fn handle_event<U: Uniform>(&self, event: &Event, renderer: &mut Renderer<U>) -> Option<(u32, u32)> {
+        match event {
+            Event::Window { window_id, win_event: WindowEvent::SizeChanged(..), .. } if *window_id == self.window.id() => {
+                self.fit_viewport(renderer);
+                Some(self.drawable_size())
+            },
+            _ => None,
+        }
+    }
+
+    /// Show or hide the cursor of the OS over the window, typically hidden while drawing a
+    /// `cursor::Cursor`.
+    pub fn set_os_cursor_visible(&self, visible: bool) {
+        self.sdl.mouse().show_cursor(visible);
+    }
+
+    /// Present the frame.
+    pub fn swap(&self) {
+        self.window.gl_swap_window();
+    }
+}
@@ -0,0 +1,97 @@
+//! Uniforms most effects need, set on every shader of a `Renderer` by reserved names, without
+//! being part of its `Uniform` enum. Enabled with `RendererBuilder::with_standard_globals`.
+//!
+//! ```glsl
+//! uniform vec2 u_resolution;
+//! uniform float u_time;
+//!
+//! void main() {
+//!     vec2 uv = gl_FragCoord.xy / u_resolution;
+//!     frag_color = vec4(uv, 0.5 + 0.5 * sin(u_time), 1.0);
+//! }
+//! ```
+//!
+//! A shader only declares the ones it uses: the others are ignored.
+
+use crate::backend::gl::{self, types::*};
+use super::{Shader, Uniform, uniform_location};
+
+/// `vec2`, see `StandardGlobals::resolution`.
+pub const GLOBAL_RESOLUTION: &str = "u_resolution";
+/// `float`, see `StandardGlobals::time`.
+pub const GLOBAL_TIME: &str = "u_time";
+/// `float`, see `StandardGlobals::delta_time`.
+pub const GLOBAL_DELTA_TIME: &str = "u_delta_time";
+/// `uint`, see `StandardGlobals::frame`. `u_frame & 1u` is the parity of the frame, to alternate
+/// dither patterns or checkerboard updates.
+pub const GLOBAL_FRAME: &str = "u_frame";
+/// `bool`, set by renderers built with `AlphaMode::Premultiplied`, whether or not they have
+/// standard globals: the RGBA textures are premultiplied, and so must be the output.
+pub const GLOBAL_PREMULTIPLIED_ALPHA: &str = "u_premultiplied_alpha";
+
+const GLOBAL_NAMES: [&str; 5] = [GLOBAL_RESOLUTION, GLOBAL_TIME, GLOBAL_DELTA_TIME, GLOBAL_FRAME, GLOBAL_PREMULTIPLIED_ALPHA];
+
+/// The values of the reserved uniforms, set by the renderer on every `Renderer::draw`. Time and
+/// frames move forward with `Renderer::begin_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StandardGlobals {
+    /// Size of the GL viewport in pixels: the window, or its letterboxed part with a
+    /// `VirtualResolution`.
+    pub resolution: (f32, f32),
+    /// Seconds since the renderer was built, or set with `Renderer::set_globals_time`.
+    pub time: f32,
+    /// Seconds between the last two `begin_frame`, 0.0 for the first frame.
+    pub delta_time: f32,
+    /// Number of `begin_frame` before this one, wrapping around.
+    pub frame: u32,
+}
+
+impl StandardGlobals {
+    /// 0 on even frames, 1 on odd ones.
+    #[inline]
+    pub fn frame_parity(&self) -> u32 {
+        self.frame & 1
+    }
+}
+
+impl<U: Uniform> Shader<U> {
+    /// Set the reserved uniforms of `StandardGlobals` the program declares, after `use_program`.
+    /// Their locations are looked up once per program.
+    pub fn set_standard_globals(&mut self, globals: &StandardGlobals) {
+        let [resolution, time, delta_time, frame, _] = self.global_locations();
+        unsafe {
+            if resolution >= 0 {
+                gl::Uniform2f(resolution, globals.resolution.0, globals.resolution.1);
+            }
+            if time >= 0 {
+                gl::Uniform1f(time, globals.time);
+            }
+            if delta_time >= 0 {
+                gl::Uniform1f(delta_time, globals.delta_time);
+            }
+            if frame >= 0 {
+                gl::Uniform1ui(frame, globals.frame);
+            }
+        }
+    }
+
+    /// Set `GLOBAL_PREMULTIPLIED_ALPHA` if the program declares it, after `use_program`.
+    pub fn set_premultiplied_alpha(&mut self, premultiplied: bool) {
+        let location = self.global_locations()[4];
+        if location >= 0 {
+            unsafe { gl::Uniform1i(location, premultiplied as GLint); }
+        }
+    }
+
+    /// The locations of `GLOBAL_NAMES`, looked up once per program.
+    fn global_locations(&mut self) -> [GLint; 5] {
+        let program = self.id;
+        *self.global_locations.get_or_insert_with(|| {
+            let mut locations = [-1; 5];
+            for (location, name) in locations.iter_mut().zip(GLOBAL_NAMES.iter()) {
+                *location = uniform_location(program, name);
+            }
+            locations
+        })
+    }
+}
@@ -1,6 +1,13 @@
-use gl::{self, types::*};
+use crate::backend::gl::{self, types::*};
 use cgmath::{Matrix4, Vector2, Vector3, Vector4};
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
+mod validate;
+mod profile;
+mod globals;
+pub use validate::*;
+pub use profile::*;
+pub use globals::*;
+
 use std::{
     ffi::{CStr, CString},
     mem::MaybeUninit,
@@ -21,7 +28,59 @@ pub trait Uniform: std::fmt::Debug + Clone + Copy + std::hash::Hash + PartialEq
 /// Represents a shader: a vertex shader, a fragment shader, a list of uniforms.
 pub struct Shader<U: Uniform> {
     id: GLuint,
+    /// negative for the inactive uniforms of a lenient shader
     uniforms: HashMap<U, GLint>,
+    // kept for `reload`
+    texture_units: Vec<String>,
+    lenient: bool,
+    /// inactive uniforms which were already logged when set
+    logged_inactive: HashSet<U>,
+    /// locations of the elements of array uniforms, "name[index]", negative if out of range
+    indexed_uniforms: HashMap<(U, u32), GLint>,
+    /// locations of the reserved uniforms of `globals`, looked up when first set
+    global_locations: Option<[GLint; 5]>,
+    /// last values set by the `set_*` methods, by uniform and first element, kept for `reload`
+    values: HashMap<(U, u32), UniformValue>,
+}
+
+/// How a `UniformValue` is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UniformKind {
+    /// floats, vec2, vec3 or vec4, with this many components
+    Float(usize),
+    /// int, ivec2, ivec3 or ivec4, with this many components
+    Int(usize),
+    UInt,
+    Matrix4,
+}
+
+/// The last value set on a uniform, or on some elements of an array uniform. Components are
+/// stored as their bits, whatever their type.
+#[derive(Debug)]
+struct UniformValue {
+    kind: UniformKind,
+    bits: Vec<u32>,
+}
+
+impl UniformValue {
+    /// Set the value again on `location`, of the program in use.
+    unsafe fn apply(&self, location: GLint) {
+        let floats = self.bits.as_ptr() as *const GLfloat;
+        let int = |index: usize| self.bits[index] as GLint;
+        match self.kind {
+            UniformKind::Float(1) => gl::Uniform1fv(location, self.bits.len() as c_int, floats),
+            UniformKind::Float(2) => gl::Uniform2fv(location, self.bits.len() as c_int / 2, floats),
+            UniformKind::Float(3) => gl::Uniform3fv(location, self.bits.len() as c_int / 3, floats),
+            UniformKind::Float(_) => gl::Uniform4fv(location, self.bits.len() as c_int / 4, floats),
+            UniformKind::Int(1) => gl::Uniform1iv(location, self.bits.len() as c_int, self.bits.as_ptr() as *const GLint),
+            // ivecs are never arrays.
+            UniformKind::Int(2) => gl::Uniform2i(location, int(0), int(1)),
+            UniformKind::Int(3) => gl::Uniform3i(location, int(0), int(1), int(2)),
+            UniformKind::Int(_) => gl::Uniform4i(location, int(0), int(1), int(2), int(3)),
+            UniformKind::UInt => gl::Uniform1uiv(location, self.bits.len() as c_int, self.bits.as_ptr()),
+            UniformKind::Matrix4 => gl::UniformMatrix4fv(location, self.bits.len() as c_int / 16, gl::FALSE, floats),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -44,67 +103,212 @@ impl ShaderBuildStep {
     }
 }
 
+/// Returns whether a shader has been compiled or a program has been linked successfully,
+/// depending on the step.
+fn build_step_succeeded(object: GLuint, step: ShaderBuildStep) -> bool {
+    let mut result: MaybeUninit<GLint> = MaybeUninit::uninit();
+    unsafe {
+        match step {
+            ShaderBuildStep::LinkProgram => gl::GetProgramiv(object, gl::LINK_STATUS, result.as_mut_ptr()),
+            _ => gl::GetShaderiv(object, gl::COMPILE_STATUS, result.as_mut_ptr()),
+        };
+        result.assume_init() == i32::from(gl::TRUE)
+    }
+}
+
+/// Retrieve the info log of a shader or a program, depending on the step.
+fn info_log(object: GLuint, step: ShaderBuildStep) -> String {
+    unsafe {
+        let mut info_log_length: MaybeUninit<GLint> = MaybeUninit::uninit();
+        match step {
+            ShaderBuildStep::LinkProgram => gl::GetProgramiv(object, gl::INFO_LOG_LENGTH, info_log_length.as_mut_ptr()),
+            _ => gl::GetShaderiv(object, gl::INFO_LOG_LENGTH, info_log_length.as_mut_ptr()),
+        };
+        let info_log_length = info_log_length.assume_init();
+        let mut error_message: Vec<c_char> = vec![0; info_log_length as usize + 1];
+        match step {
+            ShaderBuildStep::LinkProgram => gl::GetProgramInfoLog(object, info_log_length, ptr::null_mut(), error_message.as_mut_ptr()),
+            _ => gl::GetShaderInfoLog(object, info_log_length, ptr::null_mut(), error_message.as_mut_ptr()),
+        };
+        let log_message = CStr::from_ptr(error_message.as_ptr());
+        log_message.to_string_lossy().into_owned()
+    }
+}
+
+/// The components of `mats`, column by column, as bits.
+fn matrix4_bits(mats: &[Matrix4<f32>]) -> impl Iterator<Item = u32> + '_ {
+    // cgmath matrices are column major, and contiguous in a slice.
+    let floats = unsafe { std::slice::from_raw_parts(mats.as_ptr() as *const GLfloat, mats.len() * 16) };
+    floats.iter().map(|v| v.to_bits())
+}
+
+/// Returns the location of a uniform in the program, negative if it doesn't exist.
+fn uniform_location(program: GLuint, name: &str) -> GLint {
+    let cname = CString::new(name).unwrap();
+    unsafe { gl::GetUniformLocation(program, cname.as_ptr()) }
+}
+
+/// Compile a vertex and a fragment shader, and link them into a new program.
+///
+/// The shader objects are always deleted, and so is the program if something failed.
+fn build_program(fragment_source: &str, vertex_source: &str) -> Result<GLuint, ShaderError> {
+    let profile = GlProfile::detect();
+    let fragment_source = profile.glsl_source(fragment_source);
+    let vertex_source = profile.glsl_source(vertex_source);
+    let check_build_step = |object: GLuint, step: ShaderBuildStep| {
+        if build_step_succeeded(object, step) {
+            Ok(())
+        } else {
+            Err(ShaderError::new(step.as_err_type(), info_log(object, step)))
+        }
+    };
+    unsafe {
+        let vertex_shader_id = gl::CreateShader(gl::VERTEX_SHADER);
+        let fragment_shader_id = gl::CreateShader(gl::FRAGMENT_SHADER);
+        let program_id = gl::CreateProgram();
+        // shaders can be attached before being compiled, that way they can always be detached.
+        gl::AttachShader(program_id, vertex_shader_id);
+        gl::AttachShader(program_id, fragment_shader_id);
+
+        let fragment_shader = CString::new(fragment_source.as_bytes()).unwrap();
+        let vertex_shader = CString::new(vertex_source.as_bytes()).unwrap();
+
+        let result = (|| {
+            gl::ShaderSource(vertex_shader_id, 1, &vertex_shader.as_c_str().as_ptr(), ::std::ptr::null());
+            gl::CompileShader(vertex_shader_id);
+            check_build_step(vertex_shader_id, ShaderBuildStep::CompileVertexShader)?;
+
+            gl::ShaderSource(fragment_shader_id, 1, &fragment_shader.as_c_str().as_ptr(), ::std::ptr::null());
+            gl::CompileShader(fragment_shader_id);
+            check_build_step(fragment_shader_id, ShaderBuildStep::CompileFragmentShader)?;
+
+            gl::LinkProgram(program_id);
+            check_build_step(program_id, ShaderBuildStep::LinkProgram)
+        })();
+
+        gl::DetachShader(program_id, vertex_shader_id);
+        gl::DetachShader(program_id, fragment_shader_id);
+
+        gl::DeleteShader(vertex_shader_id);
+        gl::DeleteShader(fragment_shader_id);
+
+        match result {
+            Ok(()) => Ok(program_id),
+            Err(e) => {
+                gl::DeleteProgram(program_id);
+                Err(e)
+            }
+        }
+    }
+}
+
 impl<U: Uniform> Shader<U> {
-    /// Init a uniform location. If you forget to do this for some uniform, your
-    /// program will crash at runtime (opengl compile time)
-    fn init_uniform_location(&mut self, uniform: U) {
-        let name = CString::new(uniform.name()).unwrap();
-        let uniform_location = unsafe {gl::GetUniformLocation(self.id, name.as_ptr())};
+    /// Init a uniform location. Returns an error if the uniform is missing from the program, or
+    /// optimized out because it's unused, unless the shader is lenient.
+    fn init_uniform_location(&mut self, uniform: U) -> Result<(), ShaderError> {
+        let uniform_location = uniform_location(self.id, uniform.name());
         if uniform_location < 0 {
-            panic!("Error / Invalid location for {:?}: gl returned {}", uniform, uniform_location);
+            if !self.lenient {
+                return Err(ShaderError::new("UNIFORM_LOCATION", format!("missing (or unused) uniform {:?} \"{}\"", uniform, uniform.name())));
+            }
+            log::debug!("uniform {:?} \"{}\" is missing or unused, it is inactive", uniform, uniform.name());
         };
         self.uniforms.insert(uniform, uniform_location);
+        Ok(())
     }
 
-    /// Check that the build step "step" has been completed successfully, otherwise return an
-    /// Error with the proper information
-    fn check_build_step(object: GLuint, step: ShaderBuildStep) -> Result<(), ShaderError> {
-        unsafe {
-            let mut compile_result: MaybeUninit<GLint> = MaybeUninit::uninit();
-            let mut info_log_length: MaybeUninit<GLint> = MaybeUninit::uninit();
-            match step {
-                ShaderBuildStep::LinkProgram => {
-                    gl::GetProgramiv(object, gl::LINK_STATUS, compile_result.as_mut_ptr());
-                    let compile_result = compile_result.assume_init();
-                    if compile_result != i32::from(gl::TRUE) {
-                        // retrieve the error
-                        gl::GetProgramiv(object, gl::INFO_LOG_LENGTH, info_log_length.as_mut_ptr());
-                        let info_log_length = info_log_length.assume_init();
-                        let mut error_message: Vec<c_char> = Vec::with_capacity(info_log_length as usize + 1);
-                        gl::GetProgramInfoLog(object, info_log_length, ptr::null_mut(), error_message.as_mut_ptr());
-                        let log_message = CStr::from_ptr(error_message.as_ptr());
-                        return Err(ShaderError::new(step.as_err_type(), format!("{}", log_message.to_string_lossy())))
-                    }
-                },
-                _ => {
-                    gl::GetShaderiv(object, gl::COMPILE_STATUS, compile_result.as_mut_ptr());
-                    let compile_result = compile_result.assume_init();
-                    if compile_result != i32::from(gl::TRUE) {
-                        // retrieve the error
-                        gl::GetShaderiv(object, gl::INFO_LOG_LENGTH, info_log_length.as_mut_ptr());
-                        let info_log_length = info_log_length.assume_init();
-                        let mut error_message: Vec<c_char> = Vec::with_capacity(info_log_length as usize + 1);
-                        gl::GetShaderInfoLog(object, info_log_length, ptr::null_mut(), error_message.as_mut_ptr());
-                        let log_message = CStr::from_ptr(error_message.as_ptr());
-                        return Err(ShaderError::new(step.as_err_type(), format!("{}", log_message.to_string_lossy())))
-                    }
-                }
+    /// The location of an active uniform. For an inactive one, logs it the first time and returns
+    /// `None`.
+    ///
+    /// # Panics
+    ///
+    /// If the location was not initialized, which can't happen once the shader is built.
+    fn location(&mut self, uniform: U) -> Option<GLint> {
+        let location = self.uniforms.get(&uniform).cloned().expect("uniform location was not initialized");
+        if location >= 0 {
+            Some(location)
+        } else {
+            if self.logged_inactive.insert(uniform) {
+                log::warn!("uniform {:?} \"{}\" is inactive (missing or optimized out), setting it has no effect", uniform, uniform.name());
             }
+            None
         }
-        Ok(())
+    }
+
+    /// The location of the element `index` of an array uniform, looked up once and cached. Logs
+    /// the first time if it doesn't exist: inactive uniform, or `index` out of the array (or
+    /// optimized out, when the shader only reads its first elements).
+    fn indexed_location(&mut self, uniform: U, index: u32) -> Option<GLint> {
+        if index == 0 {
+            return self.location(uniform);
+        }
+        let program = self.id;
+        let location = *self.indexed_uniforms.entry((uniform, index)).or_insert_with(|| {
+            let location = uniform_location(program, &format!("{}[{}]", uniform.name(), index));
+            if location < 0 {
+                log::warn!("uniform {:?} \"{}[{}]\" is inactive or out of range, setting it has no effect", uniform, uniform.name(), index);
+            }
+            location
+        });
+        if location >= 0 {
+            Some(location)
+        } else {
+            None
+        }
+    }
+
+    /// Keep the value set on `uniform` from its element `first` on, to set it again in `reload`.
+    /// The buffer of the previous value is re-used, so that setting uniforms every frame doesn't
+    /// allocate.
+    fn keep_value<I: IntoIterator<Item = u32>>(&mut self, uniform: U, first: u32, kind: UniformKind, bits: I) {
+        let value = self.values.entry((uniform, first)).or_insert_with(|| UniformValue { kind, bits: Vec::new() });
+        value.kind = kind;
+        value.bits.clear();
+        value.bits.extend(bits);
+    }
+
+    /// Whether `uniform` is used by the program. Only lenient shaders have inactive uniforms.
+    pub fn is_active(&self, uniform: U) -> bool {
+        self.uniforms.get(&uniform).is_some_and(|location| *location >= 0)
+    }
+
+    /// The uniforms used by the program, in the order of `Uniform::for_each`.
+    pub fn active_uniforms(&self) -> Vec<U> {
+        let mut active = Vec::new();
+        U::for_each(|uniform| if self.is_active(uniform) {
+            active.push(uniform);
+        });
+        active
+    }
+
+    /// The uniforms missing from the program or optimized out by the driver, see `new_lenient`.
+    pub fn inactive_uniforms(&self) -> Vec<U> {
+        let mut inactive = Vec::new();
+        U::for_each(|uniform| if !self.is_active(uniform) {
+            inactive.push(uniform);
+        });
+        inactive
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Whether the next `reload` accepts a program without some of the uniforms, see
+    /// `new_lenient`.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
     }
 
     pub fn set_float_array(&mut self, name: U, values: &[f32]) {
         if values.len() == 0 {
             return;
         }
-        unsafe {
-            let ptr = values.as_ptr();
-            gl::Uniform1fv(
-                self.uniforms.get(&name).cloned().expect("uniform location was not initialized"),
-                values.len() as c_int,
-                ptr
-            )
+        self.keep_value(name, 0, UniformKind::Float(1), values.iter().map(|v| v.to_bits()));
+        if let Some(location) = self.location(name) {
+            unsafe {
+                gl::Uniform1fv(location, values.len() as c_int, values.as_ptr())
+            }
         }
     }
 
@@ -112,13 +316,11 @@ impl<U: Uniform> Shader<U> {
         if values.len() == 0 {
             return;
         }
-        unsafe {
-            let ptr = values.as_ptr() as *const f32;
-            gl::Uniform2fv(
-                self.uniforms.get(&name).cloned().expect("uniform location was not initialized"),
-                values.len() as c_int,
-                ptr
-            )
+        self.keep_value(name, 0, UniformKind::Float(2), values.iter().flat_map(|v| [v.x, v.y]).map(f32::to_bits));
+        if let Some(location) = self.location(name) {
+            unsafe {
+                gl::Uniform2fv(location, values.len() as c_int, values.as_ptr() as *const f32)
+            }
         }
     }
 
@@ -126,13 +328,11 @@ impl<U: Uniform> Shader<U> {
         if values.len() == 0 {
             return;
         }
-        unsafe {
-            let ptr = values.as_ptr() as *const f32;
-            gl::Uniform3fv(
-                self.uniforms.get(&name).cloned().expect("uniform location was not initialized"),
-                values.len() as c_int,
-                ptr
-            )
+        self.keep_value(name, 0, UniformKind::Float(3), values.iter().flat_map(|v| [v.x, v.y, v.z]).map(f32::to_bits));
+        if let Some(location) = self.location(name) {
+            unsafe {
+                gl::Uniform3fv(location, values.len() as c_int, values.as_ptr() as *const f32)
+            }
         }
     }
 
@@ -140,132 +340,311 @@ impl<U: Uniform> Shader<U> {
         if values.len() == 0 {
             return;
         }
-        unsafe {
-            let ptr = values.as_ptr() as *const f32;
-            gl::Uniform4fv(
-                self.uniforms.get(&name).cloned().expect("uniform location was not initialized"),
-                values.len() as c_int,
-                ptr
-            )
+        self.keep_value(name, 0, UniformKind::Float(4), values.iter().flat_map(|v| [v.x, v.y, v.z, v.w]).map(f32::to_bits));
+        if let Some(location) = self.location(name) {
+            unsafe {
+                gl::Uniform4fv(location, values.len() as c_int, values.as_ptr() as *const f32)
+            }
         }
     }
 
     /// Give a uniform a new uint value.
     pub fn set_uint(&mut self, name: U, value: GLuint) {
-        unsafe {
-            gl::Uniform1ui(self.uniforms.get(&name).cloned().expect("uniform location was not initialized"), value);
+        self.keep_value(name, 0, UniformKind::UInt, [value]);
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform1ui(location, value); }
         }
     }
     
     /// Give a uniform a new int value.
     pub fn set_int(&mut self, name: U, value: GLint) {
-        unsafe {
-            gl::Uniform1i(self.uniforms.get(&name).cloned().expect("uniform location was not initialized"), value);
+        self.keep_value(name, 0, UniformKind::Int(1), [value as u32]);
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform1i(location, value); }
+        }
+    }
+
+    /// Give a `bool` uniform a new value. GLSL booleans are set as ints, 0 or 1.
+    pub fn set_bool(&mut self, name: U, value: bool) {
+        self.set_int(name, value as GLint);
+    }
+
+    pub fn set_ivec2(&mut self, name: U, value: &Vector2<i32>) {
+        self.keep_value(name, 0, UniformKind::Int(2), [value.x as u32, value.y as u32]);
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform2i(location, value.x, value.y); }
+        }
+    }
+
+    pub fn set_ivec3(&mut self, name: U, value: &Vector3<i32>) {
+        self.keep_value(name, 0, UniformKind::Int(3), [value.x as u32, value.y as u32, value.z as u32]);
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform3i(location, value.x, value.y, value.z); }
+        }
+    }
+
+    pub fn set_ivec4(&mut self, name: U, value: &Vector4<i32>) {
+        self.keep_value(name, 0, UniformKind::Int(4), [value.x as u32, value.y as u32, value.z as u32, value.w as u32]);
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform4i(location, value.x, value.y, value.z, value.w); }
+        }
+    }
+
+    pub fn set_int_array(&mut self, name: U, values: &[GLint]) {
+        if values.is_empty() {
+            return;
+        }
+        self.keep_value(name, 0, UniformKind::Int(1), values.iter().map(|v| *v as u32));
+        if let Some(location) = self.location(name) {
+            unsafe {
+                gl::Uniform1iv(location, values.len() as c_int, values.as_ptr())
+            }
+        }
+    }
+
+    /// Set the first elements of a `uint[]` uniform, typically tile indices or flags.
+    pub fn set_uint_array(&mut self, name: U, values: &[GLuint]) {
+        if values.is_empty() {
+            return;
+        }
+        self.keep_value(name, 0, UniformKind::UInt, values.iter().copied());
+        if let Some(location) = self.location(name) {
+            unsafe {
+                gl::Uniform1uiv(location, values.len() as c_int, values.as_ptr())
+            }
         }
     }
 
     /// Give a uniform a new float value.
     pub fn set_float(&mut self, name: U, value: GLfloat) {
-        unsafe {
-            gl::Uniform1f(self.uniforms.get(&name).cloned().expect("uniform location was not initialized"), value);
+        self.keep_value(name, 0, UniformKind::Float(1), [value.to_bits()]);
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform1f(location, value); }
         }
     }
 
     /// Give a uniform a new vector4 value.
     pub fn set_vector4(&mut self, name: U, value: &Vector4<f32>) {
-        unsafe {
-            gl::Uniform4f(self.uniforms.get(&name).cloned().expect("uniform location was not initialized"), value.x, value.y, value.z, value.w);
+        self.keep_value(name, 0, UniformKind::Float(4), [value.x, value.y, value.z, value.w].map(f32::to_bits));
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform4f(location, value.x, value.y, value.z, value.w); }
         }
     }
     
     pub fn set_vector3(&mut self, name: U, value: &Vector3<f32>) {
-        unsafe {
-            gl::Uniform3f(self.uniforms.get(&name).cloned().expect("uniform location was not initialized"), value.x, value.y, value.z);
+        self.keep_value(name, 0, UniformKind::Float(3), [value.x, value.y, value.z].map(f32::to_bits));
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform3f(location, value.x, value.y, value.z); }
         }
     }
 
     pub fn set_vector2(&mut self, name: U, value: &Vector2<f32>) {
-        unsafe {
-            gl::Uniform2f(self.uniforms.get(&name).cloned().expect("uniform location was not initialized"), value.x, value.y);
+        self.keep_value(name, 0, UniformKind::Float(2), [value.x, value.y].map(f32::to_bits));
+        if let Some(location) = self.location(name) {
+            unsafe { gl::Uniform2f(location, value.x, value.y); }
         }
     }
 
     pub fn set_matrix4(&mut self, name: U, mat: &Matrix4<f32>) {
-        unsafe {
-            gl::UniformMatrix4fv(self.uniforms.get(&name).cloned().expect("uniform location was not initialized"), 1, gl::FALSE, mat as *const _ as *const GLfloat)
+        self.keep_value(name, 0, UniformKind::Matrix4, matrix4_bits(std::slice::from_ref(mat)));
+        if let Some(location) = self.location(name) {
+            unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, mat as *const _ as *const GLfloat) }
+        }
+    }
+
+    /// Set the first elements of a `mat4[]` uniform. Elements past the size of the array are
+    /// ignored by OpenGL.
+    pub fn set_matrix4_array(&mut self, name: U, mats: &[Matrix4<f32>]) {
+        self.set_matrix4_array_at(name, 0, mats);
+    }
+
+    /// Set the elements of a `mat4[]` uniform from `first` on, typically to update a part of a
+    /// batch of transforms.
+    pub fn set_matrix4_array_at(&mut self, name: U, first: u32, mats: &[Matrix4<f32>]) {
+        if mats.is_empty() {
+            return;
+        }
+        self.keep_value(name, first, UniformKind::Matrix4, matrix4_bits(mats));
+        if let Some(location) = self.indexed_location(name, first) {
+            unsafe {
+                // cgmath matrices are column major, and contiguous in a slice.
+                gl::UniformMatrix4fv(location, mats.len() as c_int, gl::FALSE, mats.as_ptr() as *const GLfloat)
+            }
+        }
+    }
+
+    /// Set the elements of a `vec4[]` uniform from `first` on.
+    pub fn set_vec4_array_at(&mut self, name: U, first: u32, values: &[Vector4<f32>]) {
+        if values.is_empty() {
+            return;
+        }
+        self.keep_value(name, first, UniformKind::Float(4), values.iter().flat_map(|v| [v.x, v.y, v.z, v.w]).map(f32::to_bits));
+        if let Some(location) = self.indexed_location(name, first) {
+            unsafe {
+                gl::Uniform4fv(location, values.len() as c_int, values.as_ptr() as *const f32)
+            }
+        }
+    }
+
+    /// Set the elements of a `float[]` uniform from `first` on.
+    pub fn set_float_array_at(&mut self, name: U, first: u32, values: &[f32]) {
+        if values.is_empty() {
+            return;
+        }
+        self.keep_value(name, first, UniformKind::Float(1), values.iter().map(|v| v.to_bits()));
+        if let Some(location) = self.indexed_location(name, first) {
+            unsafe {
+                gl::Uniform1fv(location, values.len() as c_int, values.as_ptr())
+            }
         }
     }
 
     /// Create a base fragment shader from a fragment source as raw text (not a path), and a base
     /// vertex shader as raw text as well.
     ///
-    /// `texture_units` are the names of the texture units in your shader.
+    /// `texture_units` are the names of the texture units in your shader, the first one being
+    /// bound to `TEXTURE0`. An empty name leaves its unit unused.
+    ///
+    /// Returns an error if the shader doesn't compile or link, or if one of the uniforms or texture
+    /// units is missing (or unused, and thus optimized out).
     pub fn new(
         fragment_source: &str,
         vertex_source: &str,
         texture_units: &[&str],
     ) -> Result<Shader<U>, ShaderError> {
-        unsafe {
-            let vertex_shader_id = gl::CreateShader(gl::VERTEX_SHADER);
-            let fragment_shader_id = gl::CreateShader(gl::FRAGMENT_SHADER);
-
-            let fragment_shader = CString::new(fragment_source).unwrap();
-            let vertex_shader = CString::new(vertex_source).unwrap();
-            
-            gl::ShaderSource(vertex_shader_id, 1, &vertex_shader.as_c_str().as_ptr(), ::std::ptr::null());
-            gl::CompileShader(vertex_shader_id);
-            Self::check_build_step(vertex_shader_id, ShaderBuildStep::CompileVertexShader)?;
+        Self::build(fragment_source, vertex_source, texture_units, false)
+    }
 
-            gl::ShaderSource(fragment_shader_id, 1, &fragment_shader.as_c_str().as_ptr(), ::std::ptr::null());
-            gl::CompileShader(fragment_shader_id);
-            Self::check_build_step(fragment_shader_id, ShaderBuildStep::CompileFragmentShader)?;
+    /// Same as `new`, but the uniforms and texture units missing from the program, or optimized
+    /// out because they're unused, are only recorded as inactive: setting them does nothing and
+    /// logs a warning the first time. Handy while iterating on a shader, see `inactive_uniforms`.
+    ///
+    /// Only returns an error if the shader doesn't compile or link.
+    pub fn new_lenient(fragment_source: &str, vertex_source: &str, texture_units: &[&str]) -> Result<Shader<U>, ShaderError> {
+        Self::build(fragment_source, vertex_source, texture_units, true)
+    }
 
-            let program_id = gl::CreateProgram();
-            gl::AttachShader(program_id, vertex_shader_id);
-            gl::AttachShader(program_id, fragment_shader_id);
-            gl::LinkProgram(program_id);
-            Self::check_build_step(program_id, ShaderBuildStep::LinkProgram)?;
+    fn build(
+        fragment_source: &str,
+        vertex_source: &str,
+        texture_units: &[&str],
+        lenient: bool,
+    ) -> Result<Shader<U>, ShaderError> {
+        let program_id = build_program(fragment_source, vertex_source)?;
+
+        // the program is deleted when `shader` is dropped, on error as well.
+        let mut shader = Shader {
+            id: program_id,
+            uniforms: HashMap::default(),
+            texture_units: texture_units.iter().map(|name| name.to_string()).collect(),
+            lenient,
+            logged_inactive: HashSet::default(),
+            indexed_uniforms: HashMap::default(),
+            global_locations: None,
+            values: HashMap::default(),
+        };
+        shader.use_program();
 
-            gl::DetachShader(program_id, vertex_shader_id);
-            gl::DetachShader(program_id, fragment_shader_id);
+        // make sure the names of the texture units match TEXTURE0, TEXTURE1 and so on
+        shader.use_texture_units(texture_units)?;
 
-            gl::DeleteShader(vertex_shader_id);
-            gl::DeleteShader(fragment_shader_id);
+        // initialize the cache for the glUniformLocation of all the uniforms.
+        let mut result = Ok(());
+        U::for_each(|uniform| {
+            if result.is_ok() {
+                result = shader.init_uniform_location(uniform);
+            }
+        });
+        result?;
 
-            let mut shader = Shader {
-                id: program_id,
-                uniforms: HashMap::default()
-            };
-            shader.use_program();
+        Ok(shader)
+    }
 
-            // make sure the names of the texture units match TEXTURE0, TEXTURE1 and so on
-            shader.use_texture_units(texture_units);
+    /// Same as `new`, but panics if the shader can't be built.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new` would return an error.
+    pub fn new_unchecked(fragment_source: &str, vertex_source: &str, texture_units: &[&str]) -> Shader<U> {
+        Self::new(fragment_source, vertex_source, texture_units).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-            // initialize the cache for the glUniformLocation of all the uniforms.
-            U::for_each(|uniform| { shader.init_uniform_location(uniform) });
+    /// Rebuild the shader from new sources, typically when a file watcher saw them change.
+    ///
+    /// The new program only replaces the current one if it compiles, links, and still has all
+    /// the uniforms and texture units (unless the shader is lenient). Otherwise the error is
+    /// returned and the current program is kept, so you can keep drawing while fixing your GLSL.
+    ///
+    /// OpenGL doesn't carry uniform values over to the new program: the values set with the
+    /// `set_*` methods are set again on it.
+    pub fn reload(&mut self, fragment_source: &str, vertex_source: &str) -> Result<(), ShaderError> {
+        let program_id = build_program(fragment_source, vertex_source)?;
+
+        let mut uniforms = HashMap::default();
+        let mut missing_uniforms: Vec<String> = Vec::new();
+        U::for_each(|uniform| {
+            let location = uniform_location(program_id, uniform.name());
+            if location < 0 {
+                missing_uniforms.push(uniform.name().to_owned());
+            }
+            uniforms.insert(uniform, location);
+        });
+        missing_uniforms.extend(self.texture_units.iter().filter(|name| uniform_location(program_id, name) < 0).cloned());
+        if !missing_uniforms.is_empty() && self.lenient {
+            log::debug!("reloaded shader has inactive uniforms: {}", missing_uniforms.join(", "));
+        } else if !missing_uniforms.is_empty() {
+            unsafe { gl::DeleteProgram(program_id); }
+            return Err(ShaderError::new("UNIFORM_LOCATION", format!("missing (or unused) uniforms: {}", missing_uniforms.join(", "))));
+        }
 
-            Ok(shader)
+        unsafe { gl::DeleteProgram(self.id); }
+        self.id = program_id;
+        self.uniforms = uniforms;
+        self.logged_inactive.clear();
+        self.indexed_uniforms.clear();
+        self.global_locations = None;
+        self.use_program();
+        let texture_units = std::mem::take(&mut self.texture_units);
+        // every texture unit was checked above, or may be missing when lenient.
+        let _ = self.use_texture_units(&texture_units.iter().map(|name| name.as_str()).collect::<Vec<_>>());
+        self.texture_units = texture_units;
+        let values = std::mem::take(&mut self.values);
+        for ((uniform, first), value) in &values {
+            if let Some(location) = self.indexed_location(*uniform, *first) {
+                unsafe { value.apply(location); }
+            }
         }
+        self.values = values;
+        log::debug!("shader reloaded, new program id={}", program_id);
+        Ok(())
     }
 
     /// Initialize the texture units.
-    fn use_texture_units(&mut self, names: &[&str]) {
-        for (i, name) in names.iter().enumerate() {
-            self.use_texture_unit(i as GLint, name);
+    fn use_texture_units(&mut self, names: &[&str]) -> Result<(), ShaderError> {
+        for (i, name) in names.iter().enumerate().filter(|(_, name)| !name.is_empty()) {
+            self.use_texture_unit(i as GLint, name)?;
         }
+        Ok(())
     }
 
-    fn use_texture_unit(&mut self, index: GLint, name: &str) {
-        let cname = CString::new(name).unwrap();
-        let texture_unit_location = unsafe {gl::GetUniformLocation(self.id, cname.as_ptr())};
-        if texture_unit_location < 0 {
-            panic!("Error / Invalid location for texture_unit \"{}\": gl returned {}", name, texture_unit_location);
+    fn use_texture_unit(&mut self, index: GLint, name: &str) -> Result<(), ShaderError> {
+        let texture_unit_location = uniform_location(self.id, name);
+        if texture_unit_location < 0 && self.lenient {
+            log::debug!("texture unit \"{}\" is missing or unused, it is inactive", name);
+            return Ok(());
+        } else if texture_unit_location < 0 {
+            return Err(ShaderError::new("UNIFORM_LOCATION", format!("missing (or unused) texture unit \"{}\"", name)));
         };
         unsafe {
             // set so that the "name" inside the shader matches TEXTURE`index`. 
             gl::Uniform1i(texture_unit_location, index);
             log::debug!("\"{}\" uniform initialized with value {} (p={})", name, index, texture_unit_location);
         }
+        Ok(())
+    }
+
+    pub (crate) fn program_id(&self) -> GLuint {
+        self.id
     }
 
     /// Use this program (shader).
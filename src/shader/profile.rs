@@ -0,0 +1,71 @@
+//! OpenGL ES 3.0 / WebGL2 support.
+//!
+//! Everything sprowl needs is core in ES 3.0: instanced draws, VAOs, texture arrays, integer
+//! attribs, sampler objects, `glMapBufferRange` and fences. What isn't is optional, and checked
+//! at runtime:
+//!
+//! * persistent mapping (`glBufferStorage`, base instances) falls back to `glBufferSubData`
+//! * GPU timing (`GL_TIME_ELAPSED`) is not available
+//! * `glClearDepth` is `glClearDepthf`
+//! * ETC2 compressed textures are core, BC1 and BC3 need `GL_EXT_texture_compression_s3tc`
+//! * growing arrays of compressed textures needs ES 3.2 (`glCopyImageSubData`)
+//!
+//! The bundled shaders are written in GLSL 330 core, without any implicit conversion, so they
+//! also compile as GLSL 300 es: on an ES context, `Shader::new` rewrites their `#version` line
+//! and adds default precisions. Your own shaders written the same way work as well.
+
+use std::borrow::Cow;
+
+use crate::gl_utils::gl_get_string;
+
+/// The `#version` line of the bundled shaders.
+const CORE_VERSION: &str = "#version 330 core";
+
+/// Replaces `CORE_VERSION` on ES contexts. ES has no default precision for floats in fragment
+/// shaders, nor for array samplers.
+const ES_HEADER: &str = "#version 300 es
+precision highp float;
+precision highp int;
+precision highp sampler2D;
+precision highp sampler2DArray;";
+
+/// The flavor of OpenGL of the context, see `RendererBuilder::with_gl_profile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GlProfile {
+    /// Desktop OpenGL 3.3 core or later.
+    #[default]
+    Core,
+    /// OpenGL ES 3.0 or later, and WebGL2.
+    Es,
+}
+
+impl GlProfile {
+    /// The profile of the current context, from `GL_VERSION`: ES contexts report
+    /// "OpenGL ES ...", and WebGL2 contexts "WebGL 2.0 ..." or "OpenGL ES ..." under Emscripten.
+    pub fn detect() -> GlProfile {
+        let version = gl_get_string(crate::backend::gl::VERSION).to_bytes();
+        if version.starts_with(b"OpenGL ES") || version.starts_with(b"WebGL") {
+            GlProfile::Es
+        } else {
+            GlProfile::Core
+        }
+    }
+
+    #[inline]
+    pub fn is_es(self) -> bool {
+        self == GlProfile::Es
+    }
+
+    /// `source` as it should be compiled with this profile: shaders starting with
+    /// `#version 330 core` get the `#version 300 es` header on ES. Other sources are returned
+    /// as they are.
+    pub fn glsl_source(self, source: &str) -> Cow<'_, str> {
+        match self {
+            GlProfile::Es => match source.trim_start().strip_prefix(CORE_VERSION) {
+                Some(rest) => Cow::Owned(format!("{}{}", ES_HEADER, rest)),
+                None => Cow::Borrowed(source),
+            },
+            GlProfile::Core => Cow::Borrowed(source),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Validation of shader sources, for instance in an asset pipeline or in CI.
+
+use crate::backend::gl::{self, types::*};
+use std::ffi::CString;
+
+use super::{build_step_succeeded, info_log, ShaderBuildStep};
+
+/// What a shader is expected to contain, other than valid GLSL.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShaderLayout<'a> {
+    /// Names of the uniforms, as given by your `Uniform::name`.
+    pub uniforms: &'a [&'a str],
+    /// Names of the texture units, as given to `Shader::new`.
+    pub texture_units: &'a [&'a str],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Link,
+    /// The program is valid, but doesn't match the `ShaderLayout`.
+    Layout,
+}
+
+impl std::fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Link => "link",
+            ShaderStage::Layout => "layout",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A single error or warning reported by the driver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub stage: ShaderStage,
+    /// Line in the source, starting at 1, if the driver gave one.
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{}:{}: {}", self.stage, line, column, self.message),
+            (Some(line), None) => write!(f, "{}:{}: {}", self.stage, line, self.message),
+            _ => write!(f, "{}: {}", self.stage, self.message),
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    s.trim().parse().ok()
+}
+
+/// Tries to find the line and column of a line of the info log.
+///
+/// Every driver has its own format, the most common ones are:
+///
+/// * NVIDIA: `0(12) : error C0000: syntax error`
+/// * Mesa: `0:12(5): error: syntax error`
+/// * AMD, Intel: `ERROR: 0:12: 'foo' : undeclared identifier`
+fn parse_log_line(line: &str) -> (Option<u32>, Option<u32>, &str) {
+    let stripped = line.trim_start_matches("ERROR: ").trim_start_matches("WARNING: ");
+    // NVIDIA
+    if let (Some(open), Some(close)) = (stripped.find('('), stripped.find(')')) {
+        if open < close && parse_number(&stripped[..open]).is_some() && !stripped[..open].contains(':') {
+            if let Some(line_number) = parse_number(&stripped[open + 1..close]) {
+                let message = stripped[close + 1..].trim_start_matches(&[' ', ':'][..]);
+                return (Some(line_number), None, message);
+            }
+        }
+    }
+    // Mesa, AMD, Intel: "0:line(column): message" or "0:line: message"
+    let mut parts = stripped.splitn(3, ':');
+    if let (Some(source), Some(location), Some(message)) = (parts.next(), parts.next(), parts.next()) {
+        if parse_number(source).is_some() {
+            let (line_number, column) = match location.find('(') {
+                Some(open) => (parse_number(&location[..open]), parse_number(location[open + 1..].trim_end_matches(')'))),
+                None => (parse_number(location), None),
+            };
+            if line_number.is_some() {
+                return (line_number, column, message.trim());
+            }
+        }
+    }
+    (None, None, line.trim())
+}
+
+fn diagnostics_from_log(stage: ShaderStage, log: &str, diagnostics: &mut Vec<ShaderDiagnostic>) {
+    for log_line in log.lines().filter(|l| !l.trim().is_empty()) {
+        let (line, column, message) = parse_log_line(log_line);
+        diagnostics.push(ShaderDiagnostic {
+            stage,
+            line,
+            column,
+            message: message.to_owned(),
+        });
+    }
+}
+
+/// Compiles a shader, and returns its id if it succeeded. The shader must be deleted by the caller.
+unsafe fn compile(kind: GLenum, source: &str, step: ShaderBuildStep, stage: ShaderStage, diagnostics: &mut Vec<ShaderDiagnostic>) -> Option<GLuint> {
+    let source = match CString::new(source) {
+        Ok(source) => source,
+        Err(_) => {
+            diagnostics.push(ShaderDiagnostic { stage, line: None, column: None, message: String::from("source contains a nul byte") });
+            return None;
+        }
+    };
+    let shader_id = gl::CreateShader(kind);
+    gl::ShaderSource(shader_id, 1, &source.as_c_str().as_ptr(), ::std::ptr::null());
+    gl::CompileShader(shader_id);
+    if build_step_succeeded(shader_id, step) {
+        Some(shader_id)
+    } else {
+        diagnostics_from_log(stage, &info_log(shader_id, step), diagnostics);
+        gl::DeleteShader(shader_id);
+        None
+    }
+}
+
+/// Compile and link a vertex and a fragment shader, and check that all the uniforms and texture
+/// units of `layout` are used. Returns every error found, with line numbers when the driver gives
+/// them.
+///
+/// Nothing is kept after validation, but an OpenGL context must be current: in a CI, you can
+/// create a hidden window or a headless context with your windowing library.
+pub fn validate_source(vertex_source: &str, fragment_source: &str, layout: &ShaderLayout) -> Result<(), Vec<ShaderDiagnostic>> {
+    let mut diagnostics = Vec::new();
+    unsafe {
+        let vertex_shader_id = compile(gl::VERTEX_SHADER, vertex_source, ShaderBuildStep::CompileVertexShader, ShaderStage::Vertex, &mut diagnostics);
+        let fragment_shader_id = compile(gl::FRAGMENT_SHADER, fragment_source, ShaderBuildStep::CompileFragmentShader, ShaderStage::Fragment, &mut diagnostics);
+        let (vertex_shader_id, fragment_shader_id) = match (vertex_shader_id, fragment_shader_id) {
+            (Some(v), Some(f)) => (v, f),
+            (v, f) => {
+                for id in v.into_iter().chain(f) {
+                    gl::DeleteShader(id);
+                }
+                return Err(diagnostics);
+            }
+        };
+
+        let program_id = gl::CreateProgram();
+        gl::AttachShader(program_id, vertex_shader_id);
+        gl::AttachShader(program_id, fragment_shader_id);
+        gl::LinkProgram(program_id);
+        if build_step_succeeded(program_id, ShaderBuildStep::LinkProgram) {
+            // unused uniforms are optimized out by the driver, so they have no location.
+            for (name, what) in layout.uniforms.iter().map(|n| (n, "uniform"))
+                .chain(layout.texture_units.iter().map(|n| (n, "texture unit")))
+            {
+                let cname = CString::new(*name).unwrap_or_default();
+                if gl::GetUniformLocation(program_id, cname.as_ptr()) < 0 {
+                    diagnostics.push(ShaderDiagnostic {
+                        stage: ShaderStage::Layout,
+                        line: None,
+                        column: None,
+                        message: format!("{} \"{}\" is missing or unused", what, name),
+                    });
+                }
+            }
+        } else {
+            diagnostics_from_log(ShaderStage::Link, &info_log(program_id, ShaderBuildStep::LinkProgram), &mut diagnostics);
+        }
+
+        gl::DetachShader(program_id, vertex_shader_id);
+        gl::DetachShader(program_id, fragment_shader_id);
+        gl::DeleteShader(vertex_shader_id);
+        gl::DeleteShader(fragment_shader_id);
+        gl::DeleteProgram(program_id);
+    }
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::color::Color;
+use crate::render_storage::atlas::TextureHandle;
+use crate::shader::{Shader, ShaderError};
+use super::{QuadUniform, QUAD_FRAGMENT_GLSL, QUAD_TEXTURE_UNITS};
+
+/// The vertex shader of `MeshVertex`, with the same `mat4 view` uniform as `QUAD_VERTEX_GLSL`.
+pub const MESH_VERTEX_GLSL: &str = include_str!("mesh.vs.glsl");
+
+/// Build `MESH_VERTEX_GLSL` and `QUAD_FRAGMENT_GLSL`, the shader to give to
+/// `Renderer::set_mesh_shader` next to `standard_quad_shader`. The `QUAD_FLAG_*` flags work the
+/// same, except for the ones which need an overlay.
+pub fn standard_mesh_shader() -> Result<Shader<QuadUniform>, ShaderError> {
+    Shader::new(QUAD_FRAGMENT_GLSL, MESH_VERTEX_GLSL, &QUAD_TEXTURE_UNITS)
+}
+
+/// A vertex of the triangles given to `Renderer::add_mesh`: skewed platforms, trails, sprites
+/// distorted by a grid of vertices...
+///
+/// Vertices are written as:
+///
+/// * "mesh_position" (`vec2`, location 0)
+/// * "mesh_uv" (`vec2`, location 1), normalized in the layer
+/// * "mesh_color" (`vec4`, location 2, packed as 4 normalized bytes), multiplied with the texture
+/// * "mesh_layer" (`uint`, location 3), the same for the whole mesh
+/// * "mesh_flags" (`uint`, location 4), the same for the whole mesh
+#[derive(Debug, Clone, Copy)]
+pub struct MeshVertex {
+    pub position: [f32; 2],
+    /// Texture coordinates, normalized in the layer: see `textured` for coordinates relative to a
+    /// texture.
+    pub uv: [f32; 2],
+    pub color: Color<u8>,
+}
+
+/// Size of a vertex in the vertex buffer, in bytes.
+pub (crate) const MESH_VERTEX_SIZE: usize = 4 * 4 + 4 + 4 + 4;
+
+impl MeshVertex {
+    /// A white vertex, with texture coordinates normalized in the layer.
+    pub fn new(position: [f32; 2], uv: [f32; 2]) -> MeshVertex {
+        MeshVertex {
+            position,
+            uv,
+            color: Color::<u8>::white(),
+        }
+    }
+
+    /// A white vertex sampling `texture` at `uv`, from (0.0, 0.0) at its top-left corner to
+    /// (1.0, 1.0) at its bottom-right corner.
+    pub fn textured(position: [f32; 2], texture: &TextureHandle, uv: [f32; 2]) -> MeshVertex {
+        let (x, y, w, h) = texture.uv;
+        MeshVertex::new(position, [x + uv[0] * w, y + uv[1] * h])
+    }
+
+    pub fn with_color(mut self, color: Color<u8>) -> MeshVertex {
+        self.color = color;
+        self
+    }
+
+    /// Append the vertex to `vb`, in the order of the documentation of `MeshVertex`.
+    pub (crate) fn push_to(&self, vb: &mut Vec<u8>, layer: u32, flags: u32) {
+        for value in self.position.iter().chain(self.uv.iter()) {
+            vb.extend_from_slice(&value.to_ne_bytes());
+        }
+        vb.extend_from_slice(&[self.color.r, self.color.g, self.color.b, self.color.a]);
+        vb.extend_from_slice(&layer.to_ne_bytes());
+        vb.extend_from_slice(&flags.to_ne_bytes());
+    }
+}
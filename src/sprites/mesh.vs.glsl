@@ -0,0 +1,30 @@
+#version 330 core
+
+// The vertex shader of `MeshVertex`, drawn with `QUAD_FRAGMENT_GLSL`: the outputs are the ones
+// of the quad vertex shader.
+layout (location = 0) in vec2 mesh_position;
+layout (location = 1) in vec2 mesh_uv;
+layout (location = 2) in vec4 mesh_color;
+layout (location = 3) in uint mesh_layer;
+layout (location = 4) in uint mesh_flags;
+
+out vec2 tex_coords;
+out vec4 color;
+out vec4 overlay;
+flat out uint layer;
+flat out uint flags;
+
+uniform mat4 view;
+
+void main()
+{
+    gl_Position = view * vec4(mesh_position, 0.0, 1.0);
+    // in front, like a quad with a depth of 0.0
+    gl_Position.z = -gl_Position.w;
+
+    tex_coords = mesh_uv;
+    color = mesh_color;
+    overlay = vec4(0.0);
+    layer = mesh_layer;
+    flags = mesh_flags;
+}
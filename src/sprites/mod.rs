@@ -0,0 +1,70 @@
+mod mesh;
+mod nine_patch;
+mod quad;
+mod template;
+
+pub use mesh::*;
+pub use nine_patch::*;
+pub use quad::*;
+pub use template::*;
+
+use cgmath::Vector2;
+
+use crate::render_storage::texture::TextureArrayLayer;
+
+/// A part of a texture to draw somewhere, to convert to your own vertex data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteDrawCall {
+    /// Top-left corner of where the sprite should be drawn.
+    pub dest_origin: Vector2<f32>,
+    pub dest_size: Vector2<f32>,
+    // in pixels
+    pub source_crop: (f32, f32, f32, f32),
+    pub texture_layer: TextureArrayLayer,
+}
+
+/// Mirroring of a sprite, to apply to its crop or its texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Flip {
+    #[default]
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl Flip {
+    pub fn from_bools(horizontal: bool, vertical: bool) -> Flip {
+        match (horizontal, vertical) {
+            (false, false) => Flip::None,
+            (true, false) => Flip::Horizontal,
+            (false, true) => Flip::Vertical,
+            (true, true) => Flip::Both,
+        }
+    }
+
+    #[inline]
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Flip::Horizontal | Flip::Both)
+    }
+
+    #[inline]
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Flip::Vertical | Flip::Both)
+    }
+
+    /// Mirror texture coordinates (x, y, w, h): a flipped axis starts from the other edge, with a
+    /// negative size. Drawn with `uv.xy + quad_vertex * uv.zw`, the image is mirrored.
+    pub fn apply_to_uv(self, uv: (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+        let (mut x, mut y, mut w, mut h) = uv;
+        if self.is_horizontal() {
+            x += w;
+            w = -w;
+        }
+        if self.is_vertical() {
+            y += h;
+            h = -h;
+        }
+        (x, y, w, h)
+    }
+}
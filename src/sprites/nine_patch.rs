@@ -0,0 +1,153 @@
+use cgmath::Vector2;
+use smallvec::SmallVec;
+
+use crate::render_storage::{atlas::TextureHandle, texture::TextureArrayLayer};
+use crate::renderer::{AsVertexData, Renderer};
+use crate::shader::Uniform;
+use super::SpriteDrawCall;
+
+/// Size of the borders of a `NinePatch`, in pixels of the source texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NinePatchInsets {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl NinePatchInsets {
+    pub fn new(left: u32, right: u32, top: u32, bottom: u32) -> NinePatchInsets {
+        NinePatchInsets {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    /// The same inset on every side.
+    pub fn uniform(inset: u32) -> NinePatchInsets {
+        NinePatchInsets::new(inset, inset, inset, inset)
+    }
+}
+
+/// A texture split in 9 parts, to draw panels of any size: the corners keep their size, the
+/// edges are stretched along one axis, and the center along both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NinePatch {
+    pub texture_layer: TextureArrayLayer,
+    /// The part of the layer used by the whole patch, in pixels (x, y, w, h).
+    pub crop: (u32, u32, u32, u32),
+    pub insets: NinePatchInsets,
+    /// Borders are drawn this many times bigger than in the texture. 1.0 by default.
+    pub border_scale: f32,
+}
+
+/// Splits `src_size` and `dest_size` into 3 parts, shrinking the borders if they don't fit.
+fn split_axis(start: u32, end: u32, src_size: u32, dest_size: f32, scale: f32) -> ([f32; 3], [f32; 3]) {
+    // borders larger than the source are shrunk to fit it, keeping their ratio.
+    let src_borders = start as u64 + end as u64;
+    let (start, end) = if src_borders > src_size as u64 {
+        let start = (start as u64 * src_size as u64 / src_borders) as u32;
+        (start, src_size - start)
+    } else {
+        (start, end)
+    };
+    let src = [start as f32, (src_size - start - end) as f32, end as f32];
+    let borders = (start + end) as f32 * scale;
+    let border_ratio = if borders > dest_size && borders > 0.0 { dest_size / borders } else { 1.0 };
+    let dest_start = start as f32 * scale * border_ratio;
+    let dest_end = end as f32 * scale * border_ratio;
+    (src, [dest_start, (dest_size - dest_start - dest_end).max(0.0), dest_end])
+}
+
+impl NinePatch {
+    pub fn new(texture_layer: TextureArrayLayer, crop: (u32, u32, u32, u32), insets: NinePatchInsets) -> NinePatch {
+        NinePatch {
+            texture_layer,
+            crop,
+            insets,
+            border_scale: 1.0,
+        }
+    }
+
+    /// Use a whole texture as the patch.
+    pub fn from_handle(handle: &TextureHandle, insets: NinePatchInsets) -> NinePatch {
+        let rect = handle.rect;
+        NinePatch::new(handle.layer, (rect.x, rect.y, rect.width, rect.height), insets)
+    }
+
+    pub fn with_border_scale(mut self, border_scale: f32) -> NinePatch {
+        self.border_scale = border_scale;
+        self
+    }
+
+    /// Returns the parts to draw to fill `dest` (x, y, w, h), row by row. Empty parts (for
+    /// instance the center of a patch without one) are skipped.
+    ///
+    /// If `dest` is smaller than the borders, the borders are shrunk to fit.
+    pub fn draw_calls(&self, dest: (f32, f32, f32, f32)) -> SmallVec<[SpriteDrawCall; 9]> {
+        let (src_x, src_y, src_w, src_h) = self.crop;
+        let (dest_x, dest_y, dest_w, dest_h) = dest;
+        let insets = self.insets;
+        let (src_cols, dest_cols) = split_axis(insets.left, insets.right, src_w, dest_w, self.border_scale);
+        let (src_rows, dest_rows) = split_axis(insets.top, insets.bottom, src_h, dest_h, self.border_scale);
+
+        let mut results = SmallVec::new();
+        let (mut source_y, mut y) = (src_y as f32, dest_y);
+        for row in 0..3 {
+            let (mut source_x, mut x) = (src_x as f32, dest_x);
+            for col in 0..3 {
+                if src_cols[col] > 0.0 && src_rows[row] > 0.0 && dest_cols[col] > 0.0 && dest_rows[row] > 0.0 {
+                    results.push(SpriteDrawCall {
+                        dest_origin: Vector2::new(x, y),
+                        dest_size: Vector2::new(dest_cols[col], dest_rows[row]),
+                        source_crop: (source_x, source_y, src_cols[col], src_rows[row]),
+                        texture_layer: self.texture_layer,
+                    });
+                }
+                source_x += src_cols[col];
+                x += dest_cols[col];
+            }
+            source_y += src_rows[row];
+            y += dest_rows[row];
+        }
+        results
+    }
+
+    /// Add the parts of the patch to the renderer, `to_vertex_data` converting every part to your
+    /// own vertex data.
+    pub fn add_to_renderer<U: Uniform, E: AsVertexData, F: FnMut(&SpriteDrawCall) -> E>(&self, renderer: &mut Renderer<U>, dest: (f32, f32, f32, f32), mut to_vertex_data: F) {
+        for draw_call in &self.draw_calls(dest) {
+            renderer.add_elem(&to_vertex_data(draw_call));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borders_are_clamped_to_the_source() {
+        assert_eq!(split_axis(4, 12, 8, 8.0, 1.0), ([2.0, 0.0, 6.0], [2.0, 0.0, 6.0]));
+        assert_eq!(split_axis(u32::MAX, u32::MAX, 16, 32.0, 1.0), ([8.0, 0.0, 8.0], [8.0, 16.0, 8.0]));
+    }
+
+    #[test]
+    fn borders_are_clamped_to_the_destination() {
+        assert_eq!(split_axis(4, 4, 16, 4.0, 2.0), ([4.0, 8.0, 4.0], [2.0, 0.0, 2.0]));
+        assert_eq!(split_axis(4, 12, 16, 0.0, 1.0), ([4.0, 0.0, 12.0], [0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn parts_stay_within_the_crop() {
+        let patch = NinePatch::new(0, (8, 8, 16, 16), NinePatchInsets::uniform(12));
+        let draw_calls = patch.draw_calls((0.0, 0.0, 100.0, 10.0));
+        assert_eq!(draw_calls.len(), 4, "the edges and the center are empty");
+        for draw_call in &draw_calls {
+            let (x, y, w, h) = draw_call.source_crop;
+            assert!(x >= 8.0 && y >= 8.0 && x + w <= 24.0 && y + h <= 24.0, "{:?}", draw_call.source_crop);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+#version 330 core
+
+// The fragment shader of `QuadInstance`, see `QUAD_FLAG_*` for the flags.
+#define QUAD_FLAG_UNTEXTURED 1u
+#define QUAD_FLAG_GRAYSCALE 2u
+#define QUAD_FLAG_DESATURATE 4u
+#define QUAD_FLAG_OUTLINE 8u
+#define QUAD_FLAG_PULSE 16u
+#define QUAD_FLAG_TEXT_GAMMA 32u
+// the same as QUAD_LAYER_TIER_SHIFT: the layer is in the low bits of `layer`, the RGBA tier above
+#define QUAD_LAYER_TIER_SHIFT 16u
+#define QUAD_LAYER_MASK 0xFFFFu
+// the same as DEFAULT_TEXT_GAMMA
+#define DEFAULT_TEXT_GAMMA 1.8
+#define TAU 6.2831853
+
+in vec2 tex_coords;
+in vec4 color;
+// rgb mixed over the result by a, or the color of the outline with QUAD_FLAG_OUTLINE
+in vec4 overlay;
+flat in uint layer;
+flat in uint flags;
+
+out vec4 frag_color;
+
+uniform sampler2DArray rgba_textures;
+uniform sampler2DArray grayscale_textures;
+// the RGBA tiers after the first one, see `tiers`
+uniform sampler2DArray rgba_tier_1;
+uniform sampler2DArray rgba_tier_2;
+uniform sampler2DArray rgba_tier_3;
+// in seconds, for QUAD_FLAG_PULSE: the GLOBAL_TIME of the standard globals
+uniform float u_time;
+// for QUAD_FLAG_TEXT_GAMMA, DEFAULT_TEXT_GAMMA when left to 0
+uniform float text_gamma;
+// set by renderers with AlphaMode::Premultiplied: the RGBA textures are premultiplied, and so
+// must be the output.
+uniform bool u_premultiplied_alpha;
+
+// The coverage of a glyph, from 1/gamma for black text (bolder) to gamma for white text (thinner).
+float text_coverage(float coverage) {
+    float gamma = text_gamma > 0.0 ? text_gamma : DEFAULT_TEXT_GAMMA;
+    float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+    return pow(coverage, pow(gamma, 2.0 * luminance - 1.0));
+}
+
+// The RGBA texel at `coords`, in the tier of the quad.
+vec4 sample_rgba(vec2 coords) {
+    vec3 coords_in_layer = vec3(coords, float(layer & QUAD_LAYER_MASK));
+    switch (layer >> QUAD_LAYER_TIER_SHIFT) {
+        case 1u: return texture(rgba_tier_1, coords_in_layer);
+        case 2u: return texture(rgba_tier_2, coords_in_layer);
+        case 3u: return texture(rgba_tier_3, coords_in_layer);
+        default: return texture(rgba_textures, coords_in_layer);
+    }
+}
+
+// The size of the RGBA layers of the tier of the quad.
+ivec2 rgba_size() {
+    switch (layer >> QUAD_LAYER_TIER_SHIFT) {
+        case 1u: return textureSize(rgba_tier_1, 0).xy;
+        case 2u: return textureSize(rgba_tier_2, 0).xy;
+        case 3u: return textureSize(rgba_tier_3, 0).xy;
+        default: return textureSize(rgba_textures, 0).xy;
+    }
+}
+
+vec4 sample_quad(vec2 coords) {
+    if ((flags & QUAD_FLAG_GRAYSCALE) != 0u) {
+        float alpha = texture(grayscale_textures, vec3(coords, layer & QUAD_LAYER_MASK)).r;
+        if ((flags & QUAD_FLAG_TEXT_GAMMA) != 0u) {
+            alpha = text_coverage(alpha);
+        }
+        return vec4(color.rgb, color.a * alpha);
+    } else {
+        vec4 texel = sample_rgba(coords);
+        // the effects below work on straight colors. Filtering happened on premultiplied ones,
+        // so there's no dark halo to bring back.
+        if (u_premultiplied_alpha && texel.a > 0.0) {
+            texel.rgb /= texel.a;
+        }
+        return texel * color;
+    }
+}
+
+// The highest alpha of the 8 texels around `tex_coords`.
+float outline_alpha() {
+    vec2 texel;
+    if ((flags & QUAD_FLAG_GRAYSCALE) != 0u) {
+        texel = 1.0 / vec2(textureSize(grayscale_textures, 0).xy);
+    } else {
+        texel = 1.0 / vec2(rgba_size());
+    }
+    float alpha = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            if (x != 0 || y != 0) {
+                alpha = max(alpha, sample_quad(tex_coords + texel * vec2(x, y)).a);
+            }
+        }
+    }
+    return alpha;
+}
+
+void main()
+{
+    if ((flags & QUAD_FLAG_UNTEXTURED) != 0u) {
+        frag_color = color;
+    } else {
+        frag_color = sample_quad(tex_coords);
+    }
+    if ((flags & QUAD_FLAG_DESATURATE) != 0u) {
+        frag_color.rgb = vec3(dot(frag_color.rgb, vec3(0.299, 0.587, 0.114)));
+    }
+    vec4 effect = overlay;
+    if ((flags & QUAD_FLAG_PULSE) != 0u) {
+        // once per second, from transparent to the alpha of the overlay
+        effect.a *= 0.5 - 0.5 * cos(u_time * TAU);
+    }
+    if ((flags & QUAD_FLAG_OUTLINE) != 0u && (flags & QUAD_FLAG_UNTEXTURED) == 0u) {
+        // the outline goes behind the texture, where it is not opaque
+        float alpha = outline_alpha() * effect.a;
+        frag_color = vec4(mix(effect.rgb, frag_color.rgb, frag_color.a), max(frag_color.a, alpha));
+    } else {
+        frag_color.rgb = mix(frag_color.rgb, effect.rgb, effect.a);
+    }
+    // transparent pixels must not hide what's behind them when the depth is written.
+    if (frag_color.a == 0.0) {
+        discard;
+    }
+    if (u_premultiplied_alpha) {
+        frag_color.rgb *= frag_color.a;
+    }
+}
@@ -0,0 +1,314 @@
+use crate::color::Color;
+use crate::compat;
+use crate::render_storage::atlas::{TextureHandle, TextureRegion};
+use crate::render_storage::texture::TextureArrayLayer;
+use crate::renderer::instance_data;
+use crate::shader::{GLOBAL_TIME, Shader, ShaderError, Uniform};
+use super::Flip;
+
+/// The vertex shader of `QuadInstance`, with a `mat4 view` uniform.
+pub const QUAD_VERTEX_GLSL: &str = include_str!("quad.vs.glsl");
+/// The fragment shader of `QuadInstance`, with the texture units of `QUAD_TEXTURE_UNITS`, a
+/// `float u_time` uniform for `QUAD_FLAG_PULSE` (the one of `StandardGlobals`) and a
+/// `float text_gamma` one for `QUAD_FLAG_TEXT_GAMMA`.
+pub const QUAD_FRAGMENT_GLSL: &str = include_str!("quad.fs.glsl");
+/// The texture units of `QUAD_FRAGMENT_GLSL`, indexed by the units `RenderStorage::set_active`
+/// binds them to: `TextureKind::texture_unit`, then `tiers::rgba_tier_texture_unit`. The unit of
+/// the palettes is left unused.
+pub const QUAD_TEXTURE_UNITS: [&str; 6] = ["rgba_textures", "grayscale_textures", "", "rgba_tier_1", "rgba_tier_2", "rgba_tier_3"];
+
+/// "quad_layer" holds the layer in its 16 low bits, and the RGBA tier above them.
+pub const QUAD_LAYER_TIER_SHIFT: u32 = 16;
+
+/// The quad is filled with its color, the texture is ignored.
+pub const QUAD_FLAG_UNTEXTURED: u32 = 1;
+/// The texture is in the grayscale array (glyphs, masks): it's the alpha of the color.
+pub const QUAD_FLAG_GRAYSCALE: u32 = 2;
+/// The result is turned to shades of gray, before the overlay: a disabled button, a petrified
+/// enemy.
+pub const QUAD_FLAG_DESATURATE: u32 = 4;
+/// The overlay is drawn as a 1 texel outline around the opaque parts of the texture, instead of
+/// being mixed over it. The crop needs a transparent border of 1 texel for the outline to fit,
+/// and to not pick up its neighbors in the atlas.
+pub const QUAD_FLAG_OUTLINE: u32 = 8;
+/// The alpha of the overlay goes from 0 to its value and back once per second, following the
+/// `Time` uniform: a blinking highlight, or a blinking outline with `QUAD_FLAG_OUTLINE`. Renderers
+/// built `with_standard_globals` set it on their own, see `StandardGlobals::time`.
+pub const QUAD_FLAG_PULSE: u32 = 16;
+/// With `QUAD_FLAG_GRAYSCALE`, the coverage of the glyph is adjusted for the luminance of the
+/// color: blending isn't gamma-correct, so dark text on a light background looks thinner than
+/// light text on a dark one. Dark text is made bolder and light text thinner, following the
+/// `TextGamma` uniform. See `TextOptions::with_gamma_correction`.
+pub const QUAD_FLAG_TEXT_GAMMA: u32 = 32;
+
+/// The gamma of `QUAD_FLAG_TEXT_GAMMA` while the `TextGamma` uniform is left to 0.0.
+pub const DEFAULT_TEXT_GAMMA: f32 = 1.8;
+
+/// The uniforms of `QUAD_VERTEX_GLSL` and `QUAD_FRAGMENT_GLSL`.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum QuadUniform {
+    /// `mat4`, typically `Camera::view_matrix`.
+    View,
+    /// `float`, in seconds. Only needed by `QUAD_FLAG_PULSE`, and set by the renderer with
+    /// `RendererBuilder::with_standard_globals`: this is `u_time`, the `GLOBAL_TIME`.
+    Time,
+    /// `float`, how much `QUAD_FLAG_TEXT_GAMMA` changes the weight of the text: 1.0 doesn't,
+    /// `DEFAULT_TEXT_GAMMA` while it's 0.0. Only needed by `QUAD_FLAG_TEXT_GAMMA`.
+    TextGamma,
+}
+
+impl Uniform for QuadUniform {
+    fn name(&self) -> &str {
+        match self {
+            QuadUniform::View => "view",
+            QuadUniform::Time => GLOBAL_TIME,
+            QuadUniform::TextGamma => "text_gamma",
+        }
+    }
+
+    fn for_each<F: FnMut(Self)>(mut f: F) {
+        f(QuadUniform::View);
+        f(QuadUniform::Time);
+        f(QuadUniform::TextGamma);
+    }
+}
+
+/// Build `QUAD_VERTEX_GLSL` and `QUAD_FRAGMENT_GLSL`, the shader of
+/// `RendererBuilder::standard_quad_layout`.
+pub fn standard_quad_shader() -> Result<Shader<QuadUniform>, ShaderError> {
+    Shader::new(QUAD_FRAGMENT_GLSL, QUAD_VERTEX_GLSL, &QUAD_TEXTURE_UNITS)
+}
+
+/// A quad as instance data, drawn by `QUAD_VERTEX_GLSL` and `QUAD_FRAGMENT_GLSL`:
+///
+/// * "quad_position" (`vec2`), its top-left corner before the rotation
+/// * "quad_size" (`vec2`)
+/// * "quad_rotation" (`float`), in degrees
+/// * "quad_pivot" (`vec2`), the center of the rotation relative to `position`
+/// * "quad_uv" (`vec4`), the crop in texture coordinates
+/// * "quad_layer" (`uint`), along with the tier: `layer | tier << QUAD_LAYER_TIER_SHIFT`
+/// * "quad_color" (`vec4`, packed as 4 normalized bytes), multiplied with the texture
+/// * "quad_overlay" (`vec4`, packed as 4 normalized bytes), mixed over the result by its alpha
+/// * "quad_flags" (`uint`), some of the `QUAD_FLAG_*`
+/// * "quad_depth" (`float`), from 0.0 (front) to 1.0 (back), only used with a `DepthMode`
+///
+/// `RendererBuilder::standard_quad_layout` is a layout made of these fields only.
+///
+/// The crop is flipped by `with_flip`: a flipped crop has a negative width or height, so the
+/// shader can keep computing its texture coordinates as `uv.xy + quad_vertex * uv.zw`.
+///
+/// ```ignore
+/// let mut renderer = RendererBuilder::new(10000).standard_quad_layout().build_with(standard_quad_shader()?);
+/// renderer.shader.set_matrix4(QuadUniform::View, &camera.view_matrix());
+/// let frame = animator.current();
+/// renderer.add_elem(&QuadInstance::new(position, size)
+///     .with_crop(&texture, frame.crop)
+///     .with_flip(frame.flip));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct QuadInstance {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// In degrees, clockwise on screen.
+    pub rotation: f32,
+    /// Relative to `position`, in the same unit as `size`.
+    pub pivot: [f32; 2],
+    /// The part of the layer to draw, normalized: (x, y, w, h). Not flipped, see `uv`.
+    pub crop: (f32, f32, f32, f32),
+    pub layer: TextureArrayLayer,
+    /// The RGBA tier of the texture, see `TextureHandle::array`.
+    pub tier: u32,
+    pub flip: Flip,
+    /// Multiplied with the texture: a tint, and the opacity of the quad.
+    pub color: Color<u8>,
+    /// Mixed over the tinted texture by its alpha, keeping the opacity of the texture: a damage
+    /// flash, a selection highlight. Transparent (no effect) by default.
+    pub overlay: Color<u8>,
+    pub flags: u32,
+    /// From 0.0 (front) to 1.0 (back), see `DepthMode`. For Y-sorting, something like
+    /// `1.0 - y / world_height`.
+    pub depth: f32,
+}
+
+impl QuadInstance {
+    /// A quad at `position` (its top-left corner), drawing the whole layer 0 without any tint.
+    pub fn new(position: [f32; 2], size: [f32; 2]) -> QuadInstance {
+        QuadInstance {
+            position,
+            size,
+            rotation: 0.0,
+            pivot: [size[0] / 2.0, size[1] / 2.0],
+            crop: (0.0, 0.0, 1.0, 1.0),
+            layer: 0,
+            tier: 0,
+            flip: Flip::None,
+            color: Color::<u8>::white(),
+            overlay: Color::from_rgba(0, 0, 0, 0),
+            flags: 0,
+            depth: 0.0,
+        }
+    }
+
+    /// A quad filled with `color`, without any texture.
+    pub fn filled(position: [f32; 2], size: [f32; 2], color: Color<u8>) -> QuadInstance {
+        QuadInstance::new(position, size).with_color(color).with_flags(QUAD_FLAG_UNTEXTURED)
+    }
+
+    /// Draw the whole texture, from whichever RGBA tier it is in.
+    pub fn with_texture(mut self, texture: &TextureHandle) -> QuadInstance {
+        self.crop = texture.uv;
+        self.layer = texture.layer;
+        self.tier = texture.array;
+        self
+    }
+
+    /// Draw a part of the texture, in pixels relative to the texture: (x, y, w, h). Typically the
+    /// crop of an animation frame or of a sprite sheet.
+    pub fn with_crop(mut self, texture: &TextureHandle, crop: (u32, u32, u32, u32)) -> QuadInstance {
+        self.crop = crop_to_uv(texture, crop);
+        self.layer = texture.layer;
+        self.tier = texture.array;
+        self
+    }
+
+    /// Draw a region defined with `RenderStorage::define_region`, or `TextureHandle::region`.
+    pub fn with_region(mut self, region: &TextureRegion) -> QuadInstance {
+        self.crop = region.uv;
+        self.layer = region.layer;
+        self.tier = region.array;
+        self
+    }
+
+    /// Set the normalized crop (x, y, w, h) and the layer directly. The tier is left as it is.
+    pub fn with_uv(mut self, crop: (f32, f32, f32, f32), layer: TextureArrayLayer) -> QuadInstance {
+        self.crop = crop;
+        self.layer = layer;
+        self
+    }
+
+    /// Grow the quad by `pad` pixels of the texture on every side, drawing that much of the
+    /// texture around the crop: room for an outline or a glow, drawn in the empty pixels around a
+    /// sprite. The crop and the quad grow together, so the texture keeps its scale and its place,
+    /// rotated or not.
+    ///
+    /// `layer_dims` are the dimensions of the layer of the crop, see `RenderStorage::get_max_dims`
+    /// and `RenderStorage::rgba_tier_dims`. Call it once the crop, the size and the pivot are set.
+    pub fn with_pad(mut self, pad: f32, layer_dims: (u32, u32)) -> QuadInstance {
+        let (x, y, w, h) = self.crop;
+        let (layer_w, layer_h) = (layer_dims.0 as f32, layer_dims.1 as f32);
+        // size of a pixel of the texture in the unit of `size`
+        let scale_x = if w > 0.0 { self.size[0] / (w * layer_w) } else { 1.0 };
+        let scale_y = if h > 0.0 { self.size[1] / (h * layer_h) } else { 1.0 };
+        let (pad_u, pad_v) = (pad / layer_w, pad / layer_h);
+        let (pad_x, pad_y) = (pad * scale_x, pad * scale_y);
+        self.crop = (x - pad_u, y - pad_v, w + 2.0 * pad_u, h + 2.0 * pad_v);
+        self.position = [self.position[0] - pad_x, self.position[1] - pad_y];
+        self.size = [self.size[0] + 2.0 * pad_x, self.size[1] + 2.0 * pad_y];
+        // the pivot stays on the same pixel of the texture, so the rotation doesn't move it.
+        self.pivot = [self.pivot[0] + pad_x, self.pivot[1] + pad_y];
+        self
+    }
+
+    pub fn with_flip(mut self, flip: Flip) -> QuadInstance {
+        self.flip = flip;
+        self
+    }
+
+    /// Rotate the quad by `rotation` degrees around its center.
+    pub fn with_rotation(mut self, rotation: f32) -> QuadInstance {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Rotate the quad around `pivot` instead of its center, relative to `position`.
+    pub fn with_pivot(mut self, pivot: [f32; 2]) -> QuadInstance {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color<u8>) -> QuadInstance {
+        self.color = color;
+        self
+    }
+
+    /// Only change the opacity of `color`, for fade-ins and fade-outs.
+    pub fn with_alpha(mut self, alpha: u8) -> QuadInstance {
+        self.color.a = alpha;
+        self
+    }
+
+    /// Mix `overlay` over the quad by `overlay.a`, for instance white at 255 for a full damage
+    /// flash.
+    pub fn with_overlay(mut self, overlay: Color<u8>) -> QuadInstance {
+        self.overlay = overlay;
+        self
+    }
+
+    pub fn with_flags(mut self, flags: u32) -> QuadInstance {
+        self.flags = flags;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> QuadInstance {
+        self.depth = depth;
+        self
+    }
+
+    /// The crop with the flip applied, as written in "quad_uv".
+    pub fn uv(&self) -> [f32; 4] {
+        let (x, y, w, h) = self.flip.apply_to_uv(self.crop);
+        [x, y, w, h]
+    }
+
+    /// The transformation of the quad by `QUAD_VERTEX_GLSL`, as the columns of a model matrix.
+    /// Computed with glam with the `glam` feature, see `compat::model_matrix_cols`.
+    pub fn model_matrix_cols(&self) -> [[f32; 4]; 4] {
+        // `QUAD_VERTEX_GLSL` turns clockwise on screen, the other way than `compat::model_matrix`.
+        let vec2 = |[x, y]: [f32; 2]| compat::vec2(x, y);
+        compat::model_matrix_cols(vec2(self.position), vec2(self.size), vec2(self.pivot), -self.rotation)
+    }
+
+    /// The box (x, y, w, h) around the quad once rotated, to give to
+    /// `Renderer::add_elem_with_bounds`.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        let [x, y] = self.position;
+        let [w, h] = self.size;
+        if self.rotation == 0.0 {
+            return (x.min(x + w), y.min(y + h), w.abs(), h.abs());
+        }
+        let m = self.model_matrix_cols();
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for &(u, v) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            let corner_x = m[0][0] * u + m[1][0] * v + m[3][0];
+            let corner_y = m[0][1] * u + m[1][1] * v + m[3][1];
+            min_x = min_x.min(corner_x);
+            min_y = min_y.min(corner_y);
+            max_x = max_x.max(corner_x);
+            max_y = max_y.max(corner_y);
+        }
+        (min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+
+    /// "quad_layer": the layer and the tier, see `QUAD_LAYER_TIER_SHIFT`.
+    fn packed_layer(&self) -> u32 {
+        self.layer | self.tier << QUAD_LAYER_TIER_SHIFT
+    }
+}
+
+instance_data!(impl AsVertexData for QuadInstance |quad| {
+    "quad_position": [f32; 2] = quad.position,
+    "quad_size": [f32; 2] = quad.size,
+    "quad_rotation": f32 = quad.rotation,
+    "quad_pivot": [f32; 2] = quad.pivot,
+    "quad_uv": [f32; 4] = quad.uv(),
+    "quad_layer": u32 = quad.packed_layer(),
+    "quad_color": Color<u8> = quad.color,
+    "quad_overlay": Color<u8> = quad.overlay,
+    "quad_flags": u32 = quad.flags,
+    "quad_depth": f32 = quad.depth,
+});
+
+/// A crop in pixels relative to `texture` (x, y, w, h), normalized in its layer.
+pub (crate) fn crop_to_uv(texture: &TextureHandle, crop: (u32, u32, u32, u32)) -> (f32, f32, f32, f32) {
+    texture.region(crop).uv
+}
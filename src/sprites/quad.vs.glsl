@@ -0,0 +1,41 @@
+#version 330 core
+#define DEG_TO_RAD 0.0174532925
+
+// The vertex shader of `QuadInstance::with_fields` / `RendererBuilder::standard_quad_layout`.
+layout (location = 0) in vec2 pos;
+layout (location = 1) in vec2 quad_position;
+layout (location = 2) in vec2 quad_size;
+layout (location = 3) in float quad_rotation;
+layout (location = 4) in vec2 quad_pivot;
+// (x, y, w, h), w and h are negative when flipped
+layout (location = 5) in vec4 quad_uv;
+layout (location = 6) in uint quad_layer;
+layout (location = 7) in vec4 quad_color;
+layout (location = 8) in vec4 quad_overlay;
+layout (location = 9) in uint quad_flags;
+// 0.0 (front) to 1.0 (back)
+layout (location = 10) in float quad_depth;
+
+out vec2 tex_coords;
+out vec4 color;
+out vec4 overlay;
+flat out uint layer;
+flat out uint flags;
+
+uniform mat4 view;
+
+void main()
+{
+    float rot = quad_rotation * DEG_TO_RAD;
+    mat2 rotation = mat2(cos(rot), sin(rot), -sin(rot), cos(rot));
+    vec2 local = pos * quad_size - quad_pivot;
+    vec2 world = quad_position + quad_pivot + rotation * local;
+    gl_Position = view * vec4(world, 0.0, 1.0);
+    gl_Position.z = (quad_depth * 2.0 - 1.0) * gl_Position.w;
+
+    tex_coords = quad_uv.xy + pos * quad_uv.zw;
+    color = quad_color;
+    overlay = quad_overlay;
+    layer = quad_layer;
+    flags = quad_flags;
+}
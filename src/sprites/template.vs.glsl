@@ -0,0 +1,38 @@
+#version 330 core
+// must match `MAX_TEMPLATE_FRAMES`
+#define MAX_TEMPLATE_FRAMES 128
+
+// The vertex shader of `TemplateInstance`: everything but the position and the frame comes from
+// the `QuadTemplate` uniforms. Its outputs are the ones of `QUAD_VERTEX_GLSL`.
+layout (location = 0) in vec2 pos;
+layout (location = 1) in vec2 template_position;
+layout (location = 2) in uint template_frame;
+
+out vec2 tex_coords;
+out vec4 color;
+out vec4 overlay;
+flat out uint layer;
+flat out uint flags;
+
+uniform mat4 view;
+uniform vec2 template_size;
+uniform uint template_layer;
+uniform vec4 template_color;
+uniform vec4 template_overlay;
+uniform uint template_flags;
+uniform float template_depth;
+// (x, y, w, h) of every frame, w and h are negative when flipped
+uniform vec4 template_frames[MAX_TEMPLATE_FRAMES];
+
+void main()
+{
+    gl_Position = view * vec4(template_position + pos * template_size, 0.0, 1.0);
+    gl_Position.z = (template_depth * 2.0 - 1.0) * gl_Position.w;
+
+    vec4 uv = template_frames[min(template_frame, uint(MAX_TEMPLATE_FRAMES - 1))];
+    tex_coords = uv.xy + pos * uv.zw;
+    color = template_color;
+    overlay = template_overlay;
+    layer = template_layer;
+    flags = template_flags;
+}
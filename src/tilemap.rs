@@ -0,0 +1,147 @@
+//! Big tile maps, drawn from instance data kept on the GPU.
+//!
+//! The map is split in square chunks of tiles, each one uploaded once to its own `StaticBatch`.
+//! Changing a tile only rebuilds its chunk, on the next `draw`, and only the chunks in view are
+//! drawn, one call per chunk.
+//!
+//! ```ignore
+//! let mut tilemap = TilemapRenderer::new(&renderer, 256, 256, 32, Vector2::new(16.0, 16.0));
+//! tilemap.set_tile(3, 4, Some(grass));
+//! // every frame
+//! tilemap.draw(&mut renderer, camera.visible_bounds(), |x, y, tile| to_vertex_data(x, y, tile));
+//! ```
+
+use cgmath::Vector2;
+
+use crate::renderer::{AsVertexData, Renderer, StaticBatch};
+use crate::shader::Uniform;
+
+#[derive(Debug)]
+struct Chunk {
+    batch: StaticBatch,
+    /// the tiles changed since the batch was filled
+    dirty: bool,
+}
+
+/// A grid of tiles of type `T`, see the module documentation.
+///
+/// Tile (x, y) covers the rect starting at (x * tile_size.x, y * tile_size.y) in the world. This
+/// is only used to know which chunks are in view: the position of the tiles is up to the vertex
+/// data you give to `draw`.
+#[derive(Debug)]
+pub struct TilemapRenderer<T> {
+    width: u32,
+    height: u32,
+    chunk_size: u32,
+    tile_size: Vector2<f32>,
+    tiles: Vec<Option<T>>,
+    /// row-major, like the tiles
+    chunks: Vec<Chunk>,
+}
+
+impl<T> TilemapRenderer<T> {
+    /// Create an empty map of `width` x `height` tiles, with the layout of `renderer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0
+    pub fn new<U: Uniform>(renderer: &Renderer<U>, width: u32, height: u32, chunk_size: u32, tile_size: Vector2<f32>) -> TilemapRenderer<T> {
+        assert!(chunk_size > 0, "chunk_size must not be 0");
+        let chunk_count = (width.div_ceil(chunk_size) * height.div_ceil(chunk_size)) as usize;
+        TilemapRenderer {
+            width,
+            height,
+            chunk_size,
+            tile_size,
+            tiles: (0..width as usize * height as usize).map(|_| None).collect(),
+            chunks: (0..chunk_count).map(|_| Chunk { batch: StaticBatch::new(renderer), dirty: false }).collect(),
+        }
+    }
+
+    /// Size of the map, in tiles.
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    #[inline]
+    pub fn tile_size(&self) -> Vector2<f32> {
+        self.tile_size
+    }
+
+    fn chunks_x(&self) -> u32 {
+        self.width.div_ceil(self.chunk_size)
+    }
+
+    fn chunks_y(&self) -> u32 {
+        self.height.div_ceil(self.chunk_size)
+    }
+
+    /// Returns `None` for empty tiles, and tiles outside of the map.
+    pub fn tile(&self, x: u32, y: u32) -> Option<&T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles[(y * self.width + x) as usize].as_ref()
+    }
+
+    /// Set or remove a tile. Its chunk is rebuilt on the next `draw`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if (x, y) is outside of the map
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<T>) {
+        assert!(x < self.width && y < self.height, "tile ({}, {}) is outside of the map", x, y);
+        self.tiles[(y * self.width + x) as usize] = tile;
+        let chunk = (y / self.chunk_size * self.chunks_x() + x / self.chunk_size) as usize;
+        self.chunks[chunk].dirty = true;
+    }
+
+    /// Rebuild every chunk on the next `draw`, for instance if `to_vertex_data` now gives
+    /// different results.
+    pub fn invalidate(&mut self) {
+        for chunk in &mut self.chunks {
+            chunk.dirty = true;
+        }
+    }
+
+    /// Draw the chunks intersecting `visible` (x, y, w, h, in world coordinates), rebuilding the
+    /// ones which changed with `to_vertex_data`, which receives the position of the tile in the map.
+    ///
+    /// Uses the current shader and uniforms of `renderer`, see `Renderer::draw_static_batch`.
+    pub fn draw<U: Uniform, E: AsVertexData, F: FnMut(u32, u32, &T) -> E>(&mut self, renderer: &mut Renderer<U>, visible: (f32, f32, f32, f32), mut to_vertex_data: F) {
+        let chunk_extent = self.tile_size * self.chunk_size as f32;
+        let (x, y, w, h) = visible;
+        let chunk_range = |start: f32, length: f32, extent: f32, count: u32| {
+            let first = (start / extent).floor().max(0.0) as u32;
+            let last = ((start + length) / extent).ceil().max(0.0) as u32;
+            first..last.min(count)
+        };
+        let chunks_x = self.chunks_x();
+        let range_x = chunk_range(x, w, chunk_extent.x, chunks_x);
+        let range_y = chunk_range(y, h, chunk_extent.y, self.chunks_y());
+
+        let mut instanced_vb = Vec::new();
+        for chunk_y in range_y {
+            for chunk_x in range_x.clone() {
+                let chunk = &mut self.chunks[(chunk_y * chunks_x + chunk_x) as usize];
+                if chunk.dirty {
+                    instanced_vb.clear();
+                    let mut instance_count = 0;
+                    let tiles_x = chunk_x * self.chunk_size..((chunk_x + 1) * self.chunk_size).min(self.width);
+                    let tiles_y = chunk_y * self.chunk_size..((chunk_y + 1) * self.chunk_size).min(self.height);
+                    for tile_y in tiles_y {
+                        for tile_x in tiles_x.clone() {
+                            if let Some(tile) = &self.tiles[(tile_y * self.width + tile_x) as usize] {
+                                instance_count += to_vertex_data(tile_x, tile_y, tile).add_vertex_data(&mut instanced_vb) as usize;
+                            }
+                        }
+                    }
+                    chunk.batch.set_raw_instances(&instanced_vb, instance_count);
+                    chunk.dirty = false;
+                }
+                renderer.draw_static_batch(&chunk.batch);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! What the integrations with windowing crates have in common, see `sdl` and `glutin_window`.
+
+use crate::error::SprowlError;
+use crate::shader::GlProfile;
+
+/// The window to create with `sdl2_init` or `glutin_init`.
+#[derive(Debug, Clone)]
+pub struct WindowSettings {
+    pub title: String,
+    /// In screen coordinates: the drawable size is bigger on high-DPI displays.
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+    pub vsync: bool,
+    /// The kind of context to ask for: 3.3 core, or ES 3.0.
+    pub gl_profile: GlProfile,
+    /// Samples per pixel of the default framebuffer, 0 to disable multisampling.
+    pub multisample_samples: u8,
+    /// Ask for a debug context. With the `gl-debug` feature, the messages of the driver are then
+    /// logged, see `backend::install_debug_callback`.
+    pub debug_context: bool,
+}
+
+impl WindowSettings {
+    /// A resizable window, with vsync and without multisampling.
+    pub fn new<S: Into<String>>(title: S, width: u32, height: u32) -> WindowSettings {
+        WindowSettings {
+            title: title.into(),
+            width,
+            height,
+            resizable: true,
+            vsync: true,
+            gl_profile: GlProfile::Core,
+            multisample_samples: 0,
+            debug_context: false,
+        }
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> WindowSettings {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn with_vsync(mut self, vsync: bool) -> WindowSettings {
+        self.vsync = vsync;
+        self
+    }
+
+    pub fn with_gl_profile(mut self, gl_profile: GlProfile) -> WindowSettings {
+        self.gl_profile = gl_profile;
+        self
+    }
+
+    pub fn with_multisample_samples(mut self, multisample_samples: u8) -> WindowSettings {
+        self.multisample_samples = multisample_samples;
+        self
+    }
+
+    pub fn with_debug_context(mut self, debug_context: bool) -> WindowSettings {
+        self.debug_context = debug_context;
+        self
+    }
+}
+
+/// What to do once the context is current and the functions of the `gl` crate are loaded.
+pub (crate) fn after_context_creation(settings: &WindowSettings) {
+    #[cfg(feature = "gl-debug")]
+    {
+        if settings.debug_context && !crate::backend::install_debug_callback() {
+            log::warn!("KHR_debug is not supported, falling back to glGetError");
+        }
+    }
+    #[cfg(not(feature = "gl-debug"))]
+    let _ = settings;
+    crate::gl_utils::log_gl_info();
+}
+
+pub (crate) fn window_error<E: ToString>(e: E) -> SprowlError {
+    SprowlError::Window(e.to_string())
+}
@@ -0,0 +1,123 @@
+//! Batching of the instances, run against a `RecordingBackend` instead of a GL context.
+
+mod common;
+
+use sprowl::{AlphaMode, Error};
+use sprowl::backend::gl;
+use sprowl::renderer::{BlendMode, GrowthPolicy, RendererBuilder};
+use sprowl::sprites::QuadInstance;
+
+use common::{add_quads, quad_renderer, recorder};
+
+#[test]
+fn reaching_max_instances_flushes() {
+    let recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(4));
+    recorder.clear();
+    add_quads(&mut renderer, 10);
+    // 4 and 4 are drawn as soon as the limit is reached, the 2 others by draw()
+    assert_eq!(recorder.count("DrawArraysInstanced"), 2);
+    renderer.draw();
+    assert_eq!(recorder.count("DrawArraysInstanced"), 3);
+    let stats = renderer.frame_stats();
+    assert_eq!((stats.instances, stats.draw_calls), (10, 3));
+}
+
+#[test]
+fn grow_policy_keeps_a_single_batch() {
+    let recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(4).with_growth_policy(GrowthPolicy::Grow { max_instances: 64 }));
+    recorder.clear();
+    add_quads(&mut renderer, 10);
+    renderer.draw();
+    assert_eq!(recorder.count("DrawArraysInstanced"), 1);
+    assert_eq!(recorder.count("BufferData"), 1, "the instanced VBO is only reallocated once");
+    assert_eq!(renderer.frame_stats().instances, 10);
+}
+
+#[test]
+fn blend_mode_changes_split_the_batch() {
+    let recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(16));
+    recorder.clear();
+    add_quads(&mut renderer, 3);
+    renderer.set_blend_mode(BlendMode::Additive);
+    assert_eq!(recorder.count("DrawArraysInstanced"), 1, "the quads added before are drawn with the previous blend mode");
+    add_quads(&mut renderer, 2);
+    renderer.draw();
+    assert_eq!(recorder.count("DrawArraysInstanced"), 2);
+    let stats = renderer.frame_stats();
+    assert_eq!((stats.instances, stats.draw_calls), (5, 2));
+}
+
+#[test]
+fn multiply_depends_on_the_alpha_mode() {
+    let recorder = recorder();
+    let blend_func = |alpha_mode| {
+        let mut renderer = quad_renderer(RendererBuilder::new(16).with_alpha_mode(alpha_mode));
+        recorder.clear();
+        renderer.set_blend_mode(BlendMode::Multiply);
+        recorder.calls().into_iter().filter(|call| call.function == "BlendFunc").last().unwrap().args
+    };
+    // straight colors are not weighted by their alpha, transparent texels would brighten the background
+    assert_eq!(blend_func(AlphaMode::Straight), format!("{:?}, {:?}", gl::DST_COLOR, gl::ZERO));
+    assert_eq!(blend_func(AlphaMode::Premultiplied), format!("{:?}, {:?}", gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA));
+}
+
+#[test]
+fn rotated_quads_are_culled_by_their_bounds() {
+    let _recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(16));
+    renderer.set_cull_bounds(Some((0.0, 0.0, 100.0, 100.0)));
+    // 4 px from the edge: only the corners come in once rotated by 45 degrees around the center
+    let quad = QuadInstance::new([104.0, 40.0], [20.0, 20.0]);
+    assert!(!renderer.add_elem_with_bounds(&quad, quad.bounds()));
+    let rotated = quad.with_rotation(45.0);
+    let (x, y, w, h) = rotated.bounds();
+    let half_diagonal = 10.0 * 2f32.sqrt();
+    assert!((x - (114.0 - half_diagonal)).abs() < 1e-3 && (y - (50.0 - half_diagonal)).abs() < 1e-3);
+    assert!((w - 2.0 * half_diagonal).abs() < 1e-3 && (h - w).abs() < 1e-3);
+    assert!(renderer.add_elem_with_bounds(&rotated, rotated.bounds()));
+    assert_eq!(renderer.frame_stats().culled, 1);
+}
+
+#[test]
+fn static_batches_are_drawn_with_the_renderer_state() {
+    let recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(16));
+    let quads: Vec<QuadInstance> = (0..5).map(|i| QuadInstance::new([i as f32, 0.0], [8.0, 8.0])).collect();
+    let batch = renderer.create_static_batch(&quads);
+    recorder.clear();
+    renderer.draw_batch(batch).unwrap();
+    let calls: Vec<&str> = recorder.calls().iter().map(|call| call.function).collect();
+    let draw = calls.iter().position(|function| *function == "DrawArraysInstanced").expect("the batch was not drawn");
+    for function in &["UseProgram", "BlendFunc", "Viewport"] {
+        assert!(calls[..draw].contains(function), "{} was not called before drawing the batch", function);
+    }
+    assert_eq!(renderer.frame_stats().instances, 5);
+}
+
+#[test]
+fn static_batches_preserve_the_gl_state() {
+    let recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(16));
+    renderer.set_preserve_gl_state(true);
+    let batch = renderer.create_static_batch(&[QuadInstance::new([0.0, 0.0], [8.0, 8.0])]);
+    recorder.clear();
+    renderer.draw_batch(batch).unwrap();
+    let calls: Vec<&str> = recorder.calls().iter().map(|call| call.function).collect();
+    let draw = calls.iter().position(|function| *function == "DrawArraysInstanced").expect("the batch was not drawn");
+    assert!(calls[..draw].contains(&"GetIntegerv"), "the state of the application was not captured");
+    assert!(calls[draw..].contains(&"UseProgram"), "the program of the application was not restored");
+}
+
+#[test]
+fn removed_static_batches_are_errors() {
+    let _recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(16));
+    let quad = QuadInstance::new([0.0, 0.0], [8.0, 8.0]);
+    let batch = renderer.create_static_batch(&[quad]);
+    assert!(renderer.remove_static_batch(batch).is_some());
+    assert!(matches!(renderer.update_static_batch(batch, &[quad]), Err(Error::MissingBatchId(_))));
+    assert!(matches!(renderer.draw_batch(batch), Err(Error::MissingBatchId(_))));
+}
@@ -0,0 +1,27 @@
+//! Helpers shared by the tests run against a `RecordingBackend`.
+
+// every test crate includes this module, but not all of them draw quads.
+#![allow(dead_code)]
+
+use std::rc::Rc;
+
+use sprowl::backend::{self, RecordingBackend};
+use sprowl::renderer::{Renderer, RendererBuilder};
+use sprowl::sprites::{QuadInstance, QuadUniform, standard_quad_shader};
+
+/// Record the GL calls of the current thread from now on.
+pub fn recorder() -> Rc<RecordingBackend> {
+    let recorder = Rc::new(RecordingBackend::new());
+    backend::set_backend(recorder.clone());
+    recorder
+}
+
+pub fn quad_renderer(builder: RendererBuilder) -> Renderer<QuadUniform> {
+    builder.standard_quad_layout().build_with(standard_quad_shader().unwrap())
+}
+
+pub fn add_quads(renderer: &mut Renderer<QuadUniform>, count: usize) {
+    for i in 0..count {
+        renderer.add_elem(&QuadInstance::new([i as f32 * 10.0, 0.0], [8.0, 8.0]));
+    }
+}
@@ -0,0 +1,94 @@
+//! Draw submission, run against a `RecordingBackend` instead of a GL context.
+
+mod common;
+
+use sprowl::backend::gl;
+use sprowl::renderer::{BlendMode, RendererBuilder};
+use sprowl::sprites::{QuadUniform, QUAD_FRAGMENT_GLSL, QUAD_VERTEX_GLSL, standard_quad_shader};
+
+use common::{add_quads, quad_renderer, recorder};
+
+#[test]
+fn instances_are_drawn_in_one_call() {
+    let recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(16));
+    recorder.clear();
+    add_quads(&mut renderer, 10);
+    assert_eq!(recorder.count("DrawArraysInstanced"), 0, "nothing is drawn before draw()");
+    renderer.draw();
+    assert_eq!(recorder.count("DrawArraysInstanced"), 1);
+    let stats = renderer.frame_stats();
+    assert_eq!((stats.instances, stats.draw_calls), (10, 1));
+
+    renderer.reset_frame_stats();
+    recorder.clear();
+    renderer.draw();
+    assert_eq!(recorder.count("DrawArraysInstanced"), 0, "an empty frame draws nothing");
+    assert_eq!(renderer.frame_stats().draw_calls, 0);
+}
+
+#[test]
+fn failed_persistent_mapping_falls_back_on_a_new_buffer() {
+    let recorder = recorder();
+    // mapping always fails with a `RecordingBackend`
+    let mut renderer = quad_renderer(RendererBuilder::new(16).with_persistent_mapping(true));
+    let array_buffer = gl::ARRAY_BUFFER.to_string();
+    let mut bound = String::new();
+    let mut immutable = None;
+    for call in recorder.calls() {
+        let args: Vec<&str> = call.args.split(", ").collect();
+        match call.function {
+            "BindBuffer" if args[0] == array_buffer => bound = args[1].to_string(),
+            "BufferStorage" => immutable = Some(bound.clone()),
+            "BufferData" => assert_ne!(Some(&bound), immutable.as_ref(), "glBufferData on a buffer allocated with glBufferStorage"),
+            _ => (),
+        }
+    }
+    assert!(immutable.is_some(), "persistent mapping was not attempted");
+
+    recorder.clear();
+    add_quads(&mut renderer, 3);
+    renderer.draw();
+    assert_eq!(recorder.count("DrawArraysInstanced"), 1);
+}
+
+#[test]
+fn globals_are_set_before_elements_flushed_early() {
+    let recorder = recorder();
+    let mut renderer = quad_renderer(RendererBuilder::new(16).with_standard_globals(true));
+    renderer.set_globals_time(Some(1.5));
+    renderer.begin_frame();
+    recorder.clear();
+    add_quads(&mut renderer, 2);
+    // drawn right away, before any `draw`
+    renderer.set_blend_mode(BlendMode::Additive);
+    let calls = recorder.calls();
+    let draw = calls.iter().position(|call| call.function == "DrawArraysInstanced").expect("the quads were not flushed");
+    assert!(calls[..draw].iter().any(|call| call.function == "Uniform1f" && call.args.ends_with(", 1.5")), "u_time was not set before drawing");
+}
+
+#[test]
+fn attribs_with_a_divisor_share_a_stream() {
+    let recorder = recorder();
+    let builder = RendererBuilder::new(16)
+        .with_instanced_vertex_attrib(4, gl::FLOAT, 1)
+        .with_instanced_vertex_attrib(1, gl::UNSIGNED_INT, 4)
+        .with_instanced_vertex_attrib(2, gl::FLOAT, 4);
+    let _renderer = builder.build_with(sprowl::sprites::standard_quad_shader().unwrap());
+    let divisors: Vec<String> = recorder.calls().into_iter()
+        .filter(|call| call.function == "VertexAttribDivisor")
+        .map(|call| call.args)
+        .collect();
+    assert_eq!(divisors, vec!["1, 1", "2, 4", "3, 4"]);
+}
+
+#[test]
+fn reloaded_shaders_keep_their_uniform_values() {
+    let recorder = recorder();
+    let mut shader = standard_quad_shader().unwrap();
+    shader.set_float(QuadUniform::TextGamma, 0.5);
+    recorder.clear();
+    shader.reload(QUAD_FRAGMENT_GLSL, QUAD_VERTEX_GLSL).unwrap();
+    assert_eq!(recorder.count("Uniform1fv"), 1, "the text gamma is set on the new program");
+    assert_eq!(recorder.count("UniformMatrix4fv"), 0, "the view was never set");
+}
@@ -0,0 +1,91 @@
+//! Texture storage, run against a `RecordingBackend` instead of a GL context.
+
+mod common;
+
+use sprowl::Error;
+use sprowl::render_storage::RenderStorage;
+use sprowl::render_storage::config::TextureArrayConfig;
+
+use common::recorder;
+
+#[test]
+fn removed_layers_are_reused() {
+    let _recorder = recorder();
+    let mut storage = RenderStorage::new();
+    let big = storage.add_texture_from_raw_bytes(&vec![255; 512 * 512 * 4], (512, 512)).unwrap();
+    let other = storage.add_texture_from_raw_bytes(&vec![255; 400 * 400 * 4], (400, 400)).unwrap();
+    assert_ne!(big.layer, other.layer);
+    assert_eq!(storage.storage_stats().rgba[0].used_layers, 2);
+
+    storage.remove_texture(big);
+    assert_eq!(storage.storage_stats().rgba[0].used_layers, 1);
+    let reused = storage.add_texture_from_raw_bytes(&vec![255; 300 * 300 * 4], (300, 300)).unwrap();
+    assert_eq!(reused.layer, big.layer);
+    assert_eq!(storage.storage_stats().rgba[0].used_layers, 2);
+}
+
+#[test]
+fn small_textures_share_a_layer() {
+    let _recorder = recorder();
+    let mut storage = RenderStorage::new();
+    let a = storage.add_texture_from_raw_bytes(&[255; 64 * 64 * 4], (64, 64)).unwrap();
+    let b = storage.add_texture_from_raw_bytes(&[255; 64 * 64 * 4], (64, 64)).unwrap();
+    assert_eq!(a.layer, b.layer);
+    assert_ne!(a.rect, b.rect);
+    assert_eq!(storage.storage_stats().rgba[0].used_layers, 1);
+
+    // the shared layer is only freed with its last texture
+    storage.remove_texture(a);
+    assert_eq!(storage.storage_stats().rgba[0].used_layers, 1);
+    storage.remove_texture(b);
+    assert_eq!(storage.storage_stats().rgba[0].used_layers, 0);
+}
+
+#[test]
+fn textures_larger_than_a_layer_are_rejected() {
+    let _recorder = recorder();
+    let mut storage = RenderStorage::new();
+    let result = storage.add_texture_from_raw_bytes(&vec![255; 1100 * 64 * 4], (1100, 64));
+    assert!(matches!(result, Err(Error::TextureTooLarge { width: 1100, height: 64, max: (1024, 1024) })));
+    assert_eq!(storage.storage_stats().rgba[0].used_layers, 0, "no layer is reserved for it");
+}
+
+#[test]
+fn every_rgba_tier_has_its_stats() {
+    let _recorder = recorder();
+    let mut storage = RenderStorage::new();
+    let tier = storage.add_rgba_tier(TextureArrayConfig::new(256, 256, 4)).unwrap();
+    storage.add_texture_from_raw_bytes(&vec![255; 200 * 200 * 4], (200, 200)).unwrap();
+    let stats = storage.storage_stats();
+    assert_eq!(stats.rgba.len(), 2);
+    assert_eq!(stats.rgba[0].used_layers, 0);
+    assert_eq!(stats.rgba[tier as usize].used_layers, 1);
+    assert_eq!(stats.rgba[tier as usize].layer_size, (256, 256));
+}
+
+#[test]
+fn regions_must_be_within_their_texture() {
+    let _recorder = recorder();
+    let mut storage = RenderStorage::new();
+    let texture = storage.add_texture_from_raw_bytes(&[255; 32 * 32 * 4], (32, 32)).unwrap();
+    let region = storage.define_region(texture, "left", (0, 0, 16, 32)).unwrap();
+    assert!(region.is_in(&texture));
+    assert!(matches!(storage.define_region(texture, "past", (16, 0, 17, 32)), Err(Error::RegionOutOfBounds(key)) if key == "past"));
+    assert!(matches!(storage.define_region(texture, "overflow", (u32::MAX, 0, 2, 1)), Err(Error::RegionOutOfBounds(_))));
+}
+
+#[cfg(feature = "sheet-import")]
+#[test]
+fn sprite_sheets_register_their_regions() {
+    let _recorder = recorder();
+    let mut storage = RenderStorage::new();
+    let texture = storage.add_texture_from_raw_bytes(&[255; 32 * 16 * 4], (32, 16)).unwrap();
+    let json = r#"{"frames": {"a": {"frame": {"x": 0, "y": 0, "w": 16, "h": 16}}, "b": {"frame": {"x": 16, "y": 0, "w": 16, "h": 16}}}}"#;
+    let sheet = storage.import_sprite_sheet(texture, json, "hero/").unwrap();
+    assert_eq!(storage.region_of("hero/b"), sheet.region("b"));
+    assert!(storage.region_of("b").is_none());
+    // nothing is registered when a key is taken
+    storage.define_region(texture, "other/b", (0, 0, 1, 1)).unwrap();
+    assert!(matches!(storage.import_sprite_sheet(texture, json, "other/"), Err(Error::DuplicateAssetKey(key)) if key == "other/b"));
+    assert!(storage.region_of("other/a").is_none());
+}